@@ -0,0 +1,11 @@
+alter table issue_delivery_queue add column n_retries smallint not null default 0;
+alter table issue_delivery_queue add column execute_after timestamptz not null default now();
+
+create table issue_delivery_dead_letter (
+    newsletter_issue_id uuid not null references newsletter_issues (newsletter_issue_id),
+    subscriber_email text not null,
+    n_retries smallint not null,
+    last_error text not null,
+    dead_lettered_at timestamptz not null,
+    primary key (newsletter_issue_id, subscriber_email)
+)
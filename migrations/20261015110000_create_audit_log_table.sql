@@ -0,0 +1,9 @@
+create table audit_log (
+    audit_log_id uuid not null,
+    user_id uuid not null,
+    action text not null,
+    target text not null,
+    created_at timestamptz not null,
+    primary key (audit_log_id)
+);
+create index audit_log_created_at_idx on audit_log (created_at);
@@ -0,0 +1,2 @@
+alter table issue_delivery_dead_letter add column dead_letter_id uuid not null default gen_random_uuid();
+create unique index issue_delivery_dead_letter_id_idx on issue_delivery_dead_letter (dead_letter_id);
@@ -0,0 +1,8 @@
+create table delivery_status (
+    newsletter_issue_id uuid not null references newsletter_issues (newsletter_issue_id),
+    subscriber_email text not null,
+    status text not null,
+    recorded_at timestamptz not null,
+    primary key (newsletter_issue_id, subscriber_email)
+);
+create index delivery_status_recorded_at_idx on delivery_status (recorded_at);
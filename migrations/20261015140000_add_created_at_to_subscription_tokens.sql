@@ -0,0 +1 @@
+alter table subscription_tokens add column created_at timestamptz not null default now();
@@ -0,0 +1,2 @@
+alter table newsletter_issues add column track_opens boolean not null default false;
+alter table newsletter_issues add column track_links boolean not null default false;
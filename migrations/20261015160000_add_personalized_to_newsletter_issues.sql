@@ -0,0 +1 @@
+alter table newsletter_issues add column personalized boolean not null default false;
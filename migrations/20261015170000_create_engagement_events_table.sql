@@ -0,0 +1,13 @@
+create table engagement_events (
+    engagement_event_id uuid primary key default gen_random_uuid(),
+    newsletter_issue_id uuid not null
+        references newsletter_issues (newsletter_issue_id),
+    subscriber_email text not null,
+    -- `open` or `click`
+    event_type text not null,
+    -- The clicked link, for `click` events
+    link text,
+    recorded_at timestamptz not null
+);
+
+create index engagement_events_issue_idx on engagement_events (newsletter_issue_id, event_type);
@@ -0,0 +1,8 @@
+-- Single row table: the state shared by every delivery worker.
+create table delivery_worker_state (
+    id boolean primary key default true check (id),
+    paused boolean not null default false,
+    updated_at timestamptz not null default now()
+);
+
+insert into delivery_worker_state (id, paused) values (true, false);
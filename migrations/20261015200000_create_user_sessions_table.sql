@@ -0,0 +1,9 @@
+-- Active admin sessions, so that the oldest ones can be evicted when a
+-- user goes over `application.max_sessions_per_user`.
+create table user_sessions (
+    session_id uuid primary key,
+    user_id uuid not null references users (user_id) on delete cascade,
+    created_at timestamptz not null default now()
+);
+
+create index user_sessions_user_id_created_at_idx on user_sessions (user_id, created_at);
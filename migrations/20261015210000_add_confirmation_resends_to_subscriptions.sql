@@ -0,0 +1,3 @@
+-- Confirmation emails sent again on request, capped by
+-- `subscriptions.max_confirmation_resends`.
+alter table subscriptions add column confirmation_resends integer not null default 0;
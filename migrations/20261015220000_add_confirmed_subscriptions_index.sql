@@ -0,0 +1,5 @@
+-- Publishing an issue queues a delivery for every confirmed subscriber.
+-- Partial, since that is the only status queried in bulk, and on `email`
+-- so that the emails can be read from the index alone.
+create index subscriptions_confirmed_email_idx on subscriptions (email)
+where status = 'confirmed';
@@ -0,0 +1,3 @@
+-- Set when the content of an issue is edited after publication, so that the
+-- public archive can report when it last changed.
+alter table newsletter_issues add column updated_at timestamptz;
@@ -0,0 +1,12 @@
+-- Newsletters sent from the same deployment under their own branding: the
+-- issues published to a list go out from its sender.
+create table newsletter_lists (
+    list_id uuid primary key,
+    name text not null unique,
+    sender text not null,
+    reply_to text,
+    created_at timestamptz not null default now()
+);
+
+alter table newsletter_issues
+    add column list_id uuid references newsletter_lists (list_id);
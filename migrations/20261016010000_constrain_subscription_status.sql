@@ -0,0 +1,3 @@
+-- The statuses of `domain::SubscriptionStatus`: anything else is a bug.
+alter table subscriptions add constraint subscriptions_status_check
+    check (status in ('pending', 'confirmed', 'unsubscribed'));
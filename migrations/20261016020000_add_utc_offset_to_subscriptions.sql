@@ -0,0 +1,2 @@
+-- Optional, for quiet hours in the subscriber's own time zone.
+alter table subscriptions add column utc_offset_minutes smallint;
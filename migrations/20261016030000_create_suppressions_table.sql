@@ -0,0 +1,8 @@
+-- Addresses that must never be emailed, e.g. opt-outs imported from a
+-- previous provider. Compared case-insensitively.
+create table suppressions (
+    email text not null,
+    reason text not null,
+    created_at timestamptz not null default now()
+);
+create unique index suppressions_email_idx on suppressions (lower(email));
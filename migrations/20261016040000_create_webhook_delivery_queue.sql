@@ -0,0 +1,17 @@
+-- Subscription events waiting to be posted to `webhooks.events_url`.
+create table webhook_delivery_queue (
+    webhook_delivery_id uuid primary key,
+    payload text not null,
+    n_attempts smallint not null default 0,
+    execute_after timestamptz not null default now(),
+    created_at timestamptz not null default now()
+);
+
+-- Events that failed `webhooks.events_max_attempts` times.
+create table webhook_dead_letters (
+    webhook_delivery_id uuid primary key,
+    payload text not null,
+    n_attempts smallint not null,
+    last_error text not null,
+    failed_at timestamptz not null default now()
+);
@@ -0,0 +1,2 @@
+alter table delivery_status add column message_id text;
+create index delivery_status_message_id_idx on delivery_status (message_id);
@@ -0,0 +1,3 @@
+alter table issue_delivery_queue add column enqueued_seq bigserial not null;
+create index issue_delivery_queue_subscriber_email_idx
+    on issue_delivery_queue (subscriber_email, enqueued_seq);
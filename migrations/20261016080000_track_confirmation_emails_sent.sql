@@ -0,0 +1,3 @@
+alter table subscriptions
+    add column confirmation_emails_sent integer not null default 0,
+    add column last_confirmation_email_sent_at timestamptz;
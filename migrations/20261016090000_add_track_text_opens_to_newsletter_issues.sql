@@ -0,0 +1 @@
+alter table newsletter_issues add column track_text_opens boolean not null default false;
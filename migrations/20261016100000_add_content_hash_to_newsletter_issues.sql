@@ -0,0 +1,3 @@
+-- Unset for issues published before the column existed.
+alter table newsletter_issues add column content_hash text;
+create index newsletter_issues_content_hash_idx on newsletter_issues (content_hash);
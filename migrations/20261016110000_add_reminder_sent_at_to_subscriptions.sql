@@ -0,0 +1,3 @@
+-- When a pending subscriber was reminded to confirm, see
+-- `confirmation_reminders`. Each subscriber gets at most one reminder.
+alter table subscriptions add column reminder_sent_at timestamptz;
@@ -0,0 +1,3 @@
+-- When the admin followed the link sent to their email, see
+-- `features.require_verified_admin_email`.
+alter table users add column email_verified_at timestamptz;
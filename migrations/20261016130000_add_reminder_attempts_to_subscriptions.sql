@@ -0,0 +1,3 @@
+-- Failed attempts at sending a confirmation reminder, see
+-- `SubscriptionSettings::confirmation_reminder_max_attempts`.
+alter table subscriptions add column reminder_attempts integer not null default 0;
@@ -0,0 +1,6 @@
+-- The confirmed subscribers query binds its status (see `SubscriptionStatus`),
+-- and a generic plan for a prepared statement can't use a partial index on
+-- `status = 'confirmed'`. Leading with `status` serves any bound status, and
+-- `email` still lets the emails be read from the index alone.
+drop index subscriptions_confirmed_email_idx;
+create index subscriptions_status_email_idx on subscriptions (status, email);
@@ -0,0 +1,5 @@
+-- The content hash of the request a key was used for, with
+-- `publishing.idempotency_per_content`; empty otherwise.
+alter table idempotency add column content_hash text not null default '';
+alter table idempotency drop constraint idempotency_pkey;
+alter table idempotency add primary key (user_id, idempotency_key, content_hash);
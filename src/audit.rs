@@ -0,0 +1,28 @@
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+/// Record that `user_id` performed `action` on `target`.
+///
+/// Pass the transaction holding the audited change, so that the audit entry
+/// is only persisted if the change itself is.
+#[tracing::instrument(name = "Record audit event", skip(executor))]
+pub async fn record_audit_event(
+    executor: impl PgExecutor<'_>,
+    user_id: Uuid,
+    action: &str,
+    target: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO audit_log (audit_log_id, user_id, action, target, created_at)
+        VALUES ($1, $2, $3, $4, now())
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        action,
+        target
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use anyhow::Context;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::password::compute_password_hash;
+use crate::{configuration::BootstrapSettings, telemetry::spawn_blocking_with_tracing};
+
+/// The `admin` user inserted by the seed migration, with its publicly known
+/// password.
+const SEED_ADMIN_ID: &str = "ddf8994f-d522-4659-8d02-c1d479057be6";
+const SEED_ADMIN_PASSWORD_HASH: &str = "$argon2id$v=19$m=15000,t=2,p=1$OEx/rcq+3ts//WUDzGNl2g$Am8UFBA4w5NJEmAtquGvBmAlu92q/VQcaoL5AyJPfc8";
+
+/// Create the admin described by `settings` if there are no users yet, so
+/// that a fresh deployment can be logged into without touching the
+/// database by hand. The seed admin counts as no user as long as it is the
+/// only one and its password was never changed: it is replaced by the
+/// configured admin. Nothing happens once any other user exists: a
+/// configured password never overwrites one set through the admin panel.
+///
+/// Returns whether the admin was created.
+#[tracing::instrument(name = "Ensure an admin exists", skip(pool, settings))]
+pub async fn ensure_admin_exists(
+    pool: &PgPool,
+    settings: &BootstrapSettings,
+) -> Result<bool, anyhow::Error> {
+    let (Some(username), Some(password)) = (&settings.admin_username, &settings.admin_password)
+    else {
+        return Ok(false);
+    };
+    let seed_admin_id = Uuid::parse_str(SEED_ADMIN_ID).expect("The seed admin id is a UUID");
+    let only_seed_admin = sqlx::query!(
+        r#"
+        SELECT NOT EXISTS (
+            SELECT 1 FROM users
+            WHERE NOT (user_id = $1 AND password_hash = $2)
+        ) as "only_seed_admin!"
+        "#,
+        seed_admin_id,
+        SEED_ADMIN_PASSWORD_HASH
+    )
+    .fetch_one(pool)
+    .await
+    .context("failed to check for existing users")?
+    .only_seed_admin;
+    if !only_seed_admin {
+        return Ok(false);
+    }
+
+    let password = password.clone();
+    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
+        .await?
+        .context("failed to hash password")?;
+    // Checked again on write, in case another instance is booting too.
+    let replaced = sqlx::query!(
+        r#"
+        UPDATE users
+        SET username = $3, password_hash = $4, email = $5
+        WHERE user_id = $1 AND password_hash = $2
+            AND NOT EXISTS (SELECT 1 FROM users WHERE user_id <> $1)
+        "#,
+        seed_admin_id,
+        SEED_ADMIN_PASSWORD_HASH,
+        username,
+        password_hash.expose_secret(),
+        settings.admin_email
+    )
+    .execute(pool)
+    .await
+    .context("failed to replace the seed admin")?
+    .rows_affected()
+        > 0;
+    let created = replaced
+        || sqlx::query!(
+            r#"
+            INSERT INTO users (user_id, username, password_hash, email)
+            SELECT $1, $2, $3, $4
+            WHERE NOT EXISTS (SELECT 1 FROM users)
+            ON CONFLICT DO NOTHING
+            "#,
+            Uuid::new_v4(),
+            username,
+            password_hash.expose_secret(),
+            settings.admin_email
+        )
+        .execute(pool)
+        .await
+        .context("failed to create the initial admin")?
+        .rows_affected()
+            > 0;
+    if created {
+        tracing::info!(username, "Created the initial admin");
+    }
+    Ok(created)
+}
@@ -5,11 +5,15 @@ use actix_web::{
     dev::{ServiceRequest, ServiceResponse},
     error::InternalError,
     middleware::Next,
-    FromRequest, HttpMessage,
+    web, FromRequest, HttpMessage,
 };
+use sqlx::PgPool;
 use uuid::Uuid;
 
+use super::{is_active_session, SessionMaxAge};
 use crate::{
+    clock::Clock,
+    configuration::{ExpirySettings, FeatureFlags},
     session_state::TypedSession,
     utils::{e500, see_other},
 };
@@ -40,15 +44,89 @@ pub async fn reject_anonymous_users(
         TypedSession::from_request(http_request, payload).await
     }?;
 
-    match session.get_user_id().map_err(e500)? {
-        Some(user_id) => {
-            req.extensions_mut().insert(UserId(user_id));
-            next.call(req).await
-        }
+    let user_id = match session.get_user_id().map_err(e500)? {
+        Some(user_id) => user_id,
         None => {
             let resp = see_other("/login");
             let e = anyhow::anyhow!("The user has not logged in");
-            Err(InternalError::from_response(e, resp).into())
+            return Err(InternalError::from_response(e, resp).into());
+        }
+    };
+    // Sessions are ended on logout, evicted by newer ones, or expire.
+    let active = match session.get_session_id().map_err(e500)? {
+        Some(session_id) => {
+            let pool = req
+                .app_data::<web::Data<PgPool>>()
+                .ok_or_else(|| e500("No database pool"))?;
+            let max_age = req
+                .app_data::<web::Data<SessionMaxAge>>()
+                .ok_or_else(|| e500("No session max age"))?;
+            let clock = req
+                .app_data::<web::Data<dyn Clock>>()
+                .ok_or_else(|| e500("No clock"))?;
+            let expiry = req
+                .app_data::<web::Data<ExpirySettings>>()
+                .ok_or_else(|| e500("No expiry settings"))?;
+            is_active_session(
+                pool,
+                user_id,
+                session_id,
+                *max_age.get_ref(),
+                clock.get_ref(),
+                expiry.clock_skew_tolerance(),
+            )
+            .await
+            .map_err(e500)?
         }
+        None => false,
+    };
+    if !active {
+        session.log_out();
+        let resp = see_other("/login");
+        let e = anyhow::anyhow!("The session has ended");
+        return Err(InternalError::from_response(e, resp).into());
+    }
+    req.extensions_mut().insert(UserId(user_id));
+    next.call(req).await
+}
+
+/// Where admins without a verified email are sent, see
+/// `FeatureFlags::require_verified_admin_email`.
+pub const VERIFY_EMAIL_PATH: &str = "/admin/verify_email";
+
+/// Send admins whose email isn't verified to `VERIFY_EMAIL_PATH`, when
+/// `FeatureFlags::require_verified_admin_email` is on. The verification
+/// pages themselves and logging out stay reachable. Must run after
+/// `reject_anonymous_users`.
+pub async fn reject_unverified_admins(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let required = req
+        .app_data::<web::Data<FeatureFlags>>()
+        .is_some_and(|features| features.require_verified_admin_email);
+    if !required || req.path().starts_with(VERIFY_EMAIL_PATH) || req.path() == "/admin/logout" {
+        return next.call(req).await;
+    }
+    let user_id = req
+        .extensions()
+        .get::<UserId>()
+        .copied()
+        .ok_or_else(|| e500("The user is not known yet"))?;
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .ok_or_else(|| e500("No database pool"))?;
+    let verified = sqlx::query!(
+        "SELECT email_verified_at FROM users WHERE user_id = $1",
+        *user_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .map_err(e500)?
+    .is_some_and(|r| r.email_verified_at.is_some());
+    if !verified {
+        let e = anyhow::anyhow!("The admin has not verified their email");
+        return Err(InternalError::from_response(e, see_other(VERIFY_EMAIL_PATH)).into());
     }
+    next.call(req).await
 }
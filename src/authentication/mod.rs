@@ -1,5 +1,11 @@
+mod bootstrap;
 mod middleware;
 mod password;
+mod sessions;
 
-pub use middleware::{reject_anonymous_users, UserId};
+pub use bootstrap::ensure_admin_exists;
+pub use middleware::{reject_anonymous_users, reject_unverified_admins, UserId, VERIFY_EMAIL_PATH};
 pub use password::{change_password, validate_credentials, AuthError, Credentials};
+pub use sessions::{
+    end_session, is_active_session, start_session, MaxSessionsPerUser, SessionMaxAge,
+};
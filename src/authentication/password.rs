@@ -1,7 +1,7 @@
 use anyhow::Context;
 use argon2::{password_hash::SaltString, Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
 use secrecy::{ExposeSecret, Secret};
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 
 use crate::telemetry::spawn_blocking_with_tracing;
 
@@ -31,7 +31,7 @@ pub async fn validate_credentials(
     );
 
     if let Some((stored_user_id, stored_password_hash)) =
-        get_stored_credentials(&pool, &credentials.username).await?
+        get_stored_credentials(pool, &credentials.username).await?
     {
         user_id = Some(stored_user_id);
         expected_hash = stored_password_hash;
@@ -49,7 +49,7 @@ pub fn verify_password_hash(
     expected_hash: Secret<String>,
     password: Secret<String>,
 ) -> Result<(), AuthError> {
-    let parsed_hash = PasswordHash::new(&expected_hash.expose_secret())
+    let parsed_hash = PasswordHash::new(expected_hash.expose_secret())
         .context("failed to parse hash in PHC string format")?;
     Argon2::default()
         .verify_password(password.expose_secret().as_bytes(), &parsed_hash)
@@ -76,11 +76,11 @@ pub async fn get_stored_credentials(
     Ok(row)
 }
 
-#[tracing::instrument("Change password", skip(pool, password))]
+#[tracing::instrument("Change password", skip(executor, password))]
 pub async fn change_password(
     user_id: uuid::Uuid,
     password: Secret<String>,
-    pool: &PgPool,
+    executor: impl PgExecutor<'_>,
 ) -> Result<(), anyhow::Error> {
     let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
         .await?
@@ -91,14 +91,16 @@ pub async fn change_password(
         password_hash.expose_secret(),
         user_id
     )
-    .execute(pool)
+    .execute(executor)
     .await
     .context("failed to update password in db")?;
 
     Ok(())
 }
 
-fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, anyhow::Error> {
+pub(super) fn compute_password_hash(
+    password: Secret<String>,
+) -> Result<Secret<String>, anyhow::Error> {
     let salt = SaltString::generate(&mut rand::thread_rng());
     let password_hash = Argon2::new(
         argon2::Algorithm::Argon2id,
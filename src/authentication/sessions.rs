@@ -0,0 +1,153 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{clock::Clock, expiry::is_expired};
+
+/// Cap on the active sessions of each user, `0` meaning unlimited.
+/// Logging in beyond it ends the oldest sessions.
+#[derive(Clone, Copy, Debug)]
+pub struct MaxSessionsPerUser(pub usize);
+
+/// Absolute lifetime of a session, from login. Activity doesn't extend it.
+#[derive(Clone, Copy, Debug)]
+pub struct SessionMaxAge(pub std::time::Duration);
+
+impl SessionMaxAge {
+    /// Whether a session created at `created_at` has ended at `now`.
+    fn has_expired(
+        &self,
+        created_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+        skew_tolerance: std::time::Duration,
+    ) -> bool {
+        match chrono::Duration::from_std(self.0)
+            .ok()
+            .and_then(|max_age| created_at.checked_add_signed(max_age))
+        {
+            Some(expires_at) => is_expired(expires_at, now, skew_tolerance),
+            // Too long to ever end
+            None => false,
+        }
+    }
+}
+
+/// Record a new session for `user_id`, evicting the oldest ones beyond
+/// `max_sessions`. Rows of sessions past `max_age` are cleaned up on the
+/// way, since sessions that are never logged out leave them behind.
+#[tracing::instrument(name = "Start a session", skip(pool, clock))]
+pub async fn start_session(
+    pool: &PgPool,
+    user_id: Uuid,
+    max_sessions: MaxSessionsPerUser,
+    max_age: SessionMaxAge,
+    clock: &dyn Clock,
+    skew_tolerance: std::time::Duration,
+) -> Result<Uuid, sqlx::Error> {
+    let session_id = Uuid::new_v4();
+    let now = clock.now();
+    let mut transaction = pool.begin().await?;
+    let expired: Vec<Uuid> = sqlx::query!(
+        "SELECT session_id, created_at FROM user_sessions WHERE user_id = $1",
+        user_id
+    )
+    .fetch_all(&mut *transaction)
+    .await?
+    .into_iter()
+    .filter(|r| max_age.has_expired(r.created_at, now, skew_tolerance))
+    .map(|r| r.session_id)
+    .collect();
+    if !expired.is_empty() {
+        sqlx::query!(
+            "DELETE FROM user_sessions WHERE session_id = ANY($1)",
+            &expired
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+    sqlx::query!(
+        "INSERT INTO user_sessions (session_id, user_id, created_at) VALUES ($1, $2, $3)",
+        session_id,
+        user_id,
+        now
+    )
+    .execute(&mut *transaction)
+    .await?;
+    if max_sessions.0 > 0 {
+        let n_evicted = sqlx::query!(
+            r#"
+            DELETE FROM user_sessions
+            WHERE user_id = $1 AND session_id NOT IN (
+                SELECT session_id FROM user_sessions
+                WHERE user_id = $1
+                ORDER BY created_at DESC
+                LIMIT $2
+            )
+            "#,
+            user_id,
+            max_sessions.0 as i64
+        )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+        if n_evicted > 0 {
+            tracing::info!(n_evicted, "Evicted the oldest sessions of the user");
+        }
+    }
+    transaction.commit().await?;
+    Ok(session_id)
+}
+
+#[tracing::instrument(name = "Check a session", skip(pool, clock))]
+pub async fn is_active_session(
+    pool: &PgPool,
+    user_id: Uuid,
+    session_id: Uuid,
+    max_age: SessionMaxAge,
+    clock: &dyn Clock,
+    skew_tolerance: std::time::Duration,
+) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT created_at FROM user_sessions
+        WHERE session_id = $1 AND user_id = $2
+        "#,
+        session_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.is_some_and(|r| !max_age.has_expired(r.created_at, clock.now(), skew_tolerance)))
+}
+
+#[tracing::instrument(name = "End a session", skip(pool))]
+pub async fn end_session(pool: &PgPool, session_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM user_sessions WHERE session_id = $1",
+        session_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SessionMaxAge;
+    use crate::clock::{Clock, MockClock};
+    use chrono::{Duration, Utc};
+
+    #[test]
+    fn sessions_end_right_after_their_max_age_and_the_skew_tolerance() {
+        let max_age = SessionMaxAge(std::time::Duration::from_secs(24 * 60 * 60));
+        let skew_tolerance = std::time::Duration::from_secs(30);
+        let created_at = Utc::now();
+        let clock = MockClock::new(created_at);
+
+        clock.advance(Duration::hours(24) + Duration::seconds(30));
+        assert!(!max_age.has_expired(created_at, clock.now(), skew_tolerance));
+
+        clock.advance(Duration::seconds(1));
+        assert!(max_age.has_expired(created_at, clock.now(), skew_tolerance));
+    }
+}
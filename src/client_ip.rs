@@ -0,0 +1,227 @@
+use std::net::IpAddr;
+
+use actix_web::{http::header::HeaderName, HttpRequest};
+use anyhow::Context;
+
+use crate::configuration::ApplicationSettings;
+
+/// IP addresses and CIDR networks (`10.1.0.0/16`), from the configuration.
+#[derive(Debug, Clone, Default)]
+pub struct IpNetworks(Vec<(IpAddr, u8)>);
+
+impl IpNetworks {
+    pub fn parse(entries: &[String]) -> Result<Self, anyhow::Error> {
+        entries
+            .iter()
+            .map(|entry| {
+                let (address, prefix) = match entry.split_once('/') {
+                    Some((address, prefix)) => (address, Some(prefix)),
+                    None => (entry.as_str(), None),
+                };
+                let address: IpAddr = address
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("Invalid IP address or network {entry:?}"))?;
+                let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+                let prefix = match prefix {
+                    Some(prefix) => prefix
+                        .trim()
+                        .parse::<u8>()
+                        .ok()
+                        .filter(|prefix| *prefix <= max_prefix)
+                        .with_context(|| format!("Invalid network prefix in {entry:?}"))?,
+                    None => max_prefix,
+                };
+                Ok((address, prefix))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // IPv4 clients of a dual-stack listener show up as `::ffff:a.b.c.d`.
+        let ip = ip.to_canonical();
+        self.0.iter().any(|(network, prefix)| match (network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => same_prefix(
+                u32::from(*network).into(),
+                u32::from(ip).into(),
+                *prefix,
+                32,
+            ),
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                same_prefix(u128::from(*network), u128::from(ip), *prefix, 128)
+            }
+            _ => false,
+        })
+    }
+}
+
+/// Whether the first `prefix` of the `width` low bits of `a` and `b` match.
+fn same_prefix(a: u128, b: u128, prefix: u8, width: u8) -> bool {
+    let shift = width - prefix;
+    shift == width || (a >> shift) == (b >> shift)
+}
+
+/// Which proxies in front of the application are believed about the client.
+pub struct ClientIpConfig {
+    trusted_proxies: IpNetworks,
+    real_ip_header: HeaderName,
+}
+
+impl Default for ClientIpConfig {
+    /// Trust no one: the client is the peer.
+    fn default() -> Self {
+        Self {
+            trusted_proxies: IpNetworks::default(),
+            real_ip_header: HeaderName::from_static("x-forwarded-for"),
+        }
+    }
+}
+
+impl ClientIpConfig {
+    pub fn new(trusted_proxies: IpNetworks, real_ip_header: &str) -> Result<Self, anyhow::Error> {
+        let real_ip_header = HeaderName::try_from(real_ip_header)
+            .with_context(|| format!("Invalid client IP header name {real_ip_header:?}"))?;
+        Ok(Self {
+            trusted_proxies,
+            real_ip_header,
+        })
+    }
+
+    pub fn from_settings(settings: &ApplicationSettings) -> Result<Self, anyhow::Error> {
+        Self::new(
+            IpNetworks::parse(&settings.trusted_proxies)?,
+            &settings.real_ip_header,
+        )
+    }
+}
+
+/// The IP of the client behind any trusted proxies.
+///
+/// The header is only read when the peer is a trusted proxy, since anyone
+/// else could have set it. Each proxy appends the address it got the
+/// request from, so the chain is walked from the right and the first
+/// address that isn't a trusted proxy is the client: whatever is further
+/// left was written by the client itself and can't be believed.
+pub fn client_ip(req: &HttpRequest, config: &ClientIpConfig) -> Option<IpAddr> {
+    let peer = req.peer_addr()?.ip();
+    if !config.trusted_proxies.contains(peer) {
+        return Some(peer);
+    }
+    let mut client = peer;
+    let hops = req
+        .headers()
+        .get_all(&config.real_ip_header)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect::<Vec<_>>();
+    for hop in hops.into_iter().rev() {
+        let Ok(hop) = hop.trim().parse::<IpAddr>() else {
+            // Garbled past this point: the last proxy is as far as we can go.
+            break;
+        };
+        client = hop;
+        if !config.trusted_proxies.contains(hop) {
+            break;
+        }
+    }
+    Some(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn config(trusted_proxies: &[&str]) -> ClientIpConfig {
+        let entries: Vec<String> = trusted_proxies.iter().map(|p| p.to_string()).collect();
+        ClientIpConfig::new(IpNetworks::parse(&entries).unwrap(), "X-Forwarded-For").unwrap()
+    }
+
+    fn request(peer: &str, forwarded_for: Option<&str>) -> HttpRequest {
+        let request = TestRequest::default().peer_addr(format!("{peer}:4000").parse().unwrap());
+        match forwarded_for {
+            Some(chain) => request.insert_header(("X-Forwarded-For", chain)),
+            None => request,
+        }
+        .to_http_request()
+    }
+
+    fn ip(ip: &str) -> Option<IpAddr> {
+        Some(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn networks_match_addresses_and_cidr_ranges() {
+        let networks = IpNetworks::parse(&[
+            "10.1.0.0/16".into(),
+            "192.168.1.7".into(),
+            "2001:db8::/32".into(),
+        ])
+        .unwrap();
+
+        for ip in [
+            "10.1.200.3",
+            "192.168.1.7",
+            "::ffff:10.1.0.1",
+            "2001:db8::1",
+        ] {
+            assert!(networks.contains(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["10.2.0.1", "192.168.1.8", "2001:db9::1"] {
+            assert!(!networks.contains(ip.parse().unwrap()), "{ip}");
+        }
+        assert!(IpNetworks::parse(&["0.0.0.0/0".into()])
+            .unwrap()
+            .contains("203.0.113.9".parse().unwrap()));
+    }
+
+    #[test]
+    fn invalid_networks_are_rejected() {
+        for entry in ["office", "10.0.0.0/33", "10.0.0.0/", "::1/129"] {
+            claim::assert_err!(IpNetworks::parse(&[entry.into()]));
+        }
+    }
+
+    #[test]
+    fn the_header_is_ignored_unless_the_peer_is_a_trusted_proxy() {
+        let config = config(&["10.0.0.0/8"]);
+
+        let req = request("203.0.113.9", Some("198.51.100.1"));
+
+        assert_eq!(client_ip(&req, &config), ip("203.0.113.9"));
+    }
+
+    #[test]
+    fn the_client_is_the_rightmost_untrusted_address() {
+        let config = config(&["10.0.0.0/8"]);
+
+        // The client made up the first entry; the first proxy got it from 198.51.100.1.
+        let req = request("10.0.0.2", Some("192.0.2.66, 198.51.100.1, 10.0.0.1"));
+
+        assert_eq!(client_ip(&req, &config), ip("198.51.100.1"));
+    }
+
+    #[test]
+    fn the_peer_is_kept_when_a_trusted_proxy_sent_no_chain() {
+        let config = config(&["10.0.0.0/8"]);
+
+        assert_eq!(
+            client_ip(&request("10.0.0.2", None), &config),
+            ip("10.0.0.2")
+        );
+        assert_eq!(
+            client_ip(&request("10.0.0.2", Some("garbage")), &config),
+            ip("10.0.0.2")
+        );
+    }
+
+    #[test]
+    fn a_chain_of_trusted_proxies_yields_the_leftmost_one() {
+        let config = config(&["10.0.0.0/8"]);
+
+        let req = request("10.0.0.3", Some("10.0.0.1, 10.0.0.2"));
+
+        assert_eq!(client_ip(&req, &config), ip("10.0.0.1"));
+    }
+}
@@ -1,12 +1,16 @@
-use std::str::FromStr;
+use std::{str::FromStr, sync::Arc};
 
 use secrecy::{ExposeSecret, Secret};
 use serde_aux::field_attributes::deserialize_number_from_string;
-use sqlx::postgres::PgConnectOptions;
+use sqlx::postgres::{PgConnectOptions, PgSslMode};
 use sqlx::ConnectOptions;
 
-use crate::domain::SubscriberEmail;
-use crate::email_client::EmailClient;
+use crate::clock::{system_clock, Clock};
+use crate::domain::{SubscriberEmail, SubscriberName};
+use crate::email_client::{ConnectionLimits, EmailClient, TlsPolicy};
+use crate::email_layout::EmailLayout;
+use crate::locale::Locale;
+use crate::quiet_hours::QuietHours;
 
 #[derive(serde::Deserialize, Debug, Clone)]
 pub struct Settings {
@@ -14,7 +18,237 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
     pub redis_url: Secret<String>,
+    pub subscriptions: SubscriptionSettings,
+    pub worker: WorkerSettings,
+    pub rate_limit: RateLimitSettings,
+    pub timeouts: TimeoutSettings,
+    #[serde(default)]
+    pub features: FeatureFlags,
+    pub expiry: ExpirySettings,
+    pub publishing: PublishingSettings,
+    pub webhooks: WebhookSettings,
+    pub password_change_notification: PasswordChangeNotificationSettings,
+    #[serde(default)]
+    pub bootstrap: BootstrapSettings,
 }
+impl Settings {
+    /// Catch combinations that deserialize fine but can't work, so that the
+    /// application refuses to start rather than misbehaving later.
+    /// Every problem is reported, not only the first one.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        match reqwest::Url::parse(&self.application.base_url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            Ok(_) => problems.push(format!(
+                "`application.base_url` must use http or https, got {:?}.",
+                self.application.base_url
+            )),
+            Err(e) => problems.push(format!(
+                "`application.base_url` must be an absolute URL, got {:?}: {e}",
+                self.application.base_url
+            )),
+        }
+        if reqwest::Url::parse(&self.email_client.api_url).is_err() {
+            problems.push(format!(
+                "`email_client.api_url` must be an absolute URL, got {:?}.",
+                self.email_client.api_url
+            ));
+        }
+        if self.email_client.sender().is_err() {
+            problems.push(format!(
+                "`email_client.sender` must be an email address, got {:?}.",
+                self.email_client.sender
+            ));
+        }
+        if let Some(domain) = &self.email_client.return_path_domain {
+            if domain.trim().is_empty() || domain.contains('@') {
+                problems.push(format!(
+                    "`email_client.return_path_domain` must be a domain, got {domain:?}."
+                ));
+            } else if self.email_client.sender_verification == SenderVerification::Disabled {
+                problems.push(
+                    "`email_client.return_path_domain` is only checked along with the sender \
+                    signature: turn on `email_client.sender_verification`."
+                        .into(),
+                );
+            }
+        }
+        if self.email_client.transport == EmailTransport::Filesystem
+            && self.email_client.outbox_directory.is_none()
+        {
+            problems.push(
+                "`email_client.outbox_directory` is required by the `filesystem` transport.".into(),
+            );
+        }
+        // The session cookie signing key is derived from it.
+        if self.application.hmac_secret.expose_secret().len() < 64 {
+            problems.push("`application.hmac_secret` must be at least 64 bytes long.".into());
+        }
+        for (name, secret) in [
+            (
+                "email_client.authorization_token",
+                &self.email_client.authorization_token,
+            ),
+            ("webhooks.postmark_secret", &self.webhooks.postmark_secret),
+        ] {
+            if secret.expose_secret().trim().is_empty() {
+                problems.push(format!("`{name}` cannot be empty."));
+            }
+        }
+        for (name, value) in [
+            (
+                "email_client_milliseconds",
+                self.timeouts.email_client_milliseconds,
+            ),
+            (
+                "database_acquire_milliseconds",
+                self.timeouts.database_acquire_milliseconds,
+            ),
+            (
+                "database_statement_milliseconds",
+                self.timeouts.database_statement_milliseconds,
+            ),
+        ] {
+            if !(1..=TimeoutSettings::MAX_MILLISECONDS).contains(&value) {
+                problems.push(format!(
+                    "`timeouts.{name}` must be between 1 and {} milliseconds, got {value}.",
+                    TimeoutSettings::MAX_MILLISECONDS
+                ));
+            }
+        }
+        if self.application.session_max_age_seconds == 0 {
+            problems.push("`application.session_max_age_seconds` cannot be zero.".into());
+        }
+        if self.email_client.sender_verification != SenderVerification::Disabled
+            && self.email_client.account_token.is_none()
+        {
+            problems.push(
+                "`email_client.account_token` is needed to verify the sender signature.".into(),
+            );
+        }
+        if let Some(key) = &self.subscriptions.confirmation_token_signing_key {
+            if key.expose_secret().trim().is_empty() {
+                problems.push(
+                    "`subscriptions.confirmation_token_signing_key` cannot be empty when set."
+                        .into(),
+                );
+            }
+        }
+        if self.features.background_confirmation_emails
+            && self.subscriptions.max_concurrent_confirmation_emails == 0
+        {
+            problems.push(
+                "`subscriptions.max_concurrent_confirmation_emails` must be at least 1 when \
+                background confirmation emails are enabled."
+                    .into(),
+            );
+        }
+        if self.subscriptions.token_cleanup_interval_seconds == 0 {
+            problems
+                .push("`subscriptions.token_cleanup_interval_seconds` must be at least 1.".into());
+        }
+        if self.subscriptions.confirmation_reminder_grace().is_some()
+            && self.subscriptions.confirmation_reminder_interval_seconds == 0
+        {
+            problems.push(
+                "`subscriptions.confirmation_reminder_interval_seconds` must be at least 1 when \
+                confirmation reminders are enabled."
+                    .into(),
+            );
+        }
+        if let Some(url) = &self.webhooks.events_url {
+            if reqwest::Url::parse(url).is_err() {
+                problems.push(format!(
+                    "`webhooks.events_url` must be an absolute URL, got {url:?}."
+                ));
+            }
+        }
+        if let Some(sender) = &self.password_change_notification.sender {
+            if SubscriberEmail::from_str(sender).is_err() {
+                problems.push(format!(
+                    "`password_change_notification.sender` must be an email address, got \
+                    {sender:?}."
+                ));
+            }
+        }
+        if self.webhooks.events_max_attempts < 1 {
+            problems.push("`webhooks.events_max_attempts` must be at least 1.".into());
+        }
+        if self.database.max_connections == 0 {
+            problems.push("`database.max_connections` must be at least 1.".into());
+        }
+        if self.database.slow_query_threshold_milliseconds == Some(0) {
+            problems.push(
+                "`database.slow_query_threshold_milliseconds` must be at least 1 when set.".into(),
+            );
+        }
+        if self.worker.drain_batch == 0 {
+            problems.push("`worker.drain_batch` must be at least 1.".into());
+        }
+        if self.worker.max_retries < 1 {
+            problems.push("`worker.max_retries` must be at least 1.".into());
+        }
+        if self.rate_limit.enabled && self.rate_limit.capacity == 0 {
+            problems.push(
+                "`rate_limit.capacity` must be at least 1 when rate limiting is enabled.".into(),
+            );
+        }
+        match (
+            &self.bootstrap.admin_username,
+            &self.bootstrap.admin_password,
+        ) {
+            (Some(username), Some(password)) => {
+                if username.trim().is_empty() || password.expose_secret().is_empty() {
+                    problems.push(
+                        "`bootstrap.admin_username` and `bootstrap.admin_password` cannot be \
+                        empty."
+                            .into(),
+                    );
+                }
+            }
+            (None, None) => {}
+            _ => problems.push(
+                "`bootstrap.admin_username` and `bootstrap.admin_password` must be set together."
+                    .into(),
+            ),
+        }
+        if let Some(email) = &self.bootstrap.admin_email {
+            if SubscriberEmail::from_str(email).is_err() {
+                problems.push(format!(
+                    "`bootstrap.admin_email` must be an email address, got {email:?}."
+                ));
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Log the effective configuration, once every override is applied.
+    /// Secrets are masked by their `Debug` implementation.
+    pub fn log_summary(&self) {
+        tracing::info!(
+            application = ?self.application,
+            database = ?self.database,
+            email_client = ?self.email_client,
+            redis_url = ?self.redis_url,
+            subscriptions = ?self.subscriptions,
+            worker = ?self.worker,
+            rate_limit = ?self.rate_limit,
+            timeouts = ?self.timeouts,
+            features = ?self.features,
+            expiry = ?self.expiry,
+            publishing = ?self.publishing,
+            webhooks = ?self.webhooks,
+            password_change_notification = ?self.password_change_notification,
+            bootstrap = ?self.bootstrap,
+            "Effective configuration"
+        );
+    }
+}
+
 #[derive(serde::Deserialize, Debug, Clone)]
 pub struct DatabaseSettings {
     pub username: String,
@@ -23,6 +257,41 @@ pub struct DatabaseSettings {
     pub port: u16,
     pub host: String,
     pub database_name: String,
+    /// Size of the connection pool of each process.
+    pub max_connections: u32,
+    pub ssl_mode: DatabaseSslMode,
+    /// CA certificate (PEM) to verify the server against, for `verify_ca`
+    /// and `verify_full`. The system roots are used when unset.
+    pub ssl_root_cert_path: Option<String>,
+    /// Queries taking longer than this are logged, at warn level, with their
+    /// SQL. Off when unset.
+    pub slow_query_threshold_milliseconds: Option<u64>,
+}
+
+/// See `sqlx::postgres::PgSslMode`. Managed providers usually reject
+/// connections that are not encrypted.
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DatabaseSslMode {
+    Disable,
+    Prefer,
+    Require,
+    /// Like `require`, also checking the certificate against a trusted CA.
+    VerifyCa,
+    /// Like `verify_ca`, also checking the host name.
+    VerifyFull,
+}
+
+impl From<DatabaseSslMode> for PgSslMode {
+    fn from(mode: DatabaseSslMode) -> Self {
+        match mode {
+            DatabaseSslMode::Disable => Self::Disable,
+            DatabaseSslMode::Prefer => Self::Prefer,
+            DatabaseSslMode::Require => Self::Require,
+            DatabaseSslMode::VerifyCa => Self::VerifyCa,
+            DatabaseSslMode::VerifyFull => Self::VerifyFull,
+        }
+    }
 }
 
 #[derive(serde::Deserialize, Debug, Clone)]
@@ -32,6 +301,58 @@ pub struct ApplicationSettings {
     pub host: String,
     pub base_url: String,
     pub hmac_secret: Secret<String>,
+    /// External URLs the login form may redirect to through `next`,
+    /// see `utils::RedirectAllowlist`. Local paths are always allowed.
+    pub login_redirect_allowlist: Vec<String>,
+    /// Proxies, as IP addresses or CIDR networks, whose `real_ip_header`
+    /// is believed about the client, see `client_ip::client_ip`.
+    pub trusted_proxies: Vec<String>,
+    /// Header listing the addresses a request was forwarded for.
+    pub real_ip_header: String,
+    /// How request paths are normalized before routing.
+    pub trailing_slash: TrailingSlashPolicy,
+    /// Active sessions each admin can have; logging in beyond it ends the
+    /// oldest one. `0` means unlimited.
+    pub max_sessions_per_user: usize,
+    /// Absolute lifetime of an admin session, from login.
+    pub session_max_age_seconds: u64,
+    /// Language of the public pages for clients whose `Accept-Language`
+    /// names none of the supported ones.
+    pub default_locale: Locale,
+    /// On `SIGTERM` or Ctrl-C, how long requests in flight and the
+    /// background tasks get to finish their current work, all at once, see
+    /// `shutdown`.
+    pub shutdown_timeout_seconds: u64,
+}
+
+impl ApplicationSettings {
+    pub fn session_max_age(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.session_max_age_seconds)
+    }
+
+    pub fn shutdown_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.shutdown_timeout_seconds)
+    }
+}
+
+/// See `actix_web::middleware::TrailingSlash`. Repeated slashes are merged
+/// with every policy.
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrailingSlashPolicy {
+    /// `/subscriptions/` is routed like `/subscriptions`.
+    Trim,
+    /// Trailing slashes are left alone, and must match the route exactly.
+    MergeOnly,
+}
+
+impl From<TrailingSlashPolicy> for actix_web::middleware::TrailingSlash {
+    fn from(policy: TrailingSlashPolicy) -> Self {
+        match policy {
+            TrailingSlashPolicy::Trim => Self::Trim,
+            TrailingSlashPolicy::MergeOnly => Self::MergeOnly,
+        }
+    }
 }
 
 #[derive(serde::Deserialize, Debug, Clone)]
@@ -39,7 +360,426 @@ pub struct EmailClientSettings {
     pub api_url: String,
     pub sender: String,
     pub authorization_token: Secret<String>,
-    pub timeout_milliseconds: u64,
+    /// Lowest TLS version accepted from the provider: `1.2` or `1.3`.
+    pub min_tls_version: String,
+    /// PEM file of the only CA trusted for the provider, if pinned.
+    pub pinned_ca_certificate_path: Option<String>,
+    pub max_idle_connections_per_host: usize,
+    /// Emails sent at the same time by one client. `0` means unlimited.
+    pub max_concurrent_requests: usize,
+    /// Check on startup that `sender` has a confirmed sender signature.
+    pub sender_verification: SenderVerification,
+    /// Postmark account token, needed to list sender signatures.
+    pub account_token: Option<Secret<String>>,
+    pub transport: EmailTransport,
+    /// Custom Return-Path domain expected on the sender signature, e.g.
+    /// `pm-bounces.example.com`. Postmark sets the Return-Path of every
+    /// email from the signature, routing bounces there: it is configured in
+    /// Postmark, and only checked on startup with `sender_verification`.
+    pub return_path_domain: Option<String>,
+    /// Where emails are written with the `filesystem` transport.
+    pub outbox_directory: Option<String>,
+    /// Longer subjects are cut to this many graphemes, ellipsis included.
+    /// `0` means unlimited.
+    pub max_subject_length: usize,
+}
+
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailTransport {
+    /// Send through the provider's API.
+    Http,
+    /// Write each email to `outbox_directory` as a JSON file and send
+    /// nothing, e.g. to work on templates locally.
+    Filesystem,
+}
+
+/// What to do on startup when the sender is not a confirmed signature:
+/// the provider would reject every email.
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SenderVerification {
+    /// Don't check, sparing a request to the provider.
+    Disabled,
+    Warn,
+    Abort,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct SubscriptionSettings {
+    /// Maximum length of a subscriber name, in graphemes.
+    pub max_name_length: usize,
+    /// Characters turned away in subscriber names, as a string. Unset, see
+    /// `SubscriberName::DEFAULT_FORBIDDEN_CHARACTERS`.
+    pub name_forbidden_characters: Option<String>,
+    /// `POST /subscriptions` requests handled at once, across all clients.
+    /// Extra ones get a `503`, see `load_shedding`. `0` means unlimited.
+    pub max_in_flight_requests: usize,
+    /// Also turn away addresses that the email provider is likely to reject,
+    /// see `SubscriberEmail::validate_with`.
+    pub strict_email_validation: bool,
+    /// How long a confirmation link stays valid.
+    pub confirmation_token_ttl_hours: u64,
+    /// How often expired and used confirmation tokens are purged.
+    pub token_cleanup_interval_seconds: u64,
+    /// When set, confirmation tokens carry an HMAC signature and forged
+    /// ones are turned away before the database is queried. Links sent
+    /// before the key was set stop working.
+    pub confirmation_token_signing_key: Option<Secret<String>>,
+    /// How many background confirmation emails can be in flight at once.
+    pub max_concurrent_confirmation_emails: usize,
+    /// How many times storing a new subscriber is attempted again, from the
+    /// start of its transaction, when it fails with a transient error up to
+    /// and including the commit.
+    pub max_transaction_retries: u32,
+    pub transaction_retry_delay_milliseconds: u64,
+    /// Subject of the email confirming an unsubscription, see
+    /// `FeatureFlags::unsubscribe_confirmation_emails`.
+    pub unsubscribe_confirmation_subject: String,
+    /// Opening line of that email, followed by a link to subscribe again.
+    pub unsubscribe_confirmation_message: String,
+    /// How many times a pending subscriber can ask for their confirmation
+    /// email to be sent again, whatever the rate limit. `0` disables resends.
+    pub max_confirmation_resends: u32,
+    /// Confirmed subscribers allowed by the plan of the deployment: once
+    /// reached, confirmations are refused. `0` means unlimited.
+    pub max_confirmed_subscribers: u64,
+    /// How many times the database work behind a confirmation link is
+    /// attempted again after a transient failure, before giving up with a
+    /// `503`.
+    pub confirmation_max_retries: u32,
+    pub confirmation_retry_delay_milliseconds: u64,
+    /// Pending subscribers are reminded to confirm once, this long after
+    /// their confirmation email was sent. Subscribers whose email hasn't
+    /// gone out yet are never reminded. `0` disables reminders.
+    pub confirmation_reminder_grace_seconds: u64,
+    /// How often subscribers due a reminder are looked for.
+    pub confirmation_reminder_interval_seconds: u64,
+    /// Failed sends after which a subscriber's reminder is given up on.
+    pub confirmation_reminder_max_attempts: i32,
+}
+
+impl SubscriptionSettings {
+    pub fn max_in_flight_requests(&self) -> Option<usize> {
+        (self.max_in_flight_requests > 0).then_some(self.max_in_flight_requests)
+    }
+
+    pub fn name_forbidden_characters(&self) -> &str {
+        self.name_forbidden_characters
+            .as_deref()
+            .unwrap_or(SubscriberName::DEFAULT_FORBIDDEN_CHARACTERS)
+    }
+
+    pub fn confirmed_subscriber_cap(&self) -> Option<u64> {
+        (self.max_confirmed_subscribers > 0).then_some(self.max_confirmed_subscribers)
+    }
+
+    pub fn transaction_retry_delay(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.transaction_retry_delay_milliseconds)
+    }
+
+    pub fn confirmation_retry_delay(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.confirmation_retry_delay_milliseconds)
+    }
+
+    pub fn confirmation_token_ttl(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.confirmation_token_ttl_hours * 60 * 60)
+    }
+
+    pub fn token_cleanup_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.token_cleanup_interval_seconds)
+    }
+
+    pub fn confirmation_reminder_grace(&self) -> Option<std::time::Duration> {
+        (self.confirmation_reminder_grace_seconds > 0)
+            .then(|| std::time::Duration::from_secs(self.confirmation_reminder_grace_seconds))
+    }
+
+    pub fn confirmation_reminder_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.confirmation_reminder_interval_seconds)
+    }
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct WorkerSettings {
+    /// Number of failed attempts after which a delivery task is dead-lettered.
+    pub max_retries: i16,
+    pub retry_base_delay_milliseconds: u64,
+    /// Maximum number of tasks executed per wake-up of the worker loop.
+    pub drain_batch: usize,
+    /// Maximum number of emails sent over any 24 hours window, to protect
+    /// the sender reputation. `0` means unlimited.
+    pub daily_send_cap: u64,
+    /// Subject of issues with a category, from `{category}` and `{title}`.
+    pub category_subject_format: String,
+    /// Path to an HTML template wrapped around the body of issues, see
+    /// `EmailLayout`. Issues are sent as written when unset.
+    pub html_layout: Option<EmailLayout>,
+    /// Name of the newsletter, shown by the layout.
+    pub brand: String,
+    /// Wait after the first batch that fails because the database is
+    /// unreachable. It doubles with every failure in a row.
+    pub db_backoff_base_milliseconds: u64,
+    /// Longest wait between attempts to reach the database.
+    pub db_backoff_max_seconds: u64,
+    /// When set, deliveries wait for the end of the recipient's quiet hours.
+    /// Confirmation emails are sent right away regardless.
+    pub quiet_hours: Option<QuietHours>,
+    /// Deliver issues to each subscriber in the order they were enqueued,
+    /// e.g. for multi-part series. A task then waits for every earlier task
+    /// of its subscriber, retries included, which costs some throughput.
+    pub ordered_per_subscriber: bool,
+    /// Allow issues to opt into tracking opens of their plain text body,
+    /// where a pixel can't be used: a per-subscriber link to the issue
+    /// online is appended, and following it counts as an open. Off by
+    /// default, for privacy.
+    pub text_open_tracking: bool,
+    #[serde(skip, default = "system_clock")]
+    pub clock: Arc<dyn Clock>,
+}
+
+impl WorkerSettings {
+    /// Placeholders are substituted in a single pass, so that braces in the
+    /// title or the category are kept as they are.
+    pub fn subject(&self, title: &str, category: Option<&str>) -> String {
+        let Some(category) = category else {
+            return title.to_string();
+        };
+        let mut subject = String::with_capacity(self.category_subject_format.len() + title.len());
+        let mut rest = self.category_subject_format.as_str();
+        while let Some(start) = rest.find('{') {
+            subject.push_str(&rest[..start]);
+            rest = &rest[start..];
+            if let Some(after) = rest.strip_prefix("{category}") {
+                subject.push_str(category);
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("{title}") {
+                subject.push_str(title);
+                rest = after;
+            } else {
+                subject.push('{');
+                rest = &rest[1..];
+            }
+        }
+        subject.push_str(rest);
+        subject
+    }
+
+    /// Exponential backoff before the `n_retries`-th retry of a delivery task.
+    pub fn retry_delay(&self, n_retries: i16) -> std::time::Duration {
+        let exponent = n_retries.saturating_sub(1).clamp(0, 16) as u32;
+        std::time::Duration::from_millis(self.retry_base_delay_milliseconds) * 2u32.pow(exponent)
+    }
+
+    /// Wait before the next batch after `consecutive_failures` batches in a
+    /// row could not reach the database.
+    pub fn db_backoff(&self, consecutive_failures: u32) -> std::time::Duration {
+        let exponent = consecutive_failures.saturating_sub(1).min(16);
+        let backoff = std::time::Duration::from_millis(self.db_backoff_base_milliseconds)
+            * 2u32.pow(exponent);
+        backoff.min(std::time::Duration::from_secs(self.db_backoff_max_seconds))
+    }
+}
+
+/// Per-client limits applied to `POST /subscriptions` and `POST /login`.
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct RateLimitSettings {
+    pub enabled: bool,
+    /// Number of requests a client can burst before being limited.
+    pub capacity: u32,
+    /// Time it takes for a client to regain one request.
+    pub refill_interval_milliseconds: u64,
+    /// Explain the limit in a JSON body on 429 responses.
+    pub json_body: bool,
+    /// Clients that are never limited, see `rate_limit::ExemptNetworks`.
+    /// Behind a proxy, see `application.trusted_proxies`.
+    pub exempt_networks: Vec<String>,
+}
+
+impl RateLimitSettings {
+    pub fn refill_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.refill_interval_milliseconds)
+    }
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct ExpirySettings {
+    /// Grace granted past every expiry, to absorb clock differences
+    /// between app instances. See `expiry::is_expired`.
+    pub clock_skew_tolerance_seconds: u64,
+}
+
+impl ExpirySettings {
+    pub fn clock_skew_tolerance(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.clock_skew_tolerance_seconds)
+    }
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct PublishingSettings {
+    /// Keep issues published while no subscriber is confirmed. They are
+    /// never delivered, since only confirmed subscribers get queued.
+    pub record_issues_without_recipients: bool,
+    /// Issues published sooner than this after the previous one are refused,
+    /// unless the publisher insists. `0` disables the check.
+    pub min_seconds_between_issues: u64,
+    /// Issues with the same title and content as one published within this
+    /// many seconds are refused, unless the publisher insists. `0` disables
+    /// the check.
+    pub duplicate_window_seconds: u64,
+    /// Make the title and content of an issue part of the identity of its
+    /// idempotency key: a key reused with different content publishes a new
+    /// issue instead of replaying the response to the first one.
+    /// `idempotency_key_usage` then reports the latest use of a key.
+    pub idempotency_per_content: bool,
+}
+
+impl PublishingSettings {
+    pub fn min_interval_between_issues(&self) -> Option<std::time::Duration> {
+        (self.min_seconds_between_issues > 0)
+            .then(|| std::time::Duration::from_secs(self.min_seconds_between_issues))
+    }
+
+    pub fn duplicate_window(&self) -> Option<std::time::Duration> {
+        (self.duplicate_window_seconds > 0)
+            .then(|| std::time::Duration::from_secs(self.duplicate_window_seconds))
+    }
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct WebhookSettings {
+    /// Expected in the `X-Webhook-Secret` header of Postmark webhook calls.
+    pub postmark_secret: Secret<String>,
+    /// Where subscription events are posted, as JSON, see
+    /// `webhook_delivery`. Unset, no events are recorded.
+    pub events_url: Option<String>,
+    /// Attempts at posting an event before it is moved to the dead letters.
+    pub events_max_attempts: i16,
+    pub events_retry_base_delay_milliseconds: u64,
+}
+
+impl WebhookSettings {
+    /// Exponential backoff after the `n_attempts`-th failed attempt at
+    /// posting an event, like `WorkerSettings::retry_delay`.
+    pub fn events_retry_delay(&self, n_attempts: i16) -> std::time::Duration {
+        let exponent = n_attempts.saturating_sub(1).clamp(0, 16) as u32;
+        std::time::Duration::from_millis(self.events_retry_base_delay_milliseconds)
+            * 2u32.pow(exponent)
+    }
+}
+
+/// Initial admin account, created on startup while there are no users,
+/// see `ensure_admin_exists`. The password is best passed through
+/// `APP_BOOTSTRAP__ADMIN_PASSWORD` rather than a configuration file.
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+pub struct BootstrapSettings {
+    pub admin_username: Option<String>,
+    pub admin_password: Option<Secret<String>>,
+    /// Where security notifications about the account are sent.
+    pub admin_email: Option<String>,
+}
+
+/// The email telling a user that their password was changed, so that an
+/// unauthorized change doesn't go unnoticed. Users without an email address
+/// are not notified.
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct PasswordChangeNotificationSettings {
+    pub enabled: bool,
+    /// Sent from this address rather than `email_client.sender`.
+    pub sender: Option<String>,
+    pub subject: String,
+    pub message: String,
+}
+
+impl PasswordChangeNotificationSettings {
+    pub fn sender(&self) -> Result<Option<SubscriberEmail>, String> {
+        self.sender
+            .as_deref()
+            .map(SubscriberEmail::from_str)
+            .transpose()
+    }
+}
+
+/// Optional behaviors, injected as `web::Data<FeatureFlags>` so that
+/// handlers check them the same way. Flags left out of the configuration
+/// keep their default.
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FeatureFlags {
+    /// Serve the built-in subscription form on `GET /subscribe`.
+    /// Deployments with their own frontend can turn it off.
+    pub serve_subscribe_form: bool,
+    /// Send confirmation emails from a background task, so that `subscribe`
+    /// doesn't wait on the email provider. Failed sends are only logged.
+    pub background_confirmation_emails: bool,
+    /// Generate the plain text body of an issue from the HTML one when it
+    /// is left empty.
+    pub derive_text_from_html: bool,
+    /// Log a redacted copy of rejected `POST /subscriptions` bodies, at debug
+    /// level. A troubleshooting aid.
+    pub log_request_bodies: bool,
+    /// Email subscribers once they unsubscribe, with a link to subscribe
+    /// again. Off by default, to keep sends down.
+    pub unsubscribe_confirmation_emails: bool,
+    /// Send admins who are already logged in on from `GET /login`, as if
+    /// they had just logged in, rather than showing them the form again.
+    pub skip_login_form_when_logged_in: bool,
+    /// Log every `GET /health_check` at info level. Off by default, since
+    /// orchestrators probe it every few seconds.
+    pub log_health_checks: bool,
+    /// Also subscribe on `GET /subscriptions/new?name=..&email=..`, for
+    /// embedded forms that can only issue GET. A compatibility shim, off by
+    /// default: `POST /subscriptions` is the endpoint to use.
+    pub subscribe_via_get: bool,
+    /// Keep admins out of `/admin` until they have followed a link sent to
+    /// their email, so that each of them has a working address for
+    /// security notices. See `routes::verify_email_page`.
+    pub require_verified_admin_email: bool,
+}
+
+impl Default for FeatureFlags {
+    fn default() -> Self {
+        Self {
+            serve_subscribe_form: true,
+            background_confirmation_emails: false,
+            derive_text_from_html: true,
+            log_request_bodies: false,
+            unsubscribe_confirmation_emails: false,
+            skip_login_form_when_logged_in: true,
+            log_health_checks: false,
+            subscribe_via_get: false,
+            require_verified_admin_email: false,
+        }
+    }
+}
+
+/// Every timeout used by the application, in milliseconds.
+/// They are checked by [`Settings::validate`], so that a zero or absurdly
+/// long timeout can't make it to production.
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct TimeoutSettings {
+    /// Requests to the email delivery API.
+    pub email_client_milliseconds: u64,
+    /// Waiting for a connection from the database pool.
+    pub database_acquire_milliseconds: u64,
+    /// Postgres `statement_timeout`, for every connection in the pool.
+    pub database_statement_milliseconds: u64,
+}
+
+impl TimeoutSettings {
+    pub const MAX_MILLISECONDS: u64 = 5 * 60 * 1000;
+
+    pub fn email_client(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.email_client_milliseconds)
+    }
+
+    pub fn database_acquire(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.database_acquire_milliseconds)
+    }
+
+    pub fn database_statement(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.database_statement_milliseconds)
+    }
 }
 
 impl EmailClientSettings {
@@ -47,19 +787,59 @@ impl EmailClientSettings {
         SubscriberEmail::from_str(&self.sender)
     }
 
-    pub fn timeout(&self) -> std::time::Duration {
-        std::time::Duration::from_millis(self.timeout_milliseconds)
+    pub fn tls_policy(&self) -> Result<TlsPolicy, anyhow::Error> {
+        let min_tls_version = match self.min_tls_version.as_str() {
+            "1.2" => reqwest::tls::Version::TLS_1_2,
+            "1.3" => reqwest::tls::Version::TLS_1_3,
+            other => anyhow::bail!(
+                "`email_client.min_tls_version` must be either `1.2` or `1.3`, got `{other}`."
+            ),
+        };
+        let pinned_ca_pem = self
+            .pinned_ca_certificate_path
+            .as_ref()
+            .map(|path| {
+                std::fs::read(path).map_err(|e| {
+                    anyhow::anyhow!("Failed to read the pinned CA certificate at {path}: {e}")
+                })
+            })
+            .transpose()?;
+        Ok(TlsPolicy {
+            min_tls_version,
+            pinned_ca_pem,
+        })
+    }
+
+    pub fn connection_limits(&self) -> ConnectionLimits {
+        ConnectionLimits {
+            max_idle_per_host: self.max_idle_connections_per_host,
+            max_in_flight: (self.max_concurrent_requests > 0)
+                .then_some(self.max_concurrent_requests),
+        }
     }
 
-    pub fn client(self) -> Result<EmailClient, anyhow::Error> {
+    pub fn client(self, timeout: std::time::Duration) -> Result<EmailClient, anyhow::Error> {
         let sender_email = self.sender().expect("Invalid sender email address.");
-        let timeout = self.timeout();
-        Ok(EmailClient::new(
+        let tls = self.tls_policy()?;
+        let limits = self.connection_limits();
+        let client = EmailClient::with_limits(
             sender_email,
             self.api_url,
             self.authorization_token,
             timeout,
-        )?)
+            &tls,
+            &limits,
+        )?
+        .with_max_subject_length((self.max_subject_length > 0).then_some(self.max_subject_length));
+        match (self.transport, self.outbox_directory) {
+            (EmailTransport::Http, _) => Ok(client),
+            (EmailTransport::Filesystem, Some(directory)) => {
+                Ok(client.writing_to_outbox(directory))
+            }
+            (EmailTransport::Filesystem, None) => anyhow::bail!(
+                "`email_client.outbox_directory` is required by the `filesystem` transport."
+            ),
+        }
     }
 }
 
@@ -78,7 +858,16 @@ pub fn get_configuration() -> Result<Settings, config::ConfigError> {
         config::File::from(configuration_directory.join(environment.as_str())).required(true),
     )?;
     settings.merge(config::Environment::with_prefix("app").separator("__"))?;
-    settings.try_into()
+    let settings: Settings = settings.try_into()?;
+    settings
+        .validate()
+        .map_err(|problems| config::ConfigError::Message(describe_problems(&problems)))?;
+    Ok(settings)
+}
+
+/// One line per problem reported by `Settings::validate`.
+pub fn describe_problems(problems: &[String]) -> String {
+    format!("Invalid configuration:\n- {}", problems.join("\n- "))
 }
 
 pub enum Environment {
@@ -111,11 +900,21 @@ impl TryFrom<String> for Environment {
 
 impl DatabaseSettings {
     pub fn without_db(&self) -> PgConnectOptions {
-        PgConnectOptions::new()
+        let options = PgConnectOptions::new()
             .host(&self.host)
             .username(&self.username)
             .password(self.password.expose_secret())
             .port(self.port)
+            .ssl_mode(self.ssl_mode.into());
+        match &self.ssl_root_cert_path {
+            Some(path) => options.ssl_root_cert(path),
+            None => options,
+        }
+    }
+
+    pub fn slow_query_threshold(&self) -> Option<std::time::Duration> {
+        self.slow_query_threshold_milliseconds
+            .map(std::time::Duration::from_millis)
     }
 
     pub fn with_db(&self) -> PgConnectOptions {
@@ -124,3 +923,192 @@ impl DatabaseSettings {
             .log_statements(tracing::log::LevelFilter::Trace)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use sqlx::postgres::PgSslMode;
+
+    use super::{
+        get_configuration, DatabaseSettings, DatabaseSslMode, EmailClientSettings, FeatureFlags,
+    };
+
+    #[test]
+    fn a_zero_email_timeout_is_rejected() {
+        let mut settings = get_configuration().expect("Failed to read configuration.");
+        settings.timeouts.email_client_milliseconds = 0;
+        let problems = settings.validate().unwrap_err();
+        assert_eq!(problems.len(), 1, "{problems:#?}");
+        assert!(
+            problems[0].contains("`timeouts.email_client_milliseconds` must be between 1 and"),
+            "{problems:#?}"
+        );
+    }
+
+    #[test]
+    fn an_absurdly_long_timeout_is_rejected() {
+        let mut settings = get_configuration().expect("Failed to read configuration.");
+        settings.timeouts.database_statement_milliseconds = 24 * 60 * 60 * 1000;
+        let problems = settings.validate().unwrap_err();
+        assert!(
+            problems[0].contains("database_statement_milliseconds"),
+            "{problems:#?}"
+        );
+    }
+
+    #[test]
+    fn placeholders_in_the_title_or_category_are_not_expanded() {
+        let mut worker = get_configuration().unwrap().worker;
+        worker.category_subject_format = "[{category}] {title} {unknown}".into();
+        assert_eq!(
+            worker.subject("Use {category} and {title}", Some("{title}")),
+            "[{title}] Use {category} and {title} {unknown}"
+        );
+        assert_eq!(worker.subject("{category}", None), "{category}");
+    }
+
+    fn email_client_settings(min_tls_version: &str) -> EmailClientSettings {
+        EmailClientSettings {
+            api_url: "https://example.com".into(),
+            sender: "test@example.com".into(),
+            authorization_token: secrecy::Secret::new("token".into()),
+            min_tls_version: min_tls_version.into(),
+            pinned_ca_certificate_path: None,
+            max_idle_connections_per_host: 10,
+            max_concurrent_requests: 0,
+            sender_verification: super::SenderVerification::Disabled,
+            account_token: None,
+            transport: super::EmailTransport::Http,
+            return_path_domain: None,
+            outbox_directory: None,
+            max_subject_length: 0,
+        }
+    }
+
+    #[test]
+    fn tls_versions_below_1_2_are_rejected() {
+        for version in ["1.0", "1.1", "ssl3"] {
+            let err = email_client_settings(version)
+                .tls_policy()
+                .err()
+                .unwrap()
+                .to_string();
+            assert!(err.contains("`email_client.min_tls_version`"), "{err}");
+        }
+    }
+
+    #[test]
+    fn tls_1_3_can_be_required() {
+        let policy = email_client_settings("1.3").tls_policy().unwrap();
+        assert_eq!(policy.min_tls_version, reqwest::tls::Version::TLS_1_3);
+    }
+
+    #[test]
+    fn a_missing_pinned_ca_file_is_an_error() {
+        let mut settings = email_client_settings("1.2");
+        settings.pinned_ca_certificate_path = Some("/does/not/exist.pem".into());
+        assert!(settings.tls_policy().is_err());
+    }
+
+    fn database_settings(ssl_mode: DatabaseSslMode) -> DatabaseSettings {
+        DatabaseSettings {
+            username: "postgres".into(),
+            password: secrecy::Secret::new("password".into()),
+            port: 5432,
+            host: "localhost".into(),
+            database_name: "newsletter".into(),
+            max_connections: 10,
+            ssl_mode,
+            ssl_root_cert_path: None,
+            slow_query_threshold_milliseconds: None,
+        }
+    }
+
+    #[test]
+    fn connect_options_use_the_configured_ssl_mode() {
+        let options = database_settings(DatabaseSslMode::Disable).with_db();
+        assert!(matches!(options.get_ssl_mode(), PgSslMode::Disable));
+
+        let options = database_settings(DatabaseSslMode::Require).with_db();
+        assert!(matches!(options.get_ssl_mode(), PgSslMode::Require));
+
+        let options = database_settings(DatabaseSslMode::VerifyFull).without_db();
+        assert!(matches!(options.get_ssl_mode(), PgSslMode::VerifyFull));
+    }
+
+    #[test]
+    fn the_default_configuration_is_valid() {
+        let settings = get_configuration().expect("Failed to read configuration.");
+        assert_eq!(settings.validate(), Ok(()));
+    }
+
+    #[test]
+    fn every_invalid_setting_is_reported_at_once() {
+        let mut settings = get_configuration().expect("Failed to read configuration.");
+        settings.application.base_url = "ftp://example.com".into();
+        settings.application.hmac_secret = secrecy::Secret::new("short".into());
+        settings.email_client.authorization_token = secrecy::Secret::new(" ".into());
+        settings.database.max_connections = 0;
+        settings.worker.drain_batch = 0;
+        settings.email_client.return_path_domain = Some("bounces@example.com".into());
+        settings.timeouts.email_client_milliseconds = 0;
+
+        let problems = settings.validate().unwrap_err();
+
+        assert_eq!(problems.len(), 7, "{problems:#?}");
+        for setting in [
+            "application.base_url",
+            "application.hmac_secret",
+            "email_client.authorization_token",
+            "database.max_connections",
+            "worker.drain_batch",
+            "email_client.return_path_domain",
+            "timeouts.email_client_milliseconds",
+        ] {
+            assert!(
+                problems.iter().any(|p| p.contains(setting)),
+                "{setting} missing from {problems:#?}"
+            );
+        }
+    }
+
+    #[test]
+    fn a_zero_token_cleanup_interval_is_rejected() {
+        let mut settings = get_configuration().expect("Failed to read configuration.");
+        settings.subscriptions.token_cleanup_interval_seconds = 0;
+        let problems = settings.validate().unwrap_err();
+        assert_eq!(problems.len(), 1, "{problems:#?}");
+        assert!(
+            problems[0].contains("`subscriptions.token_cleanup_interval_seconds`"),
+            "{problems:#?}"
+        );
+    }
+
+    #[test]
+    fn a_zero_reminder_interval_is_only_rejected_with_reminders_enabled() {
+        let mut settings = get_configuration().expect("Failed to read configuration.");
+        settings
+            .subscriptions
+            .confirmation_reminder_interval_seconds = 0;
+        settings.subscriptions.confirmation_reminder_grace_seconds = 0;
+        assert_eq!(settings.validate(), Ok(()));
+
+        settings.subscriptions.confirmation_reminder_grace_seconds = 3600;
+        let problems = settings.validate().unwrap_err();
+        assert_eq!(problems.len(), 1, "{problems:#?}");
+        assert!(
+            problems[0].contains("`subscriptions.confirmation_reminder_interval_seconds`"),
+            "{problems:#?}"
+        );
+    }
+
+    #[test]
+    fn feature_flags_left_out_keep_their_default() {
+        let mut settings = config::Config::default();
+        settings.set("log_request_bodies", true).unwrap();
+        let flags: FeatureFlags = settings.try_into().unwrap();
+        assert!(flags.log_request_bodies);
+        assert!(flags.serve_subscribe_form);
+        assert!(flags.derive_text_from_html);
+        assert!(!flags.background_confirmation_emails);
+    }
+}
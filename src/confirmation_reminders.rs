@@ -0,0 +1,233 @@
+use std::{str::FromStr, time::Duration};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    clock::{system_clock, Clock},
+    configuration::{Settings, SubscriptionSettings},
+    domain::{SubscriberEmail, SubscriptionStatus},
+    email_client::{AuthorizationToken, EmailClient, SendEmailError},
+    routes::{
+        new_confirmation_token, record_confirmation_email_sent, send_confirmation_email,
+        store_token,
+    },
+    shutdown::ShutdownSignal,
+    signing::SignedLinks,
+    startup::get_connection_pool,
+};
+
+/// Send a fresh confirmation link to every pending subscriber whose
+/// confirmation email went out more than `grace` before `now` and who
+/// hasn't been reminded yet.
+///
+/// Subscribers whose confirmation email is still waiting to be sent, e.g.
+/// with `features.background_confirmation_emails`, are left alone: the
+/// reminder could otherwise reach them before the email it reminds them of.
+/// A reminder that can't be sent is skipped for the rest of the run and
+/// attempted again on the next one, up to
+/// `settings.confirmation_reminder_max_attempts` times.
+#[tracing::instrument(skip(pool, email_client, links, settings))]
+pub async fn send_confirmation_reminders(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    links: &SignedLinks,
+    settings: &SubscriptionSettings,
+    grace: Duration,
+    now: DateTime<Utc>,
+) -> Result<u64, anyhow::Error> {
+    let sent_before = now - chrono::Duration::from_std(grace)?;
+    let mut n_sent = 0;
+    // Left for the next run, so that they don't hold up the others.
+    let mut failed: Vec<Uuid> = Vec::new();
+    loop {
+        let mut transaction = pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool")?;
+        let subscriber = sqlx::query!(
+            r#"
+            SELECT id, email
+            FROM subscriptions
+            WHERE
+                status = $1 AND
+                reminder_sent_at IS NULL AND
+                last_confirmation_email_sent_at < $2 AND
+                NOT (id = ANY($3))
+            ORDER BY last_confirmation_email_sent_at
+            FOR UPDATE
+            SKIP LOCKED
+            LIMIT 1
+            "#,
+            SubscriptionStatus::Pending.as_str(),
+            sent_before,
+            &failed
+        )
+        .fetch_optional(&mut *transaction)
+        .await
+        .context("Failed to look up a subscriber due a reminder")?;
+        let Some(subscriber) = subscriber else {
+            return Ok(n_sent);
+        };
+        // Marked first, so that a stored address that no longer parses
+        // isn't picked again on every run.
+        sqlx::query!(
+            "UPDATE subscriptions SET reminder_sent_at = $2 WHERE id = $1",
+            subscriber.id,
+            now
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to mark the reminder as sent")?;
+        let email = match SubscriberEmail::from_str(&subscriber.email) {
+            Ok(email) => email,
+            Err(e) => {
+                tracing::warn!(
+                    error.message = %e,
+                    "Skipping a confirmation reminder. The stored address is invalid",
+                );
+                transaction.commit().await?;
+                continue;
+            }
+        };
+        let token = new_confirmation_token(settings);
+        store_token(&mut transaction, subscriber.id, &token, now)
+            .await
+            .context("Failed to store the confirmation token for a reminder.")?;
+        if let Err(e) = send_confirmation_email(
+            email_client,
+            &email,
+            links.base_url(),
+            &token,
+            &links.subscriber_reference(subscriber.id),
+            settings.confirmation_token_ttl_hours,
+        )
+        .await
+        {
+            // Neither the token nor the mark are kept.
+            transaction.rollback().await?;
+            record_failed_attempt(pool, subscriber.id, settings, now, &e).await?;
+            failed.push(subscriber.id);
+            continue;
+        }
+        record_confirmation_email_sent(&mut *transaction, subscriber.id, now)
+            .await
+            .context("Failed to record the confirmation reminder.")?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to send a confirmation reminder.")?;
+        n_sent += 1;
+    }
+}
+
+/// Count the failed attempt, giving up on the reminder after
+/// `settings.confirmation_reminder_max_attempts`.
+async fn record_failed_attempt(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    settings: &SubscriptionSettings,
+    now: DateTime<Utc>,
+    e: &SendEmailError,
+) -> Result<(), anyhow::Error> {
+    let n_attempts = sqlx::query_scalar!(
+        r#"
+        UPDATE subscriptions
+        SET
+            reminder_attempts = reminder_attempts + 1,
+            reminder_sent_at = CASE WHEN reminder_attempts + 1 >= $3 THEN $2::timestamptz END
+        WHERE id = $1
+        RETURNING reminder_attempts
+        "#,
+        subscriber_id,
+        now,
+        settings.confirmation_reminder_max_attempts
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to record a failed confirmation reminder")?;
+    if n_attempts >= settings.confirmation_reminder_max_attempts {
+        tracing::error!(
+            n_attempts,
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to send a confirmation reminder too many times. Giving up on it.",
+        );
+    } else {
+        tracing::warn!(
+            n_attempts,
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to send a confirmation reminder. Retrying on the next run.",
+        );
+    }
+    Ok(())
+}
+
+async fn reminder_loop(
+    pool: PgPool,
+    email_client: EmailClient,
+    links: SignedLinks,
+    settings: SubscriptionSettings,
+    clock: std::sync::Arc<dyn Clock>,
+    shutdown: ShutdownSignal,
+) -> Result<(), anyhow::Error> {
+    let Some(grace) = settings.confirmation_reminder_grace() else {
+        // Reminders are off. Returning would be reported as the task exiting.
+        shutdown.triggered().await;
+        return Ok(());
+    };
+    while !shutdown.is_triggered() {
+        match send_confirmation_reminders(
+            &pool,
+            &email_client,
+            &links,
+            &settings,
+            grace,
+            clock.now(),
+        )
+        .await
+        {
+            Ok(n_sent) => tracing::info!(n_sent, "Sent confirmation reminders"),
+            Err(e) => tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to send confirmation reminders",
+            ),
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(settings.confirmation_reminder_interval()) => {},
+            _ = shutdown.triggered() => {},
+        }
+    }
+    Ok(())
+}
+
+/// `email_token` is shared with the API, see `run_worker_until_stopped`.
+/// Returns once `shutdown` is triggered and the current round is done.
+pub async fn run_confirmation_reminders_until_stopped(
+    configuration: Settings,
+    email_token: AuthorizationToken,
+    shutdown: ShutdownSignal,
+) -> Result<(), anyhow::Error> {
+    let pool = get_connection_pool(&configuration.database, &configuration.timeouts);
+    let email_client = configuration
+        .email_client
+        .client(configuration.timeouts.email_client())?
+        .sharing_token(email_token);
+    let links = SignedLinks::new(
+        configuration.application.base_url,
+        configuration.application.hmac_secret,
+    );
+    reminder_loop(
+        pool,
+        email_client,
+        links,
+        configuration.subscriptions,
+        system_clock(),
+        shutdown,
+    )
+    .await
+}
@@ -0,0 +1,243 @@
+use std::{future::Future, time::Duration};
+
+use actix_web::{error::InternalError, http::StatusCode};
+
+/// What a failed query means for the caller, whatever the query was.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbErrorKind {
+    /// The change clashes with a row that is already there (a unique
+    /// constraint violation).
+    Conflict,
+    /// The database is unreachable, or too busy to hand out a connection.
+    Unavailable,
+    /// The transaction ran into a concurrent one (a serialization failure
+    /// or a deadlock). Trying again from the start usually succeeds.
+    Retriable,
+    /// A query expecting a row didn't find any.
+    NotFound,
+    Other,
+}
+
+impl DbErrorKind {
+    /// Whether trying again later may succeed.
+    pub fn is_transient(self) -> bool {
+        matches!(self, DbErrorKind::Unavailable | DbErrorKind::Retriable)
+    }
+
+    /// The status to answer with when a request fails because of it. A
+    /// missing row is left at `500`: which rows a request may legitimately
+    /// miss is up to its handler.
+    pub fn status_code(self) -> StatusCode {
+        match self {
+            DbErrorKind::Conflict => StatusCode::CONFLICT,
+            DbErrorKind::Unavailable | DbErrorKind::Retriable => StatusCode::SERVICE_UNAVAILABLE,
+            DbErrorKind::NotFound | DbErrorKind::Other => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+pub fn classify_db_error(e: &sqlx::Error) -> DbErrorKind {
+    match e {
+        sqlx::Error::Database(e) if e.is_unique_violation() => DbErrorKind::Conflict,
+        sqlx::Error::Database(e) => match e.code().as_deref() {
+            Some("40001" | "40P01") => DbErrorKind::Retriable,
+            _ => DbErrorKind::Other,
+        },
+        sqlx::Error::Io(_)
+        | sqlx::Error::Tls(_)
+        | sqlx::Error::PoolTimedOut
+        | sqlx::Error::PoolClosed => DbErrorKind::Unavailable,
+        sqlx::Error::RowNotFound => DbErrorKind::NotFound,
+        _ => DbErrorKind::Other,
+    }
+}
+
+/// The kind of the first database error in the chain of `e`, if there is
+/// one.
+pub fn db_error_kind(e: &anyhow::Error) -> Option<DbErrorKind> {
+    e.chain()
+        .find_map(|cause| cause.downcast_ref::<sqlx::Error>())
+        .map(classify_db_error)
+}
+
+/// An error that may be worth trying again for, see
+/// `DbErrorKind::is_transient`.
+pub trait TransientError: std::fmt::Display {
+    fn is_transient(&self) -> bool;
+}
+
+impl TransientError for sqlx::Error {
+    fn is_transient(&self) -> bool {
+        classify_db_error(self).is_transient()
+    }
+}
+
+impl TransientError for anyhow::Error {
+    fn is_transient(&self) -> bool {
+        db_error_kind(self).is_some_and(DbErrorKind::is_transient)
+    }
+}
+
+/// Run `op` until it succeeds, fails with an error that isn't transient,
+/// or has been attempted again `max_retries` times.
+pub async fn retry_on_retriable<T, E, F, Fut>(
+    max_retries: u32,
+    delay: Duration,
+    mut op: F,
+) -> Result<T, E>
+where
+    E: TransientError,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut n_retries = 0;
+    loop {
+        match op().await {
+            Err(e) if n_retries < max_retries && e.is_transient() => {
+                n_retries += 1;
+                tracing::warn!(
+                    error.message = %e,
+                    n_retries,
+                    "A query failed with a transient error. Retrying."
+                );
+                tokio::time::sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Like `e500`, but with the status matching the database error behind `e`:
+/// `409 Conflict` for a clash with existing data, `503 Service Unavailable`
+/// when trying again later should help.
+pub fn e_db(e: anyhow::Error) -> actix_web::Error {
+    let status =
+        db_error_kind(&e).map_or(StatusCode::INTERNAL_SERVER_ERROR, DbErrorKind::status_code);
+    InternalError::new(e, status).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classify_db_error, db_error_kind, DbErrorKind};
+    use anyhow::Context;
+    use sqlx::error::{DatabaseError, ErrorKind};
+    use std::borrow::Cow;
+
+    /// A database error with the given SQLSTATE code, as Postgres would
+    /// report it.
+    #[derive(Debug)]
+    struct FakeDbError(&'static str);
+
+    impl std::fmt::Display for FakeDbError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "error with code {}", self.0)
+        }
+    }
+
+    impl std::error::Error for FakeDbError {}
+
+    impl DatabaseError for FakeDbError {
+        fn message(&self) -> &str {
+            "fake database error"
+        }
+
+        fn code(&self) -> Option<Cow<'_, str>> {
+            Some(Cow::Borrowed(self.0))
+        }
+
+        fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+            self
+        }
+
+        fn kind(&self) -> ErrorKind {
+            match self.0 {
+                "23505" => ErrorKind::UniqueViolation,
+                "23503" => ErrorKind::ForeignKeyViolation,
+                _ => ErrorKind::Other,
+            }
+        }
+    }
+
+    fn db_error(code: &'static str) -> sqlx::Error {
+        sqlx::Error::Database(Box::new(FakeDbError(code)))
+    }
+
+    #[test]
+    fn unique_violations_are_conflicts() {
+        assert_eq!(classify_db_error(&db_error("23505")), DbErrorKind::Conflict);
+    }
+
+    #[test]
+    fn serialization_failures_and_deadlocks_are_retriable() {
+        assert_eq!(
+            classify_db_error(&db_error("40001")),
+            DbErrorKind::Retriable
+        );
+        assert_eq!(
+            classify_db_error(&db_error("40P01")),
+            DbErrorKind::Retriable
+        );
+    }
+
+    #[test]
+    fn an_unreachable_database_is_unavailable() {
+        let io = std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "refused");
+        assert_eq!(
+            classify_db_error(&sqlx::Error::Io(io)),
+            DbErrorKind::Unavailable
+        );
+        assert_eq!(
+            classify_db_error(&sqlx::Error::PoolTimedOut),
+            DbErrorKind::Unavailable
+        );
+        assert_eq!(
+            classify_db_error(&sqlx::Error::PoolClosed),
+            DbErrorKind::Unavailable
+        );
+    }
+
+    #[test]
+    fn missing_rows_are_not_found() {
+        assert_eq!(
+            classify_db_error(&sqlx::Error::RowNotFound),
+            DbErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn only_conflicts_and_transient_failures_have_their_own_status() {
+        assert_eq!(DbErrorKind::Conflict.status_code().as_u16(), 409);
+        assert_eq!(DbErrorKind::Unavailable.status_code().as_u16(), 503);
+        assert_eq!(DbErrorKind::Retriable.status_code().as_u16(), 503);
+        assert_eq!(DbErrorKind::NotFound.status_code().as_u16(), 500);
+        assert_eq!(DbErrorKind::Other.status_code().as_u16(), 500);
+    }
+
+    #[test]
+    fn other_failures_are_other() {
+        // Foreign key violation, undefined column.
+        assert_eq!(classify_db_error(&db_error("23503")), DbErrorKind::Other);
+        assert_eq!(classify_db_error(&db_error("42703")), DbErrorKind::Other);
+        assert_eq!(
+            classify_db_error(&sqlx::Error::ColumnNotFound("id".into())),
+            DbErrorKind::Other
+        );
+    }
+
+    #[test]
+    fn the_kind_is_found_behind_added_context() {
+        let e = Err::<(), _>(db_error("23505"))
+            .context("Failed to insert new subscriber in the database.")
+            .unwrap_err();
+        assert_eq!(db_error_kind(&e), Some(DbErrorKind::Conflict));
+        assert_eq!(db_error_kind(&anyhow::anyhow!("Not a query")), None);
+    }
+}
@@ -0,0 +1,71 @@
+use std::str::FromStr;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Category of a newsletter issue, shown as a prefix of its subject so that
+/// subscribers can filter issues.
+#[derive(Debug)]
+pub struct IssueCategory(String);
+
+impl IssueCategory {
+    pub const MAX_LENGTH: usize = 32;
+}
+
+impl FromStr for IssueCategory {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let is_empty = s.is_empty();
+        let is_too_long = s.graphemes(true).count() > Self::MAX_LENGTH;
+        // Brackets would make the subject prefix ambiguous.
+        let forbidden_characters = ['[', ']'];
+        let contains_forbidden_characters = s
+            .chars()
+            .any(|c| c.is_control() || forbidden_characters.contains(&c));
+
+        if is_empty || is_too_long || contains_forbidden_characters {
+            Err(format!(
+                "The category must be a single line of at most {} characters, without brackets.",
+                Self::MAX_LENGTH
+            ))
+        } else {
+            Ok(IssueCategory(s.to_string()))
+        }
+    }
+}
+
+impl AsRef<str> for IssueCategory {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn good_categories_are_accepted_and_trimmed() {
+        let category = IssueCategory::from_str("  Weekly digest ").unwrap();
+        assert_eq!(category.as_ref(), "Weekly digest");
+    }
+
+    #[test]
+    fn category_length_check() {
+        claim::assert_ok!(IssueCategory::from_str(&"å".repeat(32)));
+        claim::assert_err!(IssueCategory::from_str(&"å".repeat(33)));
+    }
+
+    #[test]
+    fn blank_categories_are_rejected() {
+        claim::assert_err!(IssueCategory::from_str(""));
+        claim::assert_err!(IssueCategory::from_str("   "));
+    }
+
+    #[test]
+    fn brackets_and_control_characters_are_rejected() {
+        for category in ["[Weekly]", "Week]ly", "Weekly\nDigest"] {
+            claim::assert_err!(IssueCategory::from_str(category));
+        }
+    }
+}
@@ -1,7 +1,11 @@
+pub mod issue_category;
 pub mod new_subscriber;
 pub mod subscriber_email;
 pub mod subscriber_name;
+pub mod subscription_status;
 
+pub use issue_category::IssueCategory;
 pub use new_subscriber::NewSubscriber;
 pub use subscriber_email::SubscriberEmail;
 pub use subscriber_name::SubscriberName;
+pub use subscription_status::SubscriptionStatus;
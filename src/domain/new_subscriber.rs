@@ -1,6 +1,10 @@
+use chrono::FixedOffset;
+
 use super::{subscriber_name::SubscriberName, SubscriberEmail};
 
 pub struct NewSubscriber {
     pub email: SubscriberEmail,
     pub name: SubscriberName,
+    /// For quiet hours in the subscriber's own time zone.
+    pub utc_offset: Option<FixedOffset>,
 }
@@ -5,14 +5,40 @@ use validator::ValidateEmail;
 #[derive(Clone, Debug)]
 pub struct SubscriberEmail(String);
 
+impl SubscriberEmail {
+    /// Validate `s` as an email address. When `strict`, addresses that pass
+    /// the validator but that providers tend to reject are turned away too:
+    /// display-name or comment syntax, and a local part starting or ending
+    /// with a dot.
+    pub fn validate_with(s: &str, strict: bool) -> Result<Self, String> {
+        let s = s.trim();
+        if !s.validate_email() {
+            return Err("Invalid email address {s}".to_string());
+        }
+        if strict {
+            let display_name_characters = ['<', '>', '"', '(', ')'];
+            if s.chars()
+                .any(|c| c.is_whitespace() || display_name_characters.contains(&c))
+            {
+                return Err(format!(
+                    "{s} should be a bare email address, without a name"
+                ));
+            }
+            let local_part = s.rsplit_once('@').map_or(s, |(local_part, _)| local_part);
+            if local_part.starts_with('.') || local_part.ends_with('.') {
+                return Err(format!(
+                    "{s} cannot start or end with a dot before the @ symbol"
+                ));
+            }
+        }
+        Ok(Self(s.to_string()))
+    }
+}
+
 impl FromStr for SubscriberEmail {
     type Err = String;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.trim().validate_email() {
-            Ok(Self(s.trim().to_string()))
-        } else {
-            Err("Invalid email address {s}".to_string())
-        }
+        Self::validate_with(s, false)
     }
 }
 
@@ -79,4 +105,20 @@ mod tests {
         let result = SubscriberEmail::from_str("example@");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn display_names_are_rejected_in_strict_mode() {
+        claim::assert_err!(SubscriberEmail::validate_with("Name <a@b.com>", true));
+        claim::assert_err!(SubscriberEmail::validate_with("\"Name\" <a@b.com>", true));
+        claim::assert_err!(SubscriberEmail::from_str("Name <a@b.com>"));
+    }
+
+    #[test]
+    fn dots_around_the_local_part_are_only_rejected_in_strict_mode() {
+        for email in [".a@b.com", "a.@b.com"] {
+            claim::assert_err!(SubscriberEmail::validate_with(email, true));
+            claim::assert_ok!(SubscriberEmail::from_str(email));
+        }
+        claim::assert_ok!(SubscriberEmail::validate_with(" a.b@b.com ", true));
+    }
 }
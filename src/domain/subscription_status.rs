@@ -0,0 +1,91 @@
+use std::fmt;
+use std::str::FromStr;
+
+use sqlx::{
+    error::BoxDynError,
+    postgres::{PgTypeInfo, PgValueRef},
+    Decode, Postgres, Type,
+};
+
+/// Where a subscriber stands, stored as text in `subscriptions.status`.
+///
+/// Queries bind `as_str` rather than spelling statuses out, and read the
+/// column back with a `"status: SubscriptionStatus"` override, so that a
+/// misspelled status can't compile. The table's check constraint keeps
+/// the column within the same values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionStatus {
+    Pending,
+    Confirmed,
+    Unsubscribed,
+}
+
+impl SubscriptionStatus {
+    pub const ALL: [SubscriptionStatus; 3] = [Self::Pending, Self::Confirmed, Self::Unsubscribed];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Confirmed => "confirmed",
+            Self::Unsubscribed => "unsubscribed",
+        }
+    }
+}
+
+impl FromStr for SubscriptionStatus {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| format!("{s:?} is not a subscription status."))
+    }
+}
+
+impl fmt::Display for SubscriptionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Type<Postgres> for SubscriptionStatus {
+    fn type_info() -> PgTypeInfo {
+        <&str as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <&str as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for SubscriptionStatus {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        Ok(<&str as Decode<Postgres>>::decode(value)?.parse()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubscriptionStatus;
+
+    #[test]
+    fn every_status_round_trips() {
+        for status in SubscriptionStatus::ALL {
+            assert_eq!(status.to_string().parse::<SubscriptionStatus>(), Ok(status));
+            let json = serde_json::to_string(&status).unwrap();
+            assert_eq!(json, format!("\"{}\"", status.as_str()));
+            assert_eq!(
+                serde_json::from_str::<SubscriptionStatus>(&json).unwrap(),
+                status
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_statuses_are_rejected() {
+        for status in ["", "confimed", "Confirmed", " pending"] {
+            claim::assert_err!(status.parse::<SubscriptionStatus>());
+        }
+    }
+}
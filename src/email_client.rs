@@ -1,13 +1,102 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
 use secrecy::{ExposeSecret, Secret};
+use tokio::sync::Semaphore;
+use unicode_segmentation::UnicodeSegmentation;
 use validator::ValidateUrl;
 
 use crate::domain::SubscriberEmail;
 
+/// Extra, per-message knobs for `send_email_with_options`.
+/// Tracking is off unless explicitly requested.
+#[derive(Default)]
+pub struct EmailOptions {
+    pub headers: Vec<(String, String)>,
+    pub track_opens: bool,
+    /// Rewrite links in both the HTML and text bodies to track clicks.
+    pub track_links: bool,
+    /// Echoed back by the provider in webhook events about the message.
+    pub metadata: Vec<(String, String)>,
+    /// Sent from this address rather than the client's sender.
+    pub sender: Option<SubscriberEmail>,
+    pub reply_to: Option<SubscriberEmail>,
+}
+
+/// TLS requirements for connections to the email provider.
+pub struct TlsPolicy {
+    pub min_tls_version: reqwest::tls::Version,
+    /// When set, only servers presenting a chain to this CA are trusted:
+    /// the built-in root store is not used.
+    pub pinned_ca_pem: Option<Vec<u8>>,
+}
+
+impl Default for TlsPolicy {
+    fn default() -> Self {
+        Self {
+            min_tls_version: reqwest::tls::Version::TLS_1_2,
+            pinned_ca_pem: None,
+        }
+    }
+}
+
+/// Bounds on the connections opened to the email provider.
+pub struct ConnectionLimits {
+    /// Idle connections kept open per host, for reuse.
+    pub max_idle_per_host: usize,
+    /// Emails being sent at the same time. Extra sends wait for their turn.
+    pub max_in_flight: Option<usize>,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        Self {
+            max_idle_per_host: usize::MAX,
+            max_in_flight: None,
+        }
+    }
+}
+
+/// The provider token, which can be replaced while the application runs.
+/// Clones share the same token, so that a rotation reaches every client
+/// holding one.
+#[derive(Clone)]
+pub struct AuthorizationToken(Arc<RwLock<Secret<String>>>);
+
+impl AuthorizationToken {
+    pub fn new(token: Secret<String>) -> Self {
+        Self(Arc::new(RwLock::new(token)))
+    }
+
+    pub fn get(&self) -> Secret<String> {
+        self.0.read().expect("The token lock is poisoned").clone()
+    }
+
+    pub fn set(&self, token: Secret<String>) {
+        *self.0.write().expect("The token lock is poisoned") = token;
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SendEmailError {
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error("Failed to write the email to the outbox")]
+    Outbox(#[source] std::io::Error),
+}
+
 pub struct EmailClient {
     http_client: reqwest::Client,
     sender: SubscriberEmail,
     api_url: String,
-    authorization_token: Secret<String>,
+    authorization_token: AuthorizationToken,
+    in_flight: Option<Semaphore>,
+    /// When set, emails are written there instead of being sent.
+    outbox: Option<PathBuf>,
+    /// In graphemes, see `truncate_subject`.
+    max_subject_length: Option<usize>,
 }
 
 impl EmailClient {
@@ -17,20 +106,89 @@ impl EmailClient {
         authorization_token: Secret<String>,
         timeout: std::time::Duration,
     ) -> Result<Self, anyhow::Error> {
-        if api_url.trim().validate_url() {
-            let http_client: reqwest::Client = reqwest::Client::builder()
-                .timeout(timeout)
-                .build()
-                .expect("fail to build email client");
-            Ok(Self {
-                http_client,
-                sender,
-                api_url,
-                authorization_token,
-            })
-        } else {
-            Err(anyhow::anyhow!("Invalid API URL {api_url}"))
+        Self::with_tls_policy(
+            sender,
+            api_url,
+            authorization_token,
+            timeout,
+            &TlsPolicy::default(),
+        )
+    }
+
+    pub fn with_tls_policy(
+        sender: SubscriberEmail,
+        api_url: String,
+        authorization_token: Secret<String>,
+        timeout: std::time::Duration,
+        tls: &TlsPolicy,
+    ) -> Result<Self, anyhow::Error> {
+        Self::with_limits(
+            sender,
+            api_url,
+            authorization_token,
+            timeout,
+            tls,
+            &ConnectionLimits::default(),
+        )
+    }
+
+    pub fn with_limits(
+        sender: SubscriberEmail,
+        api_url: String,
+        authorization_token: Secret<String>,
+        timeout: std::time::Duration,
+        tls: &TlsPolicy,
+        limits: &ConnectionLimits,
+    ) -> Result<Self, anyhow::Error> {
+        if !api_url.trim().validate_url() {
+            return Err(anyhow::anyhow!("Invalid API URL {api_url}"));
         }
+        let mut builder = reqwest::Client::builder()
+            .timeout(timeout)
+            .pool_max_idle_per_host(limits.max_idle_per_host)
+            .min_tls_version(tls.min_tls_version);
+        if let Some(pem) = &tls.pinned_ca_pem {
+            let certificate = reqwest::Certificate::from_pem(pem)
+                .map_err(|e| anyhow::anyhow!("Invalid pinned CA certificate: {e}"))?;
+            builder = builder
+                .tls_built_in_root_certs(false)
+                .add_root_certificate(certificate);
+        }
+        let http_client = builder
+            .build()
+            .map_err(|e| anyhow::anyhow!("Failed to build the email HTTP client: {e}"))?;
+        Ok(Self {
+            http_client,
+            sender,
+            api_url,
+            authorization_token: AuthorizationToken::new(authorization_token),
+            in_flight: limits.max_in_flight.map(Semaphore::new),
+            outbox: None,
+            max_subject_length: None,
+        })
+    }
+
+    /// Cut subjects longer than `max_length` graphemes, see `truncate_subject`.
+    pub fn with_max_subject_length(mut self, max_length: Option<usize>) -> Self {
+        self.max_subject_length = max_length;
+        self
+    }
+
+    /// Write each email to `directory`, as a JSON file holding the request
+    /// the provider would have received, rather than sending it.
+    pub fn writing_to_outbox(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.outbox = Some(directory.into());
+        self
+    }
+
+    /// Use `token` instead of the token this client was built with.
+    pub fn sharing_token(mut self, token: AuthorizationToken) -> Self {
+        self.authorization_token = token;
+        self
+    }
+
+    pub fn authorization_token(&self) -> &AuthorizationToken {
+        &self.authorization_token
     }
 
     pub async fn send_email(
@@ -39,27 +197,236 @@ impl EmailClient {
         subject: &str,
         html_content: &str,
         text_context: &str,
-    ) -> Result<(), reqwest::Error> {
+    ) -> Result<(), SendEmailError> {
+        self.send_email_with_options(
+            recipient,
+            subject,
+            html_content,
+            text_context,
+            &EmailOptions::default(),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Returns the provider's id for the message, when its response has one.
+    /// Emails written to the outbox get an id of their own, which names
+    /// their file.
+    pub async fn send_email_with_options(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_context: &str,
+        options: &EmailOptions,
+    ) -> Result<Option<String>, SendEmailError> {
+        let _permit = match &self.in_flight {
+            Some(in_flight) => Some(
+                in_flight
+                    .acquire()
+                    .await
+                    .expect("The in-flight semaphore is never closed"),
+            ),
+            None => None,
+        };
         let url = format!("{}/email", self.api_url);
+        let subject = match self.max_subject_length {
+            Some(max_length) => truncate_subject(subject, max_length),
+            None => Cow::Borrowed(subject),
+        };
         let body = SendEmailRequest {
-            from: self.sender.as_ref(),
+            from: options.sender.as_ref().unwrap_or(&self.sender).as_ref(),
             to: recipient.as_ref(),
-            subject: subject,
+            reply_to: options.reply_to.as_ref().map(AsRef::as_ref),
+            subject: &subject,
             html_body: html_content,
             text_body: text_context,
+            headers: options
+                .headers
+                .iter()
+                .map(|(name, value)| EmailHeader { name, value })
+                .collect(),
+            track_opens: options.track_opens,
+            track_links: if options.track_links {
+                "HtmlAndText"
+            } else {
+                "None"
+            },
+            metadata: options
+                .metadata
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect(),
         };
-        self.http_client
+        if let Some(outbox) = &self.outbox {
+            return write_to_outbox(outbox, &body)
+                .await
+                .map(Some)
+                .map_err(SendEmailError::Outbox);
+        }
+        let response = self
+            .http_client
             .post(&url)
             .header(
                 "X-Postmark-Server-Token",
-                self.authorization_token.expose_secret(),
+                self.authorization_token.get().expose_secret(),
             )
             .json(&body)
             .send()
             .await?
             .error_for_status()?;
+        let message_id = response
+            .json::<SendEmailResponse>()
+            .await
+            .ok()
+            .map(|r| r.message_id);
+        Ok(message_id)
+    }
+
+    /// Make an authenticated request that doesn't send anything, to check
+    /// that the provider is reachable and accepts our token.
+    pub async fn check_reachability(&self) -> Result<(), reqwest::Error> {
+        if self.outbox.is_some() {
+            return Ok(());
+        }
+        self.check_token(&self.authorization_token.get()).await
+    }
+
+    /// Like `check_reachability`, with a token that is not in use yet.
+    pub async fn check_token(&self, token: &Secret<String>) -> Result<(), reqwest::Error> {
+        let url = format!("{}/server", self.api_url);
+        self.http_client
+            .get(&url)
+            .header("X-Postmark-Server-Token", token.expose_secret())
+            .header("Accept", "application/json")
+            .send()
+            .await?
+            .error_for_status()?;
         Ok(())
     }
+
+    /// Whether the sender address is a confirmed Postmark sender signature.
+    /// Listing signatures takes an account token, not a server one.
+    pub async fn sender_is_verified(
+        &self,
+        account_token: &Secret<String>,
+    ) -> Result<bool, reqwest::Error> {
+        self.is_confirmed_signature(account_token, &self.sender)
+            .await
+    }
+
+    /// Whether `address` is a confirmed Postmark sender signature.
+    pub async fn is_confirmed_signature(
+        &self,
+        account_token: &Secret<String>,
+        address: &SubscriberEmail,
+    ) -> Result<bool, reqwest::Error> {
+        Ok(self
+            .confirmed_signature(account_token, address)
+            .await?
+            .is_some())
+    }
+
+    /// Whether the sender signature has `domain` as its verified custom
+    /// Return-Path domain. Postmark sets the Return-Path of every email
+    /// itself, from the signature: that is how bounces are routed.
+    pub async fn sender_return_path_is_verified(
+        &self,
+        account_token: &Secret<String>,
+        domain: &str,
+    ) -> Result<bool, reqwest::Error> {
+        Ok(self
+            .confirmed_signature(account_token, &self.sender)
+            .await?
+            .is_some_and(|signature| {
+                signature.return_path_domain_verified
+                    && signature
+                        .return_path_domain
+                        .is_some_and(|d| d.eq_ignore_ascii_case(domain))
+            }))
+    }
+
+    async fn confirmed_signature(
+        &self,
+        account_token: &Secret<String>,
+        address: &SubscriberEmail,
+    ) -> Result<Option<SenderSignature>, reqwest::Error> {
+        let url = format!("{}/senders", self.api_url);
+        let signatures: SenderSignatures = self
+            .http_client
+            .get(&url)
+            .query(&[("count", "500"), ("offset", "0")])
+            .header("X-Postmark-Account-Token", account_token.expose_secret())
+            .header("Accept", "application/json")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(signatures.sender_signatures.into_iter().find(|signature| {
+            signature.confirmed
+                && signature
+                    .email_address
+                    .eq_ignore_ascii_case(address.as_ref())
+        }))
+    }
+}
+
+async fn write_to_outbox(
+    outbox: &std::path::Path,
+    body: &SendEmailRequest<'_>,
+) -> Result<String, std::io::Error> {
+    let message_id = uuid::Uuid::new_v4().to_string();
+    let json = serde_json::to_vec_pretty(body)?;
+    tokio::fs::create_dir_all(outbox).await?;
+    tokio::fs::write(outbox.join(format!("{message_id}.json")), json).await?;
+    tracing::info!(message_id, to = body.to, "Wrote an email to the outbox");
+    Ok(message_id)
+}
+
+#[derive(serde::Deserialize)]
+struct SendEmailResponse {
+    #[serde(rename = "MessageID")]
+    message_id: String,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SenderSignatures {
+    sender_signatures: Vec<SenderSignature>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SenderSignature {
+    email_address: String,
+    confirmed: bool,
+    #[serde(default)]
+    return_path_domain: Option<String>,
+    #[serde(default)]
+    return_path_domain_verified: bool,
+}
+
+/// Appended to subjects cut by `truncate_subject`.
+const SUBJECT_TRUNCATION_SUFFIX: &str = "…";
+
+/// `subject`, cut to at most `max_length` graphemes including the suffix
+/// marking the cut, so that neither a character nor a cluster of them
+/// (e.g. an emoji with a skin tone) is split.
+fn truncate_subject(subject: &str, max_length: usize) -> Cow<'_, str> {
+    if subject.graphemes(true).count() <= max_length {
+        return Cow::Borrowed(subject);
+    }
+    let kept = max_length.saturating_sub(SUBJECT_TRUNCATION_SUFFIX.graphemes(true).count());
+    let cut = subject
+        .grapheme_indices(true)
+        .nth(kept)
+        .map_or(subject.len(), |(i, _)| i);
+    Cow::Owned(format!(
+        "{}{}",
+        subject[..cut].trim_end(),
+        SUBJECT_TRUNCATION_SUFFIX
+    ))
 }
 
 #[derive(serde::Serialize)]
@@ -67,9 +434,24 @@ impl EmailClient {
 struct SendEmailRequest<'a> {
     from: &'a str,
     to: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to: Option<&'a str>,
     subject: &'a str,
     html_body: &'a str,
     text_body: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    headers: Vec<EmailHeader<'a>>,
+    track_opens: bool,
+    track_links: &'a str,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: BTreeMap<&'a str, &'a str>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct EmailHeader<'a> {
+    name: &'a str,
+    value: &'a str,
 }
 
 #[cfg(test)]
@@ -109,6 +491,40 @@ mod tests {
         .is_err());
     }
 
+    #[test]
+    fn client_can_require_tls_1_3() {
+        let sender = SubscriberEmail::from_str("test@example.com").unwrap();
+        let tls = TlsPolicy {
+            min_tls_version: reqwest::tls::Version::TLS_1_3,
+            pinned_ca_pem: None,
+        };
+        assert!(EmailClient::with_tls_policy(
+            sender,
+            "https://example.com".to_string(),
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_millis(200),
+            &tls,
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn an_invalid_pinned_ca_is_rejected() {
+        let sender = SubscriberEmail::from_str("test@example.com").unwrap();
+        let tls = TlsPolicy {
+            pinned_ca_pem: Some(b"-----BEGIN CERTIFICATE-----\nnot a cert\n".to_vec()),
+            ..TlsPolicy::default()
+        };
+        assert!(EmailClient::with_tls_policy(
+            sender,
+            "https://example.com".to_string(),
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_millis(200),
+            &tls,
+        )
+        .is_err());
+    }
+
     fn subject() -> String {
         Sentence(1..2).fake()
     }
@@ -170,6 +586,183 @@ mod tests {
         // Mock expectations are checked on drop
     }
 
+    #[tokio::test]
+    async fn send_email_with_options_includes_custom_headers() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let options = EmailOptions {
+            headers: vec![("List-Unsubscribe".into(), "<https://a.b/c>".into())],
+            metadata: vec![("newsletter_issue_id".into(), "42".into())],
+            sender: Some(SubscriberEmail::from_str("digest@example.com").unwrap()),
+            reply_to: Some(SubscriberEmail::from_str("editor@example.com").unwrap()),
+            ..Default::default()
+        };
+        email_client
+            .send_email_with_options(&email(), &subject(), &content(), &content(), &options)
+            .await
+            .unwrap();
+        email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await
+            .unwrap();
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let with_options: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(
+            with_options["Headers"],
+            serde_json::json!([{"Name": "List-Unsubscribe", "Value": "<https://a.b/c>"}])
+        );
+        assert_eq!(
+            with_options["Metadata"],
+            serde_json::json!({"newsletter_issue_id": "42"})
+        );
+        assert_eq!(with_options["From"], "digest@example.com");
+        assert_eq!(with_options["ReplyTo"], "editor@example.com");
+        let without_options: serde_json::Value = serde_json::from_slice(&requests[1].body).unwrap();
+        assert!(without_options.get("Headers").is_none());
+        assert!(without_options.get("Metadata").is_none());
+        assert!(without_options.get("ReplyTo").is_none());
+        assert_ne!(without_options["From"], "digest@example.com");
+        assert_eq!(without_options["TrackOpens"], false);
+        assert_eq!(without_options["TrackLinks"], "None");
+    }
+
+    #[test]
+    fn long_subjects_are_cut_at_a_grapheme_boundary() {
+        // A decomposed "é" and a family emoji are several chars each.
+        let subject = "Cafe\u{301} 👩\u{200d}👩\u{200d}👧 news of the week";
+
+        assert_eq!(
+            truncate_subject(subject, 7),
+            "Cafe\u{301} 👩\u{200d}👩\u{200d}👧…"
+        );
+        assert_eq!(truncate_subject(subject, 6), "Cafe\u{301}…");
+        assert_eq!(truncate_subject(subject, 4), "Caf…");
+        assert_eq!(truncate_subject(subject, 100), subject);
+        assert_eq!(truncate_subject(subject, 1), "…");
+    }
+
+    #[tokio::test]
+    async fn subjects_are_truncated_to_the_configured_length() {
+        let mock_server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let client = email_client(mock_server.uri()).with_max_subject_length(Some(12));
+
+        client
+            .send_email(
+                &email(),
+                "Ünïcödé 日本語のニュースレター",
+                &content(),
+                &content(),
+            )
+            .await
+            .unwrap();
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["Subject"], "Ünïcödé 日本語…");
+    }
+
+    #[tokio::test]
+    async fn send_email_with_options_returns_the_provider_message_id() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "To": "ursula@example.com",
+                "ErrorCode": 0,
+                "Message": "OK",
+                "MessageID": "b7bc2f4a-e38e-4336-af7d-e6c392c2f817",
+            })))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&mock_server)
+            .await;
+
+        let options = EmailOptions::default();
+        let with_id = email_client
+            .send_email_with_options(&email(), &subject(), &content(), &content(), &options)
+            .await
+            .unwrap();
+        let without_id = email_client
+            .send_email_with_options(&email(), &subject(), &content(), &content(), &options)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            with_id.as_deref(),
+            Some("b7bc2f4a-e38e-4336-af7d-e6c392c2f817")
+        );
+        assert_eq!(without_id, None);
+    }
+
+    fn email_client_with_limits(api_url: String, limits: &ConnectionLimits) -> EmailClient {
+        EmailClient::with_limits(
+            email(),
+            api_url,
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_secs(5),
+            &TlsPolicy::default(),
+            limits,
+        )
+        .unwrap()
+    }
+
+    /// Time taken by two simultaneous sends, each answered after 500ms.
+    async fn time_two_simultaneous_sends(limits: &ConnectionLimits) -> Duration {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client_with_limits(mock_server.uri(), limits);
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(500)))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let (recipient, subject, content) = (email(), subject(), content());
+        let start = std::time::Instant::now();
+        let (first, second) = tokio::join!(
+            email_client.send_email(&recipient, &subject, &content, &content),
+            email_client.send_email(&recipient, &subject, &content, &content),
+        );
+        assert_ok!(first);
+        assert_ok!(second);
+        start.elapsed()
+    }
+
+    #[tokio::test]
+    async fn sends_are_serialized_with_a_concurrency_limit_of_one() {
+        let limits = ConnectionLimits {
+            max_idle_per_host: 1,
+            max_in_flight: Some(1),
+        };
+
+        let elapsed = time_two_simultaneous_sends(&limits).await;
+
+        assert!(elapsed >= Duration::from_millis(1000), "{elapsed:?}");
+    }
+
+    #[tokio::test]
+    async fn sends_run_concurrently_without_a_limit() {
+        let elapsed = time_two_simultaneous_sends(&ConnectionLimits::default()).await;
+
+        assert!(elapsed < Duration::from_millis(1000), "{elapsed:?}");
+    }
+
     #[tokio::test]
     async fn send_email_return_ok_when_response_200() {
         let mock_server = MockServer::start().await;
@@ -223,4 +816,87 @@ mod tests {
 
         assert_err!(resp);
     }
+
+    #[tokio::test]
+    async fn only_confirmed_signatures_of_the_sender_verify_it() {
+        let mock_server = MockServer::start().await;
+        let email_client = EmailClient::new(
+            SubscriberEmail::from_str("sender@example.com").unwrap(),
+            mock_server.uri(),
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_millis(200),
+        )
+        .unwrap();
+        let account_token = Secret::new("account-token".to_string());
+        let cases = [
+            ("Sender@Example.com", true, true),
+            ("sender@example.com", false, false),
+            ("someone-else@example.com", true, false),
+        ];
+
+        for (email_address, confirmed, verified) in cases {
+            let _guard = Mock::given(path("/senders"))
+                .and(method("GET"))
+                .and(header("X-Postmark-Account-Token", "account-token"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "TotalCount": 1,
+                    "SenderSignatures": [
+                        { "EmailAddress": email_address, "Confirmed": confirmed }
+                    ]
+                })))
+                .expect(1)
+                .mount_as_scoped(&mock_server)
+                .await;
+
+            let outcome = email_client.sender_is_verified(&account_token).await;
+
+            assert_eq!(outcome.unwrap(), verified, "{email_address} {confirmed}");
+        }
+    }
+
+    #[tokio::test]
+    async fn only_a_verified_return_path_domain_of_the_sender_signature_counts() {
+        let mock_server = MockServer::start().await;
+        let email_client = EmailClient::new(
+            SubscriberEmail::from_str("sender@example.com").unwrap(),
+            mock_server.uri(),
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_millis(200),
+        )
+        .unwrap();
+        let account_token = Secret::new("account-token".to_string());
+        let cases = [
+            (Some("PM-Bounces.example.com"), true, true),
+            (Some("pm-bounces.example.com"), false, false),
+            (Some("pm-bounces.example.org"), true, false),
+            (None, false, false),
+        ];
+
+        for (return_path_domain, domain_verified, verified) in cases {
+            let _guard = Mock::given(path("/senders"))
+                .and(method("GET"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "TotalCount": 1,
+                    "SenderSignatures": [{
+                        "EmailAddress": "sender@example.com",
+                        "Confirmed": true,
+                        "ReturnPathDomain": return_path_domain,
+                        "ReturnPathDomainVerified": domain_verified,
+                    }]
+                })))
+                .expect(1)
+                .mount_as_scoped(&mock_server)
+                .await;
+
+            let outcome = email_client
+                .sender_return_path_is_verified(&account_token, "pm-bounces.example.com")
+                .await;
+
+            assert_eq!(
+                outcome.unwrap(),
+                verified,
+                "{return_path_domain:?} {domain_verified}"
+            );
+        }
+    }
 }
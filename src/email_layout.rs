@@ -0,0 +1,84 @@
+/// HTML document wrapped around the body of every issue, so that it renders
+/// consistently across email clients. It is read from a template file when
+/// the configuration is loaded, so that a missing file stops the startup.
+///
+/// Placeholders: `{{content}}` (required), `{{unsubscribe_link}}` and `{{brand}}`.
+#[derive(serde::Deserialize, Clone)]
+#[serde(try_from = "String")]
+pub struct EmailLayout {
+    path: String,
+    template: String,
+}
+
+impl EmailLayout {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let template = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read the email layout {path:?}: {e}"))?;
+        if !template.contains("{{content}}") {
+            return Err(format!(
+                "The email layout {path:?} has no `{{{{content}}}}` placeholder."
+            ));
+        }
+        Ok(Self {
+            path: path.to_string(),
+            template,
+        })
+    }
+
+    /// `content` is trusted HTML written by an admin, while the other values
+    /// are escaped.
+    pub fn render(&self, content: &str, unsubscribe_link: &str, brand: &str) -> String {
+        self.template
+            .replace(
+                "{{unsubscribe_link}}",
+                &htmlescape::encode_minimal(unsubscribe_link),
+            )
+            .replace("{{brand}}", &htmlescape::encode_minimal(brand))
+            // Last, so that placeholders in the issue itself are left alone.
+            .replace("{{content}}", content)
+    }
+}
+
+impl TryFrom<String> for EmailLayout {
+    type Error = String;
+
+    fn try_from(path: String) -> Result<Self, Self::Error> {
+        Self::load(&path)
+    }
+}
+
+// The template itself would clutter the configuration summary.
+impl std::fmt::Debug for EmailLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EmailLayout")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EmailLayout;
+
+    #[test]
+    fn the_bundled_layout_wraps_the_content() {
+        let layout = EmailLayout::load("templates/newsletter_layout.html").unwrap();
+
+        let html = layout.render(
+            "<p>Hello {{brand}}</p>",
+            "https://example.com/unsubscribe?a=1&b=2",
+            "Rust & co",
+        );
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<p>Hello {{brand}}</p>"));
+        assert!(html.contains(r#"<a href="https://example.com/unsubscribe?a=1&amp;b=2">"#));
+        assert!(html.contains("you subscribed to Rust &amp; co."));
+    }
+
+    #[test]
+    fn layouts_without_a_content_placeholder_are_rejected() {
+        assert!(EmailLayout::load("templates/does-not-exist.html").is_err());
+        assert!(EmailLayout::load("Cargo.toml").is_err());
+    }
+}
@@ -0,0 +1,53 @@
+use chrono::{DateTime, Utc};
+
+/// Whether something that expires at `expires_at` has expired at `now`.
+///
+/// Expiry timestamps may be written by one app instance and checked by
+/// another, so `skew_tolerance` is granted past `expires_at` to absorb
+/// small differences between their clocks.
+pub fn is_expired(
+    expires_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+    skew_tolerance: std::time::Duration,
+) -> bool {
+    let deadline = chrono::Duration::from_std(skew_tolerance)
+        .ok()
+        .and_then(|skew_tolerance| expires_at.checked_add_signed(skew_tolerance));
+    match deadline {
+        Some(deadline) => now > deadline,
+        // Too far in the future to ever expire
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_expired;
+    use chrono::{Duration, Utc};
+
+    const SKEW: std::time::Duration = std::time::Duration::from_secs(30);
+
+    #[test]
+    fn not_yet_expired_is_accepted() {
+        let now = Utc::now();
+        assert!(!is_expired(now + Duration::minutes(5), now, SKEW));
+    }
+
+    #[test]
+    fn expiry_within_the_skew_window_is_accepted() {
+        let now = Utc::now();
+        assert!(!is_expired(now - Duration::seconds(29), now, SKEW));
+        assert!(!is_expired(now - Duration::seconds(30), now, SKEW));
+    }
+
+    #[test]
+    fn expiry_past_the_skew_window_is_rejected() {
+        let now = Utc::now();
+        assert!(is_expired(now - Duration::seconds(31), now, SKEW));
+        assert!(is_expired(
+            now - Duration::seconds(1),
+            now,
+            std::time::Duration::ZERO
+        ));
+    }
+}
@@ -1,5 +1,9 @@
 #[derive(Debug)]
-pub struct IdempotencyKey(String);
+pub struct IdempotencyKey {
+    key: String,
+    /// Set with `for_content`, stored apart from the client key.
+    content_hash: Option<String>,
+}
 
 impl TryFrom<String> for IdempotencyKey {
     type Error = anyhow::Error;
@@ -12,18 +16,37 @@ impl TryFrom<String> for IdempotencyKey {
         if s.len() >= max_length {
             anyhow::bail!("The idempotency key must be shorter than {max_length} characters",);
         }
-        Ok(Self(s))
+        Ok(Self {
+            key: s,
+            content_hash: None,
+        })
+    }
+}
+
+impl IdempotencyKey {
+    /// The same client key, used once per `content_hash`: requests with other
+    /// content are processed afresh instead of replaying the saved response.
+    pub fn for_content(self, content_hash: String) -> Self {
+        Self {
+            content_hash: Some(content_hash),
+            ..self
+        }
+    }
+
+    /// Empty unless the key is used per content.
+    pub fn content_hash(&self) -> &str {
+        self.content_hash.as_deref().unwrap_or_default()
     }
 }
 
 impl From<IdempotencyKey> for String {
     fn from(k: IdempotencyKey) -> Self {
-        k.0
+        k.key
     }
 }
 
 impl AsRef<str> for IdempotencyKey {
     fn as_ref(&self) -> &str {
-        self.0.as_ref()
+        self.key.as_ref()
     }
 }
@@ -2,4 +2,6 @@ mod key;
 mod persistence;
 
 pub use key::IdempotencyKey;
-pub use persistence::{get_saved_response, save_response, try_processing, NextAction};
+pub use persistence::{
+    get_key_usage, get_saved_response, save_response, try_processing, KeyUsage, NextAction,
+};
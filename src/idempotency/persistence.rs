@@ -5,6 +5,7 @@ use actix_web::HttpResponse;
 use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
+#[allow(clippy::large_enum_variant)]
 pub enum NextAction {
     StartingProcessing(Transaction<'static, Postgres>),
     ReturnSavedResponse(HttpResponse),
@@ -17,11 +18,12 @@ pub async fn try_processing(
 ) -> Result<NextAction, anyhow::Error> {
     let mut transaction = pool.begin().await?;
     let n_inserted_rows = sqlx::query!(
-        r#"INSERT INTO idempotency (user_id, idempotency_key, created_at)
-            VALUES ($1, $2, NOW())
+        r#"INSERT INTO idempotency (user_id, idempotency_key, content_hash, created_at)
+            VALUES ($1, $2, $3, NOW())
             ON CONFLICT DO NOTHING"#,
         user_id,
-        idempotency_key.as_ref()
+        idempotency_key.as_ref(),
+        idempotency_key.content_hash()
     )
     .execute(&mut *transaction)
     .await?
@@ -43,6 +45,8 @@ struct HeaderPairRecord {
     value: Vec<u8>,
 }
 
+/// Rebuild the response stored by `save_response`: same status, every header
+/// (repeated ones included, in order) and the same body bytes.
 pub async fn get_saved_response(
     pool: &PgPool,
     idempotency_key: &IdempotencyKey,
@@ -57,10 +61,12 @@ pub async fn get_saved_response(
             FROM idempotency
             WHERE
             user_id = $1 AND
-            idempotency_key = $2
+            idempotency_key = $2 AND
+            content_hash = $3
         "#,
         user_id,
-        idempotency_key.as_ref()
+        idempotency_key.as_ref(),
+        idempotency_key.content_hash()
     )
     .fetch_optional(pool)
     .await?;
@@ -77,6 +83,46 @@ pub async fn get_saved_response(
     }
 }
 
+/// How far a request made with an idempotency key went.
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeyUsage {
+    Unused,
+    /// A request with this key is still being processed.
+    InProgress,
+    Completed(StatusCode),
+}
+
+/// Whatever the content it was used for: with per-content keys, the latest
+/// request made with the client key is reported.
+pub async fn get_key_usage(
+    pool: &PgPool,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+) -> Result<KeyUsage, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+            SELECT response_status_code
+            FROM idempotency
+            WHERE
+            user_id = $1 AND
+            idempotency_key = $2
+            ORDER BY created_at DESC
+            LIMIT 1
+        "#,
+        user_id,
+        idempotency_key.as_ref()
+    )
+    .fetch_optional(pool)
+    .await?;
+    match row.map(|r| r.response_status_code) {
+        None => Ok(KeyUsage::Unused),
+        Some(None) => Ok(KeyUsage::InProgress),
+        Some(Some(status_code)) => Ok(KeyUsage::Completed(StatusCode::from_u16(
+            status_code.try_into()?,
+        )?)),
+    }
+}
+
 pub async fn save_response(
     mut transaction: Transaction<'static, Postgres>,
     idempotency_key: &IdempotencyKey,
@@ -106,13 +152,15 @@ pub async fn save_response(
             response_body = $5
         WHERE
             user_id = $1 AND
-            idempotency_key = $2
+            idempotency_key = $2 AND
+            content_hash = $6
         "#,
         user_id,
         idempotency_key.as_ref(),
         status_code,
         headers,
         body.as_ref(),
+        idempotency_key.content_hash(),
     )
     .execute(&mut *transaction)
     .await?;
@@ -1,45 +1,329 @@
-use std::{str::FromStr, time::Duration};
+use std::{
+    str::FromStr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
 
 use crate::{
-    configuration::Settings, domain::SubscriberEmail, email_client::EmailClient,
+    configuration::{Settings, WorkerSettings},
+    db_error::{db_error_kind, DbErrorKind},
+    domain::SubscriberEmail,
+    email_client::{AuthorizationToken, EmailClient, EmailOptions},
+    personalization::{personalize_html, personalize_text, tag_site_links},
+    shutdown::ShutdownSignal,
+    signing::SignedLinks,
     startup::get_connection_pool,
 };
-use sqlx::{PgPool, Postgres, Transaction};
+use chrono::{DateTime, FixedOffset, Utc};
+use sqlx::{Connection, PgConnection, PgExecutor, PgPool, Postgres, Transaction};
 use tracing::{field::display, Span};
 use uuid::Uuid;
 
-#[tracing::instrument(skip_all)]
+struct Task {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+    n_retries: i16,
+}
+
+/// When `ordered_per_subscriber` is set, only the oldest task of each
+/// subscriber can be picked: later ones stay hidden while it is in flight,
+/// so concurrent workers never send to the same subscriber out of order.
+#[tracing::instrument(skip(connection))]
 async fn dequeue_task(
-    pool: &PgPool,
-) -> Result<Option<(Transaction<'static, Postgres>, Uuid, String)>, anyhow::Error> {
-    let mut transaction = pool.begin().await?;
-    let r = sqlx::query!(
+    connection: &mut PgConnection,
+    ordered_per_subscriber: bool,
+) -> Result<Option<(Transaction<'_, Postgres>, Task)>, anyhow::Error> {
+    let mut transaction = connection.begin().await?;
+    let r = sqlx::query_as!(
+        Task,
         r#"
-        SELECT newsletter_issue_id, subscriber_email
-        FROM issue_delivery_queue
+        SELECT newsletter_issue_id, subscriber_email, n_retries
+        FROM issue_delivery_queue q
+        WHERE execute_after <= now()
+        AND (
+            NOT $1 OR NOT EXISTS (
+                SELECT 1
+                FROM issue_delivery_queue earlier
+                WHERE
+                    earlier.subscriber_email = q.subscriber_email AND
+                    earlier.enqueued_seq < q.enqueued_seq
+            )
+        )
         FOR UPDATE
         SKIP LOCKED
         LIMIT 1
         "#,
+        ordered_per_subscriber
     )
     .fetch_optional(&mut *transaction)
     .await?;
-    if let Some(r) = r {
-        Ok(Some((
-            transaction,
-            r.newsletter_issue_id,
-            r.subscriber_email,
-        )))
-    } else {
-        Ok(None)
-    }
+    Ok(r.map(|task| (transaction, task)))
 }
 #[tracing::instrument(skip_all)]
 async fn delete_task(
-    mut transaction: Transaction<'static, Postgres>,
+    mut transaction: Transaction<'_, Postgres>,
+    issue_id: Uuid,
+    email: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE
+        newsletter_issue_id = $1 AND
+        subscriber_email = $2
+        "#,
+        issue_id,
+        email
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Final outcome of a delivery, as recorded in `delivery_status`.
+#[derive(Debug, Clone, Copy)]
+enum DeliveryStatus {
+    Sent,
+    Failed,
+}
+
+impl DeliveryStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Sent => "sent",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+}
+
+#[tracing::instrument(skip(transaction))]
+async fn record_delivery_status(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue_id: Uuid,
+    email: &str,
+    status: DeliveryStatus,
+    message_id: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO delivery_status (
+            newsletter_issue_id,
+            subscriber_email,
+            status,
+            recorded_at,
+            message_id
+        )
+        VALUES ($1, $2, $3, now(), $4)
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+        SET
+            status = EXCLUDED.status,
+            recorded_at = EXCLUDED.recorded_at,
+            message_id = EXCLUDED.message_id
+        "#,
+        issue_id,
+        email,
+        status.as_str(),
+        message_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+/// Number of delivery tasks waiting in the queue, across every issue.
+pub async fn queue_depth(pool: &PgPool) -> Result<i64, sqlx::Error> {
+    let depth = sqlx::query!(r#"SELECT count(*) as "count!" FROM issue_delivery_queue"#)
+        .fetch_one(pool)
+        .await?
+        .count;
+    Ok(depth)
+}
+
+/// Whether deliveries have been paused from the admin panel.
+/// Tasks stay in the queue while paused.
+#[tracing::instrument(skip(executor))]
+pub async fn is_paused(executor: impl PgExecutor<'_>) -> Result<bool, anyhow::Error> {
+    let paused = sqlx::query!("SELECT paused FROM delivery_worker_state")
+        .fetch_one(executor)
+        .await?
+        .paused;
+    Ok(paused)
+}
+
+#[tracing::instrument(skip(executor))]
+pub async fn set_paused(executor: impl PgExecutor<'_>, paused: bool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE delivery_worker_state SET paused = $1, updated_at = now()",
+        paused
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Delivery counts of an issue, from `delivery_status` and the queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct DeliveryProgress {
+    pub sent: i64,
+    pub failed: i64,
+    pub remaining: i64,
+}
+
+impl DeliveryProgress {
+    pub fn is_complete(&self) -> bool {
+        self.remaining == 0
+    }
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn delivery_progress(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<DeliveryProgress, anyhow::Error> {
+    let r = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT count(*) FROM delivery_status
+             WHERE newsletter_issue_id = $1 AND status = 'sent') as "sent!",
+            (SELECT count(*) FROM delivery_status
+             WHERE newsletter_issue_id = $1 AND status = 'failed') as "failed!",
+            (SELECT count(*) FROM issue_delivery_queue
+             WHERE newsletter_issue_id = $1) as "remaining!"
+        "#,
+        issue_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(DeliveryProgress {
+        sent: r.sent,
+        failed: r.failed,
+        remaining: r.remaining,
+    })
+}
+
+/// How long until another email can be sent without exceeding
+/// `daily_send_cap` over the last 24 hours, if the cap has been reached.
+#[tracing::instrument(skip(executor))]
+async fn daily_cap_resumes_in(
+    executor: impl PgExecutor<'_>,
+    daily_send_cap: u64,
+) -> Result<Option<Duration>, anyhow::Error> {
+    if daily_send_cap == 0 {
+        return Ok(None);
+    }
+    // The window frees up a slot when the `daily_send_cap`-th most recent
+    // send falls out of it.
+    let r = sqlx::query!(
+        r#"
+        SELECT extract(epoch FROM recorded_at + interval '24 hours' - now())::float8 as "resume_in_seconds!"
+        FROM delivery_status
+        WHERE status = 'sent' AND recorded_at > now() - interval '24 hours'
+        ORDER BY recorded_at DESC
+        OFFSET $1 - 1
+        LIMIT 1
+        "#,
+        daily_send_cap as i64
+    )
+    .fetch_optional(executor)
+    .await?;
+    Ok(r.map(|r| Duration::from_secs_f64(r.resume_in_seconds.max(0.0))))
+}
+
+#[tracing::instrument(skip_all)]
+async fn schedule_retry(
+    mut transaction: Transaction<'_, Postgres>,
+    issue_id: Uuid,
+    email: &str,
+    n_retries: i16,
+    delay: Duration,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET
+            n_retries = $3,
+            execute_after = now() + make_interval(secs => $4)
+        WHERE
+        newsletter_issue_id = $1 AND
+        subscriber_email = $2
+        "#,
+        issue_id,
+        email,
+        n_retries,
+        delay.as_secs_f64()
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Put a task off until `until`, without counting it as a failed attempt.
+#[tracing::instrument(skip_all)]
+async fn defer_task(
+    mut transaction: Transaction<'_, Postgres>,
+    issue_id: Uuid,
+    email: &str,
+    until: DateTime<Utc>,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET execute_after = $3
+        WHERE
+        newsletter_issue_id = $1 AND
+        subscriber_email = $2
+        "#,
+        issue_id,
+        email,
+        until
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn dead_letter_task(
+    mut transaction: Transaction<'_, Postgres>,
     issue_id: Uuid,
     email: &str,
+    n_retries: i16,
+    last_error: &str,
 ) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_dead_letter (
+            newsletter_issue_id,
+            subscriber_email,
+            n_retries,
+            last_error,
+            dead_lettered_at
+        )
+        VALUES ($1, $2, $3, $4, now())
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+        SET
+            n_retries = EXCLUDED.n_retries,
+            last_error = EXCLUDED.last_error,
+            dead_lettered_at = EXCLUDED.dead_lettered_at
+        "#,
+        issue_id,
+        email,
+        n_retries,
+        last_error
+    )
+    .execute(&mut *transaction)
+    .await?;
+    record_delivery_status(
+        &mut transaction,
+        issue_id,
+        email,
+        DeliveryStatus::Failed,
+        None,
+    )
+    .await?;
     sqlx::query!(
         r#"
         DELETE FROM issue_delivery_queue
@@ -60,25 +344,109 @@ struct NewsletterIssue {
     title: String,
     text_content: String,
     html_content: String,
+    track_opens: bool,
+    track_links: bool,
+    track_text_opens: bool,
+    personalized: bool,
+    category: Option<String>,
+    /// Set when the issue was published to a list.
+    sender: Option<String>,
+    reply_to: Option<String>,
 }
 
 #[tracing::instrument(skip_all)]
-async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, anyhow::Error> {
+async fn get_issue(
+    executor: impl PgExecutor<'_>,
+    issue_id: Uuid,
+) -> Result<NewsletterIssue, anyhow::Error> {
     let issue = sqlx::query_as!(
         NewsletterIssue,
         r#"
-        SELECT title, text_content, html_content
+        SELECT
+            title, text_content, html_content, track_opens, track_links, track_text_opens,
+            personalized, category, l.sender as "sender?", l.reply_to
         FROM newsletter_issues
+        LEFT JOIN newsletter_lists l USING (list_id)
         WHERE
         newsletter_issue_id = $1
         "#,
         issue_id
     )
-    .fetch_one(pool)
+    .fetch_one(executor)
     .await?;
     Ok(issue)
 }
 
+/// Addresses of lists are validated when the list is created.
+fn parse_list_address(address: Option<&str>) -> Result<Option<SubscriberEmail>, anyhow::Error> {
+    address
+        .map(SubscriberEmail::from_str)
+        .transpose()
+        .map_err(|e| anyhow::anyhow!("Invalid list address: {e}"))
+}
+
+struct Subscriber {
+    id: Uuid,
+    name: String,
+    utc_offset_minutes: Option<i16>,
+}
+
+impl Subscriber {
+    fn utc_offset(&self) -> Option<FixedOffset> {
+        self.utc_offset_minutes
+            .and_then(|minutes| FixedOffset::east_opt(i32::from(minutes) * 60))
+    }
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_subscriber(
+    executor: impl PgExecutor<'_>,
+    email: &str,
+) -> Result<Option<Subscriber>, anyhow::Error> {
+    let subscriber = sqlx::query_as!(
+        Subscriber,
+        r#"SELECT id, name, utc_offset_minutes FROM subscriptions WHERE email = $1"#,
+        email
+    )
+    .fetch_optional(executor)
+    .await?;
+    Ok(subscriber)
+}
+
+/// Metadata key carrying the issue of a delivery, echoed back in webhook
+/// events about it (see `routes::postmark_engagement`).
+pub const ISSUE_ID_METADATA_KEY: &str = "newsletter_issue_id";
+
+/// `List-Unsubscribe` headers, so that mailbox providers can offer a
+/// one-click unsubscribe button (RFC 8058).
+fn unsubscribe_headers(links: &SignedLinks, subscriber_id: Option<Uuid>) -> Vec<(String, String)> {
+    match subscriber_id {
+        Some(id) => vec![
+            (
+                "List-Unsubscribe".to_string(),
+                format!("<{}>", links.unsubscribe(id)),
+            ),
+            (
+                "List-Unsubscribe-Post".to_string(),
+                "List-Unsubscribe=One-Click".to_string(),
+            ),
+        ],
+        None => vec![],
+    }
+}
+
+pub async fn try_execute_task(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    settings: &WorkerSettings,
+    links: &SignedLinks,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let mut connection = pool.acquire().await?;
+    execute_task(&mut connection, email_client, settings, links).await
+}
+
+/// Everything a task needs goes through `connection`, so that a batch of
+/// them takes a single connection from the pool.
 #[tracing::instrument(
     skip_all,
     fields(
@@ -87,39 +455,160 @@ async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, any
     ),
     err
 )]
-pub async fn try_execute_task(
-    pool: &PgPool,
+async fn execute_task(
+    connection: &mut PgConnection,
     email_client: &EmailClient,
+    settings: &WorkerSettings,
+    links: &SignedLinks,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
-    let task = dequeue_task(pool).await?;
-    // TODO add retry count and retry interval
+    if is_paused(&mut *connection).await? {
+        return Ok(ExecutionOutcome::Paused);
+    }
+    if let Some(resume_in) = daily_cap_resumes_in(&mut *connection, settings.daily_send_cap).await?
+    {
+        tracing::warn!(
+            daily_send_cap = settings.daily_send_cap,
+            resume_in_seconds = resume_in.as_secs(),
+            "Daily send cap reached. Pausing deliveries until the window frees up.",
+        );
+        return Ok(ExecutionOutcome::Throttled(resume_in));
+    }
+    let task = dequeue_task(connection, settings.ordered_per_subscriber).await?;
     if task.is_none() {
         return Ok(ExecutionOutcome::EmptyQueue);
     }
-    let (transaction, issue_id, email) = task.unwrap();
+    let (mut transaction, task) = task.unwrap();
+    let Task {
+        newsletter_issue_id: issue_id,
+        subscriber_email: email,
+        n_retries,
+    } = task;
 
     Span::current()
-        .record("newsletter_issue_id", &display(issue_id))
-        .record("subscriber_email", &display(&email));
+        .record("newsletter_issue_id", display(issue_id))
+        .record("subscriber_email", display(&email));
     match SubscriberEmail::from_str(&email) {
-        Ok(email) => {
-            let issue = get_issue(pool, issue_id).await?;
-            if let Err(e) = email_client
-                .send_email(
-                    &email,
-                    &issue.title,
-                    &issue.html_content,
-                    &issue.text_content,
+        Ok(subscriber_email) => {
+            let issue = get_issue(&mut *transaction, issue_id).await?;
+            let sender = parse_list_address(issue.sender.as_deref())?;
+            let reply_to = parse_list_address(issue.reply_to.as_deref())?;
+            let subscriber = get_subscriber(&mut *transaction, &email).await?;
+            if let Some(quiet_hours) = &settings.quiet_hours {
+                let utc_offset = subscriber.as_ref().and_then(Subscriber::utc_offset);
+                if let Some(resumes_at) = quiet_hours.resumes_at(settings.clock.now(), utc_offset) {
+                    tracing::info!(
+                        %resumes_at,
+                        "Within the subscriber's quiet hours. Deferring the delivery.",
+                    );
+                    defer_task(transaction, issue_id, &email, resumes_at).await?;
+                    return Ok(ExecutionOutcome::Deferred);
+                }
+            }
+            let (html_content, text_content) = match &subscriber {
+                Some(subscriber) if issue.personalized => (
+                    personalize_html(&issue.html_content, &subscriber.name),
+                    personalize_text(&issue.text_content, &subscriber.name),
+                ),
+                _ => (issue.html_content, issue.text_content),
+            };
+            let (html_content, text_content) = match &subscriber {
+                Some(subscriber) => {
+                    let reference = links.subscriber_reference(subscriber.id);
+                    (
+                        tag_site_links(&html_content, links.base_url(), &reference),
+                        tag_site_links(&text_content, links.base_url(), &reference),
+                    )
+                }
+                None => (html_content, text_content),
+            };
+            // Personalized issues have no online copy to link to.
+            let text_content = match &subscriber {
+                Some(subscriber)
+                    if settings.text_open_tracking
+                        && issue.track_text_opens
+                        && !issue.personalized =>
+                {
+                    format!(
+                        "{}\n\nRead this issue online: {}",
+                        text_content.trim_end(),
+                        links.issue_read(issue_id, subscriber.id)
+                    )
+                }
+                _ => text_content,
+            };
+            let html_content = match &settings.html_layout {
+                Some(layout) => layout.render(
+                    &html_content,
+                    &subscriber
+                        .as_ref()
+                        .map(|s| links.unsubscribe(s.id))
+                        .unwrap_or_default(),
+                    &settings.brand,
+                ),
+                None => html_content,
+            };
+            let subject = settings.subject(&issue.title, issue.category.as_deref());
+            let message_id = match email_client
+                .send_email_with_options(
+                    &subscriber_email,
+                    &subject,
+                    &html_content,
+                    &text_content,
+                    &EmailOptions {
+                        headers: unsubscribe_headers(links, subscriber.map(|s| s.id)),
+                        track_opens: issue.track_opens,
+                        track_links: issue.track_links,
+                        metadata: vec![(ISSUE_ID_METADATA_KEY.to_string(), issue_id.to_string())],
+                        sender,
+                        reply_to,
+                    },
                 )
                 .await
             {
-                tracing::error!(
-                error.cause_chain = ?e,
-                error.message = %e,
-                "Failed to deliver issue to a confirmed subscriber. \
-                Skipping.",
-                );
-            }
+                Ok(message_id) => message_id,
+                Err(e) => {
+                    let n_retries = n_retries + 1;
+                    if n_retries >= settings.max_retries {
+                        // Counted by `telemetry::CountersLayer`, see `GET /status`.
+                        tracing::error!(
+                            monotonic_counter.poison_pill_deliveries = 1u64,
+                            newsletter_issue_id = %issue_id,
+                            subscriber_email = %email,
+                            n_retries,
+                            error.cause_chain = ?e,
+                            error.message = %e,
+                            "Delivery task failed too many times. Moving it to the dead letter queue.",
+                        );
+                        dead_letter_task(transaction, issue_id, &email, n_retries, &e.to_string())
+                            .await?;
+                    } else {
+                        tracing::warn!(
+                            n_retries,
+                            error.cause_chain = ?e,
+                            error.message = %e,
+                            "Failed to deliver issue to a confirmed subscriber. \
+                            Retrying later.",
+                        );
+                        schedule_retry(
+                            transaction,
+                            issue_id,
+                            &email,
+                            n_retries,
+                            settings.retry_delay(n_retries),
+                        )
+                        .await?;
+                    }
+                    return Ok(ExecutionOutcome::TaskCompleted);
+                }
+            };
+            record_delivery_status(
+                &mut transaction,
+                issue_id,
+                &email,
+                DeliveryStatus::Sent,
+                message_id.as_deref(),
+            )
+            .await?;
         }
         Err(e) => {
             tracing::error!(
@@ -128,35 +617,261 @@ pub async fn try_execute_task(
             "Skipping a confirmed subscriber. \
             Their stored contact details are invalid",
             );
+            record_delivery_status(
+                &mut transaction,
+                issue_id,
+                &email,
+                DeliveryStatus::Failed,
+                None,
+            )
+            .await?;
         }
     }
     delete_task(transaction, issue_id, &email).await?;
     Ok(ExecutionOutcome::TaskCompleted)
 }
 
-async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyhow::Error> {
-    loop {
-        match try_execute_task(&pool, &email_client).await {
-            Ok(ExecutionOutcome::TaskCompleted) => {}
-            Ok(ExecutionOutcome::EmptyQueue) => {
-                tokio::time::sleep(Duration::from_secs(10)).await;
+pub struct DrainReport {
+    pub completed: usize,
+    pub queue_empty: bool,
+    pub paused: bool,
+    /// Set when the daily send cap was hit: how long to wait before resuming.
+    pub throttled_for: Option<Duration>,
+}
+
+/// Execute up to `settings.drain_batch` tasks in a row on one connection,
+/// stopping early when no task is ready to be executed, deliveries are
+/// paused or the daily send cap is hit.
+pub async fn drain_batch(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    settings: &WorkerSettings,
+    links: &SignedLinks,
+) -> Result<DrainReport, anyhow::Error> {
+    let mut connection = pool.acquire().await?;
+    let mut completed = 0;
+    while completed < settings.drain_batch {
+        match execute_task(&mut connection, email_client, settings, links).await? {
+            ExecutionOutcome::TaskCompleted | ExecutionOutcome::Deferred => completed += 1,
+            ExecutionOutcome::EmptyQueue => {
+                return Ok(DrainReport {
+                    completed,
+                    queue_empty: true,
+                    paused: false,
+                    throttled_for: None,
+                })
             }
-            Err(_) => {
-                // TODO exponential backoff
-                tokio::time::sleep(Duration::from_secs(1)).await;
+            ExecutionOutcome::Paused => {
+                return Ok(DrainReport {
+                    completed,
+                    queue_empty: false,
+                    paused: true,
+                    throttled_for: None,
+                })
+            }
+            ExecutionOutcome::Throttled(resume_in) => {
+                return Ok(DrainReport {
+                    completed,
+                    queue_empty: false,
+                    paused: false,
+                    throttled_for: Some(resume_in),
+                })
+            }
+        }
+    }
+    Ok(DrainReport {
+        completed,
+        queue_empty: false,
+        paused: false,
+        throttled_for: None,
+    })
+}
+
+async fn worker_loop(
+    pool: PgPool,
+    email_client: EmailClient,
+    settings: WorkerSettings,
+    links: SignedLinks,
+    heartbeat: WorkerHeartbeat,
+    shutdown: ShutdownSignal,
+) -> Result<(), anyhow::Error> {
+    let mut db_failures = 0;
+    while !shutdown.is_triggered() {
+        let pause = run_worker_iteration(
+            &pool,
+            &email_client,
+            &settings,
+            &links,
+            &heartbeat,
+            &mut db_failures,
+        )
+        .await;
+        if !pause.is_zero() {
+            tokio::select! {
+                _ = tokio::time::sleep(pause) => {},
+                _ = shutdown.triggered() => {},
             }
         }
         // TODO add a clean up job on past deliveries
     }
+    tracing::info!("The delivery worker stopped");
+    Ok(())
+}
+
+/// Drain one batch of tasks and return how long the worker should wait
+/// before the next one.
+///
+/// `db_failures` counts the batches in a row that failed because the
+/// database couldn't be reached (or was too busy): the wait grows exponentially with it, see
+/// `WorkerSettings::db_backoff`, and the heartbeat reports the worker as
+/// degraded until a batch goes through.
+pub async fn run_worker_iteration(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    settings: &WorkerSettings,
+    links: &SignedLinks,
+    heartbeat: &WorkerHeartbeat,
+    db_failures: &mut u32,
+) -> Duration {
+    let report = match drain_batch(pool, email_client, settings, links).await {
+        Ok(report) => report,
+        // Not a concurrent transaction: only an unreachable database is
+        // worth backing off from.
+        Err(e) if db_error_kind(&e) == Some(DbErrorKind::Unavailable) => {
+            *db_failures += 1;
+            heartbeat.set(HeartbeatState::Degraded {
+                consecutive_failures: *db_failures,
+            });
+            let backoff = settings.db_backoff(*db_failures);
+            report_db_outage(&e, *db_failures, backoff);
+            return backoff;
+        }
+        Err(_) => return Duration::from_secs(1),
+    };
+    if *db_failures > 0 {
+        tracing::info!(
+            consecutive_failures = *db_failures,
+            "The delivery worker reached the database again"
+        );
+        *db_failures = 0;
+    }
+    heartbeat.set(HeartbeatState::Healthy);
+    match report {
+        DrainReport {
+            throttled_for: Some(resume_in),
+            ..
+        } => resume_in,
+        DrainReport {
+            queue_empty: false,
+            paused: false,
+            ..
+        } => Duration::ZERO,
+        DrainReport { .. } => Duration::from_secs(10),
+    }
+}
+
+/// Log a failure to reach the database, more loudly the longer it lasts.
+fn report_db_outage(e: &anyhow::Error, consecutive_failures: u32, backoff: Duration) {
+    let backoff_ms = backoff.as_millis() as u64;
+    match consecutive_failures {
+        1 => tracing::info!(
+            error.message = %e,
+            consecutive_failures,
+            backoff_ms,
+            "The delivery worker can't reach the database"
+        ),
+        2..=4 => tracing::warn!(
+            error.message = %e,
+            consecutive_failures,
+            backoff_ms,
+            "The delivery worker still can't reach the database"
+        ),
+        _ => tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            consecutive_failures,
+            backoff_ms,
+            "The delivery worker has been unable to reach the database for a while"
+        ),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HeartbeatState {
+    /// The worker hasn't finished a batch yet.
+    #[default]
+    Starting,
+    Healthy,
+    /// The last batches failed because the database was unreachable.
+    Degraded {
+        consecutive_failures: u32,
+    },
+}
+
+impl HeartbeatState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HeartbeatState::Starting => "starting",
+            HeartbeatState::Healthy => "ok",
+            HeartbeatState::Degraded { .. } => "degraded",
+        }
+    }
+}
+
+/// What the delivery worker last reported about itself. Clones share the
+/// same state, so that the API can report on a worker running in the same
+/// process.
+#[derive(Clone, Default)]
+pub struct WorkerHeartbeat(Arc<RwLock<HeartbeatState>>);
+
+impl WorkerHeartbeat {
+    pub fn get(&self) -> HeartbeatState {
+        *self.0.read().expect("The heartbeat lock is poisoned")
+    }
+
+    pub fn set(&self, state: HeartbeatState) {
+        *self.0.write().expect("The heartbeat lock is poisoned") = state;
+    }
 }
 
 pub enum ExecutionOutcome {
     TaskCompleted,
+    /// The recipient is within their quiet hours: the task was put off until
+    /// the end of them.
+    Deferred,
     EmptyQueue,
+    /// Deliveries are paused, see `set_paused`.
+    Paused,
+    /// The daily send cap has been reached; retry after the given delay.
+    Throttled(Duration),
 }
 
-pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
-    let connection_pool = get_connection_pool(&configuration.database);
-    let email_client = configuration.email_client.client()?;
-    worker_loop(connection_pool, email_client).await
+/// `email_token` is shared with the API, so that the worker follows token
+/// rotations made through the admin panel; `heartbeat` is shared so that
+/// the API can report on the worker. The worker returns once `shutdown` is
+/// triggered and its current batch is done.
+pub async fn run_worker_until_stopped(
+    configuration: Settings,
+    email_token: AuthorizationToken,
+    heartbeat: WorkerHeartbeat,
+    shutdown: ShutdownSignal,
+) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database, &configuration.timeouts);
+    let email_client = configuration
+        .email_client
+        .client(configuration.timeouts.email_client())?
+        .sharing_token(email_token);
+    let links = SignedLinks::new(
+        configuration.application.base_url,
+        configuration.application.hmac_secret,
+    );
+    worker_loop(
+        connection_pool,
+        email_client,
+        configuration.worker,
+        links,
+        heartbeat,
+        shutdown,
+    )
+    .await
 }
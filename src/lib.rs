@@ -1,11 +1,30 @@
+pub mod audit;
 pub mod authentication;
+pub mod client_ip;
+pub mod clock;
 pub mod configuration;
+pub mod confirmation_reminders;
+pub mod db_error;
 pub mod domain;
 pub mod email_client;
+pub mod email_layout;
+pub mod expiry;
 pub mod idempotency;
+pub mod issue_delivery_worker;
+pub mod load_shedding;
+pub mod locale;
+pub mod pagination;
+pub mod personalization;
+pub mod plain_text;
+pub mod quiet_hours;
+pub mod rate_limit;
 pub mod routes;
 pub mod session_state;
+pub mod shutdown;
+pub mod signing;
 pub mod startup;
 pub mod telemetry;
+pub mod token_cleanup;
+pub mod transaction;
 pub mod utils;
-pub mod issue_delivery_worker;
+pub mod webhook_delivery;
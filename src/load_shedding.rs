@@ -0,0 +1,53 @@
+use std::sync::Arc;
+
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::RETRY_AFTER,
+    middleware::Next,
+    web, HttpResponse,
+};
+use tokio::sync::Semaphore;
+
+/// Seconds clients are asked to wait before trying again.
+const RETRY_AFTER_SECONDS: u64 = 1;
+
+/// Bounds how many requests the routes wrapped with `shed_load` handle at
+/// once, whoever they come from: the global counterpart of the per-client
+/// `rate_limit`.
+pub struct InFlightLimit(Option<Arc<Semaphore>>);
+
+impl InFlightLimit {
+    /// `None` means unlimited.
+    pub fn new(max_in_flight: Option<usize>) -> Self {
+        Self(max_in_flight.map(|max| Arc::new(Semaphore::new(max))))
+    }
+}
+
+/// Turn requests away with a `503 Service Unavailable` while `InFlightLimit`
+/// requests are already being handled, rather than queueing them up in
+/// front of the database pool and the email provider.
+pub async fn shed_load(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let Some(limit) = req
+        .app_data::<web::Data<InFlightLimit>>()
+        .and_then(|limit| limit.0.clone())
+    else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    match limit.try_acquire_owned() {
+        Ok(_permit) => Ok(next.call(req).await?.map_into_left_body()),
+        Err(_) => {
+            tracing::warn!(
+                path = req.path(),
+                "Too many requests in flight, shedding load"
+            );
+            let response = HttpResponse::ServiceUnavailable()
+                .insert_header((RETRY_AFTER, RETRY_AFTER_SECONDS))
+                .finish();
+            Ok(req.into_response(response).map_into_right_body())
+        }
+    }
+}
@@ -0,0 +1,94 @@
+/// Languages the public pages are available in.
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Locale {
+    En,
+    Fr,
+}
+
+impl Locale {
+    const ALL: [Locale; 2] = [Locale::En, Locale::Fr];
+
+    /// The language tag, as used in `lang` attributes.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Fr => "fr",
+        }
+    }
+
+    /// The supported locale the client prefers, going by its
+    /// `Accept-Language` header (RFC 9110, section 12.5.4). Regional
+    /// variants match their language, e.g. `fr-CA` is served `fr`. Falls
+    /// back to `default` when the header is missing, malformed or names no
+    /// supported language.
+    pub fn negotiate(accept_language: Option<&str>, default: Locale) -> Locale {
+        let Some(accept_language) = accept_language else {
+            return default;
+        };
+        let mut best: Option<(Locale, f32)> = None;
+        for range in accept_language.split(',') {
+            let mut parts = range.split(';');
+            let tag = parts.next().unwrap_or_default().trim();
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok());
+            let Some(quality) = quality.filter(|q| *q > 0.0) else {
+                continue;
+            };
+            let language = tag.split('-').next().unwrap_or_default();
+            let locale = if tag == "*" {
+                Some(default)
+            } else {
+                Self::ALL
+                    .into_iter()
+                    .find(|locale| locale.as_str().eq_ignore_ascii_case(language))
+            };
+            if let Some(locale) = locale {
+                // The first of equally preferred languages wins.
+                if best.is_none_or(|(_, best_quality)| quality > best_quality) {
+                    best = Some((locale, quality));
+                }
+            }
+        }
+        best.map_or(default, |(locale, _)| locale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Locale;
+
+    #[test]
+    fn a_missing_header_gets_the_default() {
+        assert_eq!(Locale::negotiate(None, Locale::En), Locale::En);
+        assert_eq!(Locale::negotiate(None, Locale::Fr), Locale::Fr);
+    }
+
+    #[test]
+    fn regional_variants_match_their_language() {
+        assert_eq!(Locale::negotiate(Some("fr-CA"), Locale::En), Locale::Fr);
+        assert_eq!(Locale::negotiate(Some("EN-gb"), Locale::Fr), Locale::En);
+    }
+
+    #[test]
+    fn the_most_preferred_supported_language_wins() {
+        assert_eq!(
+            Locale::negotiate(Some("de-DE, en;q=0.5, fr;q=0.8"), Locale::En),
+            Locale::Fr
+        );
+        assert_eq!(
+            Locale::negotiate(Some("en;q=0.9, fr"), Locale::En),
+            Locale::Fr
+        );
+        assert_eq!(Locale::negotiate(Some("fr, en"), Locale::En), Locale::Fr);
+    }
+
+    #[test]
+    fn unsupported_or_refused_languages_get_the_default() {
+        assert_eq!(Locale::negotiate(Some("de, es"), Locale::En), Locale::En);
+        assert_eq!(Locale::negotiate(Some("fr;q=0"), Locale::En), Locale::En);
+        assert_eq!(Locale::negotiate(Some("*"), Locale::Fr), Locale::Fr);
+        assert_eq!(Locale::negotiate(Some(";;q=x,"), Locale::En), Locale::En);
+    }
+}
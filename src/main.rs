@@ -3,9 +3,13 @@ use std::fmt::{Debug, Display};
 use tokio::task::JoinError;
 use zero2prod::{
     configuration::get_configuration,
+    confirmation_reminders::run_confirmation_reminders_until_stopped,
     issue_delivery_worker::run_worker_until_stopped,
-    startup::Application,
+    shutdown::{shut_down, shutdown_requested, ShutdownSignal},
+    startup::{get_connection_pool, Application},
     telemetry::{get_subscriber, init_subscriber},
+    token_cleanup::run_token_cleanup_until_stopped,
+    webhook_delivery::run_webhook_worker_until_stopped,
 };
 
 #[tokio::main]
@@ -15,12 +19,56 @@ async fn main() -> anyhow::Result<()> {
 
     let configuration = get_configuration().expect("Failed to read configuration.");
     let application = Application::build(configuration.clone()).await?;
+    let email_token = application.email_token();
+    let worker_heartbeat = application.worker_heartbeat();
+    let server_handle = application.server_handle();
+    let in_flight_requests = application.in_flight_requests();
+    let shutdown = ShutdownSignal::default();
     let application_task = tokio::spawn(application.run_until_stopped());
-    let worker_task = tokio::spawn(run_worker_until_stopped(configuration));
+    let mut worker_task = tokio::spawn(run_worker_until_stopped(
+        configuration.clone(),
+        email_token.clone(),
+        worker_heartbeat,
+        shutdown.clone(),
+    ));
+    let mut reminders_task = tokio::spawn(run_confirmation_reminders_until_stopped(
+        configuration.clone(),
+        email_token,
+        shutdown.clone(),
+    ));
+    let mut token_cleanup_task = tokio::spawn(run_token_cleanup_until_stopped(
+        configuration.clone(),
+        shutdown.clone(),
+    ));
+    let mut webhook_task = tokio::spawn(run_webhook_worker_until_stopped(
+        configuration.clone(),
+        shutdown.clone(),
+    ));
 
     tokio::select! {
         o = application_task => report_exit("API", o),
-        o = worker_task =>  report_exit("Background worker", o),
+        o = &mut worker_task =>  report_exit("Background worker", o),
+        o = &mut token_cleanup_task => report_exit("Token cleanup", o),
+        o = &mut webhook_task => report_exit("Webhook worker", o),
+        o = &mut reminders_task => report_exit("Confirmation reminders", o),
+        _ = shutdown_requested() => {
+            let pool = get_connection_pool(&configuration.database, &configuration.timeouts);
+            shut_down(
+                server_handle,
+                &in_flight_requests,
+                &shutdown,
+                worker_task,
+                vec![
+                    ("Token cleanup", token_cleanup_task),
+                    ("Webhook worker", webhook_task),
+                    ("Confirmation reminders", reminders_task),
+                ],
+                &pool,
+                configuration.application.shutdown_timeout(),
+            )
+            .await
+            .log();
+        }
     };
 
     Ok(())
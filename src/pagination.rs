@@ -0,0 +1,139 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+/// Page size when the client doesn't ask for one.
+pub const DEFAULT_LIMIT: i64 = 20;
+/// Larger limits are clamped, so that a single request can't load a table.
+pub const MAX_LIMIT: i64 = 100;
+
+#[derive(serde::Deserialize, Debug, Default)]
+pub struct PaginationQuery {
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Invalid pagination cursor.")]
+pub struct InvalidCursor;
+
+/// Position of a row in a list ordered by `(created_at, id)`, newest first.
+/// A page starts right after the row its cursor points at. Clients get it
+/// encoded, and should treat it as opaque.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!(
+            "{}:{}",
+            self.created_at.timestamp_micros(),
+            self.id
+        ))
+    }
+
+    pub fn decode(encoded: &str) -> Result<Self, InvalidCursor> {
+        let decoded = URL_SAFE_NO_PAD.decode(encoded).map_err(|_| InvalidCursor)?;
+        let decoded = String::from_utf8(decoded).map_err(|_| InvalidCursor)?;
+        let (micros, id) = decoded.split_once(':').ok_or(InvalidCursor)?;
+        let micros = micros.parse().map_err(|_| InvalidCursor)?;
+        Ok(Self {
+            created_at: DateTime::from_timestamp_micros(micros).ok_or(InvalidCursor)?,
+            id: id.parse().map_err(|_| InvalidCursor)?,
+        })
+    }
+}
+
+/// The page size to use, clamped to `1..=MAX_LIMIT`, and where to start.
+pub fn parse_pagination(query: &PaginationQuery) -> Result<(i64, Option<Cursor>), InvalidCursor> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let cursor = query.cursor.as_deref().map(Cursor::decode).transpose()?;
+    Ok((limit, cursor))
+}
+
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Cursor of the following page, if there is one.
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Build a page from up to `limit + 1` rows: the extra row only tells
+    /// whether another page follows.
+    pub fn from_rows(mut rows: Vec<T>, limit: i64, cursor_of: impl Fn(&T) -> Cursor) -> Self {
+        let limit = usize::try_from(limit).unwrap_or(0);
+        let next_cursor = if rows.len() > limit {
+            rows.truncate(limit);
+            rows.last().map(|row| cursor_of(row).encode())
+        } else {
+            None
+        };
+        Self {
+            items: rows,
+            next_cursor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(limit: Option<i64>, cursor: Option<&str>) -> PaginationQuery {
+        PaginationQuery {
+            limit,
+            cursor: cursor.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn cursors_round_trip() {
+        let cursor = Cursor {
+            created_at: DateTime::from_timestamp_micros(1_700_000_000_123_456).unwrap(),
+            id: Uuid::new_v4(),
+        };
+        let (_, decoded) = parse_pagination(&query(None, Some(&cursor.encode()))).unwrap();
+        assert_eq!(decoded, Some(cursor));
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        for cursor in ["", "not base64!", &URL_SAFE_NO_PAD.encode("12:not-a-uuid")] {
+            assert!(Cursor::decode(cursor).is_err(), "{cursor}");
+        }
+    }
+
+    #[test]
+    fn limits_are_clamped() {
+        for (limit, expected) in [
+            (None, DEFAULT_LIMIT),
+            (Some(10), 10),
+            (Some(0), 1),
+            (Some(-5), 1),
+            (Some(1_000_000), MAX_LIMIT),
+        ] {
+            let (clamped, _) = parse_pagination(&query(limit, None)).unwrap();
+            assert_eq!(clamped, expected, "{limit:?}");
+        }
+    }
+
+    #[test]
+    fn pages_only_link_to_a_next_page_when_rows_are_left() {
+        let cursor_of = |id: &Uuid| Cursor {
+            created_at: DateTime::from_timestamp_micros(0).unwrap(),
+            id: *id,
+        };
+        let rows: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+
+        let page = Page::from_rows(rows.clone(), 2, cursor_of);
+        assert_eq!(page.items, rows[..2]);
+        assert_eq!(page.next_cursor, Some(cursor_of(&rows[1]).encode()));
+
+        let page = Page::from_rows(rows.clone(), 3, cursor_of);
+        assert_eq!(page.items, rows);
+        assert_eq!(page.next_cursor, None);
+    }
+}
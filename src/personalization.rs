@@ -0,0 +1,137 @@
+/// Placeholder replaced with the subscriber's name in personalized issues.
+pub const NAME_PLACEHOLDER: &str = "{{name}}";
+
+/// Substitute `{{name}}` in the HTML body of an issue.
+/// The name is escaped, since subscribers choose it themselves.
+pub fn personalize_html(template: &str, name: &str) -> String {
+    template.replace(NAME_PLACEHOLDER, &htmlescape::encode_minimal(name))
+}
+
+/// Substitute `{{name}}` in the plain text body of an issue.
+pub fn personalize_text(template: &str, name: &str) -> String {
+    template.replace(NAME_PLACEHOLDER, name)
+}
+
+/// Query parameter carrying `SignedLinks::subscriber_reference`.
+pub const SUBSCRIBER_REF_PARAM: &str = "subscriber_ref";
+
+/// Add `subscriber_ref=<reference>` to every link to our own site
+/// (`base_url`), so that analytics can attribute visits to a subscriber.
+/// Links to other sites are left alone: the reference never leaves us.
+pub fn tag_site_links(content: &str, base_url: &str, reference: &str) -> String {
+    let base_url = base_url.trim_end_matches('/');
+    let mut tagged = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find(base_url) {
+        let after_base = &rest[start + base_url.len()..];
+        let end = after_base
+            .find(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '<' | '>'))
+            .unwrap_or(after_base.len());
+        // Punctuation ending a sentence, e.g. "see https://example.com/a.",
+        // isn't part of the link.
+        let end = after_base[..end]
+            .trim_end_matches(['.', ',', ';', ':', '!', '?', ')'])
+            .len();
+        let path = &after_base[..end];
+        tagged.push_str(&rest[..start + base_url.len()]);
+        if path.is_empty() || path.starts_with(['/', '?', '#']) {
+            let (path, fragment) = path.split_at(path.find('#').unwrap_or(path.len()));
+            let separator = if path.contains('?') { '&' } else { '?' };
+            tagged.push_str(path);
+            tagged.push_str(&format!("{separator}{SUBSCRIBER_REF_PARAM}={reference}"));
+            tagged.push_str(fragment);
+        } else {
+            // Another host sharing our prefix, e.g. `example.com.evil`.
+            tagged.push_str(path);
+        }
+        rest = &after_base[end..];
+    }
+    tagged.push_str(rest);
+    tagged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{personalize_html, personalize_text, tag_site_links};
+
+    #[test]
+    fn the_name_placeholder_is_replaced() {
+        assert_eq!(
+            personalize_text("Hello {{name}}, bye {{name}}", "le guin"),
+            "Hello le guin, bye le guin"
+        );
+        assert_eq!(
+            personalize_html("<p>Hello {{name}}</p>", "le guin"),
+            "<p>Hello le guin</p>"
+        );
+    }
+
+    #[test]
+    fn names_are_escaped_in_html_only() {
+        let name = r#"<script>alert("hi")</script> & co"#;
+        assert_eq!(
+            personalize_html("<p>Hello {{name}}</p>", name),
+            "<p>Hello &lt;script&gt;alert(&quot;hi&quot;)&lt;/script&gt; &amp; co</p>"
+        );
+        assert_eq!(
+            personalize_text("Hello {{name}}", name),
+            format!("Hello {name}")
+        );
+    }
+
+    #[test]
+    fn unknown_placeholders_are_left_intact() {
+        assert_eq!(
+            personalize_text("Hi {{name}} from {{city}} {{ name }}", "ursula"),
+            "Hi ursula from {{city}} {{ name }}"
+        );
+    }
+
+    #[test]
+    fn links_to_our_site_are_tagged() {
+        let base_url = "https://example.com";
+        let content = r#"<a href="https://example.com/posts?id=1#top">a</a> https://example.com"#;
+        assert_eq!(
+            tag_site_links(content, base_url, "ref"),
+            r#"<a href="https://example.com/posts?id=1&subscriber_ref=ref#top">a</a> https://example.com?subscriber_ref=ref"#
+        );
+    }
+
+    #[test]
+    fn trailing_punctuation_is_left_out_of_the_link() {
+        let base_url = "https://example.com";
+        assert_eq!(
+            tag_site_links("Read https://example.com/posts.", base_url, "ref"),
+            "Read https://example.com/posts?subscriber_ref=ref."
+        );
+        assert_eq!(
+            tag_site_links("(see https://example.com/a?b=1), or", base_url, "ref"),
+            "(see https://example.com/a?b=1&subscriber_ref=ref), or"
+        );
+        assert_eq!(
+            tag_site_links("Visit https://example.com!", base_url, "ref"),
+            "Visit https://example.com?subscriber_ref=ref!"
+        );
+    }
+
+    #[test]
+    fn punctuation_inside_a_link_is_kept() {
+        assert_eq!(
+            tag_site_links(
+                "https://example.com/a.html?b=1 next",
+                "https://example.com",
+                "ref"
+            ),
+            "https://example.com/a.html?b=1&subscriber_ref=ref next"
+        );
+    }
+
+    #[test]
+    fn links_to_other_sites_are_left_alone() {
+        let content = "https://example.org/a https://example.com.evil/b";
+        assert_eq!(
+            tag_site_links(content, "https://example.com", "ref"),
+            content
+        );
+    }
+}
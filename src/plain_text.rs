@@ -0,0 +1,150 @@
+/// Elements that start a new line of text.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p",
+    "div",
+    "br",
+    "li",
+    "ul",
+    "ol",
+    "tr",
+    "table",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "blockquote",
+    "pre",
+    "hr",
+];
+
+/// Elements whose content is not text at all.
+const SKIPPED_ELEMENTS: &[&str] = &["script", "style", "head", "title"];
+
+/// A plain text rendition of an HTML body: tags are stripped, block
+/// elements become line breaks and entities are decoded.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut skipping: Option<String> = None;
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        if skipping.is_none() {
+            push_text(&mut text, &rest[..start]);
+        }
+        let Some(end) = rest[start..].find('>') else {
+            // Not a tag after all
+            if skipping.is_none() {
+                push_text(&mut text, &rest[start..]);
+            }
+            rest = "";
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+
+        let closing = tag.starts_with('/');
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        match &skipping {
+            Some(skipped) if closing && *skipped == name => skipping = None,
+            Some(_) => {}
+            None if !closing && SKIPPED_ELEMENTS.contains(&name.as_str()) => {
+                skipping = Some(name);
+            }
+            None if BLOCK_ELEMENTS.contains(&name.as_str()) => text.push('\n'),
+            None => {}
+        }
+    }
+    if skipping.is_none() {
+        push_text(&mut text, rest);
+    }
+    tidy(&text)
+}
+
+/// Append a run of text from between tags, collapsing whitespace like
+/// browsers do.
+fn push_text(text: &mut String, html: &str) {
+    let decoded = htmlescape::decode_html(html).unwrap_or_else(|_| html.to_string());
+    let mut previous_was_space = text.ends_with([' ', '\n']);
+    for c in decoded.chars() {
+        if c.is_whitespace() {
+            if !previous_was_space {
+                text.push(' ');
+            }
+            previous_was_space = true;
+        } else {
+            text.push(c);
+            previous_was_space = false;
+        }
+    }
+}
+
+/// Trim every line and keep at most one blank line between paragraphs.
+fn tidy(text: &str) -> String {
+    let mut tidied = String::new();
+    let mut blank_lines = 0;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+        if !tidied.is_empty() {
+            tidied.push_str(if blank_lines > 0 { "\n\n" } else { "\n" });
+        }
+        tidied.push_str(line);
+        blank_lines = 0;
+    }
+    tidied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::html_to_text;
+
+    #[test]
+    fn tags_are_stripped() {
+        assert_eq!(
+            html_to_text(r#"<p>Hello <b>world</b>, see <a href="https://a.b">this</a></p>"#),
+            "Hello world, see this"
+        );
+    }
+
+    #[test]
+    fn block_elements_become_line_breaks() {
+        assert_eq!(
+            html_to_text("<h1>Title</h1>\n\n<p>First\n   paragraph</p><p>Second<br>line</p>"),
+            "Title\n\nFirst paragraph\n\nSecond\nline"
+        );
+    }
+
+    #[test]
+    fn entities_are_decoded() {
+        assert_eq!(
+            html_to_text("<p>Fish &amp; chips &lt;3 &eacute;t&eacute;</p>"),
+            "Fish & chips <3 été"
+        );
+        // Stray ampersands are kept as they are.
+        assert_eq!(html_to_text("<p>Q&A</p>"), "Q&A");
+    }
+
+    #[test]
+    fn scripts_and_styles_are_dropped() {
+        assert_eq!(
+            html_to_text(
+                "<head><title>T</title><style>p { color: red }</style></head>\
+                 <body><script>alert('<p>hi</p>')</script><p>Body</p></body>"
+            ),
+            "Body"
+        );
+    }
+
+    #[test]
+    fn unterminated_tags_are_kept_as_text() {
+        assert_eq!(html_to_text("<p>1 < 2"), "1 < 2");
+    }
+}
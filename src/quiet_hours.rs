@@ -0,0 +1,144 @@
+use chrono::{DateTime, Days, FixedOffset, NaiveTime, TimeZone, Utc};
+
+/// Time of day during which issues are not delivered, so that subscribers
+/// aren't emailed in the middle of the night. It is read in the time zone
+/// of each subscriber when they gave one, and in `utc_offset` otherwise.
+///
+/// Time zones are fixed UTC offsets (`+02:00`): daylight saving time has to
+/// be accounted for by hand.
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "QuietHoursSettings")]
+pub struct QuietHours {
+    start: NaiveTime,
+    end: NaiveTime,
+    utc_offset: FixedOffset,
+}
+
+#[derive(serde::Deserialize)]
+struct QuietHoursSettings {
+    /// `HH:MM`, e.g. `22:00`.
+    start: String,
+    /// `HH:MM`, before `start` for windows spanning midnight.
+    end: String,
+    utc_offset: String,
+}
+
+impl TryFrom<QuietHoursSettings> for QuietHours {
+    type Error = String;
+
+    fn try_from(settings: QuietHoursSettings) -> Result<Self, Self::Error> {
+        let time = |value: &str| {
+            NaiveTime::parse_from_str(value, "%H:%M")
+                .map_err(|_| format!("Quiet hours must be given as HH:MM, got {value:?}."))
+        };
+        let quiet_hours = Self {
+            start: time(&settings.start)?,
+            end: time(&settings.end)?,
+            utc_offset: parse_utc_offset(&settings.utc_offset)?,
+        };
+        if quiet_hours.start == quiet_hours.end {
+            return Err("Quiet hours can't start and end at the same time.".into());
+        }
+        Ok(quiet_hours)
+    }
+}
+
+/// `+HH:MM` or `-HH:MM`.
+pub fn parse_utc_offset(value: &str) -> Result<FixedOffset, String> {
+    value
+        .parse()
+        .map_err(|_| format!("UTC offsets must look like +02:00, got {value:?}."))
+}
+
+impl QuietHours {
+    /// When deliveries to a recipient at `utc_offset` can resume, if `now`
+    /// falls within their quiet hours.
+    pub fn resumes_at(
+        &self,
+        now: DateTime<Utc>,
+        utc_offset: Option<FixedOffset>,
+    ) -> Option<DateTime<Utc>> {
+        let offset = utc_offset.unwrap_or(self.utc_offset);
+        let local = now.with_timezone(&offset);
+        let time = local.time();
+        let resumes_on = if self.start < self.end {
+            (self.start <= time && time < self.end).then(|| local.date_naive())?
+        } else if time < self.end {
+            local.date_naive()
+        } else if self.start <= time {
+            local.date_naive().checked_add_days(Days::new(1))?
+        } else {
+            return None;
+        };
+        let resumes_at = offset
+            .from_local_datetime(&resumes_on.and_time(self.end))
+            .single()?;
+        Some(resumes_at.with_timezone(&Utc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quiet_hours(start: &str, end: &str, utc_offset: &str) -> QuietHours {
+        QuietHoursSettings {
+            start: start.into(),
+            end: end.into(),
+            utc_offset: utc_offset.into(),
+        }
+        .try_into()
+        .unwrap()
+    }
+
+    fn utc(datetime: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(datetime).unwrap().into()
+    }
+
+    #[test]
+    fn windows_can_span_midnight() {
+        let night = quiet_hours("22:00", "07:00", "+00:00");
+
+        assert_eq!(
+            night.resumes_at(utc("2026-10-15T23:30:00Z"), None),
+            Some(utc("2026-10-16T07:00:00Z"))
+        );
+        assert_eq!(
+            night.resumes_at(utc("2026-10-16T03:00:00Z"), None),
+            Some(utc("2026-10-16T07:00:00Z"))
+        );
+        assert_eq!(night.resumes_at(utc("2026-10-16T07:00:00Z"), None), None);
+        assert_eq!(night.resumes_at(utc("2026-10-16T21:59:00Z"), None), None);
+    }
+
+    #[test]
+    fn the_recipient_offset_overrides_the_default_one() {
+        let night = quiet_hours("22:00", "07:00", "+00:00");
+        let tokyo = parse_utc_offset("+09:00").unwrap();
+
+        // 23:00 in Tokyo, but 14:00 for everyone else.
+        let now = utc("2026-10-15T14:00:00Z");
+
+        assert_eq!(night.resumes_at(now, None), None);
+        assert_eq!(
+            night.resumes_at(now, Some(tokyo)),
+            Some(utc("2026-10-15T22:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn invalid_windows_are_rejected() {
+        for (start, end, utc_offset) in [
+            ("22h", "07:00", "+00:00"),
+            ("22:00", "07:00", "CET"),
+            ("22:00", "22:00", "+00:00"),
+        ] {
+            let settings = QuietHoursSettings {
+                start: start.into(),
+                end: end.into(),
+                utc_offset: utc_offset.into(),
+            };
+            claim::assert_err!(QuietHours::try_from(settings));
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::RETRY_AFTER,
+    middleware::Next,
+    web, HttpResponse,
+};
+
+use crate::{
+    client_ip::{client_ip, ClientIpConfig, IpNetworks},
+    configuration::RateLimitSettings,
+};
+
+/// At most this many keys are tracked: beyond it, the least recently
+/// refilled bucket is forgotten to make room for a new key.
+const MAX_TRACKED_KEYS: usize = 10_000;
+
+/// A token bucket per key: each key may burst up to `capacity` requests and
+/// regains one token every `refill_interval`.
+pub struct RateLimiter {
+    capacity: f64,
+    refill_interval: Duration,
+    max_keys: usize,
+    buckets: Mutex<Buckets>,
+}
+
+#[derive(Default)]
+struct Buckets {
+    by_key: HashMap<String, Bucket>,
+    /// The same keys, least recently refilled first.
+    by_last_refill: BTreeSet<(Instant, String)>,
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+#[derive(Debug)]
+pub struct RateLimited {
+    pub retry_after: Duration,
+}
+
+impl RateLimited {
+    /// `Retry-After` only supports whole seconds: round up so that clients
+    /// retrying on time find a token waiting for them.
+    pub fn retry_after_seconds(&self) -> u64 {
+        self.retry_after.as_secs_f64().ceil().max(1.0) as u64
+    }
+}
+
+impl RateLimiter {
+    pub fn new(capacity: u32, refill_interval: Duration) -> Self {
+        Self {
+            capacity: capacity as f64,
+            refill_interval,
+            max_keys: MAX_TRACKED_KEYS,
+            buckets: Mutex::new(Buckets::default()),
+        }
+    }
+
+    /// Take a token from `key`'s bucket, or report how long until one is available.
+    pub fn check(&self, key: &str, now: Instant) -> Result<(), RateLimited> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let Buckets {
+            by_key,
+            by_last_refill,
+        } = &mut *buckets;
+        if !by_key.contains_key(key) {
+            self.make_room(by_key, by_last_refill, now);
+        }
+        let bucket = by_key.entry(key.to_string()).or_insert(Bucket {
+            tokens: self.capacity,
+            last_refill: now,
+        });
+        by_last_refill.remove(&(bucket.last_refill, key.to_string()));
+        by_last_refill.insert((now, key.to_string()));
+        bucket.tokens = self.refilled_tokens(bucket, now);
+        bucket.last_refill = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(RateLimited {
+                retry_after: self.refill_interval.mul_f64(1.0 - bucket.tokens),
+            })
+        }
+    }
+
+    /// Drop the buckets that are full again, which are as good as new, from
+    /// the least recently refilled on, then the least recently refilled
+    /// ones until a new key fits. Each key is dropped at most once, so this
+    /// is cheap on average.
+    fn make_room(
+        &self,
+        by_key: &mut HashMap<String, Bucket>,
+        by_last_refill: &mut BTreeSet<(Instant, String)>,
+        now: Instant,
+    ) {
+        while let Some((_, key)) = by_last_refill.first() {
+            let is_full = by_key
+                .get(key)
+                .is_some_and(|b| self.refilled_tokens(b, now) >= self.capacity);
+            if !is_full && by_key.len() < self.max_keys {
+                break;
+            }
+            let (_, key) = by_last_refill.pop_first().expect("The set is not empty");
+            by_key.remove(&key);
+        }
+    }
+
+    fn refilled_tokens(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        let refilled = elapsed.as_secs_f64() / self.refill_interval.as_secs_f64();
+        (bucket.tokens + refilled).min(self.capacity)
+    }
+}
+
+/// Clients that are never rate limited, such as an office network, so that
+/// admins can't lock themselves out of `/login` during an incident.
+pub struct ExemptNetworks(pub IpNetworks);
+
+#[derive(serde::Serialize)]
+struct RateLimitedBody {
+    error: &'static str,
+    message: String,
+    retry_after_seconds: u64,
+}
+
+/// What a request takes a token for: its path, except for routes that do
+/// the same thing and so share one budget.
+fn rate_limit_scope(path: &str) -> &str {
+    match path {
+        // The GET shim runs the same `subscribe` handler.
+        "/subscriptions" | "/subscriptions/new" => "subscribe",
+        path => path,
+    }
+}
+
+pub async fn rate_limit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let limiter = req.app_data::<web::Data<RateLimiter>>().cloned();
+    let settings = req.app_data::<web::Data<RateLimitSettings>>().cloned();
+    let (Some(limiter), Some(settings)) = (limiter, settings) else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    if !settings.enabled {
+        return Ok(next.call(req).await?.map_into_left_body());
+    }
+    let client_ip = match req.app_data::<web::Data<ClientIpConfig>>() {
+        Some(config) => client_ip(req.request(), config),
+        None => client_ip(req.request(), &ClientIpConfig::default()),
+    };
+    let exempt = req.app_data::<web::Data<ExemptNetworks>>();
+    if let (Some(ip), Some(exempt)) = (client_ip, exempt) {
+        if exempt.0.contains(ip) {
+            return Ok(next.call(req).await?.map_into_left_body());
+        }
+    }
+
+    let client = client_ip.map(|ip| ip.to_string()).unwrap_or_default();
+    let key = format!("{} {}", rate_limit_scope(req.path()), client);
+    match limiter.check(&key, Instant::now()) {
+        Ok(()) => Ok(next.call(req).await?.map_into_left_body()),
+        Err(limited) => {
+            let retry_after = limited.retry_after_seconds();
+            let mut response = HttpResponse::TooManyRequests();
+            response.insert_header((RETRY_AFTER, retry_after));
+            let response = if settings.json_body {
+                response.json(RateLimitedBody {
+                    error: "too_many_requests",
+                    message: format!(
+                        "At most {} requests are allowed in a burst, \
+                        with one more allowed every {} ms.",
+                        settings.capacity, settings.refill_interval_milliseconds
+                    ),
+                    retry_after_seconds: retry_after,
+                })
+            } else {
+                response.finish()
+            };
+            Ok(req.into_response(response).map_into_right_body())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_within_capacity_are_allowed() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(10));
+        let now = Instant::now();
+
+        claim::assert_ok!(limiter.check("a", now));
+        claim::assert_ok!(limiter.check("a", now));
+        claim::assert_err!(limiter.check("a", now));
+        // Buckets are independent
+        claim::assert_ok!(limiter.check("b", now));
+    }
+
+    #[test]
+    fn retry_after_reflects_the_refill_interval_and_shrinks_over_time() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(10));
+        let start = Instant::now();
+        claim::assert_ok!(limiter.check("a", start));
+
+        let limited = limiter.check("a", start).unwrap_err();
+        assert_eq!(limited.retry_after_seconds(), 10);
+
+        let limited = limiter
+            .check("a", start + Duration::from_millis(4500))
+            .unwrap_err();
+        assert_eq!(limited.retry_after_seconds(), 6);
+
+        claim::assert_ok!(limiter.check("a", start + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn the_number_of_tracked_keys_is_capped() {
+        let limiter = RateLimiter {
+            max_keys: 3,
+            ..RateLimiter::new(2, Duration::from_secs(10))
+        };
+        let start = Instant::now();
+
+        // Every bucket stays partly drained.
+        for i in 0..10u64 {
+            claim::assert_ok!(limiter.check(&i.to_string(), start + Duration::from_millis(i)));
+            let buckets = limiter.buckets.lock().unwrap();
+            assert!(buckets.by_key.len() <= 3);
+            assert_eq!(buckets.by_key.len(), buckets.by_last_refill.len());
+        }
+        // The least recently refilled ones were forgotten.
+        let buckets = limiter.buckets.lock().unwrap();
+        let mut keys: Vec<&String> = buckets.by_key.keys().collect();
+        keys.sort();
+        assert_eq!(keys, ["7", "8", "9"]);
+    }
+
+    #[test]
+    fn a_tracked_key_keeps_its_bucket_at_the_cap() {
+        let limiter = RateLimiter {
+            max_keys: 2,
+            ..RateLimiter::new(1, Duration::from_secs(10))
+        };
+        let now = Instant::now();
+        claim::assert_ok!(limiter.check("a", now));
+        claim::assert_ok!(limiter.check("b", now));
+
+        claim::assert_err!(limiter.check("a", now));
+        claim::assert_err!(limiter.check("b", now));
+    }
+}
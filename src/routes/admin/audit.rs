@@ -0,0 +1,95 @@
+use actix_web::{web, Responder};
+use actix_web_lab::respond::NdJson;
+use chrono::{DateTime, Utc};
+use futures_util::{stream, TryStreamExt};
+use sqlx::PgPool;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::utils::e400;
+
+/// How many rows can be read ahead of what has been written to the client.
+const EXPORT_BUFFER: usize = 64;
+
+#[derive(serde::Deserialize)]
+pub struct AuditExportQuery {
+    /// Only export events recorded at or after this RFC 3339 timestamp.
+    since: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct AuditEvent {
+    audit_log_id: Uuid,
+    user_id: Uuid,
+    action: String,
+    target: String,
+    created_at: String,
+}
+
+/// Export the audit log as newline-delimited JSON, oldest event first.
+///
+/// Rows are streamed from the database as the response is written, so
+/// the log is never held in memory as a whole. If the query fails halfway
+/// the connection is dropped, rather than ending a truncated export cleanly.
+#[tracing::instrument(name = "Export the audit log", skip(pool, query))]
+pub async fn export_audit_log(
+    query: web::Query<AuditExportQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<impl Responder, actix_web::Error> {
+    let since = query
+        .since
+        .as_deref()
+        .map(DateTime::parse_from_rfc3339)
+        .transpose()
+        .map_err(e400)?
+        .map(|since| since.with_timezone(&Utc));
+
+    let (sender, receiver) = mpsc::channel(EXPORT_BUFFER);
+    tokio::spawn(stream_audit_events(pool.into_inner(), since, sender));
+    let events = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|event| (event, receiver))
+    });
+    Ok(NdJson::new(events).into_responder())
+}
+
+async fn stream_audit_events(
+    pool: std::sync::Arc<PgPool>,
+    since: Option<DateTime<Utc>>,
+    sender: mpsc::Sender<Result<AuditEvent, sqlx::Error>>,
+) {
+    let mut rows = sqlx::query!(
+        r#"
+        SELECT audit_log_id, user_id, action, target, created_at
+        FROM audit_log
+        WHERE $1::timestamptz IS NULL OR created_at >= $1
+        ORDER BY created_at, audit_log_id
+        "#,
+        since
+    )
+    .fetch(pool.as_ref());
+    loop {
+        let event = match rows.try_next().await {
+            Ok(Some(r)) => Ok(AuditEvent {
+                audit_log_id: r.audit_log_id,
+                user_id: r.user_id,
+                action: r.action,
+                target: r.target,
+                created_at: r.created_at.to_rfc3339(),
+            }),
+            Ok(None) => return,
+            Err(e) => {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to read the audit log for an export"
+                );
+                Err(e)
+            }
+        };
+        let failed = event.is_err();
+        if sender.send(event).await.is_err() || failed {
+            // Either the client went away, or the export can't go on.
+            return;
+        }
+    }
+}
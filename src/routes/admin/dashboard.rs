@@ -1,13 +1,15 @@
-use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web::{web, HttpResponse};
 use anyhow::Context;
 use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::{
+    issue_delivery_worker::is_paused,
     session_state::TypedSession,
-    utils::{e500, see_other},
+    utils::{e500, html_response, see_other},
 };
 
+#[tracing::instrument(name = "admin dashboard", skip(pool, session))]
 pub async fn admin_dashboard(
     pool: web::Data<PgPool>,
     session: TypedSession,
@@ -17,10 +19,22 @@ pub async fn admin_dashboard(
     } else {
         return Ok(see_other("/login"));
     };
-    Ok(HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(format!(
-            r#"
+    let csrf_token = match session.get_csrf_token().map_err(e500)? {
+        Some(token) => token,
+        None => session.insert_csrf_token().map_err(e500)?,
+    };
+    let worker_form = if is_paused(pool.get_ref()).await.map_err(e500)? {
+        r#"<form name="resumeWorkerForm" action="/admin/worker/resume" method="post">
+            Deliveries are paused.
+            <input type="submit" value="Resume deliveries">
+        </form>"#
+    } else {
+        r#"<form name="pauseWorkerForm" action="/admin/worker/pause" method="post">
+            <input type="submit" value="Pause deliveries">
+        </form>"#
+    };
+    Ok(html_response(format!(
+        r#"
 <!DOCTYPE html>
 <html lang="en">
 <head>
@@ -32,14 +46,20 @@ pub async fn admin_dashboard(
     <p>Available actions:</p>
     <li>
         <a href="/admin/newsletters">Create a new issue</a>
+        <a href="/admin/lists">Manage newsletter lists</a>
+        <a href="/admin/suppressions">Import suppressed addresses</a>
+        <a href="/admin/deadletter">Inspect failed deliveries</a>
+        <a href="/admin/settings/email_token">Rotate the email provider token</a>
+        {worker_form}
         <form name="logoutForm" action="/admin/logout" method="post">
+            <input hidden type="text" name="csrf_token" value="{csrf_token}">
             <input type="submit" value="Logout">
         </form>
     </li>
 </body>
 </html>
         "#
-        )))
+    )))
 }
 
 #[tracing::instrument(name = "Get username", skip(pool))]
@@ -0,0 +1,104 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::utils::{e500, html_response};
+
+struct DeadLetter {
+    dead_letter_id: Uuid,
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+    n_retries: i16,
+    last_error: String,
+    dead_lettered_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "dead letter list", skip(pool, flash_messages))]
+pub async fn dead_letter_list(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let dead_letters = get_dead_letters(&pool)
+        .await
+        .context("Failed to fetch dead-lettered deliveries")
+        .map_err(e500)?;
+    let mut rows_html = String::new();
+    for d in dead_letters {
+        writeln!(
+            rows_html,
+            r#"<tr>
+            <td>{}</td>
+            <td>{}</td>
+            <td>{}</td>
+            <td>{}</td>
+            <td>{}</td>
+            <td>
+                <form action="/admin/deadletter/{}/replay" method="post">
+                    <button type="submit">Replay</button>
+                </form>
+            </td>
+        </tr>"#,
+            d.newsletter_issue_id,
+            encode_minimal(&d.subscriber_email),
+            d.n_retries,
+            encode_minimal(&d.last_error),
+            d.dead_lettered_at.to_rfc3339(),
+            d.dead_letter_id,
+        )
+        .unwrap();
+    }
+
+    Ok(html_response(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Dead-lettered deliveries</title>
+</head>
+<body>
+    {msg_html}
+    <table>
+        <tr>
+            <th>Issue</th>
+            <th>Subscriber</th>
+            <th>Attempts</th>
+            <th>Last error</th>
+            <th>Dead-lettered at</th>
+            <th></th>
+        </tr>
+        {rows_html}
+    </table>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+    )))
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_dead_letters(pool: &PgPool) -> Result<Vec<DeadLetter>, sqlx::Error> {
+    sqlx::query_as!(
+        DeadLetter,
+        r#"
+        SELECT
+            dead_letter_id,
+            newsletter_issue_id,
+            subscriber_email,
+            n_retries,
+            last_error,
+            dead_lettered_at
+        FROM issue_delivery_dead_letter
+        ORDER BY dead_lettered_at DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
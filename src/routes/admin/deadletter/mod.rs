@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::dead_letter_list;
+pub use post::replay_dead_letter;
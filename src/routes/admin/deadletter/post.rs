@@ -0,0 +1,66 @@
+use actix_web::{error::ErrorNotFound, web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use uuid::Uuid;
+
+use crate::{
+    audit::record_audit_event,
+    authentication::UserId,
+    transaction::RequestTransaction,
+    utils::{e500, see_other_with_flash},
+};
+
+/// Move a dead-lettered delivery back into the queue, with a fresh retry budget.
+#[tracing::instrument(
+    name = "Replay a dead-lettered delivery",
+    skip(transaction, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn replay_dead_letter(
+    dead_letter_id: web::Path<Uuid>,
+    transaction: RequestTransaction,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let dead_letter_id = dead_letter_id.into_inner();
+    let user_id = user_id.into_inner();
+    let mut transaction = transaction.lock().await;
+    let dead_letter = sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_dead_letter
+        WHERE dead_letter_id = $1
+        RETURNING newsletter_issue_id, subscriber_email
+        "#,
+        dead_letter_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to remove the dead-lettered delivery")
+    .map_err(e500)?
+    .ok_or_else(|| ErrorNotFound("Unknown dead-lettered delivery"))?;
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+        VALUES ($1, $2)
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+        SET n_retries = 0, execute_after = now()
+        "#,
+        dead_letter.newsletter_issue_id,
+        dead_letter.subscriber_email
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to re-enqueue the delivery")
+    .map_err(e500)?;
+    record_audit_event(
+        &mut **transaction,
+        *user_id,
+        "replay_dead_letter",
+        &dead_letter_id.to_string(),
+    )
+    .await
+    .map_err(e500)?;
+    Ok(see_other_with_flash(
+        "/admin/deadletter",
+        FlashMessage::info("The delivery has been queued again."),
+    ))
+}
@@ -0,0 +1,58 @@
+use actix_web::{error::ErrorNotFound, web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::utils::e500;
+
+#[derive(serde::Serialize)]
+struct Delivery {
+    message_id: String,
+    newsletter_issue_id: Uuid,
+    issue_title: String,
+    subscriber_email: String,
+    /// Unset when the subscriber has since been deleted.
+    subscriber_id: Option<Uuid>,
+    status: String,
+    recorded_at: String,
+}
+
+/// Find the delivery the provider knows as `message_id`, to answer
+/// "did they get it" questions that start from a provider event.
+#[tracing::instrument(name = "Look up a delivery by message id", skip(pool))]
+pub async fn delivery_by_message_id(
+    message_id: web::Path<String>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let r = sqlx::query!(
+        r#"
+        SELECT
+            d.message_id as "message_id!",
+            d.newsletter_issue_id,
+            i.title as issue_title,
+            d.subscriber_email,
+            s.id as "subscriber_id?",
+            d.status,
+            d.recorded_at
+        FROM delivery_status d
+        JOIN newsletter_issues i USING (newsletter_issue_id)
+        LEFT JOIN subscriptions s ON s.email = d.subscriber_email
+        WHERE d.message_id = $1
+        "#,
+        message_id.as_str()
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to look up the delivery")
+    .map_err(e500)?
+    .ok_or_else(|| ErrorNotFound("Unknown message id"))?;
+    Ok(HttpResponse::Ok().json(Delivery {
+        message_id: r.message_id,
+        newsletter_issue_id: r.newsletter_issue_id,
+        issue_title: r.issue_title,
+        subscriber_email: r.subscriber_email,
+        subscriber_id: r.subscriber_id,
+        status: r.status,
+        recorded_at: r.recorded_at.to_rfc3339(),
+    }))
+}
@@ -0,0 +1,92 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::utils::{e500, html_response};
+
+struct NewsletterList {
+    name: String,
+    sender: String,
+    reply_to: Option<String>,
+}
+
+#[tracing::instrument(name = "newsletter lists", skip(pool, flash_messages))]
+pub async fn newsletter_lists(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let lists = get_lists(&pool)
+        .await
+        .context("Failed to fetch the newsletter lists")
+        .map_err(e500)?;
+    let mut rows_html = String::new();
+    for l in lists {
+        writeln!(
+            rows_html,
+            r#"<tr>
+            <td>{}</td>
+            <td>{}</td>
+            <td>{}</td>
+        </tr>"#,
+            encode_minimal(&l.name),
+            encode_minimal(&l.sender),
+            encode_minimal(l.reply_to.as_deref().unwrap_or_default()),
+        )
+        .unwrap();
+    }
+
+    Ok(html_response(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Newsletter lists</title>
+</head>
+<body>
+    {msg_html}
+    <table>
+        <tr>
+            <th>Name</th>
+            <th>Sender</th>
+            <th>Reply-to</th>
+        </tr>
+        {rows_html}
+    </table>
+    <p>The sender of a new list must be a confirmed sender signature with the email provider.</p>
+    <form action="/admin/lists" method="post">
+        <label>Name
+            <input type="text" placeholder="e.g. Weekly digest" name="name">
+        </label>
+        <br>
+        <label>Sender
+            <input type="email" placeholder="digest@example.com" name="sender">
+        </label>
+        <br>
+        <label>Reply-to (optional)
+            <input type="email" placeholder="editor@example.com" name="reply_to">
+        </label>
+        <br>
+        <button type="submit">Create list</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+    )))
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_lists(pool: &PgPool) -> Result<Vec<NewsletterList>, sqlx::Error> {
+    sqlx::query_as!(
+        NewsletterList,
+        r#"SELECT name, sender, reply_to FROM newsletter_lists ORDER BY name"#
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,143 @@
+use std::str::FromStr;
+
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use uuid::Uuid;
+
+use crate::{
+    audit::record_audit_event,
+    authentication::UserId,
+    domain::SubscriberEmail,
+    email_client::EmailClient,
+    startup::SenderSignatureCheck,
+    transaction::RequestTransaction,
+    utils::{e500, see_other_with_flash},
+};
+
+const MAX_NAME_LENGTH: usize = 100;
+
+#[derive(serde::Deserialize)]
+pub struct NewListForm {
+    name: String,
+    sender: String,
+    #[serde(default)]
+    reply_to: String,
+}
+
+/// Create a list that issues can be published to, sent from its own address.
+///
+/// Unless `email_client.sender_verification` is disabled, the sender must be
+/// a confirmed signature with the email provider, which would otherwise
+/// reject every email of the list. Unlike on startup, `warn` refuses it too:
+/// the list can be created once the signature is confirmed.
+#[tracing::instrument(
+    name = "Create a newsletter list",
+    skip(form, email_client, signature_check, transaction, user_id),
+    fields(user_id=%&*user_id, list_name=%form.name)
+)]
+pub async fn create_newsletter_list(
+    form: web::Form<NewListForm>,
+    email_client: web::Data<EmailClient>,
+    signature_check: web::Data<SenderSignatureCheck>,
+    transaction: RequestTransaction,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let NewListForm {
+        name,
+        sender,
+        reply_to,
+    } = form.0;
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH || name.contains(char::is_control)
+    {
+        return Ok(error_message(format!(
+            "The name must be a single line of at most {MAX_NAME_LENGTH} characters."
+        )));
+    }
+    let sender = match SubscriberEmail::from_str(&sender) {
+        Ok(sender) => sender,
+        Err(e) => return Ok(error_message(e)),
+    };
+    let reply_to = match reply_to.trim() {
+        "" => None,
+        reply_to => match SubscriberEmail::from_str(reply_to) {
+            Ok(reply_to) => Some(reply_to),
+            Err(e) => return Ok(error_message(e)),
+        },
+    };
+    if let Err(problem) = check_list_sender(&email_client, &signature_check, &sender).await {
+        return Ok(error_message(problem));
+    }
+
+    let list_id = Uuid::new_v4();
+    let mut transaction = transaction.lock().await;
+    let created = sqlx::query!(
+        r#"
+        INSERT INTO newsletter_lists (list_id, name, sender, reply_to)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (name) DO NOTHING
+        "#,
+        list_id,
+        name,
+        sender.as_ref(),
+        reply_to.as_ref().map(AsRef::as_ref)
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(e500)?
+    .rows_affected()
+        > 0;
+    if !created {
+        return Ok(see_other_with_flash(
+            "/admin/lists",
+            FlashMessage::error(format!(
+                "A list named {} already exists.",
+                htmlescape::encode_minimal(name)
+            )),
+        ));
+    }
+    record_audit_event(
+        &mut **transaction,
+        **user_id,
+        "create_list",
+        &list_id.to_string(),
+    )
+    .await
+    .map_err(e500)?;
+    Ok(see_other_with_flash(
+        "/admin/lists",
+        FlashMessage::info(format!(
+            "The list {} has been created.",
+            htmlescape::encode_minimal(name)
+        )),
+    ))
+}
+
+/// Whether the provider has a confirmed signature for `sender`, unless
+/// verification is disabled.
+async fn check_list_sender(
+    email_client: &EmailClient,
+    signature_check: &SenderSignatureCheck,
+    sender: &SubscriberEmail,
+) -> Result<(), String> {
+    let (true, Some(account_token)) = (signature_check.enabled, &signature_check.account_token)
+    else {
+        return Ok(());
+    };
+    match email_client
+        .is_confirmed_signature(account_token, sender)
+        .await
+    {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(format!(
+            "The sender {} is not a confirmed sender signature.",
+            sender.as_ref()
+        )),
+        Err(e) => Err(format!("Failed to verify the sender signature: {e}")),
+    }
+}
+
+fn error_message(message: impl Into<String>) -> HttpResponse {
+    let message = htmlescape::encode_minimal(&message.into());
+    see_other_with_flash("/admin/lists", FlashMessage::error(message))
+}
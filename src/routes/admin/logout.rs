@@ -1,17 +1,39 @@
-use actix_web::HttpResponse;
+use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
 
 use crate::{
+    authentication::end_session,
     session_state::TypedSession,
-    utils::{e500, see_other},
+    utils::{e400, e500, see_other, see_other_with_flash},
 };
 
-pub async fn log_out(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
+#[derive(serde::Deserialize)]
+pub struct LogoutForm {
+    csrf_token: String,
+}
+
+#[tracing::instrument(name = "log out", skip(session, form, pool))]
+pub async fn log_out(
+    session: TypedSession,
+    form: web::Form<LogoutForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
     if session.get_user_id().map_err(e500)?.is_none() {
-        Ok(see_other("/login"))
-    } else {
-        session.log_out();
-        FlashMessage::info("You have successfully logged out.").send();
-        Ok(see_other("/login"))
+        return Ok(see_other("/login"));
+    }
+    // Only the dashboard's logout form knows the session's token,
+    // so a cross-site POST can't force a logout.
+    let expected_token = session.get_csrf_token().map_err(e500)?;
+    if expected_token.as_deref() != Some(form.csrf_token.as_str()) {
+        return Err(e400("Invalid CSRF token"));
+    }
+    if let Some(session_id) = session.get_session_id().map_err(e500)? {
+        end_session(&pool, session_id).await.map_err(e500)?;
     }
+    session.log_out();
+    Ok(see_other_with_flash(
+        "/login",
+        FlashMessage::info("You have successfully logged out."),
+    ))
 }
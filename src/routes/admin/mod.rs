@@ -1,9 +1,27 @@
+mod audit;
 mod dashboard;
+mod deadletter;
+mod deliveries;
+mod lists;
 mod logout;
 mod newsletters;
 mod password;
+mod settings;
+mod subscribers;
+mod suppressions;
+mod verify_email;
+mod worker;
 
+pub use audit::export_audit_log;
 pub use dashboard::admin_dashboard;
+pub use deadletter::*;
+pub use deliveries::delivery_by_message_id;
+pub use lists::*;
 pub use logout::log_out;
 pub use newsletters::*;
 pub use password::*;
+pub use settings::*;
+pub use subscribers::*;
+pub use suppressions::*;
+pub use verify_email::*;
+pub use worker::*;
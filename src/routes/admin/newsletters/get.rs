@@ -1,21 +1,48 @@
-use actix_web::http::header::ContentType;
-use actix_web::HttpResponse;
+use actix_web::{
+    error::{InternalError, UrlencodedError},
+    http::StatusCode,
+    web, HttpRequest, HttpResponse,
+};
 use actix_web_flash_messages::IncomingFlashMessages;
 use std::fmt::Write;
 
+use crate::utils::{html_response, missing_form_field};
+
+#[tracing::instrument(name = "publish newsletter form", skip(flash_messages))]
 pub async fn publish_newsletter_form(
     flash_messages: IncomingFlashMessages,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let idempotency_key = uuid::Uuid::new_v4().to_string();
     let mut msg_html = String::new();
     for m in flash_messages.iter() {
         writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
     }
+    Ok(html_response(publish_form_page(&msg_html)))
+}
+
+/// Form config for `POST /admin/newsletters`: submissions that can't be
+/// deserialized get the form back, explaining what was wrong, instead of
+/// a bare `400 Bad Request`.
+pub fn publish_form_config() -> web::FormConfig {
+    web::FormConfig::default().error_handler(|e: UrlencodedError, _req: &HttpRequest| {
+        let message = match &e {
+            UrlencodedError::Parse(e) => missing_form_field(&e.to_string())
+                .map(|field| format!("The {} is required.", field.replace('_', " ")))
+                .unwrap_or_else(|| format!("The form is invalid: {e}")),
+            e => format!("The form is invalid: {e}"),
+        };
+        let mut response = html_response(publish_form_page(&format!(
+            "<p><i>{}</i></p>\n",
+            htmlescape::encode_minimal(&message)
+        )));
+        *response.status_mut() = StatusCode::BAD_REQUEST;
+        InternalError::from_response(e, response).into()
+    })
+}
 
-    Ok(HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(format!(
-            r#"<!DOCTYPE html>
+fn publish_form_page(msg_html: &str) -> String {
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+    format!(
+        r#"<!DOCTYPE html>
 <html lang="en">
 <head>
     <meta http-equiv="content-type" content="text/html; charset=utf-8">
@@ -50,11 +77,45 @@ pub async fn publish_newsletter_form(
             ></textarea>
         </label>
         <br>
-        <input hidden type="text" name="idempotency_key" value="{idempotency_key}">
+        <label>Category (optional):<br>
+            <input type="text" placeholder="e.g. Weekly" name="category">
+        </label>
+        <br>
+        <label>List (optional, see <a href="/admin/lists">lists</a>):<br>
+            <input type="text" placeholder="Sent from the default sender" name="list">
+        </label>
+        <br>
+        <label>
+            <input type="checkbox" name="track_opens">
+            Track opens
+        </label>
+        <br>
+        <label>
+            <input type="checkbox" name="track_links">
+            Track link clicks
+        </label>
+        <br>
+        <label>
+            <input type="checkbox" name="track_text_opens">
+            Track opens of the plain text version, with a link to the issue online
+        </label>
+        <br>
+        <label>
+            <input type="checkbox" name="personalized">
+            Replace <code>{{{{name}}}}</code> with each subscriber's name
+        </label>
+        <br>
+        <label>
+            <input type="checkbox" name="force">
+            Publish anyway, even if the previous issue went out moments ago
+            or had the same content
+        </label>
+        <br>
+        <input type="hidden" name="idempotency_key" value="{idempotency_key}">
         <button type="submit">Publish</button>
     </form>
     <p><a href="/admin/dashboard">&lt;- Back</a></p>
 </body>
 </html>"#,
-        )))
+    )
 }
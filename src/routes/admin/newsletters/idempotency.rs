@@ -0,0 +1,45 @@
+use actix_web::{web, HttpResponse};
+
+use crate::{
+    authentication::UserId,
+    idempotency::{get_key_usage, IdempotencyKey, KeyUsage},
+    utils::{e400, e500},
+};
+use sqlx::PgPool;
+
+#[derive(serde::Serialize)]
+struct KeyUsageBody {
+    used: bool,
+    /// Status of the saved response, once the request has been processed.
+    status: Option<u16>,
+}
+
+/// Tell a client whether a publish request with this key already went
+/// through, so that it can decide whether to resubmit.
+#[tracing::instrument(
+    name = "Query an idempotency key",
+    skip(key, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn idempotency_key_usage(
+    key: web::Path<String>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let key: IdempotencyKey = key.into_inner().try_into().map_err(e400)?;
+    let body = match get_key_usage(&pool, &key, **user_id).await.map_err(e500)? {
+        KeyUsage::Unused => KeyUsageBody {
+            used: false,
+            status: None,
+        },
+        KeyUsage::InProgress => KeyUsageBody {
+            used: true,
+            status: None,
+        },
+        KeyUsage::Completed(status) => KeyUsageBody {
+            used: true,
+            status: Some(status.as_u16()),
+        },
+    };
+    Ok(HttpResponse::Ok().json(body))
+}
@@ -1,5 +1,13 @@
 mod get;
+mod idempotency;
 mod post;
+mod progress;
+mod put;
+mod status;
 
-pub use get::publish_newsletter_form;
+pub use get::{publish_form_config, publish_newsletter_form};
+pub use idempotency::idempotency_key_usage;
 pub use post::publish_newsletter;
+pub use progress::{newsletter_delivery_progress, report_progress};
+pub use put::update_newsletter_issue;
+pub use status::newsletter_issue_status;
@@ -1,11 +1,17 @@
 use crate::{
     authentication::UserId,
+    configuration::{FeatureFlags, PublishingSettings},
+    db_error::e_db,
+    domain::{IssueCategory, SubscriptionStatus},
     idempotency::{save_response, try_processing, IdempotencyKey, NextAction},
-    utils::{e400, e500, see_other},
+    plain_text::html_to_text,
+    utils::{e400, e500, see_other, see_other_with_flash},
 };
 use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use anyhow::Context;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use sha2::{Digest, Sha256};
 use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
@@ -15,6 +21,37 @@ pub struct PublishParams {
     html_content: String,
     text_content: String,
     idempotency_key: String,
+    #[serde(default, deserialize_with = "deserialize_checkbox")]
+    track_opens: bool,
+    #[serde(default, deserialize_with = "deserialize_checkbox")]
+    track_links: bool,
+    /// Append a tracked link to the text body, see
+    /// `WorkerSettings::text_open_tracking`.
+    #[serde(default, deserialize_with = "deserialize_checkbox")]
+    track_text_opens: bool,
+    /// Substitute `{{name}}` with each subscriber's name.
+    #[serde(default, deserialize_with = "deserialize_checkbox")]
+    personalized: bool,
+    /// Optional, prefixed to the subject of the emails.
+    #[serde(default)]
+    category: String,
+    /// Name of the list the issue is sent from, see `create_newsletter_list`.
+    /// Issues without a list are sent from the default sender.
+    #[serde(default)]
+    list: String,
+    /// Publish even if the previous issue went out too recently, see
+    /// `PublishingSettings::min_seconds_between_issues`.
+    #[serde(default, deserialize_with = "deserialize_checkbox")]
+    force: bool,
+}
+
+/// HTML checkboxes are only submitted when ticked, with the value `on`.
+fn deserialize_checkbox<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value: String = serde::Deserialize::deserialize(deserializer)?;
+    Ok(matches!(value.as_str(), "on" | "true"))
 }
 
 #[tracing::instrument(
@@ -25,6 +62,8 @@ pub struct PublishParams {
 pub async fn publish_newsletter(
     form: web::Form<PublishParams>,
     pool: web::Data<PgPool>,
+    settings: web::Data<PublishingSettings>,
+    features: web::Data<FeatureFlags>,
     user_id: web::ReqData<UserId>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
@@ -33,11 +72,66 @@ pub async fn publish_newsletter(
         text_content,
         html_content,
         idempotency_key,
+        track_opens,
+        track_links,
+        track_text_opens,
+        personalized,
+        category,
+        list,
+        force,
     } = form.0;
+    if let Err(e) = validate_content(&title, &text_content, &html_content) {
+        return Ok(see_other_with_flash(
+            "/admin/newsletters",
+            FlashMessage::error(e),
+        ));
+    }
+    let category = match category.trim() {
+        "" => None,
+        category => match category.parse::<IssueCategory>() {
+            Ok(category) => Some(category),
+            Err(e) => {
+                return Ok(see_other_with_flash(
+                    "/admin/newsletters",
+                    FlashMessage::error(e),
+                ))
+            }
+        },
+    };
+    let list_id = match list.trim() {
+        "" => None,
+        list => match find_list(&pool, list).await.map_err(e500)? {
+            Some(list_id) => Some(list_id),
+            None => {
+                return Ok(see_other_with_flash(
+                    "/admin/newsletters",
+                    FlashMessage::error(format!(
+                        "There is no list named {}.",
+                        htmlescape::encode_minimal(list)
+                    )),
+                ))
+            }
+        },
+    };
+    let text_content = if text_content.trim().is_empty() && features.derive_text_from_html {
+        html_to_text(&html_content)
+    } else {
+        text_content
+    };
+    let content_hash = content_hash(&title, &text_content, &html_content);
     let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
+    let idempotency_key = if settings.idempotency_per_content {
+        // The same content sent to another list is another request.
+        idempotency_key.for_content(match list_id {
+            Some(list_id) => format!("{content_hash}:{list_id}"),
+            None => content_hash.clone(),
+        })
+    } else {
+        idempotency_key
+    };
     let mut transaction = match try_processing(&pool, &idempotency_key, *user_id)
         .await
-        .map_err(e500)?
+        .map_err(e_db)?
     {
         NextAction::StartingProcessing(t) => t,
         NextAction::ReturnSavedResponse(saved_response) => {
@@ -45,24 +139,199 @@ pub async fn publish_newsletter(
             return Ok(saved_response);
         }
     };
-    let issue_id = insert_newsletter_issue(&mut transaction, &title, &text_content, &html_content)
-        .await
-        .context("Failed to store newsletter issue details")
-        .map_err(e500)?;
-    enqueue_delivery_tasks(&mut transaction, issue_id)
+    if let (Some(min_interval), false) = (settings.min_interval_between_issues(), force) {
+        let since_last_issue = time_since_last_issue(&mut transaction)
+            .await
+            .context("Failed to look up the previous issue")
+            .map_err(e500)?;
+        if since_last_issue.is_some_and(|elapsed| elapsed < min_interval) {
+            // Forget the idempotency key, so that the same submission can
+            // be forced through.
+            drop(transaction);
+            return Ok(see_other_with_flash(
+                "/admin/newsletters",
+                FlashMessage::error(format!(
+                    "The previous issue was published less than {}s ago. \
+                    Tick \"Publish anyway\" to send this one too.",
+                    min_interval.as_secs()
+                )),
+            ));
+        }
+    }
+    if let (Some(window), false) = (settings.duplicate_window(), force) {
+        let is_duplicate = was_published_within(&mut transaction, &content_hash, list_id, window)
+            .await
+            .context("Failed to look for a duplicate issue")
+            .map_err(e500)?;
+        if is_duplicate {
+            drop(transaction);
+            return Ok(see_other_with_flash(
+                "/admin/newsletters",
+                FlashMessage::error(format!(
+                    "An issue with the same title and content was published less than {}s ago. \
+                    Tick \"Publish anyway\" to send it again.",
+                    window.as_secs()
+                )),
+            ));
+        }
+    }
+    let issue_id = insert_newsletter_issue(
+        &mut transaction,
+        &title,
+        &text_content,
+        &html_content,
+        &content_hash,
+        IssueOptions {
+            track_opens,
+            track_links,
+            track_text_opens,
+            personalized,
+            category,
+            list_id,
+        },
+    )
+    .await
+    .context("Failed to store newsletter issue details")
+    .map_err(e_db)?;
+    let n_recipients = enqueue_delivery_tasks(&mut transaction, issue_id)
         .await
         .context("Failed to enqueue delivery tasks")
-        .map_err(e500)?;
+        .map_err(e_db)?;
+    if n_recipients == 0 && !settings.record_issues_without_recipients {
+        // Dropping the transaction forgets the idempotency key too, so
+        // the same submission can be retried once someone has confirmed.
+        drop(transaction);
+        return Ok(see_other_with_flash(
+            "/admin/newsletters",
+            no_recipients_message(),
+        ));
+    }
     let response = see_other("/admin/newsletters");
     let response = save_response(transaction, &idempotency_key, *user_id, response)
         .await
-        .map_err(e500)?;
-    success_message().send();
+        .map_err(e_db)?;
+    if n_recipients == 0 {
+        no_recipients_message().send();
+    } else {
+        success_message().send();
+    }
     Ok(response)
 }
 
+const MAX_TITLE_LENGTH: usize = 200;
+
+/// Reject content that would break providers or render badly: titles must be
+/// non-empty single lines under `MAX_TITLE_LENGTH` characters, at least one of
+/// the bodies must be non-blank, and bodies may only contain line breaks and
+/// tabs as control characters.
+pub(super) fn validate_content(
+    title: &str,
+    text_content: &str,
+    html_content: &str,
+) -> Result<(), String> {
+    if title.trim().is_empty() {
+        return Err("The title cannot be empty.".into());
+    }
+    if title.chars().count() > MAX_TITLE_LENGTH {
+        return Err(format!(
+            "The title must be at most {MAX_TITLE_LENGTH} characters long."
+        ));
+    }
+    if title.chars().any(char::is_control) {
+        return Err("The title cannot contain control characters.".into());
+    }
+    if text_content.trim().is_empty() && html_content.trim().is_empty() {
+        return Err("The issue needs either HTML or plain text content.".into());
+    }
+    let is_disallowed = |c: char| c.is_control() && !matches!(c, '\n' | '\r' | '\t');
+    if text_content.chars().any(is_disallowed) || html_content.chars().any(is_disallowed) {
+        return Err("The content cannot contain control characters.".into());
+    }
+    Ok(())
+}
+
 fn success_message() -> FlashMessage {
-    FlashMessage::info("<p><i>The newsletter issue has been accepted - emails will go out shortly.</i></p>")
+    FlashMessage::info(
+        "<p><i>The newsletter issue has been accepted - emails will go out shortly.</i></p>",
+    )
+}
+
+fn no_recipients_message() -> FlashMessage {
+    FlashMessage::warning("No confirmed subscribers - nothing was sent.")
+}
+
+struct IssueOptions {
+    track_opens: bool,
+    track_links: bool,
+    track_text_opens: bool,
+    personalized: bool,
+    category: Option<IssueCategory>,
+    list_id: Option<Uuid>,
+}
+
+/// How long ago the most recent issue was published, if any was.
+#[tracing::instrument(skip_all)]
+async fn time_since_last_issue(
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<Option<std::time::Duration>, sqlx::Error> {
+    let seconds = sqlx::query_scalar!(
+        r#"
+        SELECT extract(epoch FROM now() - max(published_at::timestamptz))::float8
+        FROM newsletter_issues
+        "#
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+    Ok(seconds.map(|seconds| std::time::Duration::from_secs_f64(seconds.max(0.0))))
+}
+
+/// Identifies the title and content of an issue, to spot the same issue
+/// being published twice.
+pub(super) fn content_hash(title: &str, text_content: &str, html_content: &str) -> String {
+    let mut hasher = Sha256::new();
+    for part in [title, text_content, html_content] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    URL_SAFE_NO_PAD.encode(hasher.finalize())
+}
+
+/// Whether an issue with `content_hash` was published to the same list in
+/// the last `window`.
+#[tracing::instrument(skip(tx))]
+async fn was_published_within(
+    tx: &mut Transaction<'_, Postgres>,
+    content_hash: &str,
+    list_id: Option<Uuid>,
+    window: std::time::Duration,
+) -> Result<bool, sqlx::Error> {
+    let exists = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM newsletter_issues
+            WHERE content_hash = $1
+            AND list_id IS NOT DISTINCT FROM $2
+            AND published_at::timestamptz > now() - make_interval(secs => $3)
+        ) as "exists!"
+        "#,
+        content_hash,
+        list_id,
+        window.as_secs_f64()
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+    Ok(exists)
+}
+
+#[tracing::instrument(skip(pool))]
+async fn find_list(pool: &PgPool, name: &str) -> Result<Option<Uuid>, sqlx::Error> {
+    let list_id = sqlx::query_scalar!(
+        r#"SELECT list_id FROM newsletter_lists WHERE name = $1"#,
+        name
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(list_id)
 }
 
 #[tracing::instrument(skip_all)]
@@ -71,6 +340,8 @@ async fn insert_newsletter_issue(
     title: &str,
     text_content: &str,
     html_content: &str,
+    content_hash: &str,
+    options: IssueOptions,
 ) -> Result<Uuid, sqlx::Error> {
     let newsletter_issue_id = Uuid::new_v4();
     sqlx::query!(
@@ -80,14 +351,28 @@ async fn insert_newsletter_issue(
             title,
             text_content,
             html_content,
+            content_hash,
+            track_opens,
+            track_links,
+            track_text_opens,
+            personalized,
+            category,
+            list_id,
             published_at
         )
-        VALUES ($1, $2, $3, $4, now())
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, now())
         "#,
         newsletter_issue_id,
         title,
         text_content,
-        html_content
+        html_content,
+        content_hash,
+        options.track_opens,
+        options.track_links,
+        options.track_text_opens,
+        options.personalized,
+        options.category.as_ref().map(|c| c.as_ref()),
+        options.list_id
     )
     .execute(&mut **tx)
     .await?;
@@ -95,21 +380,28 @@ async fn insert_newsletter_issue(
 }
 
 #[tracing::instrument(skip_all)]
+/// Queue the issue for every confirmed subscriber whose address isn't
+/// suppressed, returning how many were queued.
 async fn enqueue_delivery_tasks(
     tx: &mut Transaction<'_, Postgres>,
     newsletter_issue_id: Uuid,
-) -> Result<(), sqlx::Error> {
-    sqlx::query!(
+) -> Result<u64, sqlx::Error> {
+    let n_queued = sqlx::query!(
         r#"INSERT INTO issue_delivery_queue (
             newsletter_issue_id,
             subscriber_email
         )
         SELECT $1, email FROM subscriptions
-        WHERE status = 'confirmed'
+        WHERE status = $2 AND NOT EXISTS (
+            SELECT 1 FROM suppressions
+            WHERE lower(suppressions.email) = lower(subscriptions.email)
+        )
         "#,
-        newsletter_issue_id
+        newsletter_issue_id,
+        SubscriptionStatus::Confirmed.as_str()
     )
     .execute(&mut **tx)
-    .await?;
-    Ok(())
+    .await?
+    .rows_affected();
+    Ok(n_queued)
 }
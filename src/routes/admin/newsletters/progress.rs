@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use actix_web::{error::ErrorNotFound, web, Responder};
+use actix_web_lab::sse;
+use sqlx::PgPool;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::{
+    issue_delivery_worker::{delivery_progress, DeliveryProgress},
+    utils::e500,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Stream the delivery progress of an issue as server-sent events.
+///
+/// A `progress` event is sent whenever the counts change, then a single
+/// `complete` event once nothing is left in the queue for the issue,
+/// after which the stream is closed.
+#[tracing::instrument(name = "Stream the delivery progress of an issue", skip(pool))]
+pub async fn newsletter_delivery_progress(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<impl Responder, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    let exists = sqlx::query!(
+        "SELECT 1 as exists FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .map_err(e500)?
+    .is_some();
+    if !exists {
+        return Err(ErrorNotFound("No such newsletter issue"));
+    }
+
+    let (sender, receiver) = mpsc::channel(8);
+    tokio::spawn(report_progress(pool.into_inner(), issue_id, sender));
+    Ok(sse::Sse::from_infallible_receiver(receiver))
+}
+
+/// Feed `sender` with the progress of the issue until it is delivered or
+/// the client goes away, whichever comes first. Sends can stall for a long
+/// time (a paused worker, quiet hours), so the client going away is
+/// noticed without waiting for the next change.
+pub async fn report_progress(
+    pool: std::sync::Arc<PgPool>,
+    issue_id: Uuid,
+    sender: mpsc::Sender<sse::Event>,
+) {
+    let mut last_reported: Option<DeliveryProgress> = None;
+    loop {
+        if sender.is_closed() {
+            return;
+        }
+        let progress = match delivery_progress(&pool, issue_id).await {
+            Ok(progress) => progress,
+            Err(e) => {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to fetch the delivery progress of an issue"
+                );
+                return;
+            }
+        };
+        if last_reported != Some(progress) {
+            if sender
+                .send(progress_event(progress, "progress"))
+                .await
+                .is_err()
+            {
+                // The client went away.
+                return;
+            }
+            last_reported = Some(progress);
+        }
+        if progress.is_complete() {
+            let _ = sender.send(progress_event(progress, "complete")).await;
+            return;
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(POLL_INTERVAL) => {},
+            _ = sender.closed() => return,
+        }
+    }
+}
+
+fn progress_event(progress: DeliveryProgress, name: &'static str) -> sse::Event {
+    sse::Data::new_json(progress)
+        .expect("Delivery progress is always serializable")
+        .event(name)
+        .into()
+}
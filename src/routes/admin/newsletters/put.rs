@@ -0,0 +1,111 @@
+use actix_web::{error::ErrorConflict, error::ErrorNotFound, web, HttpResponse};
+use anyhow::Context;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use super::post::{content_hash, validate_content};
+use crate::{
+    audit::record_audit_event,
+    authentication::UserId,
+    transaction::RequestTransaction,
+    utils::{e400, e500},
+};
+
+#[derive(serde::Deserialize)]
+pub struct UpdateIssueParams {
+    title: String,
+    html_content: String,
+    text_content: String,
+}
+
+/// Replace the content of an issue that is still being delivered.
+///
+/// Tasks fetch the issue content when they are executed, so subscribers
+/// who haven't been sent the issue yet get the updated content.
+#[tracing::instrument(
+    name = "Update a newsletter issue",
+    skip(form, transaction, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn update_newsletter_issue(
+    issue_id: web::Path<Uuid>,
+    form: web::Form<UpdateIssueParams>,
+    transaction: RequestTransaction,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    let user_id = user_id.into_inner();
+    let UpdateIssueParams {
+        title,
+        html_content,
+        text_content,
+    } = form.0;
+    validate_content(&title, &text_content, &html_content).map_err(e400)?;
+
+    let mut transaction = transaction.lock().await;
+    let n_pending = count_pending_deliveries(&mut transaction, issue_id)
+        .await
+        .context("Failed to count pending deliveries")
+        .map_err(e500)?;
+    match n_pending {
+        None => return Err(ErrorNotFound("Unknown newsletter issue")),
+        Some(0) => {
+            return Err(ErrorConflict(
+                "The newsletter issue has already been fully delivered",
+            ))
+        }
+        Some(_) => {}
+    }
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET
+            title = $2,
+            text_content = $3,
+            html_content = $4,
+            content_hash = $5,
+            updated_at = now()
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id,
+        title,
+        text_content,
+        html_content,
+        content_hash(&title, &text_content, &html_content)
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to update newsletter issue")
+    .map_err(e500)?;
+    record_audit_event(
+        &mut **transaction,
+        *user_id,
+        "update_newsletter_issue",
+        &issue_id.to_string(),
+    )
+    .await
+    .map_err(e500)?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Number of deliveries still queued for the issue, or `None` if it doesn't exist.
+#[tracing::instrument(skip(transaction))]
+async fn count_pending_deliveries(
+    transaction: &mut Transaction<'static, Postgres>,
+    issue_id: Uuid,
+) -> Result<Option<i64>, sqlx::Error> {
+    let r = sqlx::query!(
+        r#"
+        SELECT (
+            SELECT count(*) FROM issue_delivery_queue q
+            WHERE q.newsletter_issue_id = i.newsletter_issue_id
+        ) as "n_pending!"
+        FROM newsletter_issues i
+        WHERE i.newsletter_issue_id = $1
+        "#,
+        issue_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    Ok(r.map(|r| r.n_pending))
+}
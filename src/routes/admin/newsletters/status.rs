@@ -0,0 +1,83 @@
+use actix_web::{error::ErrorNotFound, web, HttpResponse};
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    issue_delivery_worker::delivery_progress,
+    utils::{e500, html_response},
+};
+
+struct EngagementCounts {
+    opens: i64,
+    clicks: i64,
+}
+
+/// Delivery and engagement figures of an issue.
+#[tracing::instrument(name = "Show the status of an issue", skip(pool))]
+pub async fn newsletter_issue_status(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    let title = sqlx::query!(
+        "SELECT title FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .map_err(e500)?
+    .ok_or_else(|| ErrorNotFound("No such newsletter issue"))?
+    .title;
+    let progress = delivery_progress(&pool, issue_id).await.map_err(e500)?;
+    let engagement = get_engagement_counts(&pool, issue_id)
+        .await
+        .context("Failed to count engagement events")
+        .map_err(e500)?;
+    let title = encode_minimal(&title);
+    Ok(html_response(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>{title}</title>
+</head>
+<body>
+    <h1>{title}</h1>
+    <table>
+        <tr><th>Sent</th><td>{sent}</td></tr>
+        <tr><th>Failed</th><td>{failed}</td></tr>
+        <tr><th>Remaining</th><td>{remaining}</td></tr>
+        <tr><th>Opens</th><td>{opens}</td></tr>
+        <tr><th>Clicks</th><td>{clicks}</td></tr>
+    </table>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        sent = progress.sent,
+        failed = progress.failed,
+        remaining = progress.remaining,
+        opens = engagement.opens,
+        clicks = engagement.clicks,
+    )))
+}
+
+async fn get_engagement_counts(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<EngagementCounts, sqlx::Error> {
+    sqlx::query_as!(
+        EngagementCounts,
+        r#"
+        SELECT
+            count(*) FILTER (WHERE event_type = 'open') as "opens!",
+            count(*) FILTER (WHERE event_type = 'click') as "clicks!"
+        FROM engagement_events
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id
+    )
+    .fetch_one(pool)
+    .await
+}
@@ -1,11 +1,11 @@
-use actix_web::http::header::ContentType;
 use actix_web::HttpResponse;
 use actix_web_flash_messages::IncomingFlashMessages;
 use std::fmt::Write;
 
 use crate::session_state::TypedSession;
-use crate::utils::{e500, see_other};
+use crate::utils::{e500, html_response, see_other};
 
+#[tracing::instrument(name = "change password form", skip(session, flash_messages))]
 pub async fn change_password_form(
     session: TypedSession,
     flash_messages: IncomingFlashMessages,
@@ -19,8 +19,8 @@ pub async fn change_password_form(
         writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
     }
 
-    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
-        format!(r#"\
+    Ok(html_response(format!(
+        r#"\
 <!DOCTYPE html>
 <html lang="en">
 
@@ -1,15 +1,26 @@
 use crate::{
     authentication::{validate_credentials, AuthError, Credentials, UserId},
+    configuration::PasswordChangeNotificationSettings,
+    domain::SubscriberEmail,
+    email_client::{EmailClient, EmailOptions},
     routes::admin::dashboard::get_username,
+    session_state::TypedSession,
+    transaction::RequestTransaction,
 };
 
-use actix_web::{web, HttpResponse};
+use actix_web::{
+    error::{InternalError, UrlencodedError},
+    web, HttpRequest, HttpResponse,
+};
 use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
 use secrecy::{ExposeSecret, Secret};
 use serde::Deserialize;
 use sqlx::PgPool;
+use std::str::FromStr;
+use uuid::Uuid;
 
-use crate::utils::{e500, see_other};
+use crate::utils::{e500, missing_form_field, see_other_with_flash};
 
 #[derive(Deserialize)]
 pub struct ChangePasswordForm {
@@ -18,23 +29,55 @@ pub struct ChangePasswordForm {
     new_password_confirmed: Secret<String>,
 }
 
+/// Form config for `POST /admin/password`: a submission missing a field is
+/// sent back to the form with a message about that field, rather than a bare
+/// `400 Bad Request`. Anonymous users never get this far, since the session
+/// is checked by `reject_anonymous_users` before the form is parsed.
+pub fn change_password_form_config() -> web::FormConfig {
+    web::FormConfig::default().error_handler(|e: UrlencodedError, _req: &HttpRequest| {
+        let message = match &e {
+            UrlencodedError::Parse(parse) => match missing_form_field(&parse.to_string()) {
+                Some("current_password") => "Please enter your current password.",
+                Some("new_password") => "Please enter a new password.",
+                Some("new_password_confirmed") => "Please enter your new password a second time.",
+                _ => "The form is invalid, please fill it in again.",
+            },
+            _ => "The form is invalid, please fill it in again.",
+        };
+        let response = see_other_with_flash("/admin/password", FlashMessage::error(message));
+        InternalError::from_response(e, response).into()
+    })
+}
+
+#[tracing::instrument(
+    name = "submit a password change",
+    skip(form, pool, transaction, user_id, session, email_client, notification),
+    fields(user_id=%&*user_id)
+)]
 pub async fn change_password(
     form: web::Form<ChangePasswordForm>,
     pool: web::Data<PgPool>,
+    transaction: RequestTransaction,
     user_id: web::ReqData<UserId>,
+    session: TypedSession,
+    email_client: web::Data<EmailClient>,
+    notification: web::Data<PasswordChangeNotificationSettings>,
 ) -> Result<HttpResponse, actix_web::Error> {
     if form.new_password.expose_secret().len() < 12 {
-        FlashMessage::error("Your new password must be at least 12 characters long.").send();
-        return Ok(see_other("/admin/password"));
+        return Ok(see_other_with_flash(
+            "/admin/password",
+            FlashMessage::error("Your new password must be at least 12 characters long."),
+        ));
     }
     let user_id = user_id.into_inner();
 
     if form.new_password.expose_secret() != form.new_password_confirmed.expose_secret() {
-        FlashMessage::error(
-            "You entered two different new passwords - the field values must match.",
-        )
-        .send();
-        return Ok(see_other("/admin/password"));
+        return Ok(see_other_with_flash(
+            "/admin/password",
+            FlashMessage::error(
+                "You entered two different new passwords - the field values must match.",
+            ),
+        ));
     }
 
     let username = get_username(*user_id, &pool).await.map_err(e500)?;
@@ -45,17 +88,77 @@ pub async fn change_password(
     };
     if let Err(e) = validate_credentials(credentials, &pool).await {
         return match e {
-            AuthError::InvalidCredentials(_) => {
-                FlashMessage::error("Your current password is incorrect.").send();
-                Ok(see_other("/admin/password"))
-            }
+            AuthError::InvalidCredentials(_) => Ok(see_other_with_flash(
+                "/admin/password",
+                FlashMessage::error("Your current password is incorrect."),
+            )),
             AuthError::UnexpectedError(_) => Err(e500(e)),
         };
     }
 
-    crate::authentication::change_password(*user_id, form.0.new_password, &pool)
+    crate::authentication::change_password(
+        *user_id,
+        form.0.new_password,
+        &mut **transaction.lock().await,
+    )
+    .await
+    .map_err(e500)?;
+    // Like on login: the session key changes along with the credentials.
+    session.renew();
+    if notification.enabled {
+        // Only once the new password is saved. It is changed either way: a
+        // failed notification is only logged.
+        transaction
+            .after_commit(async move {
+                if let Err(e) =
+                    notify_password_change(*user_id, &pool, &email_client, &notification).await
+                {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to send the password change notification",
+                    );
+                }
+            })
+            .await;
+    }
+    Ok(see_other_with_flash(
+        "/admin/password",
+        FlashMessage::error("Your password has been changed."),
+    ))
+}
+
+#[tracing::instrument(
+    name = "Send a password change notification",
+    skip(pool, email_client, settings)
+)]
+async fn notify_password_change(
+    user_id: Uuid,
+    pool: &PgPool,
+    email_client: &EmailClient,
+    settings: &PasswordChangeNotificationSettings,
+) -> Result<(), anyhow::Error> {
+    let email = sqlx::query!("SELECT email FROM users WHERE user_id = $1", user_id)
+        .fetch_optional(pool)
         .await
-        .map_err(e500)?;
-    FlashMessage::error("Your password has been changed.").send();
-    Ok(see_other("/admin/password"))
+        .context("failed to query for the user email")?
+        .and_then(|r| r.email);
+    let Some(email) = email else {
+        return Ok(());
+    };
+    let recipient = SubscriberEmail::from_str(&email).map_err(anyhow::Error::msg)?;
+    let options = EmailOptions {
+        sender: settings.sender().map_err(anyhow::Error::msg)?,
+        ..Default::default()
+    };
+    email_client
+        .send_email_with_options(
+            &recipient,
+            &settings.subject,
+            &format!("<p>{}</p>", htmlescape::encode_minimal(&settings.message)),
+            &settings.message,
+            &options,
+        )
+        .await?;
+    Ok(())
 }
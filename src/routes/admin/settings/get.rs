@@ -0,0 +1,34 @@
+use actix_web::HttpResponse;
+use actix_web_flash_messages::IncomingFlashMessages;
+use std::fmt::Write;
+
+use crate::utils::html_response;
+
+#[tracing::instrument(name = "email token form", skip(flash_messages))]
+pub async fn email_token_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    html_response(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Email provider token</title>
+</head>
+<body>
+    {msg_html}
+    <p>The new token is checked with the email provider before it replaces the current one.</p>
+    <form action="/admin/settings/email_token" method="post">
+        <label>New token
+            <input type="password" placeholder="Enter the new server token" name="token">
+        </label>
+        <br>
+        <button type="submit">Rotate token</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+    ))
+}
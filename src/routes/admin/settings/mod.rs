@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::email_token_form;
+pub use post::rotate_email_token;
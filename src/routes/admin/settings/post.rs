@@ -0,0 +1,65 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use secrecy::{ExposeSecret, Secret};
+
+use crate::{
+    audit::record_audit_event,
+    authentication::UserId,
+    email_client::EmailClient,
+    transaction::RequestTransaction,
+    utils::{e500, see_other_with_flash},
+};
+
+#[derive(serde::Deserialize)]
+pub struct EmailTokenForm {
+    token: Secret<String>,
+}
+
+/// Replace the email provider token without restarting.
+///
+/// The token is only swapped in once the provider has accepted it, so a
+/// typo can't interrupt deliveries. Clients sharing the token (the delivery
+/// worker included) pick it up on their next request. The new token is not
+/// persisted: update the configuration too, or it is lost on restart.
+#[tracing::instrument(
+    name = "Rotate the email provider token",
+    skip(form, email_client, transaction, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn rotate_email_token(
+    form: web::Form<EmailTokenForm>,
+    email_client: web::Data<EmailClient>,
+    transaction: RequestTransaction,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let token = form.0.token;
+    if token.expose_secret().trim().is_empty() {
+        return Ok(see_other_with_flash(
+            "/admin/settings/email_token",
+            FlashMessage::error("The token cannot be empty."),
+        ));
+    }
+    if let Err(e) = email_client.check_token(&token).await {
+        tracing::warn!(error.message = %e, "The email provider rejected the new token");
+        return Ok(see_other_with_flash(
+            "/admin/settings/email_token",
+            FlashMessage::error("The email provider did not accept the new token."),
+        ));
+    }
+    record_audit_event(
+        &mut **transaction.lock().await,
+        **user_id,
+        "rotate_email_token",
+        "email_client",
+    )
+    .await
+    .map_err(e500)?;
+    // Only once the rotation is in the audit log.
+    transaction
+        .after_commit(async move { email_client.authorization_token().set(token) })
+        .await;
+    Ok(see_other_with_flash(
+        "/admin/settings/email_token",
+        FlashMessage::info("The email provider token has been rotated."),
+    ))
+}
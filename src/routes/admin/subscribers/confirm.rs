@@ -0,0 +1,101 @@
+use actix_web::{
+    error::{ErrorConflict, ErrorForbidden, ErrorNotFound},
+    web, HttpResponse,
+};
+use anyhow::Context;
+use uuid::Uuid;
+
+use crate::{
+    audit::record_audit_event,
+    authentication::UserId,
+    clock::Clock,
+    configuration::{SubscriptionSettings, WebhookSettings},
+    domain::SubscriptionStatus,
+    routes::{confirm_subscriber, has_room_for_confirmation},
+    transaction::RequestTransaction,
+    utils::e500,
+    webhook_delivery::{enqueue_subscription_event, SubscriptionEvent},
+};
+
+/// Confirm a pending subscriber on their behalf, e.g. when they can't open
+/// the confirmation link. Their pending confirmation tokens are discarded.
+#[tracing::instrument(
+    name = "Manually confirm a subscriber",
+    skip(transaction, user_id, settings, webhooks, clock),
+    fields(user_id=%&*user_id)
+)]
+pub async fn confirm_subscriber_manually(
+    subscriber_id: web::Path<Uuid>,
+    transaction: RequestTransaction,
+    user_id: web::ReqData<UserId>,
+    settings: web::Data<SubscriptionSettings>,
+    webhooks: web::Data<WebhookSettings>,
+    clock: web::Data<dyn Clock>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let user_id = user_id.into_inner();
+    let mut transaction = transaction.lock().await;
+    let status = sqlx::query!(
+        r#"SELECT status as "status: SubscriptionStatus" FROM subscriptions WHERE id = $1 FOR UPDATE"#,
+        subscriber_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to fetch the subscriber status")
+    .map_err(e500)?
+    .map(|r| r.status);
+    match status {
+        None => return Err(ErrorNotFound("Unknown subscriber")),
+        Some(SubscriptionStatus::Confirmed) => {
+            return Err(ErrorConflict("The subscriber is already confirmed"));
+        }
+        // Their opt-out stands: only they can subscribe again.
+        Some(SubscriptionStatus::Unsubscribed) => {
+            return Err(ErrorConflict("The subscriber has unsubscribed"));
+        }
+        Some(SubscriptionStatus::Pending) => {}
+    }
+    if !has_room_for_confirmation(&mut transaction, &settings)
+        .await
+        .context("Failed to check the confirmed subscriber cap")
+        .map_err(e500)?
+    {
+        return Err(ErrorForbidden("The confirmed subscriber cap is reached"));
+    }
+
+    let email = confirm_subscriber(&mut **transaction, subscriber_id)
+        .await
+        .context("Failed to confirm the subscriber")
+        .map_err(e500)?
+        // The row is locked above, so it is still pending.
+        .context("The subscriber is no longer pending")
+        .map_err(e500)?;
+    enqueue_subscription_event(
+        &mut **transaction,
+        &webhooks,
+        SubscriptionEvent::Confirmed,
+        subscriber_id,
+        &email,
+        clock.now(),
+    )
+    .await
+    .context("Failed to queue the confirmation event")
+    .map_err(e500)?;
+    sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscriber_id
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to delete the subscriber's confirmation tokens")
+    .map_err(e500)?;
+    record_audit_event(
+        &mut **transaction,
+        *user_id,
+        "confirm_subscriber",
+        &subscriber_id.to_string(),
+    )
+    .await
+    .map_err(e500)?;
+    Ok(HttpResponse::Ok().body("The subscriber has been confirmed."))
+}
@@ -0,0 +1,67 @@
+use actix_web::{error::ErrorNotFound, web, HttpResponse};
+use anyhow::Context;
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::utils::{e500, html_response};
+
+/// What we know about a subscriber, including how many confirmation emails
+/// went out to them, to tell "never sent" from "sent but not clicked".
+#[tracing::instrument(name = "Show a subscriber", skip(pool))]
+pub async fn subscriber_details(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT
+            email,
+            name,
+            status,
+            subscribed_at,
+            confirmation_emails_sent,
+            last_confirmation_email_sent_at
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to fetch the subscriber")
+    .map_err(e500)?
+    .ok_or_else(|| ErrorNotFound("Unknown subscriber"))?;
+    let last_sent_at = subscriber
+        .last_confirmation_email_sent_at
+        .map(|at| at.to_rfc3339())
+        .unwrap_or_else(|| "never".into());
+
+    Ok(html_response(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Subscriber</title>
+</head>
+<body>
+    <table>
+        <tr><th>Email</th><td>{}</td></tr>
+        <tr><th>Name</th><td>{}</td></tr>
+        <tr><th>Status</th><td>{}</td></tr>
+        <tr><th>Subscribed at</th><td>{}</td></tr>
+        <tr><th>Confirmation emails sent</th><td>{}</td></tr>
+        <tr><th>Last confirmation email sent at</th><td>{}</td></tr>
+    </table>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        encode_minimal(&subscriber.email),
+        encode_minimal(&subscriber.name),
+        subscriber.status,
+        subscriber.subscribed_at.to_rfc3339(),
+        subscriber.confirmation_emails_sent,
+        last_sent_at,
+    )))
+}
@@ -0,0 +1,5 @@
+mod confirm;
+mod get;
+
+pub use confirm::confirm_subscriber_manually;
+pub use get::subscriber_details;
@@ -0,0 +1,46 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::utils::{e500, html_response};
+
+#[tracing::instrument(name = "suppressions form", skip(pool, flash_messages))]
+pub async fn suppressions_form(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let n_suppressed = sqlx::query!(r#"SELECT count(*) as "count!" FROM suppressions"#)
+        .fetch_one(pool.get_ref())
+        .await
+        .context("Failed to count the suppressed addresses")
+        .map_err(e500)?
+        .count;
+
+    Ok(html_response(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Suppressed addresses</title>
+</head>
+<body>
+    {msg_html}
+    <p>{n_suppressed} addresses are never emailed.</p>
+    <form action="/admin/suppressions/import" method="post">
+        <label>Addresses to suppress, one per line or separated by commas
+            <textarea placeholder="ursula@example.com" name="emails" rows="20" cols="50"></textarea>
+        </label>
+        <br>
+        <button type="submit">Import</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+    )))
+}
@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use actix_web::{
+    error::{InternalError, UrlencodedError},
+    web, HttpRequest, HttpResponse,
+};
+use actix_web_flash_messages::FlashMessage;
+
+use crate::{
+    audit::record_audit_event,
+    authentication::UserId,
+    domain::SubscriberEmail,
+    transaction::RequestTransaction,
+    utils::{e500, see_other_with_flash},
+};
+
+/// Suppression lists exported by other providers run well past the
+/// default 16 KiB form limit.
+const MAX_IMPORT_BYTES: usize = 4 * 1024 * 1024;
+
+/// Invalid entries quoted back in the report, beyond which they are only
+/// counted.
+const MAX_REPORTED_INVALID_ENTRIES: usize = 10;
+
+/// Longer invalid entries are cut when quoted back.
+const MAX_QUOTED_ENTRY_CHARS: usize = 64;
+
+/// Bytes of escaped entries quoted back, to keep the report well within the
+/// ~4 KB flash message cookie.
+const MAX_QUOTED_BYTES: usize = 1024;
+
+#[derive(serde::Deserialize)]
+pub struct ImportSuppressionsForm {
+    emails: String,
+}
+
+pub fn import_suppressions_form_config() -> web::FormConfig {
+    web::FormConfig::default()
+        .limit(MAX_IMPORT_BYTES)
+        .error_handler(|e: UrlencodedError, _req: &HttpRequest| {
+            let message = match &e {
+                UrlencodedError::Overflow { .. } => {
+                    "The list is too long, please import it in several parts."
+                }
+                _ => "The form is invalid, please fill it in again.",
+            };
+            let response =
+                see_other_with_flash("/admin/suppressions", FlashMessage::error(message));
+            InternalError::from_response(e, response).into()
+        })
+}
+
+/// Addresses in a newline or comma separated list, e.g. a single column CSV
+/// export, deduplicated case-insensitively. Invalid entries are handed back
+/// as they were written.
+fn parse_suppression_list(list: &str) -> (Vec<SubscriberEmail>, Vec<String>) {
+    let mut valid = BTreeMap::new();
+    let mut invalid = Vec::new();
+    let entries = list
+        .split(['\n', ','])
+        .map(|entry| entry.trim().trim_matches('"').trim())
+        .filter(|entry| !entry.is_empty());
+    for entry in entries {
+        match SubscriberEmail::from_str(entry) {
+            Ok(email) => {
+                valid.entry(email.as_ref().to_lowercase()).or_insert(email);
+            }
+            Err(_) => invalid.push(entry.to_string()),
+        }
+    }
+    (valid.into_values().collect(), invalid)
+}
+
+/// The first invalid entries, cut and escaped, followed by "..." if some
+/// are left out.
+fn quote_invalid_entries(invalid: &[String]) -> String {
+    let mut quoted = Vec::new();
+    let mut n_bytes = 0;
+    for entry in invalid.iter().take(MAX_REPORTED_INVALID_ENTRIES) {
+        let mut cut: String = entry.chars().take(MAX_QUOTED_ENTRY_CHARS).collect();
+        if cut.len() < entry.len() {
+            cut.push_str("...");
+        }
+        let escaped = htmlescape::encode_minimal(&cut);
+        if n_bytes + escaped.len() > MAX_QUOTED_BYTES {
+            break;
+        }
+        n_bytes += escaped.len();
+        quoted.push(escaped);
+    }
+    if quoted.len() < invalid.len() {
+        quoted.push("...".to_string());
+    }
+    quoted.join(", ")
+}
+
+/// Suppress every valid address of the list, so that no issue is ever
+/// queued for them. Invalid entries are skipped and reported back.
+#[tracing::instrument(
+    name = "Import suppressions",
+    skip(form, transaction, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn import_suppressions(
+    form: web::Form<ImportSuppressionsForm>,
+    transaction: RequestTransaction,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (valid, invalid) = parse_suppression_list(&form.emails);
+    if valid.is_empty() && invalid.is_empty() {
+        return Ok(see_other_with_flash(
+            "/admin/suppressions",
+            FlashMessage::error("Please enter the addresses to suppress."),
+        ));
+    }
+    let emails: Vec<String> = valid.iter().map(|e| e.as_ref().to_string()).collect();
+    let mut transaction = transaction.lock().await;
+    let n_imported = sqlx::query!(
+        r#"
+        INSERT INTO suppressions (email, reason)
+        SELECT email, 'import' FROM UNNEST($1::text[]) AS imported(email)
+        ON CONFLICT DO NOTHING
+        "#,
+        &emails
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(e500)?
+    .rows_affected();
+    record_audit_event(
+        &mut **transaction,
+        **user_id,
+        "import_suppressions",
+        &n_imported.to_string(),
+    )
+    .await
+    .map_err(e500)?;
+
+    let mut report = format!(
+        "Suppressed {n_imported} new addresses, {} were already suppressed.",
+        valid.len() as u64 - n_imported
+    );
+    if !invalid.is_empty() {
+        report.push_str(&format!(
+            " Skipped {} invalid entries: {}",
+            invalid.len(),
+            quote_invalid_entries(&invalid)
+        ));
+    }
+    Ok(see_other_with_flash(
+        "/admin/suppressions",
+        FlashMessage::info(report),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_suppression_list, quote_invalid_entries};
+
+    #[test]
+    fn lists_can_be_split_by_lines_or_commas() {
+        let (valid, invalid) = parse_suppression_list(
+            "ursula@example.com\r\n\"le.guin@example.com\", not-an-email\n\n URSULA@example.com",
+        );
+
+        let valid: Vec<&str> = valid.iter().map(|e| e.as_ref()).collect();
+        assert_eq!(valid, ["le.guin@example.com", "ursula@example.com"]);
+        assert_eq!(invalid, ["not-an-email"]);
+    }
+
+    #[test]
+    fn long_invalid_entries_are_cut_when_quoted() {
+        let quoted = quote_invalid_entries(&["x".repeat(100), "short".to_string()]);
+
+        assert_eq!(quoted, format!("{}..., short", "x".repeat(64)));
+    }
+
+    #[test]
+    fn quoted_invalid_entries_stay_within_the_budget() {
+        let invalid = vec!["<".repeat(1000); 10];
+
+        let quoted = quote_invalid_entries(&invalid);
+
+        let separators = ", ".len() * super::MAX_REPORTED_INVALID_ENTRIES;
+        assert!(quoted.len() <= super::MAX_QUOTED_BYTES + separators + "...".len());
+        assert!(quoted.ends_with(", ..."));
+    }
+}
@@ -0,0 +1,264 @@
+use std::{fmt::Write, str::FromStr};
+
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    audit::record_audit_event,
+    authentication::{UserId, VERIFY_EMAIL_PATH},
+    domain::SubscriberEmail,
+    email_client::EmailClient,
+    session_state::TypedSession,
+    signing::SignedLinks,
+    transaction::RequestTransaction,
+    utils::{e500, html_response, see_other, see_other_with_flash},
+};
+
+struct AdminEmail {
+    email: Option<String>,
+    verified: bool,
+}
+
+async fn get_admin_email(
+    executor: impl sqlx::PgExecutor<'_>,
+    user_id: Uuid,
+) -> Result<AdminEmail, sqlx::Error> {
+    let r = sqlx::query!(
+        "SELECT email, email_verified_at FROM users WHERE user_id = $1",
+        user_id
+    )
+    .fetch_one(executor)
+    .await?;
+    Ok(AdminEmail {
+        email: r.email,
+        verified: r.email_verified_at.is_some(),
+    })
+}
+
+/// Where admins are sent while `features.require_verified_admin_email` is
+/// on and their email isn't verified yet.
+#[tracing::instrument(
+    name = "Admin email verification page",
+    skip(pool, session, user_id, flash_messages),
+    fields(user_id=%&*user_id)
+)]
+pub async fn verify_email_page(
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    // Logging out must stay possible for admins who can't verify.
+    let csrf_token = match session.get_csrf_token().map_err(e500)? {
+        Some(token) => token,
+        None => session.insert_csrf_token().map_err(e500)?,
+    };
+    let admin_email = get_admin_email(pool.get_ref(), **user_id)
+        .await
+        .map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    // Admins without an email, e.g. created before emails were stored, add
+    // one here. An unverified one can be corrected.
+    let address_form = |label: &str| {
+        format!(
+            r#"<form action="{VERIFY_EMAIL_PATH}/address" method="post">
+        <label>{label}
+            <input type="email" placeholder="Enter your email address" name="email">
+        </label>
+        <button type="submit">Save</button>
+    </form>"#
+        )
+    };
+    let status_html = match admin_email {
+        AdminEmail {
+            email: Some(email),
+            verified: true,
+        } => format!(
+            r#"<p>Your email address, {}, is verified.</p>
+    <p><a href="/admin/dashboard">Go to the dashboard</a></p>"#,
+            htmlescape::encode_minimal(&email)
+        ),
+        AdminEmail {
+            email: Some(email),
+            verified: false,
+        } => format!(
+            r#"<p>Verify your email address, {}, to use the admin panel.
+    It is where security notices are sent.</p>
+    <form action="{VERIFY_EMAIL_PATH}" method="post">
+        <button type="submit">Send a verification link</button>
+    </form>
+    {}"#,
+            htmlescape::encode_minimal(&email),
+            address_form("Use a different address")
+        ),
+        AdminEmail { email: None, .. } => format!(
+            r#"<p>There is no email address on your account yet. Add one to
+    verify it: it is where security notices are sent.</p>
+    {}"#,
+            address_form("Email address")
+        ),
+    };
+    Ok(html_response(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Verify your email</title>
+</head>
+<body>
+    {msg_html}
+    {status_html}
+    <form name="logoutForm" action="/admin/logout" method="post">
+        <input hidden type="text" name="csrf_token" value="{csrf_token}">
+        <input type="submit" value="Logout">
+    </form>
+</body>
+</html>"#,
+    )))
+}
+
+/// Email the admin a link to `confirm_admin_email`.
+#[tracing::instrument(
+    name = "Send an admin email verification link",
+    skip(pool, email_client, links, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn send_email_verification(
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    links: web::Data<SignedLinks>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let admin_email = get_admin_email(pool.get_ref(), **user_id)
+        .await
+        .map_err(e500)?;
+    let Some(email) = admin_email.email else {
+        return Ok(see_other(VERIFY_EMAIL_PATH));
+    };
+    let recipient = SubscriberEmail::from_str(&email).map_err(e500)?;
+    let link = links.admin_email_verification(**user_id, &email);
+    email_client
+        .send_email(
+            &recipient,
+            "Verify your email address",
+            &format!(
+                "Click <a href=\"{link}\">here</a> to verify the email address \
+                of your admin account."
+            ),
+            &format!("Visit {link} to verify the email address of your admin account."),
+        )
+        .await
+        .context("Failed to send the verification email")
+        .map_err(e500)?;
+    Ok(see_other_with_flash(
+        VERIFY_EMAIL_PATH,
+        FlashMessage::info(format!(
+            "A verification link has been sent to {}.",
+            htmlescape::encode_minimal(&email)
+        )),
+    ))
+}
+
+#[derive(serde::Deserialize)]
+pub struct AdminEmailForm {
+    email: String,
+}
+
+/// Set the email address of an admin whose address isn't verified yet. It
+/// still has to be verified.
+#[tracing::instrument(
+    name = "Set an admin email",
+    skip(form, transaction, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn set_admin_email(
+    form: web::Form<AdminEmailForm>,
+    transaction: RequestTransaction,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Ok(email) = SubscriberEmail::from_str(form.email.trim()) else {
+        return Ok(see_other_with_flash(
+            VERIFY_EMAIL_PATH,
+            FlashMessage::error("Please enter a valid email address."),
+        ));
+    };
+    let mut transaction = transaction.lock().await;
+    let updated = sqlx::query!(
+        r#"
+        UPDATE users SET email = $2
+        WHERE user_id = $1 AND email_verified_at IS NULL
+        "#,
+        **user_id,
+        email.as_ref()
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(e500)?
+    .rows_affected();
+    if updated == 0 {
+        return Ok(see_other_with_flash(
+            VERIFY_EMAIL_PATH,
+            FlashMessage::error("Your email address is already verified."),
+        ));
+    }
+    record_audit_event(&mut **transaction, **user_id, "set_email", email.as_ref())
+        .await
+        .map_err(e500)?;
+    Ok(see_other_with_flash(
+        VERIFY_EMAIL_PATH,
+        FlashMessage::info("Your email address has been saved."),
+    ))
+}
+
+#[derive(serde::Deserialize)]
+pub struct VerificationParams {
+    signature: String,
+}
+
+/// Mark the admin's email as verified. The link is only valid for the
+/// admin it was sent to, while they are logged in.
+#[tracing::instrument(
+    name = "Verify an admin email",
+    skip(params, links, transaction, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn confirm_admin_email(
+    params: web::Query<VerificationParams>,
+    links: web::Data<SignedLinks>,
+    transaction: RequestTransaction,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = transaction.lock().await;
+    let admin_email = get_admin_email(&mut **transaction, **user_id)
+        .await
+        .map_err(e500)?;
+    let verified_email = admin_email
+        .email
+        .filter(|email| links.verify_admin_email(**user_id, email, &params.signature));
+    let Some(email) = verified_email else {
+        return Ok(see_other_with_flash(
+            VERIFY_EMAIL_PATH,
+            FlashMessage::error("This verification link is invalid or outdated."),
+        ));
+    };
+    sqlx::query!(
+        "UPDATE users SET email_verified_at = now() WHERE user_id = $1",
+        **user_id
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(e500)?;
+    record_audit_event(&mut **transaction, **user_id, "verify_email", &email)
+        .await
+        .map_err(e500)?;
+    Ok(see_other_with_flash(
+        "/admin/dashboard",
+        FlashMessage::info("Your email address is verified."),
+    ))
+}
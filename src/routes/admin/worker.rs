@@ -0,0 +1,53 @@
+use actix_web::{web, HttpResponse};
+
+use crate::{
+    audit::record_audit_event,
+    authentication::UserId,
+    issue_delivery_worker::set_paused,
+    transaction::RequestTransaction,
+    utils::{e500, see_other},
+};
+
+/// Stop the delivery worker from dequeuing tasks. Queued tasks are kept
+/// and delivered once the worker is resumed.
+#[tracing::instrument(
+    name = "Pause the delivery worker",
+    skip(transaction, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn pause_worker(
+    transaction: RequestTransaction,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    toggle_worker(transaction, **user_id, true).await
+}
+
+#[tracing::instrument(
+    name = "Resume the delivery worker",
+    skip(transaction, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn resume_worker(
+    transaction: RequestTransaction,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    toggle_worker(transaction, **user_id, false).await
+}
+
+async fn toggle_worker(
+    transaction: RequestTransaction,
+    user_id: uuid::Uuid,
+    paused: bool,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = transaction.lock().await;
+    set_paused(&mut **transaction, paused).await.map_err(e500)?;
+    let action = if paused {
+        "pause_worker"
+    } else {
+        "resume_worker"
+    };
+    record_audit_event(&mut **transaction, user_id, action, "delivery_worker")
+        .await
+        .map_err(e500)?;
+    Ok(see_other("/admin/dashboard"))
+}
@@ -1,14 +1,107 @@
-use actix_web::{HttpResponse, Responder};
-use uuid::Uuid;
-
-pub async fn health_check() -> impl Responder {
-    let request_id = Uuid::new_v4();
-
-    // let request_span =
-    //     tracing::info_span!("health check", %request_id, test_key="key", test_value="value");
-    // let _request_span_guard = request_span.enter();
-    // tracing::info!("before health check");
-    // tracing::info!("after health check");
-    log::info!("Hello, log from health check {}", request_id);
-    HttpResponse::Ok()
+use actix_web::{
+    http::header::CacheControl, http::header::CacheDirective, web, HttpResponse, Responder,
+};
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+
+use crate::{
+    configuration::FeatureFlags,
+    email_client::EmailClient,
+    issue_delivery_worker::{queue_depth, WorkerHeartbeat},
+    startup::StartedAt,
+    telemetry::Counters,
+};
+
+/// Liveness probe. Probes come often, so they are only logged when
+/// `features.log_health_checks` is on.
+#[tracing::instrument(name = "health check", level = "trace", skip(features))]
+pub async fn health_check(features: web::Data<FeatureFlags>) -> impl Responder {
+    if features.log_health_checks {
+        tracing::info!("Answered a health check probe");
+    }
+    HttpResponse::Ok().finish()
+}
+
+#[derive(serde::Serialize)]
+struct EmailHealth {
+    status: &'static str,
+    latency_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Check that the email provider is reachable and accepts our credentials,
+/// without sending any email.
+#[tracing::instrument(name = "Check email provider health", skip(email_client))]
+pub async fn email_health_check(email_client: web::Data<EmailClient>) -> HttpResponse {
+    let start = std::time::Instant::now();
+    let outcome = email_client.check_reachability().await;
+    let latency_ms = start.elapsed().as_millis();
+    match outcome {
+        Ok(()) => HttpResponse::Ok().json(EmailHealth {
+            status: "ok",
+            latency_ms,
+            error: None,
+        }),
+        Err(e) => {
+            tracing::warn!(error.message = %e, "The email provider is unreachable");
+            HttpResponse::ServiceUnavailable().json(EmailHealth {
+                status: "unreachable",
+                latency_ms,
+                error: Some(e.to_string()),
+            })
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct Status {
+    version: &'static str,
+    /// Set through `GIT_COMMIT_HASH` at build time.
+    commit: &'static str,
+    uptime_seconds: u64,
+    db: &'static str,
+    /// Pending delivery tasks, unknown when the database is down.
+    queue_depth: Option<i64>,
+    /// As last reported by the delivery worker, see `HeartbeatState`.
+    worker: &'static str,
+    /// Totals since startup, see `Counters`.
+    counters: BTreeMap<String, u64>,
+}
+
+/// Version, uptime and queue depth in one JSON document, for orchestrators
+/// and dashboards. A single cheap query is made; answers may be cached for
+/// a few seconds.
+#[tracing::instrument(
+    name = "Report application status",
+    skip(pool, started_at, worker_heartbeat)
+)]
+pub async fn application_status(
+    pool: web::Data<PgPool>,
+    started_at: web::Data<StartedAt>,
+    worker_heartbeat: web::Data<WorkerHeartbeat>,
+) -> HttpResponse {
+    let queue_depth = match queue_depth(&pool).await {
+        Ok(depth) => Some(depth),
+        Err(e) => {
+            tracing::warn!(error.message = %e, "The database is unreachable");
+            None
+        }
+    };
+    let mut response = if queue_depth.is_some() {
+        HttpResponse::Ok()
+    } else {
+        HttpResponse::ServiceUnavailable()
+    };
+    response
+        .insert_header(CacheControl(vec![CacheDirective::MaxAge(5)]))
+        .json(Status {
+            version: env!("CARGO_PKG_VERSION"),
+            commit: option_env!("GIT_COMMIT_HASH").unwrap_or("unknown"),
+            uptime_seconds: started_at.0.elapsed().as_secs(),
+            db: if queue_depth.is_some() { "ok" } else { "down" },
+            queue_depth,
+            worker: worker_heartbeat.get().as_str(),
+            counters: Counters::global().snapshot(),
+        })
 }
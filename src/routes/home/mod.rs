@@ -1,7 +1,8 @@
-use actix_web::{http::header::ContentType, HttpResponse};
+use actix_web::HttpResponse;
 
+use crate::utils::html_response;
+
+#[tracing::instrument(name = "home page")]
 pub async fn home() -> HttpResponse {
-    HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(include_str!("home.html"))
+    html_response(include_str!("home.html"))
 }
@@ -0,0 +1,172 @@
+use std::time::SystemTime;
+
+use actix_web::{
+    error::ErrorNotFound,
+    http::header::{
+        ContentType, ETag, EntityTag, HttpDate, IfModifiedSince, IfNoneMatch, LastModified,
+    },
+    web, HttpMessage, HttpRequest, HttpResponse,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use htmlescape::encode_minimal;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    routes::store_engagement_event,
+    signing::SignedLinks,
+    startup::TextOpenTracking,
+    utils::{e500, see_other},
+};
+
+struct ArchivedIssue {
+    title: String,
+    text_content: String,
+    html_content: String,
+    published_at: DateTime<Utc>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+impl ArchivedIssue {
+    /// Changes whenever the content of the issue does.
+    fn etag(&self) -> EntityTag {
+        let mut hasher = Sha256::new();
+        for part in [&self.title, &self.text_content, &self.html_content] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        hasher.update(self.published_at.to_rfc3339().as_bytes());
+        EntityTag::new_strong(URL_SAFE_NO_PAD.encode(hasher.finalize()))
+    }
+
+    /// Rounded down to the second, the precision of HTTP dates.
+    fn last_modified(&self) -> HttpDate {
+        let modified_at = self.updated_at.unwrap_or(self.published_at);
+        let modified_at =
+            DateTime::from_timestamp(modified_at.timestamp(), 0).unwrap_or(modified_at);
+        SystemTime::from(modified_at).into()
+    }
+}
+
+/// Public page of a published issue, for sharing.
+///
+/// Responses carry an `ETag` and a `Last-Modified` date, so that browsers
+/// and caches can revalidate with a conditional request and get a `304` as
+/// long as the issue is unchanged. Personalized issues are not archived,
+/// since their content differs for every subscriber.
+#[tracing::instrument(name = "archived issue", skip(pool, req))]
+pub async fn archived_issue(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue = sqlx::query_as!(
+        ArchivedIssue,
+        r#"
+        SELECT
+            title,
+            text_content,
+            html_content,
+            published_at::timestamptz as "published_at!",
+            updated_at
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1 AND NOT personalized
+        "#,
+        issue_id.into_inner()
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .map_err(e500)?
+    .ok_or_else(|| ErrorNotFound("No such newsletter issue"))?;
+
+    let etag = issue.etag();
+    let last_modified = issue.last_modified();
+    if is_fresh(&req, &etag, last_modified) {
+        return Ok(HttpResponse::NotModified()
+            .insert_header(ETag(etag))
+            .insert_header(LastModified(last_modified))
+            .finish());
+    }
+    Ok(HttpResponse::Ok()
+        .insert_header(ContentType::html())
+        .insert_header(ETag(etag))
+        .insert_header(LastModified(last_modified))
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>{title}</title>
+</head>
+<body>
+    <h1>{title}</h1>
+    {content}
+</body>
+</html>"#,
+            title = encode_minimal(&issue.title),
+            content = issue.html_content,
+        )))
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct IssueReadParams {
+    subscriber_id: Uuid,
+    signature: String,
+}
+
+/// Target of the link appended to the plain text body of issues that track
+/// text opens (see `WorkerSettings::text_open_tracking`): records an open
+/// by the subscriber, then sends them to the archived issue. Nothing is
+/// recorded once text open tracking is turned off, even from links sent
+/// before.
+#[tracing::instrument(name = "record a text open", skip(pool, links, tracking))]
+pub async fn issue_read(
+    issue_id: web::Path<Uuid>,
+    p: web::Query<IssueReadParams>,
+    pool: web::Data<PgPool>,
+    links: web::Data<SignedLinks>,
+    tracking: web::Data<TextOpenTracking>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    if !links.verify_issue_read(issue_id, p.subscriber_id, &p.signature) {
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+    if !tracking.0 {
+        return Ok(see_other(&format!("/issues/{issue_id}")));
+    }
+    let email = sqlx::query_scalar!(
+        "SELECT email FROM subscriptions WHERE id = $1",
+        p.subscriber_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .map_err(e500)?;
+    // Subscribers who have left since are still shown the issue.
+    if let Some(email) = email {
+        store_engagement_event(&pool, issue_id, &email, "open", None)
+            .await
+            .map_err(e500)?;
+    }
+    Ok(see_other(&format!("/issues/{issue_id}")))
+}
+
+/// Whether the client's copy is still current (RFC 9110, section 13.2.2):
+/// `If-None-Match` is checked when present, `If-Modified-Since` otherwise.
+fn is_fresh(req: &HttpRequest, etag: &EntityTag, last_modified: HttpDate) -> bool {
+    if let Some(if_none_match) = req.get_header::<IfNoneMatch>() {
+        return match if_none_match {
+            IfNoneMatch::Any => true,
+            IfNoneMatch::Items(tags) => tags.iter().any(|tag| tag.weak_eq(etag)),
+        };
+    }
+    match req.get_header::<IfModifiedSince>() {
+        Some(IfModifiedSince(since)) => {
+            let last_modified: SystemTime = last_modified.into();
+            let since: SystemTime = since.into();
+            last_modified <= since
+        }
+        None => false,
+    }
+}
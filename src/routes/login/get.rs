@@ -1,19 +1,69 @@
 use actix_web::cookie::Cookie;
-use actix_web::http::header::ContentType;
-use actix_web::HttpResponse;
+use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
 use std::fmt::Write;
 
-pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+use super::post::redirect_after_login;
+use crate::{
+    authentication::{is_active_session, SessionMaxAge},
+    clock::Clock,
+    configuration::{ExpirySettings, FeatureFlags},
+    session_state::TypedSession,
+    utils::{e500, html_response, RedirectAllowlist},
+};
+
+#[derive(serde::Deserialize)]
+pub struct LoginFormQuery {
+    /// Where to send the user once logged in, see `login`.
+    next: Option<String>,
+}
+
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(
+    name = "login form",
+    skip(
+        query,
+        flash_messages,
+        session,
+        pool,
+        features,
+        allowlist,
+        max_age,
+        clock,
+        expiry
+    )
+)]
+pub async fn login_form(
+    query: web::Query<LoginFormQuery>,
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    features: web::Data<FeatureFlags>,
+    allowlist: web::Data<RedirectAllowlist>,
+    max_age: web::Data<SessionMaxAge>,
+    clock: web::Data<dyn Clock>,
+    expiry: web::Data<ExpirySettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if features.skip_login_form_when_logged_in
+        && is_logged_in(&session, &pool, **max_age, clock.get_ref(), &expiry).await?
+    {
+        return Ok(redirect_after_login(query.next.as_deref(), &allowlist));
+    }
     let mut error_html = String::new();
     for m in flash_messages.iter() {
         writeln!(error_html, "<p><i>{}</i></p>", m.content()).unwrap();
     }
+    let next_html = match &query.next {
+        Some(next) => format!(
+            r#"<input hidden type="text" name="next" value="{}">"#,
+            htmlescape::encode_attribute(next)
+        ),
+        None => String::new(),
+    };
 
-    let mut resp = HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(format!(
-            r#"\
+    let mut resp = html_response(format!(
+        r#"\
 <!DOCTYPE html>
 <html lang="en">
 
@@ -31,12 +81,40 @@ pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
         <label>Password
             <input type="password" placeholder="Enter Password" name="password">
         </label>
+        {next_html}
         <button type="submit">Login</button>
     </form>
 </body>
 
 </html>"#,
-        ));
+    ));
     resp.add_removal_cookie(&Cookie::new("_flash", "")).unwrap();
-    resp
+    Ok(resp)
+}
+
+/// Whether the session belongs to a user and hasn't ended, as checked by
+/// `reject_anonymous_users`.
+async fn is_logged_in(
+    session: &TypedSession,
+    pool: &PgPool,
+    max_age: SessionMaxAge,
+    clock: &dyn Clock,
+    expiry: &ExpirySettings,
+) -> Result<bool, actix_web::Error> {
+    let (Some(user_id), Some(session_id)) = (
+        session.get_user_id().map_err(e500)?,
+        session.get_session_id().map_err(e500)?,
+    ) else {
+        return Ok(false);
+    };
+    is_active_session(
+        pool,
+        user_id,
+        session_id,
+        max_age,
+        clock,
+        expiry.clock_skew_tolerance(),
+    )
+    .await
+    .map_err(e500)
 }
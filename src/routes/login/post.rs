@@ -1,16 +1,21 @@
 use actix_web::{
-    error::InternalError,
-    http::{header::LOCATION, StatusCode},
-    web, HttpResponse, ResponseError,
+    error::InternalError, http::header::LOCATION, http::StatusCode, web, HttpResponse,
+    ResponseError,
 };
 use actix_web_flash_messages::FlashMessage;
 use secrecy::Secret;
 use sqlx::PgPool;
 
 use crate::{
-    authentication::{validate_credentials, AuthError, Credentials},
+    authentication::{
+        start_session, validate_credentials, AuthError, Credentials, MaxSessionsPerUser,
+        SessionMaxAge,
+    },
+    clock::Clock,
+    configuration::ExpirySettings,
     routes::error_chain_fmt,
     session_state::TypedSession,
+    utils::{is_local_path, see_other, see_other_with_flash, RedirectAllowlist},
 };
 
 #[derive(thiserror::Error)]
@@ -36,44 +41,84 @@ impl ResponseError for LoginError {
 pub struct LoginParams {
     username: String,
     password: Secret<String>,
+    #[serde(default)]
+    next: Option<String>,
 }
 
-#[tracing::instrument("Login", skip(form, pool, session))]
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(
+    "Login",
+    skip(form, pool, session, allowlist, max_sessions, max_age, clock, expiry)
+)]
 pub async fn login(
     form: web::Form<LoginParams>,
     pool: web::Data<PgPool>,
     session: TypedSession,
+    allowlist: web::Data<RedirectAllowlist>,
+    max_sessions: web::Data<MaxSessionsPerUser>,
+    max_age: web::Data<SessionMaxAge>,
+    clock: web::Data<dyn Clock>,
+    expiry: web::Data<ExpirySettings>,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
+    let next = form.0.next;
     let cred = Credentials {
         username: form.0.username,
         password: form.0.password,
     };
-    tracing::Span::current().record("username", &tracing::field::display(&cred.username));
+    tracing::Span::current().record("username", tracing::field::display(&cred.username));
     match validate_credentials(cred, &pool).await {
         Ok(user_id) => {
-            tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+            tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+            let session_id = start_session(
+                &pool,
+                user_id,
+                **max_sessions,
+                **max_age,
+                clock.get_ref(),
+                expiry.clock_skew_tolerance(),
+            )
+            .await
+            .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into()), &next))?;
+            // A new session key, so that one planted before login (session
+            // fixation) isn't the one that gets authenticated.
             session.renew();
             session
                 .insert_user_id(user_id)
-                .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
-            Ok(HttpResponse::SeeOther()
-                .insert_header((LOCATION, "/admin/dashboard"))
-                .finish())
+                .and_then(|_| session.insert_session_id(session_id))
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into()), &next))?;
+            Ok(redirect_after_login(next.as_deref(), &allowlist))
         }
         Err(e) => {
             let e = match e {
                 AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
                 AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
             };
-            Err(login_redirect(e))
+            Err(login_redirect(e, &next))
         }
     }
 }
 
-fn login_redirect(e: LoginError) -> InternalError<LoginError> {
-    FlashMessage::error(e.to_string()).send();
-    let response = HttpResponse::SeeOther()
-        .insert_header((LOCATION, "/login"))
-        .finish();
+/// Honour `next` if it is a local path or an allowlisted URL, so that it
+/// can't be used as an open redirect. Otherwise go to the dashboard.
+pub(super) fn redirect_after_login(
+    next: Option<&str>,
+    allowlist: &RedirectAllowlist,
+) -> HttpResponse {
+    match next {
+        Some(next) if is_local_path(next) => see_other(next),
+        Some(next) if allowlist.allows(next) => HttpResponse::SeeOther()
+            .insert_header((LOCATION, next))
+            .finish(),
+        _ => see_other("/admin/dashboard"),
+    }
+}
+
+fn login_redirect(e: LoginError, next: &Option<String>) -> InternalError<LoginError> {
+    // Keep `next` around for the next attempt.
+    let location = match next {
+        Some(next) => format!("/login?next={}", urlencoding::encode(next)),
+        None => "/login".to_string(),
+    };
+    let response = see_other_with_flash(&location, FlashMessage::error(e.to_string()));
     InternalError::from_response(e, response)
 }
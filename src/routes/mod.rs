@@ -1,13 +1,23 @@
 mod admin;
 mod health_check;
 mod home;
+mod issue_archive;
 mod login;
+mod subscribe_form;
 mod subscription;
 mod subscription_confirm;
+mod subscription_resend;
+mod unsubscribe;
+mod webhooks;
 
 pub use admin::*;
 pub use health_check::*;
 pub use home::*;
+pub use issue_archive::*;
 pub use login::*;
+pub use subscribe_form::*;
 pub use subscription::*;
 pub use subscription_confirm::*;
+pub use subscription_resend::*;
+pub use unsubscribe::*;
+pub use webhooks::*;
@@ -0,0 +1,66 @@
+use actix_web::{
+    http::header::{ACCEPT_LANGUAGE, VARY},
+    web, HttpRequest, HttpResponse,
+};
+
+use crate::{locale::Locale, startup::DefaultLocale, utils::html_response};
+
+/// Copy of the form, in one language.
+struct FormText {
+    title: &'static str,
+    heading: &'static str,
+    name_label: &'static str,
+    name_placeholder: &'static str,
+    email_label: &'static str,
+    email_placeholder: &'static str,
+    submit: &'static str,
+}
+
+fn form_text(locale: Locale) -> FormText {
+    match locale {
+        Locale::En => FormText {
+            title: "Subscribe",
+            heading: "Subscribe to our newsletter",
+            name_label: "Name",
+            name_placeholder: "Enter your name",
+            email_label: "Email",
+            email_placeholder: "Enter your email",
+            submit: "Subscribe",
+        },
+        Locale::Fr => FormText {
+            title: "Abonnement",
+            heading: "Abonnez-vous à notre newsletter",
+            name_label: "Nom",
+            name_placeholder: "Saisissez votre nom",
+            email_label: "E-mail",
+            email_placeholder: "Saisissez votre adresse e-mail",
+            submit: "S'abonner",
+        },
+    }
+}
+
+/// The subscription form, in the language the client prefers, see
+/// `Locale::negotiate`.
+#[tracing::instrument(name = "subscribe form", skip_all)]
+pub async fn subscribe_form(req: HttpRequest, default: web::Data<DefaultLocale>) -> HttpResponse {
+    let accept_language = req
+        .headers()
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok());
+    let locale = Locale::negotiate(accept_language, default.0);
+    let text = form_text(locale);
+    let page = include_str!("subscribe.html")
+        .replace("{{lang}}", locale.as_str())
+        .replace("{{title}}", text.title)
+        .replace("{{heading}}", text.heading)
+        .replace("{{name_label}}", text.name_label)
+        .replace("{{name_placeholder}}", text.name_placeholder)
+        .replace("{{email_label}}", text.email_label)
+        .replace("{{email_placeholder}}", text.email_placeholder)
+        .replace("{{submit}}", text.submit);
+    let mut response = html_response(page);
+    response
+        .headers_mut()
+        .insert(VARY, ACCEPT_LANGUAGE.as_str().try_into().unwrap());
+    response
+}
@@ -1,32 +1,82 @@
-use std::str::FromStr;
+use std::sync::Arc;
 
-use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse, ResponseError};
 use anyhow::Context;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
-use sqlx::{PgPool, Postgres, Transaction};
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use tokio::sync::Semaphore;
+use tracing::{field::display, Instrument, Span};
 use uuid::Uuid;
 
 use crate::{
-    domain::{NewSubscriber, SubscriberEmail, SubscriberName},
-    email_client::EmailClient,
-    startup::ApplicationBaseUrl,
+    clock::Clock,
+    configuration::{FeatureFlags, SubscriptionSettings, WebhookSettings},
+    db_error::{db_error_kind, retry_on_retriable, DbErrorKind},
+    domain::{NewSubscriber, SubscriberEmail, SubscriberName, SubscriptionStatus},
+    email_client::{EmailClient, SendEmailError},
+    personalization::SUBSCRIBER_REF_PARAM,
+    quiet_hours::parse_utc_offset,
+    routes::count_confirmed_subscribers,
+    signing::{sign_confirmation_token, SignedLinks},
+    webhook_delivery::{enqueue_subscription_event, SubscriptionEvent},
 };
 
 #[derive(serde::Deserialize)]
 pub struct FormSubscribe {
     name: String,
     email: String,
+    /// e.g. `+02:00`, left out when unknown.
+    #[serde(default)]
+    utc_offset: String,
 }
 
-impl TryFrom<FormSubscribe> for NewSubscriber {
-    type Error = String;
+impl FormSubscribe {
+    fn try_into_new_subscriber(
+        self,
+        settings: &SubscriptionSettings,
+    ) -> Result<NewSubscriber, String> {
+        let name = SubscriberName::validate_with(
+            &self.name,
+            settings.max_name_length,
+            settings.name_forbidden_characters(),
+        )?;
+        let email = SubscriberEmail::validate_with(&self.email, settings.strict_email_validation)?;
+        let utc_offset = match self.utc_offset.trim() {
+            "" => None,
+            utc_offset => Some(parse_utc_offset(utc_offset)?),
+        };
+        Ok(NewSubscriber {
+            email,
+            name,
+            utc_offset,
+        })
+    }
+}
 
-    fn try_from(value: FormSubscribe) -> Result<Self, Self::Error> {
-        let name = SubscriberName::from_str(&value.name)?;
-        let email = SubscriberEmail::from_str(&value.email)?;
-        Ok(Self { email, name })
+/// Fields whose names may be logged by `redacted_form_body`.
+const LOGGABLE_FIELDS: [&str; 3] = ["name", "email", "utc_offset"];
+
+/// Describe a urlencoded body without any of its values, e.g.
+/// `name=<redacted, 5 chars>&email=<redacted, 0 chars>`.
+///
+/// Returns `None` when the body can't be parsed, since we can't tell what
+/// it would leak.
+fn redacted_form_body(body: &[u8]) -> Option<String> {
+    let pairs: Vec<(String, String)> = serde_urlencoded::from_bytes(body).ok()?;
+    let mut fields = Vec::new();
+    let mut n_unknown_fields = 0;
+    for (key, value) in pairs {
+        if LOGGABLE_FIELDS.contains(&key.as_str()) {
+            fields.push(format!("{key}=<redacted, {} chars>", value.chars().count()));
+        } else {
+            n_unknown_fields += 1;
+        }
     }
+    if n_unknown_fields > 0 {
+        fields.push(format!("<{n_unknown_fields} unknown fields>"));
+    }
+    Some(fields.join("&"))
 }
 
 /// Generate a random 25-characters-long case-sensitive subscription token.
@@ -38,6 +88,15 @@ fn generate_subscription_token() -> String {
         .collect()
 }
 
+/// A fresh confirmation token, signed when
+/// `subscriptions.confirmation_token_signing_key` is set.
+pub(crate) fn new_confirmation_token(settings: &SubscriptionSettings) -> String {
+    match &settings.confirmation_token_signing_key {
+        Some(key) => sign_confirmation_token(key, &generate_subscription_token()),
+        None => generate_subscription_token(),
+    }
+}
+
 #[derive(thiserror::Error)]
 pub enum SubscribeError {
     #[error("{0}")]
@@ -56,64 +115,233 @@ impl ResponseError for SubscribeError {
     fn status_code(&self) -> actix_web::http::StatusCode {
         match self {
             SubscribeError::ValidationError(_) => StatusCode::BAD_REQUEST,
-            SubscribeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            // e.g. `409 Conflict` for an email address that is already
+            // subscribed.
+            SubscribeError::UnexpectedError(e) => {
+                db_error_kind(e).map_or(StatusCode::INTERNAL_SERVER_ERROR, DbErrorKind::status_code)
+            }
         }
     }
 }
 
+// Extractors, as actix-web hands them out.
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument(
     name = "add a new subscriber",
-    skip(form, pool, email_client, base_url),
+    skip(body, pool, email_client, links, settings, permits, features, clock, webhooks),
     fields(
-        subscriber_email = %form.email,
-        subscriber_name = %form.name,
+        subscriber_email = tracing::field::Empty,
+        subscriber_name = tracing::field::Empty,
     )
 )]
 pub async fn subscribe(
-    form: web::Form<FormSubscribe>,
+    body: web::Bytes,
     email_client: web::Data<EmailClient>,
     pool: web::Data<PgPool>,
-    base_url: web::Data<ApplicationBaseUrl>,
+    links: web::Data<SignedLinks>,
+    settings: web::Data<SubscriptionSettings>,
+    permits: web::Data<ConfirmationEmailPermits>,
+    features: web::Data<FeatureFlags>,
+    clock: web::Data<dyn Clock>,
+    webhooks: web::Data<WebhookSettings>,
 ) -> Result<HttpResponse, SubscribeError> {
+    // The body is deserialized by hand, rather than with `web::Form`, so that
+    // it is still around to be logged if it turns out to be invalid.
+    let new_subscriber = serde_urlencoded::from_bytes::<FormSubscribe>(&body)
+        .map_err(|e| e.to_string())
+        .and_then(|form| {
+            Span::current()
+                .record("subscriber_email", display(&form.email))
+                .record("subscriber_name", display(&form.name));
+            form.try_into_new_subscriber(&settings)
+        });
+    let new_subscriber = match new_subscriber {
+        Ok(new_subscriber) => new_subscriber,
+        Err(e) => {
+            if features.log_request_bodies {
+                if let Some(request_body) = redacted_form_body(&body) {
+                    tracing::debug!(request_body, "Rejected a subscription request");
+                }
+            }
+            return Err(SubscribeError::ValidationError(e));
+        }
+    };
+    // Only the database work is retried: the email goes out once the
+    // subscriber is committed, so that a retry can't make a sent link dead.
+    let (subscriber_id, sub_token) = retry_on_retriable(
+        settings.max_transaction_retries,
+        settings.transaction_retry_delay(),
+        || store_new_subscriber(&pool, &new_subscriber, &settings, &webhooks, clock.now()),
+    )
+    .await?;
+    if !features.background_confirmation_emails {
+        // The subscriber is left pending if it can't be sent: they can ask
+        // for it again.
+        send_confirmation_email(
+            &email_client,
+            &new_subscriber.email,
+            links.base_url(),
+            &sub_token,
+            &links.subscriber_reference(subscriber_id),
+            settings.confirmation_token_ttl_hours,
+        )
+        .await
+        .context("Failed to send a confirmation email.")?;
+        record_confirmation_email_sent(pool.get_ref(), subscriber_id, clock.now())
+            .await
+            .context("Failed to record the confirmation email.")?;
+    }
+    if let Some(cap) = settings.confirmed_subscriber_cap() {
+        // They are still subscribed, but won't be confirmed until there is room.
+        let n_confirmed = count_confirmed_subscribers(pool.get_ref())
+            .await
+            .context("Failed to count the confirmed subscribers.")?;
+        if n_confirmed >= cap {
+            tracing::warn!(
+                max_confirmed_subscribers = cap,
+                "The confirmed subscriber cap is reached: new subscribers can't be confirmed",
+            );
+        }
+    }
+    if features.background_confirmation_emails {
+        let subscriber_ref = links.subscriber_reference(subscriber_id);
+        let permits = permits.0.clone();
+        let base_url = links.base_url().to_string();
+        let ttl_hours = settings.confirmation_token_ttl_hours;
+        tokio::spawn(
+            async move {
+                let _permit = permits.acquire_owned().await;
+                if let Err(e) = send_confirmation_email(
+                    &email_client,
+                    &new_subscriber.email,
+                    &base_url,
+                    &sub_token,
+                    &subscriber_ref,
+                    ttl_hours,
+                )
+                .await
+                {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to send a confirmation email. The subscriber is left pending.",
+                    );
+                    return;
+                }
+                if let Err(e) =
+                    record_confirmation_email_sent(pool.get_ref(), subscriber_id, clock.now()).await
+                {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to record a sent confirmation email",
+                    );
+                }
+            }
+            .instrument(Span::current()),
+        );
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// `GET /subscriptions/new`, the same as `subscribe` with the form in the
+/// query string. Only routed with `features.subscribe_via_get`: a link
+/// being followed, e.g. by a prefetcher, doesn't subscribe anyone, since
+/// the subscription still has to be confirmed from the email it sends.
+#[allow(clippy::too_many_arguments)]
+pub async fn subscribe_via_get(
+    req: HttpRequest,
+    email_client: web::Data<EmailClient>,
+    pool: web::Data<PgPool>,
+    links: web::Data<SignedLinks>,
+    settings: web::Data<SubscriptionSettings>,
+    permits: web::Data<ConfirmationEmailPermits>,
+    features: web::Data<FeatureFlags>,
+    clock: web::Data<dyn Clock>,
+    webhooks: web::Data<WebhookSettings>,
+) -> Result<HttpResponse, SubscribeError> {
+    let form = web::Bytes::copy_from_slice(req.query_string().as_bytes());
+    subscribe(
+        form,
+        email_client,
+        pool,
+        links,
+        settings,
+        permits,
+        features,
+        clock,
+        webhooks,
+    )
+    .await
+}
+
+/// One attempt at inserting a pending subscriber along with its
+/// confirmation token, committed. Returns the id and the token.
+async fn store_new_subscriber(
+    pool: &PgPool,
+    new_subscriber: &NewSubscriber,
+    settings: &SubscriptionSettings,
+    webhooks: &WebhookSettings,
+    now: DateTime<Utc>,
+) -> Result<(Uuid, String), anyhow::Error> {
     let mut transaction = pool
         .begin()
         .await
         .context("Failed to acquire a Postgres connection from the pool")?;
-    let new_subscriber = form.0.try_into().map_err(SubscribeError::ValidationError)?;
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
+    let subscriber_id = insert_subscriber(&mut transaction, new_subscriber, now)
         .await
         .context("Failed to insert new subscriber in the database.")?;
-    let sub_token = generate_subscription_token();
-    store_token(&mut transaction, subscriber_id, &sub_token)
+    let sub_token = new_confirmation_token(settings);
+    store_token(&mut transaction, subscriber_id, &sub_token, now)
         .await
         .context("Failed to store the confirmation token for a new subscriber.")?;
-    send_confirmation_email(&email_client, &new_subscriber, &base_url.0, &sub_token)
-        .await
-        .context("Failed to send a confirmation email.")?;
-
+    enqueue_subscription_event(
+        &mut *transaction,
+        webhooks,
+        SubscriptionEvent::Created,
+        subscriber_id,
+        new_subscriber.email.as_ref(),
+        now,
+    )
+    .await
+    .context("Failed to queue the event about a new subscriber.")?;
     transaction
         .commit()
         .await
         .context("Failed to commit SQL transaction to store a new subscriber.")?;
+    Ok((subscriber_id, sub_token))
+}
 
-    Ok(HttpResponse::Ok().finish())
+/// Bounds how many confirmation emails are sent in the background at once,
+/// see `FeatureFlags::background_confirmation_emails`.
+pub struct ConfirmationEmailPermits(Arc<Semaphore>);
+
+impl ConfirmationEmailPermits {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self(Arc::new(Semaphore::new(max_concurrent)))
+    }
 }
 
 #[tracing::instrument(name = "Save new subscriber to db", skip(new_subscriber, transaction))]
 pub async fn insert_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
     new_subscriber: &NewSubscriber,
+    subscribed_at: DateTime<Utc>,
 ) -> Result<Uuid, sqlx::Error> {
     let subscriber_id = Uuid::new_v4();
     sqlx::query!(
         r#"
-        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, 'pending')
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, utc_offset_minutes)
+        VALUES ($1, $2, $3, $4, $5, $6)
         "#,
         subscriber_id,
         new_subscriber.email.as_ref(),
         new_subscriber.name.as_ref(),
-        Utc::now()
+        subscribed_at,
+        SubscriptionStatus::Pending.as_str(),
+        new_subscriber
+            .utc_offset
+            .map(|offset| (offset.local_minus_utc() / 60) as i16)
     )
     .execute(&mut **transaction)
     .await?;
@@ -122,35 +350,67 @@ pub async fn insert_subscriber(
 
 #[tracing::instrument(
     name = "send a confirmation email to a new subscriber",
-    skip(email_client, new_subscriber, base_url)
+    skip(email_client, recipient, base_url)
 )]
 pub async fn send_confirmation_email(
     email_client: &EmailClient,
-    new_subscriber: &NewSubscriber,
+    recipient: &SubscriberEmail,
     base_url: &str,
     token: &str,
-) -> Result<(), reqwest::Error> {
+    subscriber_ref: &str,
+    ttl_hours: u64,
+) -> Result<(), SendEmailError> {
     let confirmation_link = format!(
-        "{}/subscriptions/confirm?subscription_token={}",
-        base_url, token
+        "{}/subscriptions/confirm?subscription_token={}&{}={}",
+        base_url, token, SUBSCRIBER_REF_PARAM, subscriber_ref
+    );
+    let expiry = format!(
+        "This link expires in {} {}.",
+        ttl_hours,
+        if ttl_hours == 1 { "hour" } else { "hours" }
     );
     email_client
         .send_email(
-            &new_subscriber.email,
+            recipient,
             "Welcome!",
             &format!(
                 "Welcome to our newsletter!<br />\
-                Click <a href=\"{}\">here</a> to confirm your subscription.",
-                confirmation_link
+                Click <a href=\"{}\">here</a> to confirm your subscription.<br />\
+                {}",
+                confirmation_link, expiry
             ),
             &format!(
-                "Welcome to our newsletter!\nVisit {} to confirm your subscription.",
-                confirmation_link
+                "Welcome to our newsletter!\nVisit {} to confirm your subscription.\n{}",
+                confirmation_link, expiry
             ),
         )
         .await
 }
 
+/// Count a confirmation email that went out, for the admin subscriber page
+/// to tell "never sent" from "sent but not clicked".
+#[tracing::instrument(name = "Record a sent confirmation email", skip(executor))]
+pub async fn record_confirmation_email_sent(
+    executor: impl PgExecutor<'_>,
+    subscriber_id: Uuid,
+    sent_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET
+            confirmation_emails_sent = confirmation_emails_sent + 1,
+            last_confirmation_email_sent_at = $2
+        WHERE id = $1
+        "#,
+        subscriber_id,
+        sent_at
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
 #[tracing::instrument(
     name = "Store subscription token in the database",
     skip(subscription_token, transaction)
@@ -159,11 +419,16 @@ pub async fn store_token(
     transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid,
     subscription_token: &str,
+    created_at: DateTime<Utc>,
 ) -> Result<(), StoreTokenError> {
     sqlx::query!(
-        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id) VALUES ($1, $2)"#,
+        r#"
+        INSERT INTO subscription_tokens (subscription_token, subscriber_id, created_at)
+        VALUES ($1, $2, $3)
+        "#,
         subscription_token,
-        subscriber_id
+        subscriber_id,
+        created_at
     )
     .execute(&mut **transaction)
     .await
@@ -1,51 +1,351 @@
-use actix_web::{web, HttpResponse};
-use sqlx::PgPool;
+use actix_web::{
+    http::{
+        header::{Accept, Header},
+        StatusCode,
+    },
+    web, HttpRequest, HttpResponse,
+};
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
+use crate::{
+    clock::Clock,
+    configuration::{ExpirySettings, SubscriptionSettings, WebhookSettings},
+    db_error::{classify_db_error, retry_on_retriable},
+    domain::SubscriptionStatus,
+    expiry::is_expired,
+    signing::verify_confirmation_token,
+    utils::html_response,
+    webhook_delivery::{enqueue_subscription_event, SubscriptionEvent},
+};
+
 #[derive(serde::Deserialize, Debug)]
 pub struct ConfirmParams {
     subscription_token: String,
 }
 
-#[tracing::instrument("confirm a pending subscriber", skip(pool))]
-pub async fn confirm(pool: web::Data<PgPool>, p: web::Query<ConfirmParams>) -> HttpResponse {
-    let id = match get_subscriber_id_by_token(&pool, &p.subscription_token).await {
-        Ok(id) => id,
-        Err(_) => return HttpResponse::InternalServerError().finish(),
+/// What following a confirmation link came to.
+#[derive(Debug)]
+enum ConfirmOutcome {
+    Confirmed,
+    AlreadyConfirmed,
+    /// The subscriber opted out since the link was sent.
+    Unsubscribed,
+    InvalidToken,
+    ExpiredToken,
+    /// See `SubscriptionSettings::max_confirmed_subscribers`.
+    CapReached,
+    /// Transient database failures outlasted the retries.
+    Unavailable,
+    Failed,
+}
+
+impl ConfirmOutcome {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ConfirmOutcome::Confirmed | ConfirmOutcome::AlreadyConfirmed => StatusCode::OK,
+            ConfirmOutcome::InvalidToken | ConfirmOutcome::ExpiredToken => StatusCode::UNAUTHORIZED,
+            ConfirmOutcome::Unsubscribed => StatusCode::CONFLICT,
+            ConfirmOutcome::CapReached => StatusCode::FORBIDDEN,
+            ConfirmOutcome::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ConfirmOutcome::Failed => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Identifies the outcome in JSON responses.
+    fn code(&self) -> &'static str {
+        match self {
+            ConfirmOutcome::Confirmed => "confirmed",
+            ConfirmOutcome::AlreadyConfirmed => "already_confirmed",
+            ConfirmOutcome::Unsubscribed => "unsubscribed",
+            ConfirmOutcome::InvalidToken => "invalid_token",
+            ConfirmOutcome::ExpiredToken => "expired_token",
+            ConfirmOutcome::CapReached => "cap_reached",
+            ConfirmOutcome::Unavailable => "unavailable",
+            ConfirmOutcome::Failed => "failed",
+        }
+    }
+
+    fn message(&self) -> &'static str {
+        match self {
+            ConfirmOutcome::Confirmed => "Your subscription has been confirmed.",
+            ConfirmOutcome::AlreadyConfirmed => "Your subscription was already confirmed.",
+            ConfirmOutcome::Unsubscribed => {
+                "You have unsubscribed since this link was sent. \
+                Subscribe again to receive the newsletter."
+            }
+            ConfirmOutcome::InvalidToken => "This confirmation link is not valid.",
+            ConfirmOutcome::ExpiredToken => {
+                "This confirmation link has expired. You can ask for a new one."
+            }
+            ConfirmOutcome::CapReached => {
+                "We can't take any more subscribers at the moment, \
+                your subscription could not be confirmed."
+            }
+            ConfirmOutcome::Unavailable => {
+                "We couldn't confirm your subscription right now. \
+                Please try the link again in a few moments."
+            }
+            ConfirmOutcome::Failed => "Something went wrong while confirming your subscription.",
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct ConfirmResponse {
+    status: &'static str,
+    message: &'static str,
+}
+
+/// Confirm the subscriber the link was sent to. Browsers get a page, API
+/// clients asking for `application/json` get a `ConfirmResponse`, with the
+/// same status code either way.
+#[tracing::instrument(
+    "confirm a pending subscriber",
+    skip(req, pool, settings, expiry, clock, webhooks)
+)]
+pub async fn confirm(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    p: web::Query<ConfirmParams>,
+    settings: web::Data<SubscriptionSettings>,
+    expiry: web::Data<ExpirySettings>,
+    clock: web::Data<dyn Clock>,
+    webhooks: web::Data<WebhookSettings>,
+) -> HttpResponse {
+    let outcome = confirm_token(
+        &pool,
+        &p.subscription_token,
+        &settings,
+        &expiry,
+        clock.get_ref(),
+        &webhooks,
+    )
+    .await;
+    if prefers_json(&req) {
+        HttpResponse::build(outcome.status_code()).json(ConfirmResponse {
+            status: outcome.code(),
+            message: outcome.message(),
+        })
+    } else {
+        let mut response = confirmation_page(outcome.message());
+        *response.status_mut() = outcome.status_code();
+        response
+    }
+}
+
+/// Whether `application/json` ranks above `text/html` in the `Accept`
+/// header. Without either, the page is served.
+fn prefers_json(req: &HttpRequest) -> bool {
+    let Ok(accept) = Accept::parse(req) else {
+        return false;
+    };
+    accept
+        .ranked()
+        .into_iter()
+        .find(|mime| matches!(mime.essence_str(), "application/json" | "text/html"))
+        .is_some_and(|mime| mime.essence_str() == "application/json")
+}
+
+async fn confirm_token(
+    pool: &PgPool,
+    subscription_token: &str,
+    settings: &SubscriptionSettings,
+    expiry: &ExpirySettings,
+    clock: &dyn Clock,
+    webhooks: &WebhookSettings,
+) -> ConfirmOutcome {
+    if let Some(key) = &settings.confirmation_token_signing_key {
+        if !verify_confirmation_token(key, subscription_token) {
+            tracing::info!("The subscription token has an invalid signature");
+            return ConfirmOutcome::InvalidToken;
+        }
+    }
+    let token = match retry_on_retriable(
+        settings.confirmation_max_retries,
+        settings.confirmation_retry_delay(),
+        || get_token(pool, subscription_token),
+    )
+    .await
+    {
+        Ok(token) => token,
+        Err(e) => return db_error_outcome(&e),
     };
-    match id {
+    match token {
         // Non-existing token!
-        None => HttpResponse::Unauthorized().finish(),
-        Some(subscriber_id) => {
-            if confirm_subscriber(&pool, subscriber_id).await.is_err() {
-                return HttpResponse::InternalServerError().finish();
+        None => ConfirmOutcome::InvalidToken,
+        // Email prefetchers and double clicks follow the link more than once.
+        Some(token) if token.status == SubscriptionStatus::Confirmed => {
+            tracing::info!("The subscriber is already confirmed");
+            ConfirmOutcome::AlreadyConfirmed
+        }
+        // An old link must not undo an opt-out.
+        Some(token) if token.status == SubscriptionStatus::Unsubscribed => {
+            tracing::info!("The subscriber has unsubscribed");
+            ConfirmOutcome::Unsubscribed
+        }
+        Some(token)
+            if is_expired(
+                token.created_at + settings.confirmation_token_ttl(),
+                clock.now(),
+                expiry.clock_skew_tolerance(),
+            ) =>
+        {
+            tracing::info!("The subscription token has expired");
+            ConfirmOutcome::ExpiredToken
+        }
+        Some(SubscriptionToken { subscriber_id, .. }) => {
+            let confirmed = retry_on_retriable(
+                settings.confirmation_max_retries,
+                settings.confirmation_retry_delay(),
+                || confirm_and_notify(pool, subscriber_id, settings, webhooks, clock.now()),
+            )
+            .await;
+            match confirmed {
+                Ok(outcome) => outcome,
+                Err(e) => db_error_outcome(&e),
             }
-            HttpResponse::Ok().finish()
         }
     }
 }
 
-#[tracing::instrument("Get subscriber id by token", skip(pool, subscription_token))]
-async fn get_subscriber_id_by_token(
+/// A transient failure is worth following the link again for.
+fn db_error_outcome(e: &sqlx::Error) -> ConfirmOutcome {
+    if !classify_db_error(e).is_transient() {
+        tracing::error!(error.message = %e, "Failed to confirm a subscriber");
+        return ConfirmOutcome::Failed;
+    }
+    tracing::warn!(error.message = %e, "Gave up confirming a subscriber after transient errors");
+    ConfirmOutcome::Unavailable
+}
+
+fn confirmation_page(message: &str) -> HttpResponse {
+    html_response(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Subscription confirmation</title>
+</head>
+<body>
+    <p>{message}</p>
+</body>
+</html>"#,
+    ))
+}
+
+struct SubscriptionToken {
+    subscriber_id: Uuid,
+    created_at: DateTime<Utc>,
+    /// Status of the subscriber the token was issued to.
+    status: SubscriptionStatus,
+}
+
+#[tracing::instrument("Get subscription token", skip(pool, subscription_token))]
+async fn get_token(
     pool: &PgPool,
     subscription_token: &str,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    let result = sqlx::query!(
-        r#"SELECT subscriber_id from subscription_tokens where subscription_token = $1"#,
+) -> Result<Option<SubscriptionToken>, sqlx::Error> {
+    sqlx::query_as!(
+        SubscriptionToken,
+        r#"
+        SELECT t.subscriber_id, t.created_at, s.status as "status: SubscriptionStatus"
+        FROM subscription_tokens t
+        JOIN subscriptions s ON s.id = t.subscriber_id
+        WHERE t.subscription_token = $1
+        "#,
         subscription_token
     )
     .fetch_optional(pool)
+    .await
+}
+
+/// Mark the subscriber as confirmed, returning their email address. `None`
+/// when they aren't pending anymore, e.g. they unsubscribed meanwhile.
+#[tracing::instrument("Mark subscriber as confirmed", skip(executor, subscriber_id))]
+pub(crate) async fn confirm_subscriber(
+    executor: impl PgExecutor<'_>,
+    subscriber_id: Uuid,
+) -> Result<Option<String>, sqlx::Error> {
+    let r = sqlx::query!(
+        r#"UPDATE subscriptions SET status = $2 WHERE id = $1 AND status = $3 RETURNING email"#,
+        subscriber_id,
+        SubscriptionStatus::Confirmed.as_str(),
+        SubscriptionStatus::Pending.as_str()
+    )
+    .fetch_optional(executor)
     .await?;
-    Ok(result.map(|r| r.subscriber_id))
+    Ok(r.map(|r| r.email))
 }
 
-#[tracing::instrument("Mark subscriber as confirmed", skip(pool, subscriber_id))]
-async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"UPDATE subscriptions SET status='confirmed' WHERE id = $1"#,
-        subscriber_id
+/// Whether there is room for one more confirmed subscriber, see
+/// `SubscriptionSettings::max_confirmed_subscribers`.
+///
+/// Concurrent confirmations are serialized until `transaction` ends, so that
+/// they can't overshoot the cap together.
+pub(crate) async fn has_room_for_confirmation(
+    transaction: &mut Transaction<'_, Postgres>,
+    settings: &SubscriptionSettings,
+) -> Result<bool, sqlx::Error> {
+    let Some(cap) = settings.confirmed_subscriber_cap() else {
+        return Ok(true);
+    };
+    sqlx::query!("SELECT pg_advisory_xact_lock(hashtext('confirmed_subscriber_cap'))")
+        .execute(&mut **transaction)
+        .await?;
+    let n_confirmed = count_confirmed_subscribers(&mut **transaction).await?;
+    Ok(n_confirmed < cap)
+}
+
+pub(crate) async fn count_confirmed_subscribers(
+    executor: impl PgExecutor<'_>,
+) -> Result<u64, sqlx::Error> {
+    let n_confirmed = sqlx::query_scalar!(
+        r#"SELECT count(*) as "count!" FROM subscriptions WHERE status = $1"#,
+        SubscriptionStatus::Confirmed.as_str()
+    )
+    .fetch_one(executor)
+    .await?;
+    Ok(n_confirmed as u64)
+}
+
+/// Confirm the subscriber along with queueing the event about it. They are
+/// left pending when the confirmed subscriber cap is reached.
+async fn confirm_and_notify(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    settings: &SubscriptionSettings,
+    webhooks: &WebhookSettings,
+    now: DateTime<Utc>,
+) -> Result<ConfirmOutcome, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    if !has_room_for_confirmation(&mut transaction, settings).await? {
+        tracing::warn!("The confirmed subscriber cap is reached");
+        return Ok(ConfirmOutcome::CapReached);
+    }
+    let Some(email) = confirm_subscriber(&mut *transaction, subscriber_id).await? else {
+        // Their status changed since the token was looked up.
+        let status = sqlx::query_scalar!(
+            r#"SELECT status as "status: SubscriptionStatus" FROM subscriptions WHERE id = $1"#,
+            subscriber_id
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        return Ok(match status {
+            SubscriptionStatus::Confirmed => ConfirmOutcome::AlreadyConfirmed,
+            _ => ConfirmOutcome::Unsubscribed,
+        });
+    };
+    enqueue_subscription_event(
+        &mut *transaction,
+        webhooks,
+        SubscriptionEvent::Confirmed,
+        subscriber_id,
+        &email,
+        now,
     )
-    .execute(pool)
     .await?;
-    Ok(())
+    transaction.commit().await?;
+    Ok(ConfirmOutcome::Confirmed)
 }
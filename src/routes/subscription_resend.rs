@@ -0,0 +1,103 @@
+use std::str::FromStr;
+
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::{
+    clock::Clock,
+    configuration::SubscriptionSettings,
+    domain::{SubscriberEmail, SubscriptionStatus},
+    email_client::EmailClient,
+    routes::{
+        new_confirmation_token, record_confirmation_email_sent, send_confirmation_email,
+        store_token, SubscribeError,
+    },
+    signing::SignedLinks,
+};
+
+#[derive(serde::Deserialize)]
+pub struct ResendConfirmationForm {
+    email: String,
+}
+
+/// Send a new confirmation link to a pending subscriber who lost theirs.
+///
+/// Besides the rate limit, each subscriber can only be sent
+/// `subscriptions.max_confirmation_resends` of them, so that the endpoint
+/// can't be used to flood an inbox over time. Requests for addresses without
+/// a pending subscription succeed without sending anything.
+#[tracing::instrument(
+    name = "resend a confirmation email",
+    skip(form, pool, email_client, links, settings, clock),
+    fields(subscriber_email = %form.email)
+)]
+pub async fn resend_confirmation(
+    form: web::Form<ResendConfirmationForm>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    links: web::Data<SignedLinks>,
+    settings: web::Data<SubscriptionSettings>,
+    clock: web::Data<dyn Clock>,
+) -> Result<HttpResponse, SubscribeError> {
+    let email = SubscriberEmail::from_str(&form.email).map_err(SubscribeError::ValidationError)?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT id, confirmation_resends
+        FROM subscriptions
+        WHERE email = $1 AND status = $2
+        FOR UPDATE
+        "#,
+        email.as_ref(),
+        SubscriptionStatus::Pending.as_str()
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to look up the pending subscriber")?;
+    let Some(subscriber) = subscriber else {
+        return Ok(HttpResponse::Ok().finish());
+    };
+    if i64::from(subscriber.confirmation_resends) >= i64::from(settings.max_confirmation_resends) {
+        return Ok(HttpResponse::Forbidden().body(
+            "No more confirmation emails can be sent to this address. \
+            Please use one of the links you already received.",
+        ));
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions SET confirmation_resends = confirmation_resends + 1
+        WHERE id = $1
+        "#,
+        subscriber.id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to count the confirmation resend")?;
+    let token = new_confirmation_token(&settings);
+    store_token(&mut transaction, subscriber.id, &token, clock.now())
+        .await
+        .context("Failed to store the confirmation token for a resend.")?;
+    send_confirmation_email(
+        &email_client,
+        &email,
+        links.base_url(),
+        &token,
+        &links.subscriber_reference(subscriber.id),
+        settings.confirmation_token_ttl_hours,
+    )
+    .await
+    .context("Failed to resend a confirmation email.")?;
+    record_confirmation_email_sent(&mut *transaction, subscriber.id, clock.now())
+        .await
+        .context("Failed to record the resent confirmation email.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to resend a confirmation email.")?;
+    Ok(HttpResponse::Ok().finish())
+}
@@ -0,0 +1,156 @@
+use std::str::FromStr;
+
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    configuration::{FeatureFlags, SubscriptionSettings},
+    domain::{SubscriberEmail, SubscriptionStatus},
+    email_client::EmailClient,
+    signing::SignedLinks,
+    utils::{e500, html_response},
+};
+
+#[derive(serde::Deserialize, Debug)]
+pub struct UnsubscribeParams {
+    subscriber_id: Uuid,
+    signature: String,
+}
+
+/// Landing page for the link in the `List-Unsubscribe` header.
+/// Unsubscribing only happens on `POST`, so that link scanners following
+/// the URL do not unsubscribe anybody.
+#[tracing::instrument("Show unsubscribe page", skip(links))]
+pub async fn unsubscribe_form(
+    links: web::Data<SignedLinks>,
+    p: web::Query<UnsubscribeParams>,
+) -> HttpResponse {
+    if !links.verify_unsubscribe(p.subscriber_id, &p.signature) {
+        return HttpResponse::Unauthorized().finish();
+    }
+    let action = links.unsubscribe(p.subscriber_id);
+    html_response(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Unsubscribe</title>
+</head>
+<body>
+    <p>Do you want to stop receiving our newsletter?</p>
+    <form action="{action}" method="post">
+        <input hidden type="text" name="List-Unsubscribe" value="One-Click">
+        <button type="submit">Unsubscribe</button>
+    </form>
+</body>
+</html>"#,
+    ))
+}
+
+/// One-click unsubscribe endpoint (RFC 8058).
+#[tracing::instrument(
+    "Unsubscribe a subscriber",
+    skip(pool, links, email_client, settings, features)
+)]
+pub async fn unsubscribe(
+    pool: web::Data<PgPool>,
+    links: web::Data<SignedLinks>,
+    p: web::Query<UnsubscribeParams>,
+    email_client: web::Data<EmailClient>,
+    settings: web::Data<SubscriptionSettings>,
+    features: web::Data<FeatureFlags>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !links.verify_unsubscribe(p.subscriber_id, &p.signature) {
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+    let newly_unsubscribed = unsubscribe_subscriber(&pool, p.subscriber_id)
+        .await
+        .map_err(e500)?;
+    if let (Some(email), true) = (newly_unsubscribed, features.unsubscribe_confirmation_emails) {
+        let resubscribe_link = if features.serve_subscribe_form {
+            format!("{}/subscribe", links.base_url())
+        } else {
+            format!("{}/", links.base_url())
+        };
+        // They are unsubscribed either way: a failed send is only logged.
+        if let Err(e) =
+            send_unsubscribe_confirmation(&email_client, &email, &settings, &resubscribe_link).await
+        {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to send an unsubscribe confirmation email",
+            );
+        }
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Returns the email of the subscriber, unless they were already
+/// unsubscribed (or don't exist).
+#[tracing::instrument("Mark subscriber as unsubscribed", skip(pool))]
+async fn unsubscribe_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<String>, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let row = sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = $2
+        FROM (SELECT id, status FROM subscriptions WHERE id = $1 FOR UPDATE) previous
+        WHERE subscriptions.id = previous.id
+        RETURNING
+            subscriptions.email,
+            previous.status AS "previous_status: SubscriptionStatus"
+        "#,
+        subscriber_id,
+        SubscriptionStatus::Unsubscribed.as_str()
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    if let Some(row) = &row {
+        // Issues still waiting to be delivered should not reach them either.
+        sqlx::query!(
+            r#"DELETE FROM issue_delivery_queue WHERE subscriber_email = $1"#,
+            row.email
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+    transaction.commit().await?;
+    Ok(row
+        .filter(|row| row.previous_status != SubscriptionStatus::Unsubscribed)
+        .map(|row| row.email))
+}
+
+#[tracing::instrument(
+    "Send an unsubscribe confirmation email",
+    skip(email_client, email, settings)
+)]
+async fn send_unsubscribe_confirmation(
+    email_client: &EmailClient,
+    email: &str,
+    settings: &SubscriptionSettings,
+    resubscribe_link: &str,
+) -> Result<(), anyhow::Error> {
+    let recipient = SubscriberEmail::from_str(email).map_err(anyhow::Error::msg)?;
+    let message = &settings.unsubscribe_confirmation_message;
+    email_client
+        .send_email(
+            &recipient,
+            &settings.unsubscribe_confirmation_subject,
+            &format!(
+                "<p>{}</p>\
+                <p>Changed your mind? <a href=\"{}\">Subscribe again</a>.</p>",
+                htmlescape::encode_minimal(message),
+                htmlescape::encode_minimal(resubscribe_link)
+            ),
+            &format!(
+                "{}\nChanged your mind? Subscribe again at {}",
+                message, resubscribe_link
+            ),
+        )
+        .await?;
+    Ok(())
+}
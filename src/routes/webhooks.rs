@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    configuration::WebhookSettings, issue_delivery_worker::ISSUE_ID_METADATA_KEY, utils::e500,
+};
+
+/// Custom header configured on the Postmark webhook, carrying the shared secret.
+pub const WEBHOOK_SECRET_HEADER: &str = "X-Webhook-Secret";
+
+/// The fields we use out of a Postmark webhook event.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PostmarkEvent {
+    record_type: String,
+    recipient: Option<String>,
+    #[serde(default)]
+    metadata: HashMap<String, String>,
+    original_link: Option<String>,
+}
+
+/// Whether the request carries the shared secret. Digests are compared
+/// rather than the secrets themselves, so that timing leaks nothing useful.
+fn has_shared_secret(request: &HttpRequest, secret: &Secret<String>) -> bool {
+    match request.headers().get(WEBHOOK_SECRET_HEADER) {
+        Some(provided) => {
+            Sha256::digest(provided.as_bytes()) == Sha256::digest(secret.expose_secret())
+        }
+        None => false,
+    }
+}
+
+/// Store Open and Click events about newsletter deliveries.
+///
+/// Other event types, and events about emails that aren't newsletter
+/// deliveries, are acknowledged and ignored so that Postmark doesn't retry them.
+#[tracing::instrument(
+    name = "Ingest a Postmark engagement event",
+    skip_all,
+    fields(record_type = %event.record_type)
+)]
+pub async fn postmark_engagement(
+    request: HttpRequest,
+    event: web::Json<PostmarkEvent>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<WebhookSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !has_shared_secret(&request, &settings.postmark_secret) {
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+    let event_type = match event.record_type.as_str() {
+        "Open" => "open",
+        "Click" => "click",
+        _ => {
+            tracing::info!("Ignoring an unsupported webhook event");
+            return Ok(HttpResponse::Ok().finish());
+        }
+    };
+    let issue_id = event
+        .metadata
+        .get(ISSUE_ID_METADATA_KEY)
+        .and_then(|id| Uuid::parse_str(id).ok());
+    let (Some(issue_id), Some(recipient)) = (issue_id, &event.recipient) else {
+        tracing::info!("Ignoring a webhook event that is not about a newsletter delivery");
+        return Ok(HttpResponse::Ok().finish());
+    };
+    store_engagement_event(
+        &pool,
+        issue_id,
+        recipient,
+        event_type,
+        event.original_link.as_deref(),
+    )
+    .await
+    .map_err(e500)?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[tracing::instrument(skip(pool))]
+pub(crate) async fn store_engagement_event(
+    pool: &PgPool,
+    issue_id: Uuid,
+    subscriber_email: &str,
+    event_type: &str,
+    link: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    // Events about issues that no longer exist are dropped.
+    sqlx::query!(
+        r#"
+        INSERT INTO engagement_events (
+            newsletter_issue_id,
+            subscriber_email,
+            event_type,
+            link,
+            recorded_at
+        )
+        SELECT $1, $2, $3, $4, now()
+        WHERE EXISTS (
+            SELECT 1 FROM newsletter_issues WHERE newsletter_issue_id = $1
+        )
+        "#,
+        issue_id,
+        subscriber_email,
+        event_type,
+        link
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
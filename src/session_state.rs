@@ -2,12 +2,15 @@ use std::future::{ready, Ready};
 
 use actix_session::{Session, SessionExt, SessionGetError, SessionInsertError};
 use actix_web::{dev::Payload, FromRequest, HttpRequest};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use uuid::Uuid;
 
 pub struct TypedSession(Session);
 
 impl TypedSession {
     const USER_ID_KEY: &'static str = "user_id";
+    const CSRF_TOKEN_KEY: &'static str = "csrf_token";
+    const SESSION_ID_KEY: &'static str = "session_id";
 
     pub fn renew(&self) {
         self.0.renew();
@@ -21,6 +24,30 @@ impl TypedSession {
         self.0.get(Self::USER_ID_KEY)
     }
 
+    /// Id of the session in `user_sessions`, see `start_session`.
+    pub fn insert_session_id(&self, session_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::SESSION_ID_KEY, session_id)
+    }
+
+    pub fn get_session_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::SESSION_ID_KEY)
+    }
+
+    pub fn get_csrf_token(&self) -> Result<Option<String>, SessionGetError> {
+        self.0.get(Self::CSRF_TOKEN_KEY)
+    }
+
+    /// Generate a fresh CSRF token and store it in the session.
+    pub fn insert_csrf_token(&self) -> Result<String, SessionInsertError> {
+        let mut rng = thread_rng();
+        let token: String = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+            .map(char::from)
+            .take(32)
+            .collect();
+        self.0.insert(Self::CSRF_TOKEN_KEY, &token)?;
+        Ok(token)
+    }
+
     pub fn log_out(self) {
         self.0.purge();
     }
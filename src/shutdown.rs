@@ -0,0 +1,219 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServerHandle, ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web,
+};
+use futures_util::future::join_all;
+use sqlx::PgPool;
+use tokio::{sync::watch, task::JoinHandle};
+
+/// Requests being handled by the API. Clones share the same count.
+#[derive(Clone, Default)]
+pub struct InFlightRequests(Arc<AtomicUsize>);
+
+impl InFlightRequests {
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Decrements the count when the request is done, however it ends.
+struct InFlightGuard(Arc<AtomicUsize>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Keep `InFlightRequests` up to date, for the shutdown report to tell how
+/// many requests were drained.
+pub async fn count_in_flight(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let _guard = req
+        .app_data::<web::Data<InFlightRequests>>()
+        .map(|in_flight| {
+            in_flight.0.fetch_add(1, Ordering::SeqCst);
+            InFlightGuard(in_flight.0.clone())
+        });
+    next.call(req).await
+}
+
+/// Tells background tasks that the process is shutting down, so that they
+/// stop once done with their current work. Clones share the same signal.
+#[derive(Clone)]
+pub struct ShutdownSignal(Arc<watch::Sender<bool>>);
+
+impl Default for ShutdownSignal {
+    fn default() -> Self {
+        Self(Arc::new(watch::channel(false).0))
+    }
+}
+
+impl ShutdownSignal {
+    pub fn trigger(&self) {
+        self.0.send_replace(true);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Resolves once `trigger` has been called, right away if it already was.
+    pub async fn triggered(&self) {
+        let mut receiver = self.0.subscribe();
+        // The sender lives as long as `self`, so this can't fail.
+        let _ = receiver.wait_for(|triggered| *triggered).await;
+    }
+}
+
+/// Resolves on Ctrl-C, or on `SIGTERM` where there is such a thing.
+pub async fn shutdown_requested() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for Ctrl-C");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// What was left behind by a graceful shutdown, for checking a deploy.
+#[derive(Debug)]
+pub struct ShutdownReport {
+    /// Deliveries still queued, to be sent by the next worker. `None` when
+    /// the database couldn't be reached.
+    pub queued_deliveries: Option<i64>,
+    /// Requests being handled when the shutdown started.
+    pub in_flight_requests_drained: usize,
+    /// Whether the delivery worker stopped after its current batch within
+    /// the shutdown timeout, rather than being cut off or having failed.
+    pub worker_finished_task: bool,
+    /// Whether the other background tasks all stopped within the shutdown
+    /// timeout.
+    pub background_tasks_stopped: bool,
+}
+
+impl ShutdownReport {
+    pub fn log(&self) {
+        tracing::info!(
+            queued_deliveries = self.queued_deliveries,
+            in_flight_requests_drained = self.in_flight_requests_drained,
+            worker_finished_task = self.worker_finished_task,
+            background_tasks_stopped = self.background_tasks_stopped,
+            "Shutdown report"
+        );
+    }
+}
+
+/// A background task of the process, named for the logs.
+pub type BackgroundTask = (&'static str, JoinHandle<Result<(), anyhow::Error>>);
+
+/// Stop the API and the background tasks gracefully and report on it.
+///
+/// Every task is told to stop through `signal`. The API stops accepting
+/// connections and finishes the requests in flight while the delivery
+/// worker finishes its current batch and the other tasks their current
+/// round; all of them share `timeout`.
+pub async fn shut_down(
+    server: ServerHandle,
+    in_flight: &InFlightRequests,
+    signal: &ShutdownSignal,
+    worker: JoinHandle<Result<(), anyhow::Error>>,
+    background: Vec<BackgroundTask>,
+    pool: &PgPool,
+    timeout: Duration,
+) -> ShutdownReport {
+    tracing::info!("Shutting down");
+    let in_flight_requests_drained = in_flight.count();
+    signal.trigger();
+    let deadline = tokio::time::Instant::now() + timeout;
+    let (_, worker, background) = tokio::join!(
+        tokio::time::timeout_at(deadline, server.stop(true)),
+        tokio::time::timeout_at(deadline, worker),
+        join_all(
+            background
+                .into_iter()
+                .map(|(name, task)| stop_background_task(name, task, deadline))
+        ),
+    );
+    let worker_finished_task = matches!(worker, Ok(Ok(Ok(()))));
+    let background_tasks_stopped = background.into_iter().all(|stopped| stopped);
+    let queued_deliveries = match count_queued_deliveries(pool).await {
+        Ok(n) => Some(n),
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to count the deliveries left in the queue",
+            );
+            None
+        }
+    };
+    ShutdownReport {
+        queued_deliveries,
+        in_flight_requests_drained,
+        worker_finished_task,
+        background_tasks_stopped,
+    }
+}
+
+async fn stop_background_task(
+    name: &'static str,
+    task: JoinHandle<Result<(), anyhow::Error>>,
+    deadline: tokio::time::Instant,
+) -> bool {
+    match tokio::time::timeout_at(deadline, task).await {
+        Ok(Ok(Ok(()))) => true,
+        Ok(Ok(Err(e))) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                task = name,
+                "A background task failed while shutting down",
+            );
+            false
+        }
+        Ok(Err(e)) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                task = name,
+                "A background task panicked while shutting down",
+            );
+            false
+        }
+        Err(_) => {
+            tracing::warn!(task = name, "A background task didn't stop in time");
+            false
+        }
+    }
+}
+
+async fn count_queued_deliveries(pool: &PgPool) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar!(r#"SELECT count(*) as "count!" FROM issue_delivery_queue"#)
+        .fetch_one(pool)
+        .await
+}
@@ -0,0 +1,200 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Sign `message` with HMAC-SHA256, returning a URL-safe signature.
+pub fn sign(secret: &Secret<String>, message: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.expose_secret().as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(message.as_bytes());
+    URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+}
+
+/// Check `signature` against `message` in constant time.
+pub fn verify(secret: &Secret<String>, message: &str, signature: &str) -> bool {
+    let Ok(signature) = URL_SAFE_NO_PAD.decode(signature) else {
+        return false;
+    };
+    let mut mac = HmacSha256::new_from_slice(secret.expose_secret().as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(message.as_bytes());
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Append a signature to a random confirmation token, as `{token}.{signature}`.
+pub fn sign_confirmation_token(secret: &Secret<String>, token: &str) -> String {
+    let signature = sign(secret, &confirmation_token_message(token));
+    format!("{token}.{signature}")
+}
+
+/// Whether `signed_token` was produced by `sign_confirmation_token`.
+/// This only weeds out forgeries: the token must still be looked up.
+pub fn verify_confirmation_token(secret: &Secret<String>, signed_token: &str) -> bool {
+    match signed_token.split_once('.') {
+        Some((token, signature)) => verify(secret, &confirmation_token_message(token), signature),
+        None => false,
+    }
+}
+
+fn confirmation_token_message(token: &str) -> String {
+    format!("confirm:{token}")
+}
+
+/// Builds links to the public endpoints that identify a subscriber without
+/// a database-backed token.
+#[derive(Clone)]
+pub struct SignedLinks {
+    base_url: String,
+    hmac_secret: Secret<String>,
+}
+
+impl SignedLinks {
+    pub fn new(base_url: String, hmac_secret: Secret<String>) -> Self {
+        Self {
+            base_url,
+            hmac_secret,
+        }
+    }
+
+    pub fn unsubscribe(&self, subscriber_id: Uuid) -> String {
+        format!(
+            "{}/subscriptions/unsubscribe?subscriber_id={}&signature={}",
+            self.base_url,
+            subscriber_id,
+            sign(&self.hmac_secret, &Self::unsubscribe_message(subscriber_id))
+        )
+    }
+
+    pub fn verify_unsubscribe(&self, subscriber_id: Uuid, signature: &str) -> bool {
+        verify(
+            &self.hmac_secret,
+            &Self::unsubscribe_message(subscriber_id),
+            signature,
+        )
+    }
+
+    /// Link to the online copy of an issue that records an open by the
+    /// subscriber when followed, see `routes::issue_read`.
+    pub fn issue_read(&self, issue_id: Uuid, subscriber_id: Uuid) -> String {
+        format!(
+            "{}/issues/{}/read?subscriber_id={}&signature={}",
+            self.base_url,
+            issue_id,
+            subscriber_id,
+            sign(
+                &self.hmac_secret,
+                &Self::issue_read_message(issue_id, subscriber_id)
+            )
+        )
+    }
+
+    pub fn verify_issue_read(&self, issue_id: Uuid, subscriber_id: Uuid, signature: &str) -> bool {
+        verify(
+            &self.hmac_secret,
+            &Self::issue_read_message(issue_id, subscriber_id),
+            signature,
+        )
+    }
+
+    /// Link proving that the admin `user_id` receives mail at `email`, see
+    /// `routes::confirm_admin_email`. It stops working if the email changes.
+    pub fn admin_email_verification(&self, user_id: Uuid, email: &str) -> String {
+        format!(
+            "{}/admin/verify_email/confirm?signature={}",
+            self.base_url,
+            sign(
+                &self.hmac_secret,
+                &Self::admin_email_message(user_id, email)
+            )
+        )
+    }
+
+    pub fn verify_admin_email(&self, user_id: Uuid, email: &str, signature: &str) -> bool {
+        verify(
+            &self.hmac_secret,
+            &Self::admin_email_message(user_id, email),
+            signature,
+        )
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// An opaque reference to a subscriber for analytics: stable for a given
+    /// subscriber, and revealing neither their email nor their id.
+    pub fn subscriber_reference(&self, subscriber_id: Uuid) -> String {
+        sign(
+            &self.hmac_secret,
+            &format!("subscriber_ref:{subscriber_id}"),
+        )
+    }
+
+    fn unsubscribe_message(subscriber_id: Uuid) -> String {
+        format!("unsubscribe:{subscriber_id}")
+    }
+
+    fn issue_read_message(issue_id: Uuid, subscriber_id: Uuid) -> String {
+        format!("issue_read:{issue_id}:{subscriber_id}")
+    }
+
+    fn admin_email_message(user_id: Uuid, email: &str) -> String {
+        format!("admin_email:{user_id}:{email}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secret() -> Secret<String> {
+        Secret::new("a-secret".to_string())
+    }
+
+    #[test]
+    fn signatures_round_trip() {
+        let signature = sign(&secret(), "a message");
+        assert!(verify(&secret(), "a message", &signature));
+    }
+
+    #[test]
+    fn signatures_are_bound_to_message_and_secret() {
+        let signature = sign(&secret(), "a message");
+        assert!(!verify(&secret(), "another message", &signature));
+        assert!(!verify(
+            &Secret::new("another-secret".to_string()),
+            "a message",
+            &signature
+        ));
+        assert!(!verify(&secret(), "a message", "not base64!"));
+    }
+
+    #[test]
+    fn confirmation_tokens_round_trip() {
+        let signed = sign_confirmation_token(&secret(), "a-token");
+        assert!(signed.starts_with("a-token."));
+        assert!(verify_confirmation_token(&secret(), &signed));
+    }
+
+    #[test]
+    fn tampered_confirmation_tokens_are_rejected() {
+        let signed = sign_confirmation_token(&secret(), "a-token");
+        let tampered = signed.replacen("a-token", "b-token", 1);
+        assert!(!verify_confirmation_token(&secret(), &tampered));
+        assert!(!verify_confirmation_token(&secret(), "a-token"));
+    }
+
+    #[test]
+    fn subscriber_references_are_stable_and_opaque() {
+        let links = SignedLinks::new("https://example.com".into(), secret());
+        let subscriber_id = Uuid::new_v4();
+        let reference = links.subscriber_reference(subscriber_id);
+        assert_eq!(reference, links.subscriber_reference(subscriber_id));
+        assert_ne!(reference, links.subscriber_reference(Uuid::new_v4()));
+        assert!(!reference.contains(&subscriber_id.to_string()));
+    }
+}
@@ -1,40 +1,83 @@
 use crate::{
-    authentication::reject_anonymous_users,
-    configuration::{DatabaseSettings, Settings},
-    email_client::EmailClient,
+    authentication::{
+        ensure_admin_exists, reject_anonymous_users, reject_unverified_admins, MaxSessionsPerUser,
+        SessionMaxAge,
+    },
+    client_ip::{ClientIpConfig, IpNetworks},
+    clock::{system_clock, Clock},
+    configuration::{
+        describe_problems, DatabaseSettings, EmailClientSettings, SenderVerification, Settings,
+        TimeoutSettings,
+    },
+    email_client::{AuthorizationToken, EmailClient},
+    issue_delivery_worker::WorkerHeartbeat,
+    load_shedding::{shed_load, InFlightLimit},
+    locale::Locale,
+    rate_limit::{rate_limit, ExemptNetworks, RateLimiter},
     routes::{
-        admin_dashboard, change_password, change_password_form, confirm, health_check, home,
-        log_out, login, login_form, publish_newsletter, publish_newsletter_form, subscribe,
+        admin_dashboard, application_status, archived_issue, change_password, change_password_form,
+        change_password_form_config, confirm, confirm_admin_email, confirm_subscriber_manually,
+        create_newsletter_list, dead_letter_list, delivery_by_message_id, email_health_check,
+        email_token_form, export_audit_log, health_check, home, idempotency_key_usage,
+        import_suppressions, import_suppressions_form_config, issue_read, log_out, login,
+        login_form, newsletter_delivery_progress, newsletter_issue_status, newsletter_lists,
+        pause_worker, postmark_engagement, publish_form_config, publish_newsletter,
+        publish_newsletter_form, replay_dead_letter, resend_confirmation, resume_worker,
+        rotate_email_token, send_email_verification, set_admin_email, subscribe, subscribe_form,
+        subscribe_via_get, subscriber_details, suppressions_form, unsubscribe, unsubscribe_form,
+        update_newsletter_issue, verify_email_page, ConfirmationEmailPermits,
     },
+    shutdown::{count_in_flight, InFlightRequests},
+    signing::SignedLinks,
+    telemetry::QuietHealthChecks,
+    transaction::commit_request_transaction,
+    utils::RedirectAllowlist,
+};
+use actix_session::{config::PersistentSession, storage::RedisSessionStore, SessionMiddleware};
+use actix_web::{
+    cookie::Key,
+    dev::{Server, ServerHandle},
+    middleware::{from_fn, NormalizePath},
+    web, App, HttpServer,
 };
-use actix_session::{storage::RedisSessionStore, SessionMiddleware};
-use actix_web::{cookie::Key, dev::Server, middleware::from_fn, web, App, HttpServer};
 use actix_web_flash_messages::{storage::CookieMessageStore, FlashMessagesFramework};
 use secrecy::{ExposeSecret, Secret};
-use sqlx::{postgres::PgPoolOptions, PgPool};
-use std::net::TcpListener;
+use sqlx::{postgres::PgPoolOptions, ConnectOptions, PgPool};
+use std::{net::TcpListener, sync::Arc, time::Duration};
+use tracing::log::LevelFilter;
 use tracing_actix_web::TracingLogger;
 
 pub struct Application {
     port: u16,
     server: Server,
+    email_token: AuthorizationToken,
+    worker_heartbeat: WorkerHeartbeat,
+    in_flight_requests: InFlightRequests,
 }
 
 impl Application {
     pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
-        let connection_pool = get_connection_pool(&configuration.database);
+        Self::build_with_clock(configuration, system_clock()).await
+    }
+
+    /// Like `build`, with the API reading the time from `clock`.
+    pub async fn build_with_clock(
+        configuration: Settings,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Self, anyhow::Error> {
+        configuration
+            .validate()
+            .map_err(|problems| anyhow::anyhow!(describe_problems(&problems)))?;
+        configuration.log_summary();
+        let connection_pool = get_connection_pool(&configuration.database, &configuration.timeouts);
 
-        let sender = configuration
+        let email_client = configuration
             .email_client
-            .sender()
-            .expect("Invalid sender email address");
-        let email_client = EmailClient::new(
-            sender,
-            configuration.email_client.api_url.clone(),
-            configuration.email_client.authorization_token.clone(),
-            configuration.email_client.timeout(),
-        )
-        .expect("fail to build a email client");
+            .clone()
+            .client(configuration.timeouts.email_client())?;
+        let email_token = email_client.authorization_token().clone();
+        check_sender_signature(&email_client, &configuration.email_client).await?;
+        ensure_admin_exists(&connection_pool, &configuration.bootstrap).await?;
 
         let address = format!(
             "{}:{}",
@@ -42,22 +85,53 @@ impl Application {
         );
         let listener = TcpListener::bind(address).expect("Failed to bind listener");
         let port = listener.local_addr().unwrap().port();
+        let worker_heartbeat = WorkerHeartbeat::default();
+        let in_flight_requests = InFlightRequests::default();
         let server = run(
             listener,
             connection_pool,
             email_client,
-            configuration.application.base_url,
-            configuration.redis_url,
-            configuration.application.hmac_secret,
+            configuration,
+            worker_heartbeat.clone(),
+            in_flight_requests.clone(),
+            clock,
         )
         .await?;
 
-        Ok(Self { port, server })
+        Ok(Self {
+            port,
+            server,
+            email_token,
+            worker_heartbeat,
+            in_flight_requests,
+        })
     }
 
     pub fn port(&self) -> u16 {
         self.port
     }
+
+    /// The email provider token used by the API, for other email clients
+    /// of the process to follow its rotations.
+    pub fn email_token(&self) -> AuthorizationToken {
+        self.email_token.clone()
+    }
+
+    /// Where the delivery worker of the process should report its state,
+    /// for `/status` to show it.
+    pub fn worker_heartbeat(&self) -> WorkerHeartbeat {
+        self.worker_heartbeat.clone()
+    }
+
+    /// Stops the API, see `shutdown::shut_down`. The API doesn't handle
+    /// signals itself.
+    pub fn server_handle(&self) -> ServerHandle {
+        self.server.handle()
+    }
+
+    pub fn in_flight_requests(&self) -> InFlightRequests {
+        self.in_flight_requests.clone()
+    }
     // A more expressive name that makes it clear that
     // this function only returns when the application is stopped.
     pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
@@ -65,14 +139,78 @@ impl Application {
     }
 }
 
-pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
+pub fn get_connection_pool(configuration: &DatabaseSettings, timeouts: &TimeoutSettings) -> PgPool {
+    let statement_timeout = timeouts.database_statement().as_millis().to_string();
+    let options = configuration
+        .with_db()
+        .options([("statement_timeout", statement_timeout.as_str())]);
+    // sqlx logs statements slower than a second unless told otherwise.
+    let options = match configuration.slow_query_threshold() {
+        Some(threshold) => options.log_slow_statements(LevelFilter::Warn, threshold),
+        None => options.log_slow_statements(LevelFilter::Off, Duration::default()),
+    };
     PgPoolOptions::new()
-        .acquire_timeout(std::time::Duration::from_secs(2))
-        .connect_lazy_with(configuration.with_db())
+        .max_connections(configuration.max_connections)
+        .acquire_timeout(timeouts.database_acquire())
+        .connect_lazy_with(options)
+}
+
+/// Catch an unverified sender on boot, rather than through failed sends.
+async fn check_sender_signature(
+    email_client: &EmailClient,
+    settings: &EmailClientSettings,
+) -> Result<(), anyhow::Error> {
+    let (SenderVerification::Warn | SenderVerification::Abort, Some(account_token)) =
+        (settings.sender_verification, &settings.account_token)
+    else {
+        return Ok(());
+    };
+    let problem = match email_client.sender_is_verified(account_token).await {
+        Ok(true) => match &settings.return_path_domain {
+            None => return Ok(()),
+            Some(domain) => match email_client
+                .sender_return_path_is_verified(account_token, domain)
+                .await
+            {
+                Ok(true) => return Ok(()),
+                Ok(false) => format!(
+                    "The sender signature of {} has no verified Return-Path domain {domain}: \
+                    bounces are not routed there.",
+                    settings.sender
+                ),
+                Err(e) => format!("Failed to verify the Return-Path domain: {e}"),
+            },
+        },
+        Ok(false) => format!(
+            "The sender {} is not a confirmed sender signature.",
+            settings.sender
+        ),
+        Err(e) => format!("Failed to verify the sender signature: {e}"),
+    };
+    if settings.sender_verification == SenderVerification::Abort {
+        anyhow::bail!(problem);
+    }
+    tracing::warn!("{problem}");
+    Ok(())
+}
+
+/// How the sender of a new list is checked, see `create_newsletter_list`.
+pub struct SenderSignatureCheck {
+    pub enabled: bool,
+    pub account_token: Option<Secret<String>>,
 }
 
 pub struct ApplicationBaseUrl(pub String);
 
+/// See `ApplicationSettings::default_locale`.
+pub struct DefaultLocale(pub Locale);
+
+/// See `WorkerSettings::text_open_tracking`.
+pub struct TextOpenTracking(pub bool);
+
+/// When the server started, for `GET /status`.
+pub struct StartedAt(pub std::time::Instant);
+
 #[derive(Clone)]
 pub struct HmacSecret(pub Secret<String>);
 
@@ -80,47 +218,253 @@ pub async fn run(
     listener: TcpListener,
     db_pool: PgPool,
     email_client: EmailClient,
-    base_url: String,
-    redis_url: Secret<String>,
-    hmac_secret: Secret<String>,
+    configuration: Settings,
+    worker_heartbeat: WorkerHeartbeat,
+    in_flight_requests: InFlightRequests,
+    clock: Arc<dyn Clock>,
 ) -> Result<Server, anyhow::Error> {
+    let clock: web::Data<dyn Clock> = web::Data::from(clock);
+    let started_at = web::Data::new(StartedAt(std::time::Instant::now()));
+    let worker_heartbeat = web::Data::new(worker_heartbeat);
+    let in_flight_requests = web::Data::new(in_flight_requests);
+    let shutdown_timeout = configuration.application.shutdown_timeout();
     let db_pool = web::Data::new(db_pool);
     let email_client = web::Data::new(email_client);
-    let base_url = web::Data::new(ApplicationBaseUrl(base_url));
+    let signed_links = web::Data::new(SignedLinks::new(
+        configuration.application.base_url.clone(),
+        configuration.application.hmac_secret.clone(),
+    ));
+    let signature_check = web::Data::new(SenderSignatureCheck {
+        enabled: configuration.email_client.sender_verification != SenderVerification::Disabled,
+        account_token: configuration.email_client.account_token.clone(),
+    });
+    let trailing_slash = configuration.application.trailing_slash;
+    let session_max_age = configuration.application.session_max_age();
+    let session_max_age_data = web::Data::new(SessionMaxAge(session_max_age));
+    let max_sessions_per_user = web::Data::new(MaxSessionsPerUser(
+        configuration.application.max_sessions_per_user,
+    ));
+    let client_ip_config =
+        web::Data::new(ClientIpConfig::from_settings(&configuration.application)?);
+    let redirect_allowlist = web::Data::new(RedirectAllowlist::parse(
+        &configuration.application.login_redirect_allowlist,
+    )?);
+    let default_locale = web::Data::new(DefaultLocale(configuration.application.default_locale));
+    let text_open_tracking =
+        web::Data::new(TextOpenTracking(configuration.worker.text_open_tracking));
+    let base_url = web::Data::new(ApplicationBaseUrl(configuration.application.base_url));
+    let hmac_secret = configuration.application.hmac_secret;
     let secret_key = Key::from(hmac_secret.expose_secret().as_bytes());
-    let redis_store = RedisSessionStore::new(redis_url.expose_secret()).await?;
+    // The cookie and its state in Redis go away with the session.
+    let session_cookie_ttl = actix_web::cookie::time::Duration::seconds(
+        i64::try_from(session_max_age.as_secs()).unwrap_or(i64::MAX),
+    );
+    let redis_store = RedisSessionStore::new(configuration.redis_url.expose_secret()).await?;
     let message_store = CookieMessageStore::builder(secret_key.clone()).build();
     let message_framework = FlashMessagesFramework::builder(message_store).build();
     let hmac_secret = web::Data::new(HmacSecret(hmac_secret));
+    let serve_subscribe_form = configuration.features.serve_subscribe_form;
+    let serve_subscribe_via_get = configuration.features.subscribe_via_get;
+    let confirmation_email_permits = web::Data::new(ConfirmationEmailPermits::new(
+        configuration
+            .subscriptions
+            .max_concurrent_confirmation_emails,
+    ));
+    let subscribe_in_flight = web::Data::new(InFlightLimit::new(
+        configuration.subscriptions.max_in_flight_requests(),
+    ));
+    let subscription_settings = web::Data::new(configuration.subscriptions);
+    let rate_limiter = web::Data::new(RateLimiter::new(
+        configuration.rate_limit.capacity,
+        configuration.rate_limit.refill_interval(),
+    ));
+    let exempt_networks = web::Data::new(ExemptNetworks(IpNetworks::parse(
+        &configuration.rate_limit.exempt_networks,
+    )?));
+    let rate_limit_settings = web::Data::new(configuration.rate_limit);
+    let feature_flags = web::Data::new(configuration.features);
+    let expiry_settings = web::Data::new(configuration.expiry);
+    let publishing_settings = web::Data::new(configuration.publishing);
+    let webhook_settings = web::Data::new(configuration.webhooks);
+    let password_change_notification = web::Data::new(configuration.password_change_notification);
     let server = HttpServer::new(move || {
         App::new()
             .wrap(message_framework.clone())
-            .wrap(SessionMiddleware::new(
-                redis_store.clone(),
-                secret_key.clone(),
-            ))
-            .wrap(TracingLogger::default())
-            .route("/health_check", web::get().to(health_check))
-            .route("/", web::get().to(home))
-            .route("/login", web::get().to(login_form))
-            .route("/login", web::post().to(login))
-            .route("/subscriptions", web::post().to(subscribe))
-            .route("/subscriptions/confirm", web::get().to(confirm))
+            .wrap(
+                SessionMiddleware::builder(redis_store.clone(), secret_key.clone())
+                    .session_lifecycle(PersistentSession::default().session_ttl(session_cookie_ttl))
+                    .build(),
+            )
+            .wrap(TracingLogger::<QuietHealthChecks>::new())
+            .wrap(NormalizePath::new(trailing_slash.into()))
+            .wrap(from_fn(count_in_flight))
+            // Each path is a single resource, with its methods as routes: a
+            // wrong method then gets a 405 with an `Allow` header rather than
+            // a 404. `App::route` would move the method guard to the resource.
+            .service(web::resource("/health_check").route(web::get().to(health_check)))
+            .service(web::resource("/status").route(web::get().to(application_status)))
+            .service(
+                web::resource("/health/email")
+                    .wrap(from_fn(reject_anonymous_users))
+                    .route(web::get().to(email_health_check)),
+            )
+            .service(web::resource("/").route(web::get().to(home)))
+            .service(
+                web::resource("/login")
+                    .route(web::get().to(login_form))
+                    .route(web::post().to(login).wrap(from_fn(rate_limit))),
+            )
+            .service(
+                web::resource("/subscriptions")
+                    .wrap(from_fn(shed_load))
+                    .wrap(from_fn(rate_limit))
+                    .route(web::post().to(subscribe)),
+            )
+            .configure(|cfg| {
+                if serve_subscribe_form {
+                    cfg.service(web::resource("/subscribe").route(web::get().to(subscribe_form)));
+                }
+                if serve_subscribe_via_get {
+                    cfg.service(
+                        web::resource("/subscriptions/new")
+                            .wrap(from_fn(shed_load))
+                            .wrap(from_fn(rate_limit))
+                            .route(web::get().to(subscribe_via_get)),
+                    );
+                }
+            })
+            .service(
+                web::resource("/subscriptions/resend_confirmation")
+                    .wrap(from_fn(rate_limit))
+                    .route(web::post().to(resend_confirmation)),
+            )
+            .service(web::resource("/subscriptions/confirm").route(web::get().to(confirm)))
+            .service(web::resource("/issues/{issue_id}").route(web::get().to(archived_issue)))
+            .service(web::resource("/issues/{issue_id}/read").route(web::get().to(issue_read)))
+            .service(
+                web::resource("/subscriptions/unsubscribe")
+                    .route(web::get().to(unsubscribe_form))
+                    .route(web::post().to(unsubscribe)),
+            )
+            .service(
+                web::resource("/webhooks/postmark/engagement")
+                    .route(web::post().to(postmark_engagement)),
+            )
             .service(
                 web::scope("/admin")
+                    .wrap(from_fn(commit_request_transaction))
+                    .wrap(from_fn(reject_unverified_admins))
                     .wrap(from_fn(reject_anonymous_users))
-                    .route("/dashboard", web::get().to(admin_dashboard))
-                    .route("/newsletters", web::get().to(publish_newsletter_form))
-                    .route("/newsletters", web::post().to(publish_newsletter))
-                    .route("/password", web::get().to(change_password_form))
-                    .route("/password", web::post().to(change_password))
-                    .route("/logout", web::post().to(log_out)),
+                    .service(web::resource("/dashboard").route(web::get().to(admin_dashboard)))
+                    .service(
+                        web::resource("/verify_email")
+                            .route(web::get().to(verify_email_page))
+                            .route(web::post().to(send_email_verification)),
+                    )
+                    .service(
+                        web::resource("/verify_email/confirm")
+                            .route(web::get().to(confirm_admin_email)),
+                    )
+                    .service(
+                        web::resource("/verify_email/address")
+                            .route(web::post().to(set_admin_email)),
+                    )
+                    .service(
+                        web::resource("/audit/export.ndjson")
+                            .route(web::get().to(export_audit_log)),
+                    )
+                    .service(
+                        web::resource("/newsletters")
+                            .app_data(publish_form_config())
+                            .route(web::get().to(publish_newsletter_form))
+                            .route(web::post().to(publish_newsletter)),
+                    )
+                    .service(
+                        web::resource("/newsletters/{issue_id}")
+                            .route(web::get().to(newsletter_issue_status))
+                            .route(web::put().to(update_newsletter_issue)),
+                    )
+                    .service(
+                        web::resource("/newsletters/idempotency/{key}")
+                            .route(web::get().to(idempotency_key_usage)),
+                    )
+                    .service(
+                        web::resource("/newsletters/{issue_id}/progress")
+                            .route(web::get().to(newsletter_delivery_progress)),
+                    )
+                    .service(
+                        web::resource("/lists")
+                            .route(web::get().to(newsletter_lists))
+                            .route(web::post().to(create_newsletter_list)),
+                    )
+                    .service(web::resource("/suppressions").route(web::get().to(suppressions_form)))
+                    .service(
+                        web::resource("/suppressions/import")
+                            .app_data(import_suppressions_form_config())
+                            .route(web::post().to(import_suppressions)),
+                    )
+                    .service(web::resource("/deadletter").route(web::get().to(dead_letter_list)))
+                    .service(
+                        web::resource("/deliveries/by_message/{message_id}")
+                            .route(web::get().to(delivery_by_message_id)),
+                    )
+                    .service(
+                        web::resource("/deadletter/{dead_letter_id}/replay")
+                            .route(web::post().to(replay_dead_letter)),
+                    )
+                    .service(
+                        web::resource("/subscribers/{subscriber_id}")
+                            .route(web::get().to(subscriber_details)),
+                    )
+                    .service(
+                        web::resource("/subscribers/{subscriber_id}/confirm")
+                            .route(web::post().to(confirm_subscriber_manually)),
+                    )
+                    .service(
+                        web::resource("/settings/email_token")
+                            .route(web::get().to(email_token_form))
+                            .route(web::post().to(rotate_email_token)),
+                    )
+                    .service(web::resource("/worker/pause").route(web::post().to(pause_worker)))
+                    .service(web::resource("/worker/resume").route(web::post().to(resume_worker)))
+                    .service(
+                        web::resource("/password")
+                            .app_data(change_password_form_config())
+                            .route(web::get().to(change_password_form))
+                            .route(web::post().to(change_password)),
+                    )
+                    .service(web::resource("/logout").route(web::post().to(log_out))),
             )
             .app_data(db_pool.clone())
+            .app_data(started_at.clone())
+            .app_data(worker_heartbeat.clone())
+            .app_data(in_flight_requests.clone())
             .app_data(email_client.clone())
             .app_data(base_url.clone())
             .app_data(hmac_secret.clone())
+            .app_data(signed_links.clone())
+            .app_data(subscription_settings.clone())
+            .app_data(confirmation_email_permits.clone())
+            .app_data(rate_limiter.clone())
+            .app_data(subscribe_in_flight.clone())
+            .app_data(rate_limit_settings.clone())
+            .app_data(exempt_networks.clone())
+            .app_data(client_ip_config.clone())
+            .app_data(feature_flags.clone())
+            .app_data(expiry_settings.clone())
+            .app_data(publishing_settings.clone())
+            .app_data(redirect_allowlist.clone())
+            .app_data(max_sessions_per_user.clone())
+            .app_data(session_max_age_data.clone())
+            .app_data(default_locale.clone())
+            .app_data(text_open_tracking.clone())
+            .app_data(webhook_settings.clone())
+            .app_data(password_change_notification.clone())
+            .app_data(signature_check.clone())
+            .app_data(clock.clone())
     })
+    .disable_signals()
+    .shutdown_timeout(shutdown_timeout.as_secs())
     .listen(listener)?
     .run();
     Ok(server)
@@ -1,8 +1,30 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    web,
+};
+use once_cell::sync::Lazy;
 use tokio::task::JoinHandle;
-use tracing::{subscriber::set_global_default, Subscriber};
+use tracing::{
+    field::{Field, Visit},
+    subscriber::set_global_default,
+    Event, Span, Subscriber,
+};
+use tracing_actix_web::{DefaultRootSpanBuilder, RootSpanBuilder};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
-use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, EnvFilter, Registry};
+use tracing_subscriber::{
+    fmt::MakeWriter,
+    layer::{Context, Layer, SubscriberExt},
+    EnvFilter, Registry,
+};
+
+use crate::configuration::FeatureFlags;
 
 /// Compose multiple layers into a `tracing`'s subscriber.
 ///
@@ -27,6 +49,7 @@ where
     let formatting_layer = BunyanFormattingLayer::new(name, sink);
     Registry::default()
         .with(env_filter)
+        .with(CountersLayer(Counters::global()))
         .with(JsonStorageLayer)
         .with(formatting_layer)
 }
@@ -38,6 +61,94 @@ pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) {
     set_global_default(subscriber).expect("Failed to set subscriber");
 }
 
+/// Prefix of the event fields that are counted as metrics, as with
+/// `tracing-opentelemetry`'s `MetricsLayer`:
+/// `monotonic_counter.poison_pill_deliveries = 1u64` adds one to the
+/// `poison_pill_deliveries` counter.
+const COUNTER_PREFIX: &str = "monotonic_counter.";
+
+static COUNTERS: Lazy<Counters> = Lazy::new(Counters::default);
+
+/// Totals of the `monotonic_counter.*` fields seen since startup, exported
+/// by `GET /status`. Clones share the same totals.
+#[derive(Clone, Debug, Default)]
+pub struct Counters(Arc<Mutex<BTreeMap<String, u64>>>);
+
+impl Counters {
+    /// The counters fed by every subscriber built with `get_subscriber`.
+    pub fn global() -> Self {
+        COUNTERS.clone()
+    }
+
+    pub fn get(&self, name: &str) -> u64 {
+        self.snapshot().get(name).copied().unwrap_or(0)
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, u64> {
+        self.0
+            .lock()
+            .expect("The counters lock is poisoned")
+            .clone()
+    }
+
+    fn add(&self, name: &str, value: u64) {
+        let mut counters = self.0.lock().expect("The counters lock is poisoned");
+        let total = counters.entry(name.to_string()).or_default();
+        *total = total.saturating_add(value);
+    }
+}
+
+/// Feeds `Counters` from the events that go through the subscriber.
+pub struct CountersLayer(pub Counters);
+
+impl<S: Subscriber> Layer<S> for CountersLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        event.record(&mut CounterVisitor(&self.0));
+    }
+}
+
+struct CounterVisitor<'a>(&'a Counters);
+
+impl Visit for CounterVisitor<'_> {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if let Some(name) = field.name().strip_prefix(COUNTER_PREFIX) {
+            self.0.add(name, value);
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        if let Ok(value) = u64::try_from(value) {
+            self.record_u64(field, value);
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+/// `TracingLogger`'s request spans, but at `TRACE` for health check probes:
+/// they come often. `features.log_health_checks` brings them back to `INFO`.
+pub struct QuietHealthChecks;
+
+impl RootSpanBuilder for QuietHealthChecks {
+    fn on_request_start(request: &ServiceRequest) -> Span {
+        let log_health_checks = request
+            .app_data::<web::Data<FeatureFlags>>()
+            .is_some_and(|features| features.log_health_checks);
+        if request.path() == "/health_check" && !log_health_checks {
+            tracing_actix_web::root_span!(level = tracing::Level::TRACE, request)
+        } else {
+            tracing_actix_web::root_span!(request)
+        }
+    }
+
+    fn on_request_end<B: MessageBody>(
+        span: Span,
+        outcome: &Result<ServiceResponse<B>, actix_web::Error>,
+    ) {
+        DefaultRootSpanBuilder::on_request_end(span, outcome);
+    }
+}
+
 pub fn spawn_blocking_with_tracing<F, R>(f: F) -> JoinHandle<R>
 where
     F: FnOnce() -> R + Send + 'static,
@@ -46,3 +157,25 @@ where
     let current_span = tracing::Span::current();
     tokio::task::spawn_blocking(move || current_span.in_scope(f))
 }
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    use super::{Counters, CountersLayer};
+
+    #[test]
+    fn monotonic_counter_fields_are_added_up() {
+        let counters = Counters::default();
+        let subscriber = Registry::default().with(CountersLayer(counters.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::error!(monotonic_counter.poison_pill_deliveries = 1u64, "Failed");
+            tracing::error!(monotonic_counter.poison_pill_deliveries = 2u64, "Failed");
+            tracing::info!(poison_pill_deliveries = 5u64, "Not a counter");
+        });
+
+        assert_eq!(counters.get("poison_pill_deliveries"), 3);
+        assert_eq!(counters.snapshot().len(), 1);
+    }
+}
@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+
+use crate::{configuration::Settings, shutdown::ShutdownSignal, startup::get_connection_pool};
+
+/// Delete subscription tokens that can no longer be used: those past their
+/// TTL (plus the clock skew tolerance). Those of confirmed subscribers are
+/// kept until then too, so that following the link again still tells the
+/// subscriber they are already confirmed.
+#[tracing::instrument(skip(pool))]
+pub async fn purge_stale_tokens(
+    pool: &PgPool,
+    ttl: Duration,
+    skew_tolerance: Duration,
+) -> Result<u64, sqlx::Error> {
+    let max_age = (ttl + skew_tolerance).as_secs_f64();
+    let n_deleted = sqlx::query!(
+        r#"
+        DELETE FROM subscription_tokens
+        WHERE created_at < now() - make_interval(secs => $1)
+        "#,
+        max_age
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+    Ok(n_deleted)
+}
+
+async fn cleanup_loop(
+    pool: PgPool,
+    interval: Duration,
+    ttl: Duration,
+    skew_tolerance: Duration,
+    shutdown: ShutdownSignal,
+) -> Result<(), anyhow::Error> {
+    while !shutdown.is_triggered() {
+        match purge_stale_tokens(&pool, ttl, skew_tolerance).await {
+            Ok(n_deleted) => tracing::info!(n_deleted, "Purged stale subscription tokens"),
+            Err(e) => tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to purge stale subscription tokens",
+            ),
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {},
+            _ = shutdown.triggered() => {},
+        }
+    }
+    Ok(())
+}
+
+/// Returns once `shutdown` is triggered and the current purge is done.
+pub async fn run_token_cleanup_until_stopped(
+    configuration: Settings,
+    shutdown: ShutdownSignal,
+) -> Result<(), anyhow::Error> {
+    let pool = get_connection_pool(&configuration.database, &configuration.timeouts);
+    cleanup_loop(
+        pool,
+        configuration.subscriptions.token_cleanup_interval(),
+        configuration.subscriptions.confirmation_token_ttl(),
+        configuration.expiry.clock_skew_tolerance(),
+        shutdown,
+    )
+    .await
+}
@@ -0,0 +1,89 @@
+use std::{future::Future, pin::Pin, sync::Arc};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web, FromRequest, HttpMessage, HttpRequest,
+};
+use futures_util::future::LocalBoxFuture;
+use sqlx::{PgPool, Postgres, Transaction};
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
+
+use crate::utils::e500;
+
+type SharedTransaction = Arc<Mutex<Option<Transaction<'static, Postgres>>>>;
+type AfterCommit = Arc<Mutex<Vec<LocalBoxFuture<'static, ()>>>>;
+
+/// Placeholder inserted by `commit_request_transaction` and filled in by
+/// the `RequestTransaction` extractor, if the handler asks for one.
+#[derive(Clone, Default)]
+struct TransactionSlot {
+    transaction: SharedTransaction,
+    after_commit: AfterCommit,
+}
+
+/// A database transaction scoped to the current request.
+///
+/// The transaction is committed by `commit_request_transaction` once the
+/// handler returns `Ok`; it is rolled back if the handler returns `Err`
+/// (or panics, since dropping a `Transaction` rolls it back).
+pub struct RequestTransaction(TransactionSlot);
+
+impl RequestTransaction {
+    pub async fn lock(&self) -> MappedMutexGuard<'_, Transaction<'static, Postgres>> {
+        MutexGuard::map(self.0.transaction.lock().await, |t| {
+            t.as_mut()
+                .expect("The request transaction has already been finalized")
+        })
+    }
+
+    /// Run `task` once the transaction is committed, e.g. to send an email
+    /// about a change only once it is saved. It is dropped if the
+    /// transaction is rolled back or fails to commit.
+    pub async fn after_commit(&self, task: impl Future<Output = ()> + 'static) {
+        self.0.after_commit.lock().await.push(Box::pin(task));
+    }
+}
+
+impl FromRequest for RequestTransaction {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let slot = req.extensions().get::<TransactionSlot>().cloned();
+        let pool = req.app_data::<web::Data<PgPool>>().cloned();
+        Box::pin(async move {
+            let slot = slot.ok_or_else(|| {
+                e500("`RequestTransaction` requires the `commit_request_transaction` middleware")
+            })?;
+            let pool = pool.ok_or_else(|| e500("No database pool registered as app data"))?;
+            let transaction = pool.begin().await.map_err(e500)?;
+            *slot.transaction.lock().await = Some(transaction);
+            Ok(Self(slot))
+        })
+    }
+}
+
+pub async fn commit_request_transaction(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let slot = TransactionSlot::default();
+    req.extensions_mut().insert(slot.clone());
+    let response = next.call(req).await?;
+
+    let transaction = slot.transaction.lock().await.take();
+    if let Some(transaction) = transaction {
+        if response.response().error().is_none() {
+            transaction.commit().await.map_err(e500)?;
+            let after_commit = std::mem::take(&mut *slot.after_commit.lock().await);
+            for task in after_commit {
+                task.await;
+            }
+        } else {
+            transaction.rollback().await.map_err(e500)?;
+        }
+    }
+    Ok(response)
+}
@@ -1,5 +1,8 @@
+use actix_web::body::MessageBody;
 use actix_web::http::header::LOCATION;
 use actix_web::HttpResponse;
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
 
 pub fn e500<T>(e: T) -> actix_web::Error
 where
@@ -8,15 +11,181 @@ where
     actix_web::error::ErrorInternalServerError(e)
 }
 
+/// A `200 OK` HTML page, explicitly declared as UTF-8 so that browsers don't
+/// have to guess the encoding of non-ASCII content (e.g. usernames).
+pub fn html_response(body: impl MessageBody + 'static) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(body)
+}
+
+/// Whether `location` is a path on this site, rather than a URL that
+/// browsers would resolve to another host (`https://...`, `//host/...`).
+pub fn is_local_path(location: &str) -> bool {
+    location.starts_with('/')
+        && !location.starts_with("//")
+        && !location.starts_with("/\\")
+        && !location.chars().any(char::is_control)
+}
+
+/// Absolute URLs of other sites that users may be sent to after logging in
+/// (e.g. a separate admin frontend). An entry allows any URL with the same
+/// origin, below the entry's path.
+#[derive(Debug, Clone, Default)]
+pub struct RedirectAllowlist(Vec<reqwest::Url>);
+
+impl RedirectAllowlist {
+    pub fn parse(entries: &[String]) -> Result<Self, anyhow::Error> {
+        entries
+            .iter()
+            .map(|entry| {
+                let url = reqwest::Url::parse(entry)
+                    .with_context(|| format!("Invalid redirect allowlist entry {entry:?}"))?;
+                if !matches!(url.scheme(), "http" | "https") || url.host().is_none() {
+                    anyhow::bail!("Redirect allowlist entries must be http(s) URLs, got {entry:?}");
+                }
+                Ok(url)
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    pub fn allows(&self, location: &str) -> bool {
+        if location.chars().any(char::is_control) {
+            return false;
+        }
+        let Ok(url) = reqwest::Url::parse(location) else {
+            return false;
+        };
+        self.0.iter().any(|entry| {
+            let prefix = entry.path();
+            entry.origin() == url.origin()
+                && (url.path() == prefix
+                    || prefix.ends_with('/') && url.path().starts_with(prefix)
+                    || url
+                        .path()
+                        .strip_prefix(prefix)
+                        .is_some_and(|rest| rest.starts_with('/')))
+        })
+    }
+}
+
+/// Redirect to `location`, which must be a local path.
+///
+/// Anything else is a bug: it fails loudly in debug builds, and is replaced
+/// by `/` in release builds rather than turning into an open redirect.
 pub fn see_other(location: &str) -> HttpResponse {
+    debug_assert!(
+        is_local_path(location),
+        "Refusing to redirect to non-local location {location:?}"
+    );
+    let location = if is_local_path(location) {
+        location
+    } else {
+        "/"
+    };
     HttpResponse::SeeOther()
         .insert_header((LOCATION, location))
         .finish()
 }
 
+/// `see_other`, with `message` flashed to the page we redirect to.
+pub fn see_other_with_flash(location: &str, message: FlashMessage) -> HttpResponse {
+    message.send();
+    see_other(location)
+}
+
+/// Extract `title` from serde's "missing field `title`" error message.
+pub fn missing_form_field(error: &str) -> Option<&str> {
+    error.strip_prefix("missing field `")?.strip_suffix('`')
+}
+
 pub fn e400<T>(e: T) -> actix_web::Error
 where
     T: std::fmt::Debug + std::fmt::Display + 'static,
 {
     actix_web::error::ErrorBadRequest(e)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_paths_are_accepted() {
+        assert!(is_local_path("/"));
+        assert!(is_local_path("/admin/dashboard"));
+        assert!(is_local_path("/login?next=%2Fadmin"));
+    }
+
+    #[test]
+    fn external_urls_are_rejected() {
+        for location in [
+            "https://evil.example.com",
+            "//evil.example.com/login",
+            "/\\evil.example.com",
+            "admin/dashboard",
+            "/admin\r\nSet-Cookie: a=b",
+            "",
+        ] {
+            assert!(!is_local_path(location), "accepted {location:?}");
+        }
+    }
+
+    fn allowlist() -> RedirectAllowlist {
+        RedirectAllowlist::parse(&[
+            "https://admin.example.com/".into(),
+            "https://apps.example.com/newsletter".into(),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn allowlisted_urls_are_allowed() {
+        let allowlist = allowlist();
+        for location in [
+            "https://admin.example.com/",
+            "https://admin.example.com/issues?page=2",
+            "https://apps.example.com/newsletter",
+            "https://apps.example.com/newsletter/drafts",
+        ] {
+            assert!(allowlist.allows(location), "rejected {location:?}");
+        }
+    }
+
+    #[test]
+    fn urls_outside_the_allowlist_are_not_allowed() {
+        let allowlist = allowlist();
+        for location in [
+            "https://evil.example.com/",
+            "http://admin.example.com/",
+            "https://admin.example.com:8443/",
+            "https://admin.example.com@evil.example.com/",
+            "https://apps.example.com/newsletters",
+            "https://apps.example.com/newsletter/../admin",
+            "https://apps.example.com/other",
+            "/admin/dashboard",
+            "https://admin.example.com/\r\nSet-Cookie: a=b",
+        ] {
+            assert!(!allowlist.allows(location), "accepted {location:?}");
+        }
+        assert!(!RedirectAllowlist::default().allows("https://admin.example.com/"));
+    }
+
+    #[test]
+    fn allowlist_entries_must_be_absolute_http_urls() {
+        for entry in ["/admin", "javascript:alert(1)", "admin.example.com"] {
+            assert!(
+                RedirectAllowlist::parse(&[entry.into()]).is_err(),
+                "accepted {entry:?}"
+            );
+        }
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, should_panic(expected = "non-local location"))]
+    fn see_other_does_not_redirect_to_external_urls() {
+        let response = see_other("https://evil.example.com");
+        assert_eq!(response.headers().get(LOCATION).unwrap(), "/");
+    }
+}
@@ -0,0 +1,239 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    configuration::{Settings, WebhookSettings},
+    shutdown::ShutdownSignal,
+    startup::get_connection_pool,
+};
+
+/// Receivers that hang are given up on, to be retried later.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Events about subscribers, posted to `webhooks.events_url` (e.g. a CRM).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriptionEvent {
+    Created,
+    Confirmed,
+}
+
+impl SubscriptionEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "subscriber.created",
+            Self::Confirmed => "subscriber.confirmed",
+        }
+    }
+}
+
+/// Queue `event` for delivery, in the transaction that made it happen so
+/// that it is only posted if that commits. Nothing is queued while
+/// `webhooks.events_url` is unset.
+#[tracing::instrument(name = "Queue a subscription event", skip(executor, settings, email))]
+pub async fn enqueue_subscription_event(
+    executor: impl PgExecutor<'_>,
+    settings: &WebhookSettings,
+    event: SubscriptionEvent,
+    subscriber_id: Uuid,
+    email: &str,
+    occurred_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    if settings.events_url.is_none() {
+        return Ok(());
+    }
+    let payload = serde_json::json!({
+        "event": event.as_str(),
+        "subscriber_id": subscriber_id,
+        "email": email,
+        "occurred_at": occurred_at.to_rfc3339(),
+    });
+    sqlx::query!(
+        r#"
+        INSERT INTO webhook_delivery_queue (webhook_delivery_id, payload)
+        VALUES ($1, $2)
+        "#,
+        Uuid::new_v4(),
+        payload.to_string()
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+struct WebhookTask {
+    webhook_delivery_id: Uuid,
+    payload: String,
+    n_attempts: i16,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum WebhookOutcome {
+    Delivered,
+    /// The receiver failed; the event is retried after a backoff.
+    Retrying,
+    /// The receiver failed `webhooks.events_max_attempts` times.
+    DeadLettered,
+    EmptyQueue,
+}
+
+#[tracing::instrument(skip_all)]
+async fn dequeue_webhook(
+    pool: &PgPool,
+) -> Result<Option<(Transaction<'static, Postgres>, WebhookTask)>, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let r = sqlx::query_as!(
+        WebhookTask,
+        r#"
+        SELECT webhook_delivery_id, payload, n_attempts
+        FROM webhook_delivery_queue
+        WHERE execute_after <= now()
+        ORDER BY created_at
+        FOR UPDATE
+        SKIP LOCKED
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    Ok(r.map(|task| (transaction, task)))
+}
+
+/// Post the oldest due event to `url`. Failed posts are retried with an
+/// exponential backoff, see `WebhookSettings::events_retry_delay`.
+#[tracing::instrument(
+    skip_all,
+    fields(webhook_delivery_id=tracing::field::Empty)
+)]
+pub async fn try_deliver_webhook(
+    pool: &PgPool,
+    http_client: &reqwest::Client,
+    url: &str,
+    settings: &WebhookSettings,
+) -> Result<WebhookOutcome, anyhow::Error> {
+    let Some((mut transaction, task)) = dequeue_webhook(pool).await? else {
+        return Ok(WebhookOutcome::EmptyQueue);
+    };
+    tracing::Span::current().record(
+        "webhook_delivery_id",
+        tracing::field::display(task.webhook_delivery_id),
+    );
+    let outcome = http_client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(task.payload.clone())
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    let Err(e) = outcome else {
+        sqlx::query!(
+            "DELETE FROM webhook_delivery_queue WHERE webhook_delivery_id = $1",
+            task.webhook_delivery_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        return Ok(WebhookOutcome::Delivered);
+    };
+
+    let n_attempts = task.n_attempts + 1;
+    if n_attempts >= settings.events_max_attempts {
+        tracing::error!(
+            n_attempts,
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Posting a subscription event failed too many times. Moving it to the dead letters.",
+        );
+        sqlx::query!(
+            r#"
+            INSERT INTO webhook_dead_letters (webhook_delivery_id, payload, n_attempts, last_error)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            task.webhook_delivery_id,
+            task.payload,
+            n_attempts,
+            e.to_string()
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM webhook_delivery_queue WHERE webhook_delivery_id = $1",
+            task.webhook_delivery_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        return Ok(WebhookOutcome::DeadLettered);
+    }
+    tracing::warn!(
+        n_attempts,
+        error.cause_chain = ?e,
+        error.message = %e,
+        "Failed to post a subscription event. Retrying later.",
+    );
+    sqlx::query!(
+        r#"
+        UPDATE webhook_delivery_queue
+        SET
+            n_attempts = $2,
+            execute_after = now() + make_interval(secs => $3)
+        WHERE webhook_delivery_id = $1
+        "#,
+        task.webhook_delivery_id,
+        n_attempts,
+        settings.events_retry_delay(n_attempts).as_secs_f64()
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(WebhookOutcome::Retrying)
+}
+
+async fn webhook_loop(
+    pool: PgPool,
+    http_client: reqwest::Client,
+    url: String,
+    settings: WebhookSettings,
+    shutdown: ShutdownSignal,
+) -> Result<(), anyhow::Error> {
+    while !shutdown.is_triggered() {
+        let pause = match try_deliver_webhook(&pool, &http_client, &url, &settings).await {
+            Ok(WebhookOutcome::EmptyQueue) => Duration::from_secs(1),
+            Ok(_) => Duration::ZERO,
+            Err(e) => {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to process the subscription event queue",
+                );
+                Duration::from_secs(1)
+            }
+        };
+        if !pause.is_zero() {
+            tokio::select! {
+                _ = tokio::time::sleep(pause) => {},
+                _ = shutdown.triggered() => {},
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Post queued subscription events until `shutdown` is triggered. Idles
+/// when `webhooks.events_url` is unset.
+pub async fn run_webhook_worker_until_stopped(
+    configuration: Settings,
+    shutdown: ShutdownSignal,
+) -> Result<(), anyhow::Error> {
+    let Some(url) = configuration.webhooks.events_url.clone() else {
+        shutdown.triggered().await;
+        return Ok(());
+    };
+    let pool = get_connection_pool(&configuration.database, &configuration.timeouts);
+    let http_client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()?;
+    webhook_loop(pool, http_client, url, configuration.webhooks, shutdown).await
+}
@@ -0,0 +1,90 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::helper::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn insert_audit_event(app: &TestApp, action: &str, created_at: &str) {
+    sqlx::query!(
+        r#"
+        INSERT INTO audit_log (audit_log_id, user_id, action, target, created_at)
+        VALUES ($1, $2, $3, 'delivery_worker', $4)
+        "#,
+        Uuid::new_v4(),
+        app.test_user.user_id,
+        action,
+        DateTime::parse_from_rfc3339(created_at)
+            .unwrap()
+            .with_timezone(&Utc)
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+fn exported_actions(body: &str) -> Vec<String> {
+    body.lines()
+        .map(|line| {
+            let event: serde_json::Value = serde_json::from_str(line).unwrap();
+            event["action"].as_str().unwrap().to_string()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_export_the_audit_log() {
+    let app = spawn_app().await;
+
+    let response = app.get_audit_export(None).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_export_has_one_json_object_per_audit_event() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_audit_event(&app, "resume_worker", "2026-01-02T00:00:00Z").await;
+    insert_audit_event(&app, "pause_worker", "2026-01-01T00:00:00Z").await;
+
+    let response = app.get_audit_export(None).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"].to_str().unwrap(),
+        "application/x-ndjson"
+    );
+    let body = response.text().await.unwrap();
+    assert_eq!(exported_actions(&body), ["pause_worker", "resume_worker"]);
+    let first: serde_json::Value = serde_json::from_str(body.lines().next().unwrap()).unwrap();
+    assert_eq!(first["user_id"], app.test_user.user_id.to_string());
+    assert_eq!(first["target"], "delivery_worker");
+    assert_eq!(first["created_at"], "2026-01-01T00:00:00+00:00");
+}
+
+#[tokio::test]
+async fn the_export_only_includes_events_since_the_given_timestamp() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_audit_event(&app, "pause_worker", "2026-01-01T00:00:00Z").await;
+    insert_audit_event(&app, "resume_worker", "2026-01-02T00:00:00Z").await;
+    insert_audit_event(&app, "rotate_email_token", "2026-01-03T00:00:00Z").await;
+
+    let response = app.get_audit_export(Some("2026-01-02T00:00:00Z")).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.text().await.unwrap();
+    assert_eq!(
+        exported_actions(&body),
+        ["resume_worker", "rotate_email_token"]
+    );
+}
+
+#[tokio::test]
+async fn an_invalid_since_timestamp_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_audit_export(Some("yesterday")).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -1,6 +1,8 @@
 use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
 
-use crate::helper::{assert_is_redirect_to, spawn_app};
+use crate::helper::{assert_is_redirect_to, spawn_app, spawn_app_with};
 
 #[tokio::test]
 async fn must_login_to_see_change_password_form() {
@@ -26,6 +28,41 @@ async fn must_login_to_change_password() {
     assert_is_redirect_to(&resp, "/login");
 }
 
+#[tokio::test]
+async fn anonymous_users_are_redirected_before_the_form_is_validated() {
+    let app = spawn_app().await;
+
+    for body in [
+        serde_json::json!({}),
+        serde_json::json!({
+            "current_password": "something",
+            "new_password": "short",
+            "new_password_confirmed": "short",
+        }),
+    ] {
+        let resp = app.post_change_password(&body).await;
+
+        assert_is_redirect_to(&resp, "/login");
+    }
+}
+
+#[tokio::test]
+async fn a_missing_field_is_reported_on_the_form() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let resp = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": "a-long-enough-new-password",
+        }))
+        .await;
+
+    assert_is_redirect_to(&resp, "/admin/password");
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("<p><i>Please enter your new password a second time.</i></p>"));
+}
+
 #[tokio::test]
 async fn new_password_field_must_match() {
     let app = spawn_app().await;
@@ -118,3 +155,58 @@ async fn changing_password_works() {
     let resp = app.post_login(&login_body).await;
     assert_is_redirect_to(&resp, "/admin/dashboard");
 }
+
+#[tokio::test]
+async fn changing_password_sends_a_notification_to_the_user() {
+    let app = spawn_app_with(|c| {
+        c.password_change_notification.sender = Some("security@example.com".into());
+        c.password_change_notification.subject = "Security alert".into();
+    })
+    .await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let new_password = Uuid::new_v4().to_string();
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": &new_password,
+            "new_password_confirmed": &new_password,
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/password");
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(body["To"], app.test_user.email);
+    assert_eq!(body["From"], "security@example.com");
+    assert_eq!(body["Subject"], "Security alert");
+}
+
+#[tokio::test]
+async fn password_change_notifications_can_be_turned_off() {
+    let app = spawn_app_with(|c| c.password_change_notification.enabled = false).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    let new_password = Uuid::new_v4().to_string();
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": &new_password,
+            "new_password_confirmed": &new_password,
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/password");
+}
@@ -0,0 +1,198 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{any, body_partial_json},
+    Mock, ResponseTemplate,
+};
+use zero2prod::{
+    configuration::get_configuration, confirmation_reminders::send_confirmation_reminders,
+};
+
+use crate::helper::spawn_app;
+
+/// A pending subscriber whose confirmation email was sent `sent_ago`, or is
+/// still queued when `None`.
+async fn seed_pending_subscriber(
+    pool: &PgPool,
+    name: &str,
+    sent_ago: Option<chrono::Duration>,
+) -> Uuid {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (
+            id, email, name, subscribed_at, status,
+            confirmation_emails_sent, last_confirmation_email_sent_at
+        )
+        VALUES ($1, $2, $3, $4, 'pending', $5, $6)
+        "#,
+        subscriber_id,
+        format!("{name}@example.com"),
+        name,
+        Utc::now() - chrono::Duration::days(1),
+        i32::from(sent_ago.is_some()),
+        sent_ago.map(|ago| Utc::now() - ago)
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+    subscriber_id
+}
+
+async fn reminder_sent_at(pool: &PgPool, subscriber_id: Uuid) -> Option<chrono::DateTime<Utc>> {
+    sqlx::query!(
+        "SELECT reminder_sent_at FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap()
+    .reminder_sent_at
+}
+
+#[tokio::test]
+async fn only_subscribers_whose_confirmation_email_went_out_past_the_grace_are_reminded() {
+    let app = spawn_app().await;
+    let due = seed_pending_subscriber(&app.db_pool, "due", Some(chrono::Duration::hours(2))).await;
+    let queued = seed_pending_subscriber(&app.db_pool, "queued", None).await;
+    let recent =
+        seed_pending_subscriber(&app.db_pool, "recent", Some(chrono::Duration::minutes(5))).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let settings = get_configuration().unwrap().subscriptions;
+
+    let n_sent = send_confirmation_reminders(
+        &app.db_pool,
+        &app.email_client,
+        &app.links,
+        &settings,
+        Duration::from_secs(60 * 60),
+        Utc::now(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(n_sent, 1);
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(body["To"], "due@example.com");
+    assert!(reminder_sent_at(&app.db_pool, due).await.is_some());
+    assert!(reminder_sent_at(&app.db_pool, queued).await.is_none());
+    assert!(reminder_sent_at(&app.db_pool, recent).await.is_none());
+}
+
+#[tokio::test]
+async fn subscribers_are_reminded_only_once() {
+    let app = spawn_app().await;
+    seed_pending_subscriber(&app.db_pool, "due", Some(chrono::Duration::hours(2))).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let settings = get_configuration().unwrap().subscriptions;
+
+    for _ in 0..2 {
+        send_confirmation_reminders(
+            &app.db_pool,
+            &app.email_client,
+            &app.links,
+            &settings,
+            Duration::from_secs(60 * 60),
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn a_reminder_that_fails_to_send_is_attempted_again() {
+    let app = spawn_app().await;
+    let due = seed_pending_subscriber(&app.db_pool, "due", Some(chrono::Duration::hours(2))).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let settings = get_configuration().unwrap().subscriptions;
+    let grace = Duration::from_secs(60 * 60);
+
+    let n_sent = send_confirmation_reminders(
+        &app.db_pool,
+        &app.email_client,
+        &app.links,
+        &settings,
+        grace,
+        Utc::now(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(n_sent, 0);
+    assert!(reminder_sent_at(&app.db_pool, due).await.is_none());
+
+    let n_sent = send_confirmation_reminders(
+        &app.db_pool,
+        &app.email_client,
+        &app.links,
+        &settings,
+        grace,
+        Utc::now(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(n_sent, 1);
+    assert!(reminder_sent_at(&app.db_pool, due).await.is_some());
+}
+
+#[tokio::test]
+async fn an_undeliverable_reminder_does_not_hold_up_the_others_and_is_given_up_on() {
+    let app = spawn_app().await;
+    // Due first, so that it is picked first on every run.
+    let rejected =
+        seed_pending_subscriber(&app.db_pool, "rejected", Some(chrono::Duration::hours(3))).await;
+    let due = seed_pending_subscriber(&app.db_pool, "due", Some(chrono::Duration::hours(2))).await;
+    let settings = get_configuration().unwrap().subscriptions;
+    let max_attempts = settings.confirmation_reminder_max_attempts;
+    Mock::given(body_partial_json(
+        serde_json::json!({ "To": "rejected@example.com" }),
+    ))
+    .respond_with(ResponseTemplate::new(422))
+    .expect(max_attempts as u64)
+    .mount(&app.email_server)
+    .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    for _ in 0..max_attempts + 1 {
+        send_confirmation_reminders(
+            &app.db_pool,
+            &app.email_client,
+            &app.links,
+            &settings,
+            Duration::from_secs(60 * 60),
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+        assert!(reminder_sent_at(&app.db_pool, due).await.is_some());
+    }
+
+    // Given up on, rather than tried on every run.
+    assert!(reminder_sent_at(&app.db_pool, rejected).await.is_some());
+}
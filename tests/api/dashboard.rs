@@ -1,4 +1,7 @@
-use crate::helper::{assert_is_redirect_to, spawn_app};
+use actix_web::{test, web, App};
+use zero2prod::routes::admin_dashboard;
+
+use crate::helper::{assert_is_redirect_to, spawn_app, CapturedLogs};
 
 #[tokio::test]
 async fn you_must_be_logged_in_to_access_the_admin_dashboard() {
@@ -28,8 +31,111 @@ async fn logout_clears_session_state() {
     assert_is_redirect_to(&resp, "/login");
 
     let html_page = app.get_login_html().await;
-    assert!(html_page.contains(&format!("<p><i>You have successfully logged out.</i></p>")));
+    assert!(html_page.contains("<p><i>You have successfully logged out.</i></p>"));
+
+    let resp = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&resp, "/login");
+}
+
+#[tokio::test]
+async fn logging_out_with_get_is_not_allowed() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let resp = app
+        .api_client
+        .get(format!("{}/admin/logout", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status().as_u16(), 405);
+    assert_eq!(resp.headers().get("Allow").unwrap(), "POST");
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
+}
+
+#[tokio::test]
+async fn logout_without_a_valid_csrf_token_keeps_the_session() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    for body in [
+        serde_json::json!({}),
+        serde_json::json!({ "csrf_token": "not-the-right-token" }),
+    ] {
+        let resp = app.post_logout_with(&body).await;
+        assert_eq!(resp.status().as_u16(), 400);
+
+        let html_page = app.get_admin_dashboard_html().await;
+        assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
+    }
 
+    let resp = app.post_logout().await;
+    assert_is_redirect_to(&resp, "/login");
     let resp = app.get_admin_dashboard().await;
     assert_is_redirect_to(&resp, "/login");
 }
+
+#[tokio::test]
+async fn non_ascii_usernames_are_rendered_as_utf8() {
+    let mut app = spawn_app().await;
+    app.test_user.username = "Émilie Brontë".to_string();
+    sqlx::query!(
+        "UPDATE users SET username = $1 WHERE user_id = $2",
+        app.test_user.username,
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.test_user.login(&app).await;
+
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "text/html; charset=utf-8"
+    );
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Welcome Émilie Brontë!"));
+}
+
+#[tokio::test]
+async fn a_session_for_a_deleted_user_is_logged_out() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    sqlx::query!(
+        "DELETE FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    // The session is gone, not just rejected once.
+    let response = app.get_change_password().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_admin_dashboard_is_served_within_a_named_span() {
+    let app = spawn_app().await;
+    // Served on the test thread, so that its logs can be captured.
+    let service = test::init_service(
+        App::new()
+            .route("/admin/dashboard", web::get().to(admin_dashboard))
+            .app_data(web::Data::new(app.db_pool.clone())),
+    )
+    .await;
+    let (logs, _guard) = CapturedLogs::start("info");
+
+    let request = test::TestRequest::get()
+        .uri("/admin/dashboard")
+        .to_request();
+    test::call_service(&service, request).await;
+
+    assert_eq!(logs.with_message("[ADMIN DASHBOARD - START]").len(), 1);
+}
@@ -0,0 +1,57 @@
+use wiremock::ResponseTemplate;
+
+use crate::helper::{assert_is_redirect_to, spawn_app};
+
+const MESSAGE_ID: &str = "b7bc2f4a-e38e-4336-af7d-e6c392c2f817";
+
+/// What the provider answers every send with.
+fn sent(email: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(serde_json::json!({
+        "To": email,
+        "ErrorCode": 0,
+        "Message": "OK",
+        "MessageID": MESSAGE_ID,
+    }))
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_look_up_a_delivery() {
+    let app = spawn_app().await;
+
+    let response = app.get_delivery_by_message_id(MESSAGE_ID).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_stored_message_id_resolves_to_its_subscriber_and_issue() {
+    let app = spawn_app().await;
+    let email = "ursula@example.com";
+
+    let issue_id = app.deliver_an_issue(email, sent(email)).await;
+
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    let response = app.get_delivery_by_message_id(MESSAGE_ID).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let delivery: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(delivery["message_id"], MESSAGE_ID);
+    assert_eq!(delivery["newsletter_issue_id"], issue_id.to_string());
+    assert_eq!(delivery["issue_title"], "Newsletter title");
+    assert_eq!(delivery["subscriber_email"], email);
+    assert_eq!(delivery["subscriber_id"], subscriber_id.to_string());
+    assert_eq!(delivery["status"], "sent");
+}
+
+#[tokio::test]
+async fn unknown_message_ids_are_not_found() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_delivery_by_message_id(MESSAGE_ID).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
@@ -1,4 +1,11 @@
-use crate::helper::spawn_app;
+use wiremock::{
+    matchers::{header_exists, method, path},
+    Mock, ResponseTemplate,
+};
+
+use zero2prod::configuration::FeatureFlags;
+
+use crate::helper::{assert_is_redirect_to, spawn_app, spawn_app_with};
 
 #[tokio::test]
 async fn health_check_works() {
@@ -13,3 +20,141 @@ async fn health_check_works() {
     assert!(response.status().is_success());
     assert_eq!(Some(0), response.content_length());
 }
+
+#[tokio::test]
+async fn wrong_methods_on_known_routes_list_the_allowed_ones() {
+    let app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(format!("{}/health_check", &app.address))
+        .send()
+        .await
+        .expect("failed to send request");
+    assert_eq!(response.status().as_u16(), 405);
+    assert_eq!(response.headers().get("Allow").unwrap(), "GET");
+
+    let response = client
+        .delete(format!("{}/subscriptions/unsubscribe", &app.address))
+        .send()
+        .await
+        .expect("failed to send request");
+    assert_eq!(response.status().as_u16(), 405);
+    let allow = response.headers().get("Allow").unwrap().to_str().unwrap();
+    let mut allowed: Vec<&str> = allow.split(", ").collect();
+    allowed.sort();
+    assert_eq!(allowed, ["GET", "POST"]);
+
+    let response = client
+        .get(format!("{}/not_a_route", &app.address))
+        .send()
+        .await
+        .expect("failed to send request");
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+/// Log lines at info level or above, for one probe.
+async fn health_check_logs(log_health_checks: bool) -> Vec<serde_json::Value> {
+    let app = spawn_app_with(|c| c.features.log_health_checks = log_health_checks).await;
+
+    let response = reqwest::get(format!("{}/health_check", &app.address))
+        .await
+        .expect("failed to send request");
+
+    assert!(response.status().is_success());
+    app.server_logs()
+}
+
+#[tokio::test]
+async fn health_checks_are_not_logged_by_default() {
+    assert!(!FeatureFlags::default().log_health_checks);
+    assert_eq!(
+        health_check_logs(false).await,
+        Vec::<serde_json::Value>::new()
+    );
+}
+
+#[tokio::test]
+async fn health_checks_are_logged_when_enabled() {
+    let logs = health_check_logs(true).await;
+    assert!(logs.iter().any(|l| l["msg"]
+        .as_str()
+        .is_some_and(|m| m.ends_with("Answered a health check probe"))));
+    assert!(logs.iter().all(|l| l["http.target"] == "/health_check"));
+}
+
+#[tokio::test]
+async fn status_reports_version_uptime_and_queue_depth() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!("{}/status", &app.address))
+        .await
+        .expect("failed to send request");
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("Cache-Control").unwrap(),
+        "max-age=5"
+    );
+    let status: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(status["version"], env!("CARGO_PKG_VERSION"));
+    assert!(status["uptime_seconds"].is_u64(), "{status}");
+    assert_eq!(status["db"], "ok");
+    assert_eq!(status["queue_depth"], 0);
+    assert_eq!(status["worker"], "starting");
+    assert!(status["counters"].is_object(), "{status}");
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_check_the_email_provider() {
+    let app = spawn_app().await;
+
+    let response = app.get_email_health().await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn email_health_reports_a_reachable_provider() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(method("GET"))
+        .and(path("/server"))
+        .and(header_exists("X-Postmark-Server-Token"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    // Checking health must never send an email
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.get_email_health().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "ok");
+    assert!(body["latency_ms"].is_u64());
+}
+
+#[tokio::test]
+async fn email_health_reports_a_rejected_token() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(method("GET"))
+        .and(path("/server"))
+        .respond_with(ResponseTemplate::new(401))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.get_email_health().await;
+
+    assert_eq!(response.status().as_u16(), 503);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "unreachable");
+    assert!(body["error"].as_str().unwrap().contains("401"));
+}
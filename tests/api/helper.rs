@@ -1,15 +1,26 @@
+use actix_web::dev::ServerHandle;
 use argon2::{
     password_hash::{PasswordHasher, SaltString},
     Argon2,
 };
+use chrono::{SubsecRound, Utc};
 use once_cell::sync::Lazy;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
+use std::sync::{Arc, Mutex};
+use tracing::subscriber::DefaultGuard;
+use tracing_subscriber::fmt::MakeWriter;
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
 use zero2prod::{
-    configuration::{get_configuration, DatabaseSettings},
+    clock::MockClock,
+    configuration::{
+        get_configuration, DatabaseSettings, Settings, WebhookSettings, WorkerSettings,
+    },
     email_client::EmailClient,
-    issue_delivery_worker::{try_execute_task, ExecutionOutcome},
+    issue_delivery_worker::{try_execute_task, ExecutionOutcome, WorkerHeartbeat},
+    shutdown::InFlightRequests,
+    signing::SignedLinks,
     startup::{get_connection_pool, Application},
     telemetry::{get_subscriber, init_subscriber},
 };
@@ -22,12 +33,22 @@ pub struct TestApp {
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
     pub email_client: EmailClient,
+    pub worker_settings: WorkerSettings,
+    pub webhook_settings: WebhookSettings,
+    pub links: SignedLinks,
+    pub worker_heartbeat: WorkerHeartbeat,
+    pub server_handle: ServerHandle,
+    pub in_flight_requests: InFlightRequests,
+    /// Read by both the API and `worker_settings`. Starts at the real time.
+    pub clock: Arc<MockClock>,
+    /// What the API was built with, for starting the background tasks.
+    pub configuration: Settings,
 }
 
 impl TestApp {
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/subscriptions", self.address))
+            .post(format!("{}/subscriptions", self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
@@ -35,28 +56,74 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_resend_confirmation(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/subscriptions/resend_confirmation",
+                self.address
+            ))
+            .form(&[("email", email)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscribe_form(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/subscribe", self.address))
+            .send()
+            .await
+            .expect("Failed to get the subscribe form.")
+    }
+
+    pub async fn get_subscriptions_new(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/subscriptions/new?{}", self.address, query))
+            .send()
+            .await
+            .expect("Failed to subscribe through GET.")
+    }
+
     pub async fn get_confirmation_link(&self, req: &wiremock::Request) -> reqwest::Url {
-        let body: serde_json::Value = serde_json::from_slice(&req.body).unwrap();
+        let email = SentEmail::from_request(req);
+        assert_eq!(email.html_links.len(), 1);
+        self.local_link(&email.html_links[0])
+    }
+
+    /// `link`, pointed at the port of this app.
+    pub fn local_link(&self, link: &str) -> reqwest::Url {
+        let mut link = reqwest::Url::parse(link).expect("invalid link from resp");
+        link.set_port(Some(self.port)).unwrap();
+        assert_eq!(link.host_str().unwrap(), "127.0.0.1");
+        link
+    }
 
-        let get_link = |s: &str| {
-            let links: Vec<_> = linkify::LinkFinder::new()
-                .links(s)
-                .filter(|l| *l.kind() == linkify::LinkKind::Url)
-                .collect();
-            assert_eq!(links.len(), 1);
-            links[0].as_str().to_owned()
-        };
+    /// Every email the mock email server received, oldest first.
+    pub async fn sent_emails(&self) -> Vec<SentEmail> {
+        self.recent_emails(usize::MAX).await
+    }
+
+    /// The last `n` emails the mock email server received, oldest first.
+    pub async fn recent_emails(&self, n: usize) -> Vec<SentEmail> {
+        let requests = self.email_server.received_requests().await.unwrap();
+        let skipped = requests.len().saturating_sub(n);
+        requests[skipped..]
+            .iter()
+            .map(SentEmail::from_request)
+            .collect()
+    }
 
-        let raw_link = get_link(&body["HtmlBody"].as_str().unwrap());
-        let mut confirmation_link = reqwest::Url::parse(&raw_link).expect("invalid link from resp");
-        confirmation_link.set_port(Some(self.port)).unwrap();
-        assert_eq!(confirmation_link.host_str().unwrap(), "127.0.0.1");
-        confirmation_link
+    /// The last email the mock email server received. Panics if there is none.
+    pub async fn last_email(&self) -> SentEmail {
+        self.recent_emails(1)
+            .await
+            .pop()
+            .expect("No email was received")
     }
 
     pub async fn get_publish_newsletters(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/newsletters", &self.address))
+            .get(format!("{}/admin/newsletters", &self.address))
             .send()
             .await
             .expect("failed to get publish newsletters")
@@ -71,39 +138,235 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/newsletters", self.address))
+            .post(format!("{}/admin/newsletters", self.address))
             .form(&body)
             .send()
             .await
             .expect("failed to post publish newsletter")
     }
 
-    pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
+    pub async fn put_newsletter_issue<Body>(&self, issue_id: Uuid, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/login", self.address))
+            .put(format!("{}/admin/newsletters/{}", self.address, issue_id))
             .form(body)
             .send()
             .await
-            .expect("failed to post login")
+            .expect("failed to put newsletter issue")
     }
 
-    pub async fn get_login_html(&self) -> String {
+    pub async fn get_delivery_progress(&self, issue_id: uuid::Uuid) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/login", self.address))
+            .get(format!(
+                "{}/admin/newsletters/{}/progress",
+                &self.address, issue_id
+            ))
             .send()
             .await
-            .expect("failed to get login html")
+            .expect("failed to get delivery progress")
+    }
+
+    pub async fn get_archived_issue(
+        &self,
+        issue_id: uuid::Uuid,
+        conditions: &[(&str, &str)],
+    ) -> reqwest::Response {
+        let mut request = self
+            .api_client
+            .get(format!("{}/issues/{}", &self.address, issue_id));
+        for (name, value) in conditions {
+            request = request.header(*name, *value);
+        }
+        request
+            .send()
+            .await
+            .expect("failed to get the archived issue")
+    }
+
+    pub async fn get_issue_status_html(&self, issue_id: uuid::Uuid) -> String {
+        self.api_client
+            .get(format!("{}/admin/newsletters/{}", &self.address, issue_id))
+            .send()
+            .await
+            .expect("failed to get issue status")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_engagement_event(
+        &self,
+        event: &serde_json::Value,
+        secret: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/webhooks/postmark/engagement", &self.address))
+            .header("X-Webhook-Secret", secret)
+            .json(event)
+            .send()
+            .await
+            .expect("failed to post engagement event")
+    }
+
+    pub async fn post_rotate_email_token(&self, token: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/settings/email_token", &self.address))
+            .form(&serde_json::json!({ "token": token }))
+            .send()
+            .await
+            .expect("failed to rotate the email token")
+    }
+
+    pub async fn get_email_token_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/settings/email_token", &self.address))
+            .send()
+            .await
+            .expect("failed to get the email token form")
             .text()
             .await
             .unwrap()
     }
 
+    pub async fn post_create_list<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/lists", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("failed to create a list")
+    }
+
+    pub async fn get_lists_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/lists", &self.address))
+            .send()
+            .await
+            .expect("failed to get the lists")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_import_suppressions(&self, emails: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/suppressions/import", &self.address))
+            .form(&[("emails", emails)])
+            .send()
+            .await
+            .expect("failed to import suppressions")
+    }
+
+    pub async fn get_suppressions_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/suppressions", &self.address))
+            .send()
+            .await
+            .expect("failed to get the suppressions")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_dead_letters(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/deadletter", &self.address))
+            .send()
+            .await
+            .expect("failed to get dead letters")
+    }
+
+    pub async fn get_dead_letters_html(&self) -> String {
+        self.get_dead_letters().await.text().await.unwrap()
+    }
+
+    pub async fn get_subscriber_html(&self, subscriber_id: Uuid) -> String {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscribers/{}",
+                &self.address, subscriber_id
+            ))
+            .send()
+            .await
+            .expect("failed to get the subscriber")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_delivery_by_message_id(&self, message_id: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/deliveries/by_message/{}",
+                &self.address, message_id
+            ))
+            .send()
+            .await
+            .expect("failed to look up the delivery")
+    }
+
+    pub async fn post_replay_dead_letter(&self, dead_letter_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/deadletter/{}/replay",
+                &self.address, dead_letter_id
+            ))
+            .send()
+            .await
+            .expect("failed to replay dead letter")
+    }
+
+    pub async fn post_confirm_subscriber(&self, subscriber_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/subscribers/{}/confirm",
+                &self.address, subscriber_id
+            ))
+            .send()
+            .await
+            .expect("failed to confirm subscriber")
+    }
+
+    pub async fn get_email_health(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/health/email", &self.address))
+            .send()
+            .await
+            .expect("failed to get email health")
+    }
+
+    pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/login", self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("failed to post login")
+    }
+
+    pub async fn get_login(&self, next: Option<&str>) -> reqwest::Response {
+        let mut request = self.api_client.get(format!("{}/login", self.address));
+        if let Some(next) = next {
+            request = request.query(&[("next", next)]);
+        }
+        request.send().await.expect("failed to get login")
+    }
+
+    pub async fn get_login_html(&self) -> String {
+        self.get_login(None).await.text().await.unwrap()
+    }
+
     pub async fn get_admin_dashboard(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/dashboard", self.address))
+            .get(format!("{}/admin/dashboard", self.address))
             .send()
             .await
             .expect("failed to get /admin/dashboard")
@@ -117,9 +380,47 @@ impl TestApp {
             .expect("failed to get text from admin dashboad response")
     }
 
+    pub async fn get_verify_email_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/verify_email", self.address))
+            .send()
+            .await
+            .expect("failed to get /admin/verify_email")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_verify_email(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/verify_email", self.address))
+            .send()
+            .await
+            .expect("failed to post /admin/verify_email")
+    }
+
+    pub async fn post_verify_email_address(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/verify_email/address", self.address))
+            .form(&serde_json::json!({ "email": email }))
+            .send()
+            .await
+            .expect("failed to post /admin/verify_email/address")
+    }
+
+    /// Log lines about requests to this app, at info level or above.
+    pub fn server_logs(&self) -> Vec<serde_json::Value> {
+        let host = self.address.trim_start_matches("http://");
+        SERVER_LOGS
+            .lines()
+            .into_iter()
+            .filter(|l| l["http.host"] == host)
+            .collect()
+    }
+
     pub async fn get_change_password(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/password", &self.address))
+            .get(format!("{}/admin/password", &self.address))
             .send()
             .await
             .expect("Failed to get change password")
@@ -134,38 +435,182 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/password", &self.address))
+            .post(format!("{}/admin/password", &self.address))
             .form(body)
             .send()
             .await
             .expect("failed to post change password")
     }
 
+    /// Read the logout CSRF token off the admin dashboard.
+    pub async fn get_csrf_token(&self) -> String {
+        let html_page = self.get_admin_dashboard_html().await;
+        let marker = r#"name="csrf_token" value=""#;
+        let start = html_page.find(marker).expect("no csrf token in dashboard") + marker.len();
+        let end = start + html_page[start..].find('"').unwrap();
+        html_page[start..end].to_string()
+    }
+
     pub async fn post_logout(&self) -> reqwest::Response {
+        let csrf_token = self.get_csrf_token().await;
+        self.post_logout_with(&serde_json::json!({ "csrf_token": csrf_token }))
+            .await
+    }
+
+    pub async fn post_logout_with<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
         self.api_client
-            .post(&format!("{}/admin/logout", &self.address))
+            .post(format!("{}/admin/logout", &self.address))
+            .form(body)
             .send()
             .await
             .expect("failed to do admin logout")
     }
 
+    pub async fn get_idempotency_key_usage(&self, key: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/newsletters/idempotency/{key}",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("failed to query the idempotency key")
+    }
+
+    pub async fn get_audit_export(&self, since: Option<&str>) -> reqwest::Response {
+        let mut request = self
+            .api_client
+            .get(format!("{}/admin/audit/export.ndjson", &self.address));
+        if let Some(since) = since {
+            request = request.query(&[("since", since)]);
+        }
+        request
+            .send()
+            .await
+            .expect("failed to export the audit log")
+    }
+
+    pub async fn post_pause_worker(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/worker/pause", &self.address))
+            .send()
+            .await
+            .expect("failed to pause the delivery worker")
+    }
+
+    pub async fn post_resume_worker(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/worker/resume", &self.address))
+            .send()
+            .await
+            .expect("failed to resume the delivery worker")
+    }
+
     pub async fn dispatch_all_pending_emails(&self) {
         loop {
-            if let ExecutionOutcome::EmptyQueue =
-                try_execute_task(&self.db_pool, &self.email_client)
-                    .await
-                    .unwrap()
+            if let ExecutionOutcome::EmptyQueue
+            | ExecutionOutcome::Paused
+            | ExecutionOutcome::Throttled(_) = try_execute_task(
+                &self.db_pool,
+                &self.email_client,
+                &self.worker_settings,
+                &self.links,
+            )
+            .await
+            .unwrap()
             {
                 break;
             }
         }
     }
+
+    /// Subscribe and confirm `email`, then publish an issue with open
+    /// tracking and deliver it, with the provider answering every send with
+    /// `response`. Returns the id of the issue.
+    pub async fn deliver_an_issue(&self, email: &str, response: ResponseTemplate) -> Uuid {
+        let body = format!("name=le%20guin&email={}", urlencoding::encode(email));
+        let _mock_guard = Mock::given(path("/email"))
+            .and(method("POST"))
+            .respond_with(response)
+            .mount_as_scoped(&self.email_server)
+            .await;
+        self.post_subscriptions(body)
+            .await
+            .error_for_status()
+            .unwrap();
+        let confirmation = self.email_server.received_requests().await.unwrap();
+        let link = self.get_confirmation_link(&confirmation[0]).await;
+        reqwest::get(link)
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+
+        self.test_user.login(self).await;
+        self.post_publish_newsletters(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "track_opens": "on",
+        }))
+        .await;
+        self.dispatch_all_pending_emails().await;
+        sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+            .fetch_one(&self.db_pool)
+            .await
+            .unwrap()
+            .newsletter_issue_id
+    }
+}
+
+/// An email as sent to the provider's API, with the links in its bodies.
+pub struct SentEmail {
+    pub to: String,
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+    pub html_links: Vec<String>,
+    pub text_links: Vec<String>,
+    /// The whole request body, for the fields not picked above.
+    pub json: serde_json::Value,
+}
+
+impl SentEmail {
+    pub fn from_request(req: &wiremock::Request) -> Self {
+        let json: serde_json::Value =
+            serde_json::from_slice(&req.body).expect("The email request is not JSON");
+        let field = |name: &str| json[name].as_str().unwrap_or_default().to_string();
+        let html_body = field("HtmlBody");
+        let text_body = field("TextBody");
+        Self {
+            to: field("To"),
+            subject: field("Subject"),
+            html_links: find_links(&html_body),
+            text_links: find_links(&text_body),
+            html_body,
+            text_body,
+            json,
+        }
+    }
+}
+
+fn find_links(s: &str) -> Vec<String> {
+    linkify::LinkFinder::new()
+        .links(s)
+        .filter(|l| *l.kind() == linkify::LinkKind::Url)
+        .map(|l| l.as_str().to_owned())
+        .collect()
 }
 
 pub struct TestUser {
     pub user_id: uuid::Uuid,
     pub username: String,
     pub password: String,
+    pub email: String,
 }
 
 impl TestUser {
@@ -174,6 +619,7 @@ impl TestUser {
             user_id: Uuid::new_v4(),
             username: Uuid::new_v4().to_string(),
             password: Uuid::new_v4().to_string(),
+            email: format!("{}@example.com", Uuid::new_v4()),
         }
     }
 
@@ -197,10 +643,11 @@ impl TestUser {
         .unwrap()
         .to_string();
         sqlx::query!(
-            "insert into users (user_id, username, password_hash) values ($1, $2, $3)",
+            "insert into users (user_id, username, password_hash, email) values ($1, $2, $3, $4)",
             self.user_id,
             self.username,
-            password_hash
+            password_hash,
+            self.email
         )
         .execute(pool)
         .await
@@ -208,6 +655,11 @@ impl TestUser {
     }
 }
 
+/// What the spawned apps log, from the server's own threads, which
+/// `CapturedLogs::start` can't reach. Shared by all tests: see
+/// `TestApp::server_logs`. Empty with `TEST_LOG`, where logs go to stdout.
+static SERVER_LOGS: Lazy<CapturedLogs> = Lazy::new(CapturedLogs::default);
+
 static TRACING: Lazy<()> = Lazy::new(|| {
     let default_filter_level = "info".to_string();
     let subscriber_name = "test".to_string();
@@ -215,12 +667,17 @@ static TRACING: Lazy<()> = Lazy::new(|| {
         let subscriber = get_subscriber(subscriber_name, default_filter_level, std::io::stdout);
         init_subscriber(subscriber);
     } else {
-        let subscriber = get_subscriber(subscriber_name, default_filter_level, std::io::sink);
+        let subscriber = get_subscriber(subscriber_name, default_filter_level, SERVER_LOGS.clone());
         init_subscriber(subscriber);
     }
 });
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+/// Like `spawn_app`, but lets the test tweak the configuration first.
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
     Lazy::force(&TRACING);
 
     let email_server: MockServer = MockServer::start().await;
@@ -238,28 +695,52 @@ pub async fn spawn_app() -> TestApp {
         c.application.port = 0;
         // Use the mock email server
         c.email_client.api_url = email_server.uri();
+        configure(&mut c);
         c
     };
     // Create and migrate the database
     configure_database(&configuration.database).await;
     // Launch the application as a background task
-    let server = Application::build(configuration.clone())
+    // Postgres keeps microseconds: a finer start would make timestamps read
+    // back from the database drift from the clock.
+    let clock = Arc::new(MockClock::new(Utc::now().trunc_subsecs(6)));
+    let server = Application::build_with_clock(configuration.clone(), clock.clone())
         .await
         .expect("Failed to build application.");
     let port = server.port();
     let address = format!("http://127.0.0.1:{}", &port);
-    let _ = tokio::spawn(server.run_until_stopped());
+    let worker_heartbeat = server.worker_heartbeat();
+    let server_handle = server.server_handle();
+    let in_flight_requests = server.in_flight_requests();
+    tokio::spawn(server.run_until_stopped());
     let test_user = TestUser::generate();
-    let pool = get_connection_pool(&configuration.database);
+    let pool = get_connection_pool(&configuration.database, &configuration.timeouts);
     test_user.store(&pool).await;
     TestApp {
-        address: address,
-        port: port,
+        configuration: configuration.clone(),
+        address,
+        port,
         db_pool: pool,
-        email_server: email_server,
-        test_user: test_user,
+        email_server,
+        test_user,
         api_client: client,
-        email_client: configuration.email_client.client().unwrap(),
+        email_client: configuration
+            .email_client
+            .client(configuration.timeouts.email_client())
+            .unwrap(),
+        webhook_settings: configuration.webhooks,
+        worker_settings: WorkerSettings {
+            clock: clock.clone(),
+            ..configuration.worker
+        },
+        links: SignedLinks::new(
+            configuration.application.base_url,
+            configuration.application.hmac_secret,
+        ),
+        worker_heartbeat,
+        server_handle,
+        in_flight_requests,
+        clock,
     }
 }
 
@@ -283,6 +764,55 @@ async fn configure_database(config: &DatabaseSettings) -> PgPool {
     connection_pool
 }
 
+/// Collects the JSON log lines emitted on the current thread while the guard
+/// returned by `CapturedLogs::start` is alive.
+#[derive(Clone, Default)]
+pub struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+impl CapturedLogs {
+    pub fn start(env_filter: &str) -> (Self, DefaultGuard) {
+        let logs = Self::default();
+        let subscriber = get_subscriber("test".into(), env_filter.into(), logs.clone());
+        let guard = tracing::subscriber::set_default(subscriber);
+        (logs, guard)
+    }
+
+    pub fn lines(&self) -> Vec<serde_json::Value> {
+        let buffer = self.0.lock().unwrap();
+        String::from_utf8_lossy(&buffer)
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("Log line is not valid JSON"))
+            .collect()
+    }
+
+    /// Log lines whose message contains `msg`.
+    pub fn with_message(&self, msg: &str) -> Vec<serde_json::Value> {
+        self.lines()
+            .into_iter()
+            .filter(|l| l["msg"].as_str().is_some_and(|m| m.contains(msg)))
+            .collect()
+    }
+}
+
+impl std::io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for CapturedLogs {
+    type Writer = Self;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
 pub fn assert_is_redirect_to(resp: &reqwest::Response, location: &str) {
     assert_eq!(resp.status().as_u16(), 303);
     assert_eq!(
@@ -0,0 +1,117 @@
+use actix_web::{body::to_bytes, http::header::ContentType, HttpResponse};
+use zero2prod::idempotency::{get_saved_response, save_response, try_processing, NextAction};
+
+use crate::helper::{assert_is_redirect_to, spawn_app, spawn_app_with};
+
+#[tokio::test]
+async fn saved_responses_are_replayed_faithfully() {
+    let app = spawn_app().await;
+    let key = uuid::Uuid::new_v4().to_string().try_into().unwrap();
+    let user_id = app.test_user.user_id;
+
+    let NextAction::StartingProcessing(transaction) =
+        try_processing(&app.db_pool, &key, user_id).await.unwrap()
+    else {
+        panic!("Expected to start processing a fresh idempotency key");
+    };
+    let original = HttpResponse::Accepted()
+        .content_type(ContentType::html())
+        .append_header(("X-Repeated", "first"))
+        .append_header(("X-Repeated", "second"))
+        .body("<p>Accepted: ünïcödé</p>");
+    let original = save_response(transaction, &key, user_id, original)
+        .await
+        .unwrap();
+    let replayed = get_saved_response(&app.db_pool, &key, user_id)
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(original.status(), replayed.status());
+    let headers = |r: &HttpResponse| {
+        let mut headers: Vec<(String, Vec<u8>)> = r
+            .headers()
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.as_bytes().to_vec()))
+            .collect();
+        headers.sort();
+        headers
+    };
+    assert_eq!(headers(&original), headers(&replayed));
+    assert_eq!(
+        replayed.headers().get("content-type").unwrap(),
+        "text/html; charset=utf-8"
+    );
+    assert_eq!(
+        to_bytes(original.into_body()).await.unwrap(),
+        to_bytes(replayed.into_body()).await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_query_an_idempotency_key() {
+    let app = spawn_app().await;
+
+    let response = app.get_idempotency_key_usage("a-key").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn idempotency_keys_report_whether_they_were_used() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let used_key = uuid::Uuid::new_v4().to_string();
+    app.post_publish_newsletters(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": used_key,
+    }))
+    .await;
+
+    let used: serde_json::Value = app
+        .get_idempotency_key_usage(&used_key)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(used, serde_json::json!({ "used": true, "status": 303 }));
+
+    let unused: serde_json::Value = app
+        .get_idempotency_key_usage(&uuid::Uuid::new_v4().to_string())
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(unused, serde_json::json!({ "used": false, "status": null }));
+}
+
+#[tokio::test]
+async fn per_content_idempotency_keys_can_still_be_queried() {
+    let app = spawn_app_with(|c| c.publishing.idempotency_per_content = true).await;
+    app.test_user.login(&app).await;
+    let used_key = uuid::Uuid::new_v4().to_string();
+    app.post_publish_newsletters(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": used_key,
+    }))
+    .await;
+
+    let used: serde_json::Value = app
+        .get_idempotency_key_usage(&used_key)
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(used, serde_json::json!({ "used": true, "status": 303 }));
+    let stored = sqlx::query!("SELECT idempotency_key, content_hash FROM idempotency")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(stored.idempotency_key, used_key);
+    assert!(!stored.content_hash.is_empty());
+}
@@ -1,4 +1,6 @@
-use crate::helper::{assert_is_redirect_to, spawn_app};
+use std::time::Duration;
+
+use crate::helper::{assert_is_redirect_to, spawn_app, spawn_app_with};
 
 #[tokio::test]
 async fn an_error_flash_message_is_set_on_failure() {
@@ -33,3 +35,269 @@ async fn redirect_to_admin_dashboard_after_login_success() {
     let html_page = app.get_admin_dashboard_html().await;
     assert!(html_page.contains(&format!("Welcome {}", &app.test_user.username)));
 }
+
+#[tokio::test]
+async fn login_attempts_beyond_the_rate_limit_get_429_with_retry_after() {
+    let app = spawn_app_with(|c| {
+        c.rate_limit.capacity = 1;
+        c.rate_limit.refill_interval_milliseconds = 3000;
+    })
+    .await;
+    let login_body = serde_json::json!({
+        "username": "invalid-username",
+        "password": "invalid-password"
+    });
+
+    let resp = app.post_login(&login_body).await;
+    assert_is_redirect_to(&resp, "/login");
+
+    let resp = app.post_login(&login_body).await;
+    assert_eq!(resp.status().as_u16(), 429);
+    assert_eq!(resp.headers()["Retry-After"], "3");
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["retry_after_seconds"], 3);
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let resp = app.post_login(&login_body).await;
+    assert_eq!(resp.status().as_u16(), 429);
+    assert_eq!(resp.headers()["Retry-After"], "2");
+}
+
+#[tokio::test]
+async fn only_clients_from_exempt_networks_bypass_the_login_rate_limit() {
+    for (exempt_network, exempt) in [("127.0.0.0/8", true), ("10.0.0.0/8", false)] {
+        let app = spawn_app_with(|c| {
+            c.rate_limit.capacity = 1;
+            c.rate_limit.exempt_networks = vec![exempt_network.into()];
+        })
+        .await;
+        let login_body = serde_json::json!({
+            "username": "invalid-username",
+            "password": "invalid-password"
+        });
+
+        let mut n_limited = 0;
+        for _ in 0..10 {
+            let resp = app.post_login(&login_body).await;
+            if resp.status().as_u16() == 429 {
+                n_limited += 1;
+            } else {
+                assert_is_redirect_to(&resp, "/login");
+            }
+        }
+
+        assert_eq!(n_limited, if exempt { 0 } else { 9 }, "{exempt_network}");
+    }
+}
+
+#[tokio::test]
+async fn logged_in_users_are_sent_on_from_the_login_form() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let resp = app.get_login(None).await;
+    assert_is_redirect_to(&resp, "/admin/dashboard");
+
+    let resp = app.get_login(Some("/admin/newsletters")).await;
+    assert_is_redirect_to(&resp, "/admin/newsletters");
+}
+
+#[tokio::test]
+async fn the_login_form_can_be_shown_to_logged_in_users() {
+    let app = spawn_app_with(|c| c.features.skip_login_form_when_logged_in = false).await;
+    app.test_user.login(&app).await;
+
+    let resp = app.get_login(None).await;
+
+    assert_eq!(resp.status().as_u16(), 200);
+    assert!(resp
+        .text()
+        .await
+        .unwrap()
+        .contains(r#"<form action="/login""#));
+}
+
+#[tokio::test]
+async fn login_redirects_to_a_local_next_path() {
+    let app = spawn_app().await;
+
+    let resp = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+            "next": "/admin/newsletters",
+        }))
+        .await;
+
+    assert_is_redirect_to(&resp, "/admin/newsletters");
+}
+
+#[tokio::test]
+async fn login_redirects_to_an_allowlisted_external_next_url() {
+    let app = spawn_app_with(|c| {
+        c.application.login_redirect_allowlist = vec!["https://admin.example.com/".into()];
+    })
+    .await;
+
+    let resp = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+            "next": "https://admin.example.com/issues",
+        }))
+        .await;
+
+    assert_is_redirect_to(&resp, "https://admin.example.com/issues");
+}
+
+#[tokio::test]
+async fn login_ignores_a_next_url_outside_the_allowlist() {
+    let app = spawn_app_with(|c| {
+        c.application.login_redirect_allowlist = vec!["https://admin.example.com/".into()];
+    })
+    .await;
+
+    for next in [
+        "https://evil.example.com/",
+        "//evil.example.com/",
+        "https://admin.example.com.evil.example.com/",
+    ] {
+        let resp = app
+            .post_login(&serde_json::json!({
+                "username": &app.test_user.username,
+                "password": &app.test_user.password,
+                "next": next,
+            }))
+            .await;
+
+        assert_is_redirect_to(&resp, "/admin/dashboard");
+    }
+}
+
+#[tokio::test]
+async fn the_login_form_carries_next_across_failed_attempts() {
+    let app = spawn_app().await;
+
+    let resp = app
+        .post_login(&serde_json::json!({
+            "username": "invalid-username",
+            "password": "invalid-password",
+            "next": "/admin/newsletters",
+        }))
+        .await;
+    assert_is_redirect_to(&resp, "/login?next=%2Fadmin%2Fnewsletters");
+
+    let html_page = app
+        .api_client
+        .get(format!("{}/login?next=%2Fadmin%2Fnewsletters", app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(&format!(
+        r#"name="next" value="{}""#,
+        htmlescape::encode_attribute("/admin/newsletters")
+    )));
+}
+
+#[tokio::test]
+async fn logging_in_beyond_the_session_cap_ends_the_oldest_session() {
+    let app = spawn_app_with(|c| c.application.max_sessions_per_user = 1).await;
+    app.test_user.login(&app).await;
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Log in again from another browser.
+    let other_client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    let response = other_client
+        .post(format!("{}/login", app.address))
+        .form(&serde_json::json!({
+            "username": app.test_user.username,
+            "password": app.test_user.password,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+    let response = other_client
+        .get(format!("{}/admin/dashboard", app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+fn session_cookie(response: &reqwest::Response) -> String {
+    response
+        .cookies()
+        .find(|c| c.name() == "id")
+        .expect("No session cookie was set")
+        .value()
+        .to_string()
+}
+
+#[tokio::test]
+async fn logging_in_rotates_the_session_key() {
+    let app = spawn_app().await;
+    let login_body = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    });
+    let first_key = session_cookie(&app.post_login(&login_body).await);
+
+    // Logging in again on the same session, as a victim would on a key
+    // planted by an attacker.
+    let second_key = session_cookie(&app.post_login(&login_body).await);
+
+    assert_ne!(first_key, second_key);
+    let response = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .get(format!("{}/admin/dashboard", &app.address))
+        .header("Cookie", format!("id={first_key}"))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn sessions_past_their_max_age_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+
+    // The default max age is a day, however active the session was.
+    sqlx::query!("UPDATE user_sessions SET created_at = now() - interval '25 hours'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+}
+
+#[tokio::test]
+async fn sessions_end_right_after_their_max_age_and_the_skew_tolerance() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // A day of max age and 30 seconds of skew tolerance
+    app.clock
+        .advance(chrono::Duration::hours(24) + chrono::Duration::seconds(30));
+    let on_the_boundary = app.get_admin_dashboard().await;
+    app.clock.advance(chrono::Duration::seconds(1));
+    let expired = app.get_admin_dashboard().await;
+
+    assert_eq!(on_the_boundary.status().as_u16(), 200);
+    assert_is_redirect_to(&expired, "/login");
+}
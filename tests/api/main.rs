@@ -1,8 +1,19 @@
+mod audit;
 mod change_password;
+mod confirmation_reminders;
 mod dashboard;
+mod deliveries;
 mod health_check;
 mod helper;
+mod idempotency;
 mod login;
 mod newsletter;
+mod settings;
+mod startup;
 mod subscription;
 mod subscription_confirm;
+mod suppressions;
+mod token_cleanup;
+mod transaction;
+mod verify_email;
+mod webhooks;
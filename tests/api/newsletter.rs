@@ -1,11 +1,22 @@
 use std::time::Duration;
 
-use crate::helper::{assert_is_redirect_to, spawn_app, TestApp};
+use chrono::DateTime;
+
+use crate::helper::{assert_is_redirect_to, spawn_app, spawn_app_with, CapturedLogs, TestApp};
 use fake::faker::internet::en::SafeEmail;
 use fake::faker::name::en::Name;
 use fake::Fake;
+use secrecy::Secret;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 use wiremock::matchers::{any, method, path};
-use wiremock::{Mock, MockBuilder, ResponseTemplate};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::configuration::{SenderVerification, WorkerSettings};
+use zero2prod::email_layout::EmailLayout;
+use zero2prod::issue_delivery_worker::{
+    drain_batch, run_worker_iteration, try_execute_task, ExecutionOutcome, HeartbeatState,
+};
+use zero2prod::routes::report_progress;
+use zero2prod::telemetry::Counters;
 
 #[tokio::test]
 async fn you_must_be_logged_in_to_publish_newsletter() {
@@ -53,10 +64,41 @@ async fn newsletters_are_not_delivered_to_unconfirmed_subscribers() {
     assert_is_redirect_to(&response, "/admin/newsletters");
 
     let html_page = app.get_publish_newsletters_html().await;
-    assert!(html_page.contains(
-        "<p><i>The newsletter issue has been accepted - emails will go out shortly.</i></p>"
-    ));
+    assert!(html_page.contains("<p><i>No confirmed subscribers - nothing was sent.</i></p>"));
+    assert!(!html_page.contains("emails will go out shortly"));
     app.dispatch_all_pending_emails().await;
+    // The issue is still recorded by default.
+    let n_issues = sqlx::query!(r#"SELECT count(*) as "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 1);
+}
+
+#[tokio::test]
+async fn issues_without_recipients_can_be_discarded() {
+    let app = spawn_app_with(|c| c.publishing.record_issues_without_recipients = false).await;
+    create_unconfirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletters(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let html_page = app.get_publish_newsletters_html().await;
+    assert!(html_page.contains("<p><i>No confirmed subscribers - nothing was sent.</i></p>"));
+    let n_issues = sqlx::query!(r#"SELECT count(*) as "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 0);
 }
 
 #[tokio::test]
@@ -86,6 +128,277 @@ async fn newsletters_are_delivered_to_confirmed_subscribers() {
     app.dispatch_all_pending_emails().await;
 }
 
+#[tokio::test]
+async fn you_must_be_logged_in_to_pause_the_delivery_worker() {
+    let app = spawn_app().await;
+
+    let response = app.post_pause_worker().await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn nothing_is_sent_while_the_delivery_worker_is_paused() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let response = app.post_pause_worker().await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    assert!(app
+        .get_admin_dashboard_html()
+        .await
+        .contains("Deliveries are paused."));
+
+    let paused_guard = Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .named("No delivery while paused")
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    app.post_publish_newsletters(&newsletter_request_body).await;
+    let outcome = try_execute_task(
+        &app.db_pool,
+        &app.email_client,
+        &app.worker_settings,
+        &app.links,
+    )
+    .await
+    .unwrap();
+    assert!(matches!(outcome, ExecutionOutcome::Paused));
+    drop(paused_guard);
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = app.post_resume_worker().await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    app.dispatch_all_pending_emails().await;
+    let n_queued = sqlx::query!(r#"SELECT count(*) as "count!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_queued, 0);
+}
+
+#[tokio::test]
+async fn the_worker_backs_off_and_reports_degraded_while_the_database_is_unreachable() {
+    let app = spawn_app().await;
+    let unreachable_pool = PgPoolOptions::new()
+        .acquire_timeout(Duration::from_millis(200))
+        .connect_lazy_with(PgConnectOptions::new().host("127.0.0.1").port(1));
+    let settings = WorkerSettings {
+        db_backoff_base_milliseconds: 100,
+        db_backoff_max_seconds: 1,
+        ..app.worker_settings.clone()
+    };
+    let (logs, _guard) = CapturedLogs::start("info");
+
+    let mut db_failures = 0;
+    let mut backoffs = Vec::new();
+    for _ in 0..5 {
+        backoffs.push(
+            run_worker_iteration(
+                &unreachable_pool,
+                &app.email_client,
+                &settings,
+                &app.links,
+                &app.worker_heartbeat,
+                &mut db_failures,
+            )
+            .await,
+        );
+    }
+
+    assert_eq!(
+        backoffs,
+        [100, 200, 400, 800, 1000].map(Duration::from_millis)
+    );
+    assert_eq!(
+        app.worker_heartbeat.get(),
+        HeartbeatState::Degraded {
+            consecutive_failures: 5
+        }
+    );
+    let alerts =
+        logs.with_message("The delivery worker has been unable to reach the database for a while");
+    assert_eq!(alerts.len(), 1);
+    let status: serde_json::Value = reqwest::get(format!("{}/status", &app.address))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["worker"], "degraded");
+
+    let pause = run_worker_iteration(
+        &app.db_pool,
+        &app.email_client,
+        &settings,
+        &app.links,
+        &app.worker_heartbeat,
+        &mut db_failures,
+    )
+    .await;
+    assert_eq!(pause, Duration::from_secs(10));
+    assert_eq!(db_failures, 0);
+    assert_eq!(app.worker_heartbeat.get(), HeartbeatState::Healthy);
+}
+
+#[tokio::test]
+async fn issues_are_queued_for_exactly_the_confirmed_subscribers() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        SELECT
+            gen_random_uuid(),
+            'subscriber' || i || '@example.com',
+            'Subscriber ' || i,
+            now(),
+            (ARRAY['confirmed', 'pending', 'unsubscribed'])[i % 3 + 1]
+        FROM generate_series(1, 900) AS i
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    let resp = app.post_publish_newsletters(&body).await;
+    assert_is_redirect_to(&resp, "/admin/newsletters");
+
+    let queued: Vec<String> =
+        sqlx::query!("SELECT subscriber_email FROM issue_delivery_queue ORDER BY subscriber_email")
+            .fetch_all(&app.db_pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| r.subscriber_email)
+            .collect();
+    let mut expected: Vec<String> = (1..=900)
+        .filter(|i| i % 3 == 0)
+        .map(|i| format!("subscriber{i}@example.com"))
+        .collect();
+    expected.sort();
+    assert_eq!(queued, expected);
+
+    // The planner answers the query of `enqueue_delivery_tasks` from the
+    // index, even with the generic plan of a cached prepared statement.
+    let mut transaction = app.db_pool.begin().await.unwrap();
+    for statement in [
+        "SET LOCAL enable_seqscan = off",
+        "SET LOCAL plan_cache_mode = force_generic_plan",
+        r#"PREPARE enqueue_delivery_tasks (uuid, text) AS
+        INSERT INTO issue_delivery_queue (
+            newsletter_issue_id,
+            subscriber_email
+        )
+        SELECT $1, email FROM subscriptions
+        WHERE status = $2 AND NOT EXISTS (
+            SELECT 1 FROM suppressions
+            WHERE lower(suppressions.email) = lower(subscriptions.email)
+        )"#,
+    ] {
+        sqlx::query(statement)
+            .execute(&mut *transaction)
+            .await
+            .unwrap();
+    }
+    let plan: Vec<String> = sqlx::query_scalar(
+        "EXPLAIN EXECUTE enqueue_delivery_tasks (gen_random_uuid(), 'confirmed')",
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .unwrap();
+    assert!(
+        plan.iter()
+            .any(|line| line.contains("subscriptions_status_email_idx")),
+        "{plan:?}"
+    );
+}
+
+#[tokio::test]
+async fn archived_issues_support_conditional_requests() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    let resp = app.post_publish_newsletters(&body).await;
+    assert_is_redirect_to(&resp, "/admin/newsletters");
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    let resp = app.get_archived_issue(issue_id, &[]).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let header =
+        |resp: &reqwest::Response, name: &str| resp.headers()[name].to_str().unwrap().to_owned();
+    let etag = header(&resp, "ETag");
+    let last_modified = header(&resp, "Last-Modified");
+    assert!(resp
+        .text()
+        .await
+        .unwrap()
+        .contains("<p>Newsletter body as HTML</p>"));
+
+    let resp = app
+        .get_archived_issue(issue_id, &[("If-None-Match", &etag)])
+        .await;
+    assert_eq!(resp.status().as_u16(), 304);
+    assert_eq!(header(&resp, "ETag"), etag);
+    assert!(resp.bytes().await.unwrap().is_empty());
+
+    let resp = app
+        .get_archived_issue(issue_id, &[("If-Modified-Since", &last_modified)])
+        .await;
+    assert_eq!(resp.status().as_u16(), 304);
+
+    // Editing the issue invalidates copies fetched before.
+    let fixed_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Fixed newsletter body",
+        "html_content": "<p>Fixed newsletter body</p>",
+    });
+    let resp = app.put_newsletter_issue(issue_id, &fixed_body).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let resp = app
+        .get_archived_issue(issue_id, &[("If-None-Match", &etag)])
+        .await;
+    assert_eq!(resp.status().as_u16(), 200);
+    assert_ne!(header(&resp, "ETag"), etag);
+}
+
+#[tokio::test]
+async fn unknown_issues_are_not_in_the_archive() {
+    let app = spawn_app().await;
+
+    let resp = app.get_archived_issue(uuid::Uuid::new_v4(), &[]).await;
+
+    assert_eq!(resp.status().as_u16(), 404);
+}
+
 #[tokio::test]
 async fn newsletteres_returns_400_for_invalid_data() {
     let app = spawn_app().await;
@@ -108,6 +421,151 @@ async fn newsletteres_returns_400_for_invalid_data() {
     }
 }
 
+#[tokio::test]
+async fn missing_fields_re_render_the_form_naming_the_field() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let body = serde_json::json!({
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    let resp = app.post_publish_newsletters(&body).await;
+
+    assert_eq!(resp.status().as_u16(), 400);
+    let html_page = resp.text().await.unwrap();
+    assert!(html_page.contains("<p><i>The title is required.</i></p>"));
+    assert!(html_page.contains(r#"<form action="/admin/newsletters" method="post">"#));
+}
+
+#[tokio::test]
+async fn newsletters_with_an_invalid_title_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    for (title, error_msg) in [
+        ("", "The title cannot be empty."),
+        (
+            "Newsletter\u{7}title",
+            "The title cannot contain control characters.",
+        ),
+    ] {
+        let body = serde_json::json!({
+            "title": title,
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        });
+        let resp = app.post_publish_newsletters(&body).await;
+        assert_is_redirect_to(&resp, "/admin/newsletters");
+
+        let html_page = app.get_publish_newsletters_html().await;
+        assert!(
+            html_page.contains(error_msg),
+            "missing message: {error_msg}"
+        );
+    }
+
+    let issues = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(issues.is_empty());
+}
+
+#[tokio::test]
+async fn newsletters_without_any_content_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "  ",
+        "html_content": "",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    let resp = app.post_publish_newsletters(&body).await;
+    assert_is_redirect_to(&resp, "/admin/newsletters");
+
+    let html_page = app.get_publish_newsletters_html().await;
+    assert!(html_page.contains("The issue needs either HTML or plain text content."));
+    let issues = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(issues.is_empty());
+}
+
+#[tokio::test]
+async fn html_only_newsletters_are_accepted() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    let resp = app.post_publish_newsletters(&body).await;
+    assert_is_redirect_to(&resp, "/admin/newsletters");
+
+    let html_page = app.get_publish_newsletters_html().await;
+    assert!(html_page.contains("The newsletter issue has been accepted"));
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn html_only_newsletters_get_a_text_body_derived_from_the_html() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": " ",
+        "html_content": "<h1>Hello</h1><p>Fish &amp; <b>chips</b></p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    let resp = app.post_publish_newsletters(&body).await;
+    assert_is_redirect_to(&resp, "/admin/newsletters");
+
+    let text_content = sqlx::query!("SELECT text_content FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .text_content;
+    assert_eq!(text_content, "Hello\n\nFish & chips");
+}
+
+#[tokio::test]
+async fn deriving_text_bodies_can_be_turned_off() {
+    let app = spawn_app_with(|c| c.features.derive_text_from_html = false).await;
+    app.test_user.login(&app).await;
+
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    let resp = app.post_publish_newsletters(&body).await;
+    assert_is_redirect_to(&resp, "/admin/newsletters");
+
+    let text_content = sqlx::query!("SELECT text_content FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .text_content;
+    assert_eq!(text_content, "");
+}
+
 #[tokio::test]
 async fn newsletter_creation_is_idempotent() {
     let app = spawn_app().await;
@@ -148,63 +606,1656 @@ async fn newsletter_creation_is_idempotent() {
 }
 
 #[tokio::test]
-async fn concurrent_form_submission_is_handled_gracefully() {
+async fn resubmitting_the_rendered_publish_form_publishes_once() {
     let app = spawn_app().await;
     create_confirmed_subscriber(&app).await;
     app.test_user.login(&app).await;
-
     Mock::given(path("/email"))
         .and(method("POST"))
-        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(2)))
+        .respond_with(ResponseTemplate::new(200))
         .expect(1)
         .mount(&app.email_server)
         .await;
 
+    let idempotency_key = hidden_idempotency_key(&app.get_publish_newsletters_html().await);
+    assert_ne!(
+        idempotency_key,
+        hidden_idempotency_key(&app.get_publish_newsletters_html().await),
+        "Every render should carry a fresh key"
+    );
+
     let body = serde_json::json!({
         "title": "Newsletter title",
         "text_content": "Newsletter body as plain text",
         "html_content": "<p>Newsletter body as HTML</p>",
-        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "idempotency_key": idempotency_key,
     });
-    let r1 = app.post_publish_newsletters(&body);
-    let r2 = app.post_publish_newsletters(&body);
-    let (r1, r2) = tokio::join!(r1, r2);
-
-    assert_eq!(r1.status(), r2.status());
-    assert_eq!(r1.text().await.unwrap(), r2.text().await.unwrap());
+    // Refreshing the page after submitting posts the same form again.
+    for _ in 0..2 {
+        let resp = app.post_publish_newsletters(&body).await;
+        assert_is_redirect_to(&resp, "/admin/newsletters");
+    }
 
+    let n_issues = sqlx::query_scalar!(r#"SELECT count(*) as "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_issues, 1);
     app.dispatch_all_pending_emails().await;
 }
 
-async fn create_unconfirmed_subscriber(app: &TestApp) -> reqwest::Url {
-    let name: String = Name().fake();
-    let email: String = SafeEmail().fake();
-    let body = serde_urlencoded::to_string(serde_json::json!({
-        "name": name,
-        "email": email,
-    }))
-    .unwrap();
-
-    let _mock_guard = Mock::given(path("/email"))
+#[tokio::test]
+async fn replayed_publish_responses_match_the_original() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
         .and(method("POST"))
         .respond_with(ResponseTemplate::new(200))
-        .named("Create unconfirmed subscriber")
         .expect(1)
-        .mount_as_scoped(&app.email_server)
+        .mount(&app.email_server)
         .await;
-    app.post_subscriptions(body.into())
-        .await
-        .error_for_status()
-        .unwrap();
 
-    let email_request = &app
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    let first = app.post_publish_newsletters(&body).await;
+    let replayed = app.post_publish_newsletters(&body).await;
+
+    assert_eq!(first.status(), replayed.status());
+    // `date` changes between requests and the flash cookie is added by
+    // middleware after the handler, so neither is part of the saved response.
+    let saved_headers = |r: &reqwest::Response| {
+        let mut headers: Vec<(String, Vec<u8>)> = r
+            .headers()
+            .iter()
+            .filter(|(name, _)| !matches!(name.as_str(), "date" | "set-cookie"))
+            .map(|(name, value)| (name.to_string(), value.as_bytes().to_vec()))
+            .collect();
+        headers.sort();
+        headers
+    };
+    assert_eq!(saved_headers(&first), saved_headers(&replayed));
+    assert_eq!(
+        first.headers().get("content-type"),
+        replayed.headers().get("content-type")
+    );
+    assert_eq!(
+        first.bytes().await.unwrap(),
+        replayed.bytes().await.unwrap()
+    );
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn concurrent_form_submission_is_handled_gracefully() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(2)))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    let r1 = app.post_publish_newsletters(&body);
+    let r2 = app.post_publish_newsletters(&body);
+    let (r1, r2) = tokio::join!(r1, r2);
+
+    assert_eq!(r1.status(), r2.status());
+    assert_eq!(r1.text().await.unwrap(), r2.text().await.unwrap());
+
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn repeatedly_failing_delivery_raises_a_single_alert_and_is_dead_lettered() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    let resp = app.post_publish_newsletters(&body).await;
+    assert_is_redirect_to(&resp, "/admin/newsletters");
+
+    let settings = WorkerSettings {
+        max_retries: 3,
+        retry_base_delay_milliseconds: 0,
+        ..app.worker_settings.clone()
+    };
+    let poison_pills_before = Counters::global().get("poison_pill_deliveries");
+    let (logs, _guard) = CapturedLogs::start("info");
+    while let ExecutionOutcome::TaskCompleted =
+        try_execute_task(&app.db_pool, &app.email_client, &settings, &app.links)
+            .await
+            .unwrap()
+    {}
+
+    let alerts = logs.with_message("Delivery task failed too many times");
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0]["level"], 50);
+    assert_eq!(alerts[0]["monotonic_counter.poison_pill_deliveries"], 1);
+    // Other tests may dead-letter deliveries concurrently.
+    assert!(Counters::global().get("poison_pill_deliveries") > poison_pills_before);
+    assert_eq!(alerts[0]["n_retries"], 3);
+    assert!(alerts[0]["subscriber_email"].is_string());
+    assert!(alerts[0]["newsletter_issue_id"].is_string());
+
+    let dead_letters = sqlx::query!("SELECT n_retries FROM issue_delivery_dead_letter")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(dead_letters.len(), 1);
+    assert_eq!(dead_letters[0].n_retries, 3);
+    let queued = sqlx::query!("SELECT newsletter_issue_id FROM issue_delivery_queue")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(queued.is_empty());
+}
+
+#[tokio::test]
+async fn worker_drains_several_tasks_per_wake_up() {
+    let app = spawn_app().await;
+    let n_subscribers = 5;
+    for _ in 0..n_subscribers {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(n_subscribers)
+        .mount(&app.email_server)
+        .await;
+
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    let resp = app.post_publish_newsletters(&body).await;
+    assert_is_redirect_to(&resp, "/admin/newsletters");
+
+    let settings = WorkerSettings {
+        drain_batch: 2,
+        ..app.worker_settings.clone()
+    };
+    // A batch runs on a single connection, so it doesn't need a second one.
+    let single_connection = PgPoolOptions::new()
+        .max_connections(1)
+        .acquire_timeout(Duration::from_secs(2))
+        .connect_with((*app.db_pool.connect_options()).clone())
+        .await
+        .unwrap();
+    let mut wake_ups = 0;
+    let mut completed = 0;
+    loop {
+        wake_ups += 1;
+        let report = drain_batch(&single_connection, &app.email_client, &settings, &app.links)
+            .await
+            .unwrap();
+        assert!(report.completed <= settings.drain_batch);
+        completed += report.completed;
+        if report.queue_empty {
+            break;
+        }
+    }
+
+    assert_eq!(completed, n_subscribers as usize);
+    assert_eq!(wake_ups, 3);
+}
+
+#[tokio::test]
+async fn editing_an_issue_mid_delivery_updates_the_remaining_sends() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body with a tpyo",
+        "html_content": "<p>Newsletter body with a tpyo</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    let resp = app.post_publish_newsletters(&body).await;
+    assert_is_redirect_to(&resp, "/admin/newsletters");
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    try_execute_task(
+        &app.db_pool,
+        &app.email_client,
+        &app.worker_settings,
+        &app.links,
+    )
+    .await
+    .unwrap();
+    let fixed_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body without a typo",
+        "html_content": "<p>Newsletter body without a typo</p>",
+    });
+    let resp = app.put_newsletter_issue(issue_id, &fixed_body).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    app.dispatch_all_pending_emails().await;
+
+    let sent_texts: Vec<String> = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|r| r.url.path() == "/email")
+        .map(|r| {
+            let body: serde_json::Value = serde_json::from_slice(&r.body).unwrap();
+            body["TextBody"].as_str().unwrap().to_owned()
+        })
+        .collect();
+    assert_eq!(
+        &sent_texts[sent_texts.len() - 2..],
+        [
+            "Newsletter body with a tpyo",
+            "Newsletter body without a typo"
+        ]
+    );
+
+    let audit = sqlx::query!("SELECT action, target FROM audit_log")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(audit.action, "update_newsletter_issue");
+    assert_eq!(audit.target, issue_id.to_string());
+
+    // Everyone has been sent the issue: there is nothing left to update.
+    let resp = app.put_newsletter_issue(issue_id, &fixed_body).await;
+    assert_eq!(resp.status().as_u16(), 409);
+}
+
+#[tokio::test]
+async fn newsletters_carry_a_one_click_unsubscribe_header() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletters(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    let emails = app.sent_emails().await;
+    assert!(emails[0].json.get("Headers").is_none());
+
+    let headers = emails.last().unwrap().json["Headers"].as_array().unwrap();
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|h| h["Name"] == name)
+            .and_then(|h| h["Value"].as_str())
+            .unwrap()
+            .to_owned()
+    };
+    assert_eq!(
+        header("List-Unsubscribe-Post"),
+        "List-Unsubscribe=One-Click"
+    );
+    let unsubscribe_link = header("List-Unsubscribe");
+    let mut unsubscribe_link = reqwest::Url::parse(
+        unsubscribe_link
+            .strip_prefix('<')
+            .and_then(|l| l.strip_suffix('>'))
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(unsubscribe_link.path(), "/subscriptions/unsubscribe");
+
+    // Mailbox providers POST `List-Unsubscribe=One-Click` to the link
+    unsubscribe_link.set_port(Some(app.port)).unwrap();
+    let response = reqwest::Client::new()
+        .post(unsubscribe_link)
+        .form(&[("List-Unsubscribe", "One-Click")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "unsubscribed");
+}
+
+/// Unsubscribe the only subscriber through a signed link, like a mailbox
+/// provider would.
+async fn unsubscribe_the_subscriber(app: &TestApp) {
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    let mut unsubscribe_link = reqwest::Url::parse(&app.links.unsubscribe(subscriber_id)).unwrap();
+    unsubscribe_link.set_port(Some(app.port)).unwrap();
+    let response = reqwest::Client::new()
+        .post(unsubscribe_link)
+        .form(&[("List-Unsubscribe", "One-Click")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn unsubscribing_sends_a_confirmation_with_a_resubscribe_link_when_enabled() {
+    let app = spawn_app_with(|c| c.features.unsubscribe_confirmation_emails = true).await;
+    create_confirmed_subscriber(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    unsubscribe_the_subscriber(&app).await;
+    // Unsubscribing again doesn't send another confirmation.
+    unsubscribe_the_subscriber(&app).await;
+
+    let email = app.last_email().await;
+    assert_eq!(email.subject, "You have been unsubscribed");
+    let resubscribe_link = format!("{}/subscribe", app.links.base_url());
+    assert!(email
+        .html_body
+        .contains(&format!(r#"<a href="{resubscribe_link}">"#)));
+    assert!(email.text_body.contains(&resubscribe_link));
+}
+
+#[tokio::test]
+async fn unsubscribing_sends_no_confirmation_by_default() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    unsubscribe_the_subscriber(&app).await;
+}
+
+#[tokio::test]
+async fn unsubscribe_links_with_a_bad_signature_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = reqwest::Client::new()
+        .post(format!(
+            "{}/subscriptions/unsubscribe?subscriber_id={}&signature=forged",
+            app.address,
+            uuid::Uuid::new_v4()
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_dead_lettered_deliveries() {
+    let app = spawn_app().await;
+
+    let response = app.get_dead_letters().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app.post_replay_dead_letter(uuid::Uuid::new_v4()).await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn dead_lettered_deliveries_are_listed_with_their_last_error() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    dead_letter_a_delivery(&app).await;
+
+    let html_page = app.get_dead_letters_html().await;
+    assert!(html_page.contains("500 Internal Server Error"));
+    let dead_letter =
+        sqlx::query!("SELECT dead_letter_id, subscriber_email FROM issue_delivery_dead_letter")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert!(html_page.contains(&dead_letter.subscriber_email));
+    assert!(html_page.contains(&format!(
+        "/admin/deadletter/{}/replay",
+        dead_letter.dead_letter_id
+    )));
+}
+
+#[tokio::test]
+async fn replaying_a_dead_lettered_delivery_puts_it_back_in_the_queue() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    dead_letter_a_delivery(&app).await;
+    let dead_letter_id = sqlx::query!("SELECT dead_letter_id FROM issue_delivery_dead_letter")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .dead_letter_id;
+
+    let response = app.post_replay_dead_letter(dead_letter_id).await;
+    assert_is_redirect_to(&response, "/admin/deadletter");
+    let html_page = app.get_dead_letters_html().await;
+    assert!(html_page.contains("<p><i>The delivery has been queued again.</i></p>"));
+
+    let queued = sqlx::query!("SELECT n_retries FROM issue_delivery_queue")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].n_retries, 0);
+    let dead_letters = sqlx::query!("SELECT n_retries FROM issue_delivery_dead_letter")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(dead_letters.is_empty());
+
+    // Replaying twice is not possible
+    let response = app.post_replay_dead_letter(dead_letter_id).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    // Once the provider recovers, the replayed delivery goes out
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn deliveries_pause_at_the_daily_send_cap_and_resume_after_the_window() {
+    let app = spawn_app().await;
+    for _ in 0..3 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    let resp = app.post_publish_newsletters(&body).await;
+    assert_is_redirect_to(&resp, "/admin/newsletters");
+    let n_confirmation_emails = 3;
+
+    let settings = WorkerSettings {
+        daily_send_cap: 2,
+        ..app.worker_settings.clone()
+    };
+    let (logs, _guard) = CapturedLogs::start("info");
+    let mut outcome =
+        try_execute_task(&app.db_pool, &app.email_client, &settings, &app.links).await;
+    while let Ok(ExecutionOutcome::TaskCompleted) = outcome {
+        outcome = try_execute_task(&app.db_pool, &app.email_client, &settings, &app.links).await;
+    }
+    let Ok(ExecutionOutcome::Throttled(resume_in)) = outcome else {
+        panic!("Expected deliveries to be throttled");
+    };
+    assert!(resume_in <= Duration::from_secs(24 * 60 * 60));
+    let sent = app.email_server.received_requests().await.unwrap().len() - n_confirmation_emails;
+    assert_eq!(sent, 2);
+    assert_eq!(logs.with_message("Daily send cap reached").len(), 1);
+
+    // Once the earlier sends fall out of the 24 hours window, deliveries resume
+    sqlx::query!("UPDATE delivery_status SET recorded_at = now() - interval '25 hours'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    while let ExecutionOutcome::TaskCompleted =
+        try_execute_task(&app.db_pool, &app.email_client, &settings, &app.links)
+            .await
+            .unwrap()
+    {}
+    let sent = app.email_server.received_requests().await.unwrap().len() - n_confirmation_emails;
+    assert_eq!(sent, 3);
+}
+
+#[tokio::test]
+async fn broadcasts_wait_for_the_end_of_quiet_hours_but_confirmations_do_not() {
+    let app = spawn_app().await;
+    // Sent by the subscription handler, whatever the time
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    publish_an_issue(&app).await;
+
+    // In the past, so that the deferred task is due again for the database
+    let night = quiet_hours_at(&app, "2020-01-01T23:00:00Z");
+    let outcome = try_execute_task(&app.db_pool, &app.email_client, &night, &app.links)
+        .await
+        .unwrap();
+
+    assert!(matches!(outcome, ExecutionOutcome::Deferred));
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 1);
+    let task = sqlx::query!("SELECT execute_after, n_retries FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(task.execute_after.to_rfc3339(), "2020-01-02T07:00:00+00:00");
+    assert_eq!(task.n_retries, 0);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let morning = quiet_hours_at(&app, "2020-01-02T07:30:00Z");
+    let outcome = try_execute_task(&app.db_pool, &app.email_client, &morning, &app.links)
+        .await
+        .unwrap();
+    assert!(matches!(outcome, ExecutionOutcome::TaskCompleted));
+}
+
+#[tokio::test]
+async fn quiet_hours_follow_the_time_zone_of_each_subscriber() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    for (email, utc_offset) in [("paris@example.com", ""), ("tokyo@example.com", "+09:00")] {
+        let body = format!(
+            "name=le%20guin&email={email}&utc_offset={}",
+            utc_offset.replace('+', "%2B")
+        );
+        app.post_subscriptions(body)
+            .await
+            .error_for_status()
+            .unwrap();
+        let email_request = app
+            .email_server
+            .received_requests()
+            .await
+            .unwrap()
+            .pop()
+            .unwrap();
+        let confirmation_link = app.get_confirmation_link(&email_request).await;
+        reqwest::get(confirmation_link)
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+    app.test_user.login(&app).await;
+    publish_an_issue(&app).await;
+
+    // 23:00 in Tokyo, 14:00 for everyone else. In the future, so that the
+    // deferred task isn't due again for the database.
+    let settings = quiet_hours_at(&app, "2099-01-01T14:00:00Z");
+    let report = drain_batch(&app.db_pool, &app.email_client, &settings, &app.links)
+        .await
+        .unwrap();
+
+    assert_eq!(report.completed, 2);
+    assert!(report.queue_empty);
+    let deferred = sqlx::query!("SELECT subscriber_email, execute_after FROM issue_delivery_queue")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(deferred.len(), 1);
+    assert_eq!(deferred[0].subscriber_email, "tokyo@example.com");
+    assert_eq!(
+        deferred[0].execute_after.to_rfc3339(),
+        "2099-01-01T22:00:00+00:00"
+    );
+}
+
+#[tokio::test]
+async fn deliveries_follow_the_tracking_choices_of_their_issue() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    for (track_opens, track_links) in [(true, false), (false, true)] {
+        let mut body = serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        });
+        // Unticked checkboxes are not submitted
+        if track_opens {
+            body["track_opens"] = "on".into();
+        }
+        if track_links {
+            body["track_links"] = "on".into();
+        }
+        let resp = app.post_publish_newsletters(&body).await;
+        assert_is_redirect_to(&resp, "/admin/newsletters");
+        app.dispatch_all_pending_emails().await;
+    }
+
+    let bodies: Vec<serde_json::Value> = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| serde_json::from_slice(&r.body).unwrap())
+        .collect();
+    let [confirmation, opens_only, links_only] = &bodies[..] else {
+        panic!("Expected 3 emails, got {}", bodies.len());
+    };
+    assert_eq!(confirmation["TrackOpens"], false);
+    assert_eq!(confirmation["TrackLinks"], "None");
+    assert_eq!(opens_only["TrackOpens"], true);
+    assert_eq!(opens_only["TrackLinks"], "None");
+    assert_eq!(links_only["TrackOpens"], false);
+    assert_eq!(links_only["TrackLinks"], "HtmlAndText");
+}
+
+#[tokio::test]
+async fn following_the_text_open_link_records_an_open() {
+    let app = spawn_app_with(|c| c.worker.text_open_tracking = true).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "track_text_opens": "on",
+    });
+    let resp = app.post_publish_newsletters(&body).await;
+    assert_is_redirect_to(&resp, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    let email = app.last_email().await;
+    let (_, link) = email
+        .text_body
+        .split_once("Read this issue online: ")
+        .expect("The text body has no tracked link");
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    assert!(
+        link.contains(&format!("subscriber_id={subscriber_id}")),
+        "{link}"
+    );
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+    let resp = app
+        .api_client
+        .get(app.local_link(link.trim()))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&resp, &format!("/issues/{issue_id}"));
+    let events = sqlx::query!("SELECT subscriber_email, event_type FROM engagement_events")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].event_type, "open");
+    assert_eq!(events[0].subscriber_email, email.to);
+}
+
+#[tokio::test]
+async fn text_open_links_record_nothing_once_the_tracking_is_disabled() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "track_text_opens": "on",
+    });
+    app.post_publish_newsletters(&body).await;
+    app.dispatch_all_pending_emails().await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    // A link sent while the tracking was still enabled.
+    let link = app.links.issue_read(issue_id, subscriber_id);
+    let resp = app
+        .api_client
+        .get(app.local_link(&link))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&resp, &format!("/issues/{issue_id}"));
+    let n_events = sqlx::query_scalar!(r#"SELECT count(*) as "count!" FROM engagement_events"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_events, 0);
+}
+
+#[tokio::test]
+async fn issues_get_no_text_open_link_unless_the_tracking_is_enabled() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "track_text_opens": "on",
+    });
+    app.post_publish_newsletters(&body).await;
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+    assert_eq!(email["TextBody"], "Newsletter body as plain text");
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_follow_delivery_progress() {
+    let app = spawn_app().await;
+
+    let resp = app.get_delivery_progress(uuid::Uuid::new_v4()).await;
+
+    assert_is_redirect_to(&resp, "/login");
+}
+
+#[tokio::test]
+async fn delivery_progress_of_an_unknown_issue_is_not_found() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let resp = app.get_delivery_progress(uuid::Uuid::new_v4()).await;
+
+    assert_eq!(resp.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn delivery_progress_is_streamed_until_the_issue_is_delivered() {
+    let app = spawn_app().await;
+    let n_subscribers = 3;
+    for _ in 0..n_subscribers {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    app.post_publish_newsletters(&body).await;
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    let mut resp = app.get_delivery_progress(issue_id).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    assert_eq!(
+        resp.headers()["Content-Type"].to_str().unwrap(),
+        "text/event-stream"
+    );
+    let mut stream = String::new();
+
+    for sent in 0..n_subscribers {
+        let (event, progress) = next_sse_event(&mut resp, &mut stream).await;
+        assert_eq!(event, "progress");
+        assert_eq!(progress["sent"], sent);
+        assert_eq!(progress["remaining"], n_subscribers - sent);
+        try_execute_task(
+            &app.db_pool,
+            &app.email_client,
+            &app.worker_settings,
+            &app.links,
+        )
+        .await
+        .unwrap();
+    }
+    let (event, progress) = next_sse_event(&mut resp, &mut stream).await;
+    assert_eq!(event, "progress");
+    assert_eq!(progress["sent"], n_subscribers);
+    let (event, progress) = next_sse_event(&mut resp, &mut stream).await;
+    assert_eq!(event, "complete");
+    assert_eq!(
+        progress,
+        serde_json::json!({"sent": n_subscribers, "failed": 0, "remaining": 0})
+    );
+    // The stream is closed once delivery is complete.
+    let rest = tokio::time::timeout(std::time::Duration::from_secs(5), resp.chunk())
+        .await
+        .expect("the stream was not closed")
+        .unwrap();
+    assert!(rest.is_none());
+}
+
+#[tokio::test]
+async fn progress_reporting_stops_when_the_client_goes_away_mid_send() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    app.post_publish_newsletters(&body).await;
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    // Nothing is dispatched, so the counts never change.
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(8);
+    let task = tokio::spawn(report_progress(
+        std::sync::Arc::new(app.db_pool.clone()),
+        issue_id,
+        sender,
+    ));
+    let _progress = receiver.recv().await.expect("no progress was reported");
+    drop(receiver);
+
+    tokio::time::timeout(std::time::Duration::from_secs(1), task)
+        .await
+        .expect("progress was still being reported")
+        .unwrap();
+}
+
+/// Read the next `event:`/`data:` pair off a server-sent events response.
+async fn next_sse_event(
+    resp: &mut reqwest::Response,
+    buffer: &mut String,
+) -> (String, serde_json::Value) {
+    loop {
+        if let Some(end) = buffer.find("\n\n") {
+            let message: String = buffer.drain(..end + 2).collect();
+            let mut event = String::new();
+            let mut data = String::new();
+            for line in message.lines() {
+                if let Some(value) = line.strip_prefix("event: ") {
+                    event = value.to_string();
+                } else if let Some(value) = line.strip_prefix("data: ") {
+                    data.push_str(value);
+                }
+            }
+            return (event, serde_json::from_str(&data).unwrap());
+        }
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), resp.chunk())
+            .await
+            .expect("no server-sent event received in time")
+            .unwrap()
+            .expect("the stream ended early");
+        buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+    }
+}
+
+#[tokio::test]
+async fn personalized_issues_greet_subscribers_by_their_escaped_name() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    sqlx::query!("UPDATE subscriptions SET name = 'le guin & co'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    for personalized in [true, false] {
+        let mut body = serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Hello {{name}}, from {{city}}",
+            "html_content": "<p>Hello {{name}}, from {{city}}</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        });
+        if personalized {
+            body["personalized"] = "on".into();
+        }
+        let resp = app.post_publish_newsletters(&body).await;
+        assert_is_redirect_to(&resp, "/admin/newsletters");
+        app.dispatch_all_pending_emails().await;
+    }
+
+    let bodies: Vec<serde_json::Value> = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| serde_json::from_slice(&r.body).unwrap())
+        .collect();
+    let [_confirmation, personalized, plain] = &bodies[..] else {
+        panic!("Expected 3 emails, got {}", bodies.len());
+    };
+    assert_eq!(
+        personalized["HtmlBody"],
+        "<p>Hello le guin &amp; co, from {{city}}</p>"
+    );
+    assert_eq!(
+        personalized["TextBody"],
+        "Hello le guin & co, from {{city}}"
+    );
+    assert_eq!(plain["HtmlBody"], "<p>Hello {{name}}, from {{city}}</p>");
+    assert_eq!(plain["TextBody"], "Hello {{name}}, from {{city}}");
+}
+
+#[tokio::test]
+async fn links_to_our_site_carry_an_opaque_subscriber_reference() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Read http://127.0.0.1/posts/1 or https://example.org/elsewhere",
+        "html_content": r#"<a href="http://127.0.0.1/posts/1">Read</a>"#,
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    app.post_publish_newsletters(&body).await;
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let issue: serde_json::Value = serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+    let reference = app.links.subscriber_reference(subscriber_id);
+    assert_eq!(
+        issue["HtmlBody"],
+        format!(r#"<a href="http://127.0.0.1/posts/1?subscriber_ref={reference}">Read</a>"#)
+    );
+    assert_eq!(
+        issue["TextBody"],
+        format!(
+            "Read http://127.0.0.1/posts/1?subscriber_ref={reference} or https://example.org/elsewhere"
+        )
+    );
+}
+
+#[tokio::test]
+async fn issues_can_be_wrapped_in_an_html_layout() {
+    let app = spawn_app_with(|c| {
+        c.worker.html_layout = Some(EmailLayout::load("templates/newsletter_layout.html").unwrap());
+        c.worker.brand = "Earthsea letters".into();
+    })
+    .await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    app.post_publish_newsletters(&body).await;
+    app.dispatch_all_pending_emails().await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let issue: serde_json::Value = serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+    let html = issue["HtmlBody"].as_str().unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>"), "{html}");
+    assert!(html.contains("<p>Newsletter body as HTML</p>"), "{html}");
+    assert!(
+        html.contains("you subscribed to Earthsea letters."),
+        "{html}"
+    );
+    let unsubscribe_link = app.links.unsubscribe(subscriber_id).replace('&', "&amp;");
+    assert!(
+        html.contains(&format!(r#"<a href="{unsubscribe_link}">Unsubscribe</a>"#)),
+        "{html}"
+    );
+    // Only the HTML body has a layout.
+    assert_eq!(issue["TextBody"], "Newsletter body as plain text");
+}
+
+#[tokio::test]
+async fn the_category_of_an_issue_prefixes_the_subject() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    for category in ["Weekly", ""] {
+        let body = serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+            "category": category,
+        });
+        let resp = app.post_publish_newsletters(&body).await;
+        assert_is_redirect_to(&resp, "/admin/newsletters");
+        app.dispatch_all_pending_emails().await;
+    }
+
+    let subjects: Vec<String> = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .skip(1)
+        .map(|r| {
+            let body: serde_json::Value = serde_json::from_slice(&r.body).unwrap();
+            body["Subject"].as_str().unwrap().to_owned()
+        })
+        .collect();
+    assert_eq!(subjects, ["[Weekly] Newsletter title", "Newsletter title"]);
+}
+
+#[tokio::test]
+async fn invalid_categories_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "category": "[Weekly]",
+    });
+    let resp = app.post_publish_newsletters(&body).await;
+    assert_is_redirect_to(&resp, "/admin/newsletters");
+
+    let html_page = app.get_publish_newsletters_html().await;
+    assert!(html_page.contains("The category must be a single line"));
+    let n_issues = sqlx::query!(r#"SELECT count(*) as "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 0);
+}
+
+#[tokio::test]
+async fn issues_published_too_soon_after_the_previous_one_are_refused() {
+    let app = spawn_app_with(|c| c.publishing.min_seconds_between_issues = 1).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let issue = |force: bool| {
+        serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "force": if force { "on" } else { "" },
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        })
+    };
+    let n_issues = || async {
+        sqlx::query_scalar!(r#"SELECT count(*) as "count!" FROM newsletter_issues"#)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap()
+    };
+
+    let response = app.post_publish_newsletters(&issue(false)).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let response = app.post_publish_newsletters(&issue(false)).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let html_page = app.get_publish_newsletters_html().await;
+    assert!(html_page.contains("The previous issue was published less than 1s ago."));
+    assert_eq!(n_issues().await, 1);
+
+    let response = app.post_publish_newsletters(&issue(true)).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    assert_eq!(n_issues().await, 2);
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let response = app.post_publish_newsletters(&issue(false)).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    assert_eq!(n_issues().await, 3);
+}
+
+#[tokio::test]
+async fn republishing_the_same_content_within_the_window_is_refused_unless_forced() {
+    let app = spawn_app_with(|c| c.publishing.duplicate_window_seconds = 3600).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let issue = |text_content: &str, force: bool| {
+        serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": text_content,
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "force": if force { "on" } else { "" },
+            // A new key every time: idempotency doesn't catch these.
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        })
+    };
+    let n_issues = || async {
+        sqlx::query_scalar!(r#"SELECT count(*) as "count!" FROM newsletter_issues"#)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap()
+    };
+
+    let response = app.post_publish_newsletters(&issue("Body", false)).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let response = app.post_publish_newsletters(&issue("Body", false)).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let html_page = app.get_publish_newsletters_html().await;
+    assert!(html_page.contains("An issue with the same title and content was published"));
+    assert_eq!(n_issues().await, 1);
+
+    let response = app
+        .post_publish_newsletters(&issue("Other body", false))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    assert_eq!(n_issues().await, 2);
+
+    let response = app.post_publish_newsletters(&issue("Body", true)).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    assert_eq!(n_issues().await, 3);
+}
+
+#[tokio::test]
+async fn the_same_content_can_be_published_to_another_list_within_the_window() {
+    let app = spawn_app_with(|c| c.publishing.duplicate_window_seconds = 3600).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    for name in ["List A", "List B"] {
+        let response = app
+            .post_create_list(&serde_json::json!({
+                "name": name,
+                "sender": "list@example.com",
+                "reply_to": "",
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/admin/lists");
+    }
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let issue = |list: &str| {
+        serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Body",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "list": list,
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        })
+    };
+    let n_issues = || async {
+        sqlx::query_scalar!(r#"SELECT count(*) as "count!" FROM newsletter_issues"#)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap()
+    };
+
+    for list in ["List A", "List B", ""] {
+        let response = app.post_publish_newsletters(&issue(list)).await;
+        assert_is_redirect_to(&response, "/admin/newsletters");
+    }
+    assert_eq!(n_issues().await, 3);
+
+    let response = app.post_publish_newsletters(&issue("List A")).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let html_page = app.get_publish_newsletters_html().await;
+    assert!(html_page.contains("An issue with the same title and content was published"));
+    assert_eq!(n_issues().await, 3);
+}
+
+#[tokio::test]
+async fn with_per_content_idempotency_a_reused_key_publishes_to_another_list() {
+    let app = spawn_app_with(|c| c.publishing.idempotency_per_content = true).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let response = app
+        .post_create_list(&serde_json::json!({
+            "name": "List A",
+            "sender": "list@example.com",
+            "reply_to": "",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/lists");
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+    let issue = |list: &str| {
+        serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Body",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "list": list,
+            "idempotency_key": idempotency_key,
+        })
+    };
+
+    for list in ["", "List A", "List A"] {
+        let response = app.post_publish_newsletters(&issue(list)).await;
+        assert_is_redirect_to(&response, "/admin/newsletters");
+    }
+
+    let n_issues = sqlx::query_scalar!(r#"SELECT count(*) as "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_issues, 2);
+}
+
+#[tokio::test]
+async fn with_per_content_idempotency_a_reused_key_publishes_new_content() {
+    let app = spawn_app_with(|c| c.publishing.idempotency_per_content = true).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+    let issue = |text_content: &str| {
+        serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": text_content,
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": idempotency_key,
+        })
+    };
+    let n_issues = || async {
+        sqlx::query_scalar!(r#"SELECT count(*) as "count!" FROM newsletter_issues"#)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap()
+    };
+
+    let response = app.post_publish_newsletters(&issue("Body")).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let response = app.post_publish_newsletters(&issue("Body")).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    assert_eq!(n_issues().await, 1);
+
+    let response = app.post_publish_newsletters(&issue("Other body")).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    assert_eq!(n_issues().await, 2);
+    let response = app.post_publish_newsletters(&issue("Other body")).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    assert_eq!(n_issues().await, 2);
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn by_default_a_reused_key_replays_whatever_the_content() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+    let issue = |text_content: &str| {
+        serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": text_content,
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": idempotency_key,
+        })
+    };
+
+    app.post_publish_newsletters(&issue("Body")).await;
+    let response = app.post_publish_newsletters(&issue("Other body")).await;
+
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let n_issues = sqlx::query_scalar!(r#"SELECT count(*) as "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_issues, 1);
+}
+
+#[tokio::test]
+async fn issues_published_to_a_list_are_sent_from_its_sender() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    for (name, sender, reply_to) in [
+        ("List A", "a@example.com", "editor-a@example.com"),
+        ("List B", "b@example.com", ""),
+    ] {
+        let response = app
+            .post_create_list(&serde_json::json!({
+                "name": name,
+                "sender": sender,
+                "reply_to": reply_to,
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/admin/lists");
+    }
+    assert!(app.get_lists_html().await.contains("editor-a@example.com"));
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    for list in ["List A", "List B"] {
+        let response = app
+            .post_publish_newsletters(&serde_json::json!({
+                "title": format!("An issue of {list}"),
+                "text_content": "Newsletter body as plain text",
+                "html_content": "<p>Newsletter body as HTML</p>",
+                "list": list,
+                "idempotency_key": uuid::Uuid::new_v4().to_string(),
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/admin/newsletters");
+        app.dispatch_all_pending_emails().await;
+    }
+
+    let emails: Vec<serde_json::Value> = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|r| r.url.path() == "/email")
+        .map(|r| serde_json::from_slice(&r.body).unwrap())
+        .collect();
+    let issues = &emails[emails.len() - 2..];
+    assert_eq!(issues[0]["Subject"], "An issue of List A");
+    assert_eq!(issues[0]["From"], "a@example.com");
+    assert_eq!(issues[0]["ReplyTo"], "editor-a@example.com");
+    assert_eq!(issues[1]["Subject"], "An issue of List B");
+    assert_eq!(issues[1]["From"], "b@example.com");
+    assert!(issues[1].get("ReplyTo").is_none());
+}
+
+#[tokio::test]
+async fn a_list_sender_without_a_confirmed_signature_is_refused() {
+    let app = spawn_app_with(|c| {
+        c.email_client.sender_verification = SenderVerification::Warn;
+        c.email_client.account_token = Some(Secret::new("account-token".into()));
+    })
+    .await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/senders"))
+        .and(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "TotalCount": 1,
+            "SenderSignatures": [
+                { "EmailAddress": "confirmed@example.com", "Confirmed": true }
+            ]
+        })))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_create_list(&serde_json::json!({
+            "name": "Unconfirmed",
+            "sender": "unconfirmed@example.com",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/lists");
+    let html_page = app.get_lists_html().await;
+    assert!(html_page
+        .contains("The sender unconfirmed@example.com is not a confirmed sender signature."));
+
+    let response = app
+        .post_create_list(&serde_json::json!({
+            "name": "Confirmed",
+            "sender": "confirmed@example.com",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/lists");
+    let names: Vec<String> = sqlx::query_scalar!("SELECT name FROM newsletter_lists")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(names, vec!["Confirmed".to_string()]);
+}
+
+#[tokio::test]
+async fn list_names_are_escaped_in_the_already_exists_message() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let list = serde_json::json!({
+        "name": "<script>alert(1)</script>",
+        "sender": "digest@example.com",
+    });
+    assert_is_redirect_to(&app.post_create_list(&list).await, "/admin/lists");
+    app.get_lists_html().await;
+
+    let response = app.post_create_list(&list).await;
+
+    assert_is_redirect_to(&response, "/admin/lists");
+    let html_page = app.get_lists_html().await;
+    assert!(
+        html_page.contains("A list named &lt;script&gt;alert(1)&lt;/script&gt; already exists.")
+    );
+    assert!(!html_page.contains("<script>"), "{html_page}");
+}
+
+#[tokio::test]
+async fn ordered_deliveries_reach_a_subscriber_in_enqueue_order_under_concurrent_workers() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    for title in ["Part 1", "Part 2"] {
+        let body = serde_json::json!({
+            "title": title,
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        });
+        let resp = app.post_publish_newsletters(&body).await;
+        assert_is_redirect_to(&resp, "/admin/newsletters");
+    }
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(500)))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    let settings = WorkerSettings {
+        ordered_per_subscriber: true,
+        ..app.worker_settings.clone()
+    };
+
+    // The second worker starts while the first one is still sending.
+    let (first, second) = tokio::join!(
+        try_execute_task(&app.db_pool, &app.email_client, &settings, &app.links),
+        async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            try_execute_task(&app.db_pool, &app.email_client, &settings, &app.links).await
+        }
+    );
+    assert!(matches!(first.unwrap(), ExecutionOutcome::TaskCompleted));
+    assert!(matches!(second.unwrap(), ExecutionOutcome::EmptyQueue));
+    let third = try_execute_task(&app.db_pool, &app.email_client, &settings, &app.links)
+        .await
+        .unwrap();
+    assert!(matches!(third, ExecutionOutcome::TaskCompleted));
+
+    let subjects: Vec<String> = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| serde_json::from_slice::<serde_json::Value>(&r.body).unwrap())
+        .filter_map(|body| body["Subject"].as_str().map(str::to_owned))
+        .filter(|subject| subject.starts_with("Part"))
+        .collect();
+    assert_eq!(subjects, vec!["Part 1", "Part 2"]);
+}
+
+async fn create_unconfirmed_subscriber(app: &TestApp) -> reqwest::Url {
+    let name: String = Name().fake();
+    let email: String = SafeEmail().fake();
+    let body = serde_urlencoded::to_string(serde_json::json!({
+        "name": name,
+        "email": email,
+    }))
+    .unwrap();
+
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .named("Create unconfirmed subscriber")
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions(body)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let email_request = &app
         .email_server
         .received_requests()
         .await
         .unwrap()
         .pop()
         .unwrap();
-    app.get_confirmation_link(&email_request).await
+    app.get_confirmation_link(email_request).await
 }
 
 async fn create_confirmed_subscriber(app: &TestApp) {
@@ -215,3 +2266,69 @@ async fn create_confirmed_subscriber(app: &TestApp) {
         .error_for_status()
         .unwrap();
 }
+
+/// Worker settings with quiet hours from 22:00 to 07:00 UTC, with the clock
+/// set to `now`.
+fn quiet_hours_at(app: &TestApp, now: &str) -> WorkerSettings {
+    let quiet_hours = serde_json::from_value(serde_json::json!({
+        "start": "22:00",
+        "end": "07:00",
+        "utc_offset": "+00:00",
+    }))
+    .unwrap();
+    app.clock
+        .set(DateTime::parse_from_rfc3339(now).unwrap().into());
+    WorkerSettings {
+        quiet_hours: Some(quiet_hours),
+        ..app.worker_settings.clone()
+    }
+}
+
+/// The idempotency key embedded in the publish form.
+fn hidden_idempotency_key(html_page: &str) -> String {
+    let prefix = r#"<input type="hidden" name="idempotency_key" value=""#;
+    let start = html_page.find(prefix).expect("No hidden idempotency key") + prefix.len();
+    let end = start + html_page[start..].find('"').unwrap();
+    html_page[start..end].to_string()
+}
+
+/// Expects a logged-in user.
+async fn publish_an_issue(app: &TestApp) {
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    let resp = app.post_publish_newsletters(&body).await;
+    assert_is_redirect_to(&resp, "/admin/newsletters");
+}
+
+/// Publish an issue to a single confirmed subscriber and fail its delivery
+/// until it ends up in the dead letter queue. Expects a logged-in user.
+async fn dead_letter_a_delivery(app: &TestApp) {
+    create_confirmed_subscriber(app).await;
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    let resp = app.post_publish_newsletters(&body).await;
+    assert_is_redirect_to(&resp, "/admin/newsletters");
+
+    let settings = WorkerSettings {
+        max_retries: 1,
+        ..app.worker_settings.clone()
+    };
+    while let ExecutionOutcome::TaskCompleted =
+        try_execute_task(&app.db_pool, &app.email_client, &settings, &app.links)
+            .await
+            .unwrap()
+    {}
+}
@@ -0,0 +1,99 @@
+use secrecy::Secret;
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helper::{assert_is_redirect_to, spawn_app_with, TestApp};
+
+const OLD_TOKEN: &str = "old-server-token";
+const NEW_TOKEN: &str = "new-server-token";
+
+async fn spawn_app() -> TestApp {
+    spawn_app_with(|c| c.email_client.authorization_token = Secret::new(OLD_TOKEN.into())).await
+}
+
+/// The token sent along with the confirmation email of a new subscriber.
+async fn token_used_for_the_next_email(app: &TestApp) -> String {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+    let requests = app.email_server.received_requests().await.unwrap();
+    let email_request = requests
+        .iter()
+        .rev()
+        .find(|r| r.url.path() == "/email")
+        .unwrap();
+    email_request
+        .headers
+        .get(&"X-Postmark-Server-Token".into())
+        .unwrap()
+        .as_str()
+        .to_string()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_rotate_the_email_token() {
+    let app = spawn_app().await;
+
+    let resp = app.post_rotate_email_token(NEW_TOKEN).await;
+
+    assert_is_redirect_to(&resp, "/login");
+}
+
+#[tokio::test]
+async fn emails_are_sent_with_the_rotated_token() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/server"))
+        .and(method("GET"))
+        .and(header("X-Postmark-Server-Token", NEW_TOKEN))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let resp = app.post_rotate_email_token(NEW_TOKEN).await;
+
+    assert_is_redirect_to(&resp, "/admin/settings/email_token");
+    let html_page = app.get_email_token_html().await;
+    assert!(html_page.contains("<p><i>The email provider token has been rotated.</i></p>"));
+    assert_eq!(token_used_for_the_next_email(&app).await, NEW_TOKEN);
+
+    let audit = sqlx::query!("SELECT action, target FROM audit_log")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(audit.action, "rotate_email_token");
+    assert_eq!(audit.target, "email_client");
+}
+
+#[tokio::test]
+async fn tokens_rejected_by_the_provider_are_not_used() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/server"))
+        .and(method("GET"))
+        .respond_with(ResponseTemplate::new(401))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let resp = app.post_rotate_email_token(NEW_TOKEN).await;
+
+    assert_is_redirect_to(&resp, "/admin/settings/email_token");
+    let html_page = app.get_email_token_html().await;
+    assert!(html_page.contains("<p><i>The email provider did not accept the new token.</i></p>"));
+    assert_eq!(token_used_for_the_next_email(&app).await, OLD_TOKEN);
+    let n_audit_events = sqlx::query!(r#"SELECT count(*) as "count!" FROM audit_log"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_audit_events, 0);
+}
@@ -0,0 +1,365 @@
+use secrecy::Secret;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use zero2prod::authentication::ensure_admin_exists;
+use zero2prod::configuration::{
+    get_configuration, BootstrapSettings, SenderVerification, Settings,
+};
+use zero2prod::confirmation_reminders::run_confirmation_reminders_until_stopped;
+use zero2prod::shutdown::{shut_down, ShutdownSignal};
+use zero2prod::startup::Application;
+use zero2prod::token_cleanup::run_token_cleanup_until_stopped;
+use zero2prod::webhook_delivery::run_webhook_worker_until_stopped;
+
+use crate::helper::{assert_is_redirect_to, spawn_app, spawn_app_with, CapturedLogs, TestApp};
+
+type BreakConfiguration = fn(&mut Settings);
+
+#[tokio::test]
+async fn the_effective_configuration_is_logged_with_secrets_masked() {
+    let (logs, _guard) = CapturedLogs::start("info");
+
+    spawn_app_with(|c| {
+        c.application.hmac_secret = Secret::new(
+            "hmac-secret-value-long-enough-to-derive-the-session-cookie-signing-key".into(),
+        );
+        c.email_client.authorization_token = Secret::new("postmark-token-value".into());
+    })
+    .await;
+
+    let summary = logs.with_message("Effective configuration");
+    assert_eq!(summary.len(), 1);
+    let summary = summary[0].to_string();
+    for secret in [
+        "hmac-secret-value-long-enough-to-derive-the-session-cookie-signing-key",
+        "postmark-token-value",
+        "redis://127.0.0.1:6379",
+        r#"password: \"password\""#,
+    ] {
+        assert!(!summary.contains(secret), "{secret} leaked in {summary}");
+    }
+    assert!(summary.contains("REDACTED"));
+    // Non-secret settings are shown as they took effect.
+    assert!(summary.contains("max_connections: 10"), "{summary}");
+}
+
+#[tokio::test]
+async fn invalid_configurations_abort_startup() {
+    let cases: [(&str, BreakConfiguration); 4] = [
+        ("application.base_url", |c| {
+            c.application.base_url = "127.0.0.1:8000".into()
+        }),
+        ("database.max_connections", |c| {
+            c.database.max_connections = 0
+        }),
+        ("worker.drain_batch", |c| c.worker.drain_batch = 0),
+        ("bootstrap.admin_password", |c| {
+            c.bootstrap.admin_username = Some("admin".into())
+        }),
+    ];
+    for (setting, break_configuration) in cases {
+        let mut configuration = get_configuration().expect("Failed to read configuration.");
+        configuration.application.port = 0;
+        break_configuration(&mut configuration);
+
+        let err = match Application::build(configuration).await {
+            Ok(_) => panic!("Started with an invalid {setting}"),
+            Err(e) => e.to_string(),
+        };
+
+        assert!(err.contains(setting), "{err}");
+    }
+}
+
+#[tokio::test]
+async fn an_unverified_sender_can_abort_startup() {
+    for (confirmed, starts) in [(true, true), (false, false)] {
+        let email_server = MockServer::start().await;
+        Mock::given(path("/senders"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "TotalCount": 1,
+                "SenderSignatures": [
+                    { "EmailAddress": "test@example.com", "Confirmed": confirmed }
+                ]
+            })))
+            .expect(1)
+            .mount(&email_server)
+            .await;
+        let mut configuration = get_configuration().expect("Failed to read configuration.");
+        configuration.application.port = 0;
+        configuration.email_client.api_url = email_server.uri();
+        configuration.email_client.sender = "test@example.com".into();
+        configuration.email_client.sender_verification = SenderVerification::Abort;
+        configuration.email_client.account_token = Some(Secret::new("account-token".into()));
+
+        match Application::build(configuration).await {
+            Ok(_) => assert!(starts, "Started with an unverified sender"),
+            Err(e) => {
+                assert!(!starts, "Failed to start: {e}");
+                assert!(
+                    e.to_string().contains("not a confirmed sender signature"),
+                    "{e}"
+                );
+            }
+        }
+    }
+}
+
+fn bootstrap_settings() -> BootstrapSettings {
+    BootstrapSettings {
+        admin_username: Some("first-admin".into()),
+        admin_password: Some(Secret::new("a-bootstrap-password".into())),
+        admin_email: None,
+    }
+}
+
+async fn usernames(app: &TestApp) -> Vec<String> {
+    sqlx::query!("SELECT username FROM users ORDER BY username")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.username)
+        .collect()
+}
+
+#[tokio::test]
+async fn the_configured_admin_is_created_when_there_are_no_users() {
+    let app = spawn_app().await;
+    sqlx::query!("DELETE FROM users")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let created = ensure_admin_exists(&app.db_pool, &bootstrap_settings())
+        .await
+        .unwrap();
+
+    assert!(created);
+    assert_eq!(usernames(&app).await, ["first-admin"]);
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": "first-admin",
+            "password": "a-bootstrap-password",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+/// Back to the state left by the migrations, with only the seed admin.
+async fn remove_test_user(app: &TestApp) {
+    sqlx::query!(
+        "DELETE FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn the_configured_admin_replaces_the_untouched_seed_admin() {
+    let app = spawn_app().await;
+    remove_test_user(&app).await;
+    assert_eq!(usernames(&app).await, ["admin"]);
+
+    let created = ensure_admin_exists(&app.db_pool, &bootstrap_settings())
+        .await
+        .unwrap();
+
+    assert!(created);
+    assert_eq!(usernames(&app).await, ["first-admin"]);
+    let seed_password_left = sqlx::query!(
+        r#"SELECT EXISTS (
+            SELECT 1 FROM users WHERE password_hash LIKE '%$Am8UFBA4w5NJEmAtquGvBmAlu92q/VQcaoL5AyJPfc8'
+        ) as "exists!""#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .exists;
+    assert!(!seed_password_left);
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": "first-admin",
+            "password": "a-bootstrap-password",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_seed_admin_whose_password_was_changed_is_kept() {
+    let app = spawn_app().await;
+    remove_test_user(&app).await;
+    sqlx::query!("UPDATE users SET password_hash = 'changed' WHERE username = 'admin'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let created = ensure_admin_exists(&app.db_pool, &bootstrap_settings())
+        .await
+        .unwrap();
+
+    assert!(!created);
+    assert_eq!(usernames(&app).await, ["admin"]);
+}
+
+#[tokio::test]
+async fn the_configured_admin_is_not_created_once_a_user_exists() {
+    let app = spawn_app().await;
+    let before = usernames(&app).await;
+
+    let created = ensure_admin_exists(&app.db_pool, &bootstrap_settings())
+        .await
+        .unwrap();
+
+    assert!(!created);
+    assert_eq!(usernames(&app).await, before);
+}
+
+async fn log_lines_for_a_slow_query(app: &TestApp, seconds: f64) -> Vec<serde_json::Value> {
+    let (logs, _guard) = CapturedLogs::start("info");
+    sqlx::query!(r#"SELECT true as "slept!" FROM pg_sleep($1)"#, seconds)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    logs.with_message("slow statement")
+}
+
+#[tokio::test]
+async fn queries_over_the_threshold_are_logged_as_slow() {
+    let app = spawn_app_with(|c| c.database.slow_query_threshold_milliseconds = Some(50)).await;
+
+    let slow_queries = log_lines_for_a_slow_query(&app, 0.2).await;
+
+    assert_eq!(slow_queries.len(), 1);
+    assert!(slow_queries[0]["db.statement"]
+        .as_str()
+        .unwrap()
+        .contains("pg_sleep"));
+}
+
+#[tokio::test]
+async fn slow_queries_are_not_logged_by_default() {
+    let app = spawn_app().await;
+
+    // Longer than the second after which sqlx would log it on its own.
+    assert!(log_lines_for_a_slow_query(&app, 1.2).await.is_empty());
+}
+
+#[tokio::test]
+async fn shutting_down_logs_a_report_with_the_deliveries_left() {
+    let app = spawn_app_with(|c| {
+        // Every background task is busy rather than idling for good.
+        c.subscriptions.confirmation_reminder_grace_seconds = 60;
+        c.webhooks.events_url = Some("http://127.0.0.1:9/events".into());
+    })
+    .await;
+    let issue_id = uuid::Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id, title, text_content, html_content, published_at
+        )
+        VALUES ($1, 'Title', 'Text', '<p>Html</p>', now())
+        "#,
+        issue_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    for email in ["a@example.com", "b@example.com"] {
+        sqlx::query!(
+            r#"
+            INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+            VALUES ($1, $2)
+            "#,
+            issue_id,
+            email
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+    let signal = ShutdownSignal::default();
+    // Stands in for the delivery worker, which stops once signalled.
+    let worker = tokio::spawn({
+        let signal = signal.clone();
+        async move {
+            signal.triggered().await;
+            Ok(())
+        }
+    });
+    let background = vec![
+        (
+            "Token cleanup",
+            tokio::spawn(run_token_cleanup_until_stopped(
+                app.configuration.clone(),
+                signal.clone(),
+            )),
+        ),
+        (
+            "Webhook worker",
+            tokio::spawn(run_webhook_worker_until_stopped(
+                app.configuration.clone(),
+                signal.clone(),
+            )),
+        ),
+        (
+            "Confirmation reminders",
+            tokio::spawn(run_confirmation_reminders_until_stopped(
+                app.configuration.clone(),
+                app.email_client.authorization_token().clone(),
+                signal.clone(),
+            )),
+        ),
+    ];
+    let (logs, _guard) = CapturedLogs::start("info");
+
+    shut_down(
+        app.server_handle.clone(),
+        &app.in_flight_requests,
+        &signal,
+        worker,
+        background,
+        &app.db_pool,
+        std::time::Duration::from_secs(5),
+    )
+    .await
+    .log();
+
+    let report = logs.with_message("Shutdown report");
+    assert_eq!(report.len(), 1);
+    assert_eq!(report[0]["queued_deliveries"], 2);
+    assert_eq!(report[0]["in_flight_requests_drained"], 0);
+    assert_eq!(report[0]["worker_finished_task"], true);
+    assert_eq!(report[0]["background_tasks_stopped"], true);
+    assert!(reqwest::get(format!("{}/health_check", app.address))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn tasks_that_dont_stop_share_a_single_shutdown_deadline() {
+    let app = spawn_app().await;
+    let never_stops = || tokio::spawn(std::future::pending::<Result<(), anyhow::Error>>());
+    let timeout = std::time::Duration::from_secs(1);
+    let started = std::time::Instant::now();
+
+    let report = shut_down(
+        app.server_handle.clone(),
+        &app.in_flight_requests,
+        &ShutdownSignal::default(),
+        never_stops(),
+        vec![("First", never_stops()), ("Second", never_stops())],
+        &app.db_pool,
+        timeout,
+    )
+    .await;
+
+    assert!(started.elapsed() < 2 * timeout);
+    assert!(!report.worker_finished_task);
+    assert!(!report.background_tasks_stopped);
+}
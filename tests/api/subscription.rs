@@ -1,9 +1,22 @@
 use wiremock::{
-    matchers::{method, path},
+    matchers::{any, method, path},
     Mock, ResponseTemplate,
 };
 
-use crate::helper::spawn_app;
+use actix_web::{test, web, App};
+use secrecy::Secret;
+use zero2prod::{
+    clock::{system_clock, Clock},
+    configuration::{
+        EmailTransport, FeatureFlags, SubscriptionSettings, TrailingSlashPolicy, WebhookSettings,
+    },
+    domain::SubscriberEmail,
+    email_client::EmailClient,
+    locale::Locale,
+    routes::{subscribe, ConfirmationEmailPermits},
+};
+
+use crate::helper::{spawn_app, spawn_app_with, CapturedLogs, TestApp};
 
 #[tokio::test]
 async fn subscribe_return_200_for_valid_input() {
@@ -33,6 +46,100 @@ async fn subscribe_return_200_for_valid_input() {
     assert_eq!(saved.status, "pending")
 }
 
+#[tokio::test]
+async fn subscribe_with_a_trailing_slash_behaves_like_without() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    for (route, email) in [
+        ("subscriptions", "ursula_le_guin%40gmail.com"),
+        ("subscriptions/", "le_guin%40gmail.com"),
+    ] {
+        let response = app
+            .api_client
+            .post(format!("{}/{route}", app.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(format!("name=le%20guin&email={email}"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200, "{route}");
+    }
+
+    let n_saved = sqlx::query!(r#"SELECT count(*) as "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_saved, 2);
+}
+
+#[tokio::test]
+async fn trailing_slashes_can_be_required_to_match_exactly() {
+    let app = spawn_app_with(|c| {
+        c.application.trailing_slash = TrailingSlashPolicy::MergeOnly;
+    })
+    .await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions/", app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn confirmation_emails_can_be_sent_in_the_background() {
+    let app = spawn_app_with(|c| c.features.background_confirmation_emails = true).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(2)))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let start = std::time::Instant::now();
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        start.elapsed() < std::time::Duration::from_secs(1),
+        "subscribe waited on the email provider: {:?}",
+        start.elapsed()
+    );
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "pending");
+    // The email still goes out.
+    for _ in 0..50 {
+        if !app
+            .email_server
+            .received_requests()
+            .await
+            .unwrap()
+            .is_empty()
+        {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 1);
+}
+
 #[tokio::test]
 async fn subscribe_return_40x_for_invalid_input() {
     let app = spawn_app().await;
@@ -53,6 +160,45 @@ async fn subscribe_return_40x_for_invalid_input() {
     }
 }
 
+#[tokio::test]
+async fn strict_email_validation_rejects_addresses_providers_would_bounce() {
+    let app = spawn_app_with(|c| c.subscriptions.strict_email_validation = true).await;
+    let test_cases = vec![
+        (
+            "name=le%20guin&email=Ursula%20%3Cursula%40gmail.com%3E",
+            "a display name",
+        ),
+        ("name=le%20guin&email=.ursula%40gmail.com", "a leading dot"),
+        ("name=le%20guin&email=ursula.%40gmail.com", "a trailing dot"),
+    ];
+
+    for (body, desc) in test_cases {
+        let response = app.post_subscriptions(body.to_owned()).await;
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not reject an email with {desc}."
+        );
+    }
+}
+
+#[tokio::test]
+async fn dots_around_the_local_part_are_accepted_by_default() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions("name=le%20guin&email=.ursula%40gmail.com".into())
+        .await;
+
+    assert_eq!(200, response.status().as_u16());
+}
+
 #[tokio::test]
 async fn subscribe_return_400_for_incomplete_input() {
     let app = spawn_app().await;
@@ -102,23 +248,165 @@ async fn subscribe_sends_a_confirmation_email_with_a_link() {
         .await;
     app.post_subscriptions(body.into()).await;
 
-    // Get the first intercepted request
-    let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    // Parse the body as JSON, starting from raw bytes
-    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
-
-    let get_link = |s: &str| {
-        let links: Vec<_> = linkify::LinkFinder::new()
-            .links(s)
-            .filter(|l| *l.kind() == linkify::LinkKind::Url)
-            .collect();
-        assert_eq!(links.len(), 1);
-        links[0].as_str().to_owned()
-    };
+    let email = app.last_email().await;
+    assert_eq!(email.html_links.len(), 1);
+    assert_eq!(email.text_links.len(), 1);
+    assert_eq!(email.html_links, email.text_links);
+}
+
+#[tokio::test]
+async fn confirmation_email_mentions_the_configured_link_expiry() {
+    let app = spawn_app_with(|c| c.subscriptions.confirmation_token_ttl_hours = 48).await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+
+    let email = app.last_email().await;
+    for content in [&email.html_body, &email.text_body] {
+        assert!(content.contains("This link expires in 48 hours."));
+    }
+}
 
-    let html_link = get_link(&body["HtmlBody"].as_str().unwrap());
-    let text_link = get_link(&body["TextBody"].as_str().unwrap());
-    assert_eq!(html_link, text_link);
+#[tokio::test]
+async fn the_filesystem_transport_writes_emails_to_the_outbox_instead_of_sending_them() {
+    let outbox = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+    let app = spawn_app_with(|c| {
+        c.email_client.transport = EmailTransport::Filesystem;
+        c.email_client.outbox_directory = Some(outbox.to_string_lossy().into_owned());
+    })
+    .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let resp = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    assert_eq!(resp.status().as_u16(), 200);
+    let files = std::fs::read_dir(&outbox)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    assert_eq!(files.len(), 1);
+    let email: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&files[0]).unwrap()).unwrap();
+    assert_eq!(email["To"], "ursula_le_guin@gmail.com");
+    assert_eq!(email["Subject"], "Welcome!");
+    std::fs::remove_dir_all(&outbox).unwrap();
+}
+
+#[tokio::test]
+async fn ampersands_in_names_are_rejected_by_default() {
+    let app = spawn_app().await;
+
+    let resp = app
+        .post_subscriptions("name=Tom%20%26%20Jerry%20Fan&email=tom%40example.com".into())
+        .await;
+
+    assert_eq!(resp.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn relaxed_names_are_accepted_and_escaped_when_rendered() {
+    let app = spawn_app_with(|c| {
+        c.subscriptions.name_forbidden_characters = Some("<>".into());
+    })
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let resp = app
+        .post_subscriptions("name=Tom%20%26%20Jerry%20%22Fan%22&email=tom%40example.com".into())
+        .await;
+
+    assert_eq!(resp.status().as_u16(), 200);
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    app.test_user.login(&app).await;
+    let html_page = app.get_subscriber_html(subscriber_id).await;
+    assert!(
+        html_page.contains("Tom &amp; Jerry &quot;Fan&quot;"),
+        "{html_page}"
+    );
+}
+
+#[tokio::test]
+async fn subscriptions_beyond_the_in_flight_limit_are_shed() {
+    let app = spawn_app_with(|c| c.subscriptions.max_in_flight_requests = 1).await;
+    // Keeps the first request in flight while the others come in.
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(std::time::Duration::from_millis(500)))
+        .mount(&app.email_server)
+        .await;
+
+    let responses =
+        futures_util::future::join_all((0..3).map(|i| {
+            app.post_subscriptions(format!("name=le%20guin&email=ursula_{i}%40gmail.com"))
+        }))
+        .await;
+
+    let statuses: Vec<u16> = responses.iter().map(|r| r.status().as_u16()).collect();
+    assert_eq!(
+        statuses.iter().filter(|s| **s == 200).count(),
+        1,
+        "{statuses:?}"
+    );
+    assert_eq!(
+        statuses.iter().filter(|s| **s == 503).count(),
+        2,
+        "{statuses:?}"
+    );
+    for shed in responses.iter().filter(|r| r.status().as_u16() == 503) {
+        assert_eq!(shed.headers()["Retry-After"], "1");
+    }
+    // Once the first one is done, there is room again.
+    let resp = app
+        .post_subscriptions("name=le%20guin&email=ursula_3%40gmail.com".into())
+        .await;
+    assert_eq!(resp.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn subscriptions_through_get_share_the_in_flight_limit() {
+    let app = spawn_app_with(|c| {
+        c.features.subscribe_via_get = true;
+        c.subscriptions.max_in_flight_requests = 1;
+    })
+    .await;
+    // Keeps the first request in flight while the other comes in.
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(std::time::Duration::from_millis(500)))
+        .mount(&app.email_server)
+        .await;
+
+    let (in_flight, shed) = tokio::join!(
+        app.post_subscriptions("name=le%20guin&email=ursula_0%40gmail.com".into()),
+        async {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            app.get_subscriptions_new("name=le%20guin&email=ursula_1%40gmail.com")
+                .await
+        }
+    );
+
+    assert_eq!(in_flight.status().as_u16(), 200);
+    assert_eq!(shed.status().as_u16(), 503);
+    assert_eq!(shed.headers()["Retry-After"], "1");
 }
 
 #[tokio::test]
@@ -134,3 +422,485 @@ async fn subscribe_fails_if_fatal_db_error() {
 
     assert_eq!(resp.status().as_u16(), 500);
 }
+
+/// Make the first insert into `subscriptions` fail with a serialization
+/// failure. A sequence keeps count, since it isn't rolled back along with
+/// the failed transaction.
+async fn fail_first_subscriber_insert(app: &TestApp) {
+    sqlx::query!("CREATE SEQUENCE subscriber_insert_attempts;")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    sqlx::query!(
+        r#"
+        CREATE FUNCTION fail_first_subscriber_insert() RETURNS trigger AS $$
+        BEGIN
+            IF nextval('subscriber_insert_attempts') = 1 THEN
+                RAISE EXCEPTION 'simulated contention' USING ERRCODE = 'serialization_failure';
+            END IF;
+            RETURN NEW;
+        END
+        $$ LANGUAGE plpgsql;
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        r#"
+        CREATE TRIGGER fail_first_subscriber_insert BEFORE INSERT ON subscriptions
+        FOR EACH ROW EXECUTE FUNCTION fail_first_subscriber_insert();
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn subscribe_is_retried_after_a_serialization_failure() {
+    let app = spawn_app().await;
+    fail_first_subscriber_insert(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let resp = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    assert_eq!(resp.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT email, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+    assert_eq!(saved.status, "pending");
+}
+
+/// Make the first commit of a new subscriber fail with a serialization
+/// failure, raised by a deferred trigger once the transaction commits.
+async fn fail_first_subscriber_commit(app: &TestApp) {
+    sqlx::query!("CREATE SEQUENCE subscriber_commit_attempts;")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    sqlx::query!(
+        r#"
+        CREATE FUNCTION fail_first_subscriber_commit() RETURNS trigger AS $$
+        BEGIN
+            IF nextval('subscriber_commit_attempts') = 1 THEN
+                RAISE EXCEPTION 'simulated contention' USING ERRCODE = 'serialization_failure';
+            END IF;
+            RETURN NULL;
+        END
+        $$ LANGUAGE plpgsql;
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        r#"
+        CREATE CONSTRAINT TRIGGER fail_first_subscriber_commit AFTER INSERT ON subscriptions
+        DEFERRABLE INITIALLY DEFERRED
+        FOR EACH ROW EXECUTE FUNCTION fail_first_subscriber_commit();
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn subscribe_is_retried_after_a_serialization_failure_at_commit() {
+    let app = spawn_app().await;
+    fail_first_subscriber_commit(&app).await;
+    // The email only goes out once the subscriber is committed.
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let resp = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    assert_eq!(resp.status().as_u16(), 200);
+    let emails = app.email_server.received_requests().await.unwrap();
+    let link = app.get_confirmation_link(&emails[0]).await;
+    let resp = reqwest::get(link).await.unwrap();
+    assert_eq!(resp.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn subscribe_fails_after_a_serialization_failure_when_retries_are_disabled() {
+    let app = spawn_app_with(|c| c.subscriptions.max_transaction_retries = 0).await;
+    fail_first_subscriber_insert(&app).await;
+
+    let resp = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    // Trying again later should go through.
+    assert_eq!(resp.status().as_u16(), 503);
+}
+
+#[tokio::test]
+async fn subscribing_an_email_that_is_already_subscribed_is_a_conflict() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    assert_eq!(
+        app.post_subscriptions(body.into()).await.status().as_u16(),
+        200
+    );
+
+    let resp = app.post_subscriptions(body.into()).await;
+
+    assert_eq!(resp.status().as_u16(), 409);
+}
+
+#[tokio::test]
+async fn confirmation_emails_can_be_resent_up_to_the_configured_maximum() {
+    let app = spawn_app_with(|c| {
+        c.subscriptions.max_confirmation_resends = 2;
+        // The cap holds however slowly the requests come in.
+        c.rate_limit.enabled = false;
+    })
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    for _ in 0..2 {
+        let resp = app
+            .post_resend_confirmation("ursula_le_guin@gmail.com")
+            .await;
+        assert_eq!(resp.status().as_u16(), 200);
+    }
+    for _ in 0..2 {
+        let resp = app
+            .post_resend_confirmation("ursula_le_guin@gmail.com")
+            .await;
+        assert_eq!(resp.status().as_u16(), 403);
+        assert!(resp
+            .text()
+            .await
+            .unwrap()
+            .contains("No more confirmation emails can be sent to this address."));
+    }
+
+    // Every link sent remains valid.
+    let requests = app.email_server.received_requests().await.unwrap();
+    let confirmation_link = app.get_confirmation_link(requests.last().unwrap()).await;
+    let resp = reqwest::get(confirmation_link).await.unwrap();
+    assert_eq!(resp.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn every_confirmation_email_sent_is_counted_and_timestamped() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    let confirmation_emails = || async {
+        sqlx::query!(
+            "SELECT id, confirmation_emails_sent, last_confirmation_email_sent_at \
+            FROM subscriptions"
+        )
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+    };
+
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+    let sent = confirmation_emails().await;
+    assert_eq!(sent.confirmation_emails_sent, 1);
+    assert_eq!(sent.last_confirmation_email_sent_at, Some(app.clock.now()));
+
+    app.clock.advance(chrono::Duration::minutes(10));
+    app.post_resend_confirmation("ursula_le_guin@gmail.com")
+        .await
+        .error_for_status()
+        .unwrap();
+    let resent = confirmation_emails().await;
+    assert_eq!(resent.confirmation_emails_sent, 2);
+    assert_eq!(
+        resent.last_confirmation_email_sent_at,
+        Some(app.clock.now())
+    );
+
+    app.test_user.login(&app).await;
+    let html_page = app.get_subscriber_html(resent.id).await;
+    assert!(html_page.contains("<tr><th>Confirmation emails sent</th><td>2</td></tr>"));
+    assert!(html_page.contains(&app.clock.now().to_rfc3339()));
+}
+
+#[tokio::test]
+async fn resending_to_an_address_without_a_pending_subscription_sends_nothing() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let resp = app.post_resend_confirmation("nobody@example.com").await;
+
+    assert_eq!(resp.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn subscribe_form_posts_name_and_email_to_subscriptions() {
+    let app = spawn_app().await;
+
+    let resp = app.get_subscribe_form().await;
+
+    assert_eq!(resp.status().as_u16(), 200);
+    let html_page = resp.text().await.unwrap();
+    assert!(html_page.contains(r#"<form action="/subscriptions" method="post">"#));
+    assert!(html_page.contains(r#"name="name""#));
+    assert!(html_page.contains(r#"name="email""#));
+}
+
+#[tokio::test]
+async fn subscribe_form_is_rendered_in_the_language_the_client_prefers() {
+    let app = spawn_app().await;
+    let get_form = |accept_language: &'static str| {
+        app.api_client
+            .get(format!("{}/subscribe", app.address))
+            .header("Accept-Language", accept_language)
+            .send()
+    };
+
+    let french = get_form("fr-FR,fr;q=0.9,en;q=0.5").await.unwrap();
+    assert_eq!(french.headers()["Vary"], "accept-language");
+    let french = french.text().await.unwrap();
+    assert!(french.contains(r#"<html lang="fr">"#));
+    assert!(french.contains("Abonnez-vous à notre newsletter"));
+    assert!(french.contains("S'abonner"));
+
+    // Unsupported languages fall back to the default.
+    let german = get_form("de-DE,de").await.unwrap().text().await.unwrap();
+    assert!(german.contains(r#"<html lang="en">"#));
+    assert!(german.contains("Subscribe to our newsletter"));
+}
+
+#[tokio::test]
+async fn subscribe_form_falls_back_to_the_configured_default_locale() {
+    let app = spawn_app_with(|c| c.application.default_locale = Locale::Fr).await;
+
+    let html_page = app.get_subscribe_form().await.text().await.unwrap();
+
+    assert!(html_page.contains("Abonnez-vous à notre newsletter"));
+}
+
+#[tokio::test]
+async fn subscribe_form_is_not_served_when_disabled() {
+    let app = spawn_app_with(|c| c.features.serve_subscribe_form = false).await;
+
+    let resp = app.get_subscribe_form().await;
+
+    assert_eq!(resp.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn subscribing_through_get_sends_a_confirmation_email_when_enabled() {
+    let app = spawn_app_with(|c| c.features.subscribe_via_get = true).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let resp = app
+        .get_subscriptions_new("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+
+    assert_eq!(resp.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT email, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+    assert_eq!(saved.status, "pending");
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    app.get_confirmation_link(email_request).await;
+}
+
+#[tokio::test]
+async fn subscribing_through_get_shares_the_rate_limit_of_the_form() {
+    let app = spawn_app_with(|c| {
+        c.features.subscribe_via_get = true;
+        c.rate_limit.capacity = 1;
+    })
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let resp = app
+        .post_subscriptions("name=le%20guin&email=ursula_0%40gmail.com".into())
+        .await;
+    assert_eq!(resp.status().as_u16(), 200);
+
+    let resp = app
+        .get_subscriptions_new("name=le%20guin&email=ursula_1%40gmail.com")
+        .await;
+    assert_eq!(resp.status().as_u16(), 429);
+}
+
+#[tokio::test]
+async fn subscribing_through_get_is_not_routed_by_default() {
+    let app = spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let resp = app
+        .get_subscriptions_new("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+
+    assert_eq!(resp.status().as_u16(), 404);
+}
+
+/// Post a malformed subscription, on the test thread so that its logs can be
+/// captured, and return the lines logging the request body.
+async fn log_lines_for_a_malformed_subscription(
+    app: &TestApp,
+    log_request_bodies: bool,
+) -> Vec<serde_json::Value> {
+    let email_client = EmailClient::new(
+        "sender@example.com".parse::<SubscriberEmail>().unwrap(),
+        app.email_server.uri(),
+        Secret::new("token".into()),
+        std::time::Duration::from_millis(200),
+    )
+    .unwrap();
+    let service = test::init_service(
+        App::new()
+            .route("/subscriptions", web::post().to(subscribe))
+            .app_data(web::Data::new(app.db_pool.clone()))
+            .app_data(web::Data::new(email_client))
+            .app_data(web::Data::new(app.links.clone()))
+            .app_data(web::Data::new(SubscriptionSettings {
+                max_name_length: 256,
+                max_in_flight_requests: 0,
+                name_forbidden_characters: None,
+                strict_email_validation: false,
+                confirmation_token_ttl_hours: 24,
+                token_cleanup_interval_seconds: 3600,
+                confirmation_token_signing_key: None,
+                max_concurrent_confirmation_emails: 10,
+                max_transaction_retries: 2,
+                transaction_retry_delay_milliseconds: 0,
+                unsubscribe_confirmation_subject: "You have been unsubscribed".into(),
+                unsubscribe_confirmation_message: "Goodbye.".into(),
+                max_confirmation_resends: 3,
+                max_confirmed_subscribers: 0,
+                confirmation_max_retries: 0,
+                confirmation_retry_delay_milliseconds: 0,
+                confirmation_reminder_grace_seconds: 0,
+                confirmation_reminder_interval_seconds: 300,
+                confirmation_reminder_max_attempts: 3,
+            }))
+            .app_data(web::Data::new(ConfirmationEmailPermits::new(10)))
+            .app_data(web::Data::from(system_clock()))
+            .app_data(web::Data::new(WebhookSettings {
+                postmark_secret: Secret::new("postmark-secret".into()),
+                events_url: None,
+                events_max_attempts: 5,
+                events_retry_base_delay_milliseconds: 1000,
+            }))
+            .app_data(web::Data::new(FeatureFlags {
+                log_request_bodies,
+                ..FeatureFlags::default()
+            })),
+    )
+    .await;
+
+    let (logs, _guard) = CapturedLogs::start("debug");
+    let request = test::TestRequest::post()
+        .uri("/subscriptions")
+        .insert_header(("Content-Type", "application/x-www-form-urlencoded"))
+        .set_payload("name=le%20guin&email=not-an-email&password=hunter2")
+        .to_request();
+    let response = test::call_service(&service, request).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    logs.with_message("Rejected a subscription request")
+}
+
+#[tokio::test]
+async fn malformed_subscription_bodies_are_logged_redacted_when_enabled() {
+    let app = spawn_app().await;
+
+    let lines = log_lines_for_a_malformed_subscription(&app, true).await;
+
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0]["level"], 20);
+    assert_eq!(
+        lines[0]["request_body"],
+        "name=<redacted, 7 chars>&email=<redacted, 12 chars>&<1 unknown fields>"
+    );
+    // Fields we don't know about are never logged
+    assert!(!lines[0].to_string().contains("hunter2"));
+}
+
+#[tokio::test]
+async fn malformed_subscription_bodies_are_not_logged_by_default() {
+    let app = spawn_app().await;
+
+    let lines = log_lines_for_a_malformed_subscription(&app, false).await;
+
+    assert!(lines.is_empty());
+}
+
+#[tokio::test]
+async fn the_database_only_accepts_known_subscription_statuses() {
+    let app = spawn_app().await;
+
+    let misspelled = sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES (gen_random_uuid(), 'ursula_le_guin@gmail.com', 'le guin', now(), 'confimed')
+        "#
+    )
+    .execute(&app.db_pool)
+    .await;
+
+    let error = misspelled.unwrap_err();
+    assert!(
+        error.to_string().contains("subscriptions_status_check"),
+        "{error}"
+    );
+}
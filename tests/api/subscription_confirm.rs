@@ -3,7 +3,11 @@ use wiremock::{
     Mock, ResponseTemplate,
 };
 
-use crate::helper::spawn_app;
+use chrono::Duration;
+use secrecy::Secret;
+use zero2prod::token_cleanup::purge_stale_tokens;
+
+use crate::helper::{assert_is_redirect_to, spawn_app, spawn_app_with, TestApp};
 
 #[tokio::test]
 async fn subscribe_confirm_return_400_for_empty_token() {
@@ -28,7 +32,7 @@ async fn confirm_subscriber_with_confirm_link() {
     app.post_subscriptions(body.into()).await;
 
     let subscribe_req = &app.email_server.received_requests().await.unwrap()[0];
-    let confirmation_link = app.get_confirmation_link(&subscribe_req).await;
+    let confirmation_link = app.get_confirmation_link(subscribe_req).await;
 
     let resp_confirm = reqwest::get(confirmation_link).await.unwrap();
     assert_eq!(resp_confirm.status().as_u16(), 200);
@@ -41,3 +45,525 @@ async fn confirm_subscriber_with_confirm_link() {
     assert_eq!(saved.name, "le guin");
     assert_eq!(saved.status, "confirmed");
 }
+
+async fn spawn_app_signing_tokens() -> TestApp {
+    spawn_app_with(|c| {
+        c.subscriptions.confirmation_token_signing_key =
+            Some(Secret::new("a-confirmation-signing-key".into()))
+    })
+    .await
+}
+
+#[tokio::test]
+async fn signed_confirmation_links_confirm_the_subscriber() {
+    let app = spawn_app_signing_tokens().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    let subscribe_req = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = app.get_confirmation_link(subscribe_req).await;
+    let token = confirmation_link
+        .query_pairs()
+        .find(|(k, _)| k == "subscription_token")
+        .unwrap()
+        .1
+        .into_owned();
+    assert!(token.contains('.'), "{token}");
+
+    let resp_confirm = reqwest::get(confirmation_link).await.unwrap();
+    assert_eq!(resp_confirm.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn tampered_confirmation_tokens_are_rejected_before_querying_the_database() {
+    let app = spawn_app_signing_tokens().await;
+    // Any lookup would now fail with a 500.
+    sqlx::query!("ALTER TABLE subscription_tokens DROP COLUMN subscription_token;")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    for token in ["forged", "forged.c2lnbmF0dXJl"] {
+        let response = reqwest::get(format!(
+            "{}/subscriptions/confirm?subscription_token={token}",
+            app.address
+        ))
+        .await
+        .unwrap();
+        assert_eq!(response.status().as_u16(), 401, "{token}");
+    }
+}
+
+#[tokio::test]
+async fn confirmation_links_carry_a_stable_subscriber_reference() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+
+    let subscribe_req = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = app.get_confirmation_link(subscribe_req).await;
+    let reference = confirmation_link
+        .query_pairs()
+        .find(|(k, _)| k == "subscriber_ref")
+        .unwrap()
+        .1
+        .into_owned();
+
+    // Derived from the subscriber id alone, so any email sent to the same
+    // subscriber carries the same reference.
+    assert_eq!(reference, app.links.subscriber_reference(subscriber_id));
+    assert!(!reference.contains("ursula_le_guin"));
+    let resp_confirm = reqwest::get(confirmation_link).await.unwrap();
+    assert_eq!(resp_confirm.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn clicking_a_confirmation_link_twice_succeeds_both_times() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    let subscribe_req = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = app.get_confirmation_link(subscribe_req).await;
+
+    let first = reqwest::get(confirmation_link.clone()).await.unwrap();
+    let second = reqwest::get(confirmation_link).await.unwrap();
+
+    assert_eq!(first.status().as_u16(), 200);
+    assert!(first
+        .text()
+        .await
+        .unwrap()
+        .contains("Your subscription has been confirmed."));
+    assert_eq!(second.status().as_u16(), 200);
+    assert!(second
+        .text()
+        .await
+        .unwrap()
+        .contains("Your subscription was already confirmed."));
+}
+
+#[tokio::test]
+async fn clicking_a_confirmation_link_again_after_a_token_purge_is_still_already_confirmed() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    let subscribe_req = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = app.get_confirmation_link(subscribe_req).await;
+    let first = reqwest::get(confirmation_link.clone()).await.unwrap();
+    assert_eq!(first.status().as_u16(), 200);
+
+    purge_stale_tokens(
+        &app.db_pool,
+        std::time::Duration::from_secs(24 * 60 * 60),
+        std::time::Duration::from_secs(30),
+    )
+    .await
+    .unwrap();
+    let second = reqwest::get(confirmation_link).await.unwrap();
+
+    assert_eq!(second.status().as_u16(), 200);
+    assert!(second
+        .text()
+        .await
+        .unwrap()
+        .contains("Your subscription was already confirmed."));
+}
+
+#[tokio::test]
+async fn confirmations_are_refused_once_the_confirmed_subscriber_cap_is_reached() {
+    let app = spawn_app_with(|c| c.subscriptions.max_confirmed_subscribers = 1).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    for body in [
+        "name=le%20guin&email=ursula_le_guin%40gmail.com",
+        "name=butler&email=octavia_butler%40gmail.com",
+    ] {
+        app.post_subscriptions(body.into())
+            .await
+            .error_for_status()
+            .unwrap();
+    }
+    let emails = app.sent_emails().await;
+    assert_eq!(emails.len(), 2);
+    assert!(emails.iter().all(|email| email.html_links.len() == 1));
+    let first_link = app.local_link(&emails[0].html_links[0]);
+    let second_link = app.local_link(&emails[1].html_links[0]);
+
+    let response = reqwest::get(first_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let response = reqwest::get(second_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("We can't take any more subscribers at the moment"));
+
+    let statuses: Vec<(String, String)> =
+        sqlx::query!("SELECT email, status FROM subscriptions ORDER BY email")
+            .fetch_all(&app.db_pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.email, r.status))
+            .collect();
+    assert_eq!(
+        statuses,
+        [
+            ("octavia_butler@gmail.com".into(), "pending".into()),
+            ("ursula_le_guin@gmail.com".into(), "confirmed".into()),
+        ]
+    );
+
+    // The cap holds for admins too.
+    let pending_id = sqlx::query!("SELECT id FROM subscriptions WHERE status = 'pending'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    app.test_user.login(&app).await;
+    let response = app.post_confirm_subscriber(pending_id).await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_confirm_a_subscriber() {
+    let app = spawn_app().await;
+
+    let response = app.post_confirm_subscriber(uuid::Uuid::new_v4()).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn manually_confirming_a_subscriber_invalidates_their_confirmation_link() {
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions(body.into()).await;
+    let subscribe_req = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = app.get_confirmation_link(subscribe_req).await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    app.test_user.login(&app).await;
+
+    let response = app.post_confirm_subscriber(subscriber_id).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+    let audit = sqlx::query!("SELECT action, target FROM audit_log")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(audit.action, "confirm_subscriber");
+    assert_eq!(audit.target, subscriber_id.to_string());
+
+    // The token has been cleared
+    let resp_confirm = reqwest::get(confirmation_link).await.unwrap();
+    assert_eq!(resp_confirm.status().as_u16(), 401);
+
+    // Confirming twice is reported
+    let response = app.post_confirm_subscriber(subscriber_id).await;
+    assert_eq!(response.status().as_u16(), 409);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "The subscriber is already confirmed"
+    );
+}
+
+#[tokio::test]
+async fn unsubscribed_subscribers_cannot_be_confirmed_manually() {
+    let app = spawn_app().await;
+    let subscriber_id = uuid::Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, 'ursula_le_guin@gmail.com', 'le guin', now(), 'unsubscribed')
+        "#,
+        subscriber_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.test_user.login(&app).await;
+
+    let response = app.post_confirm_subscriber(subscriber_id).await;
+
+    assert_eq!(response.status().as_u16(), 409);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "The subscriber has unsubscribed"
+    );
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "unsubscribed");
+}
+
+#[tokio::test]
+async fn following_a_confirmation_link_after_unsubscribing_does_not_resubscribe() {
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions(body.into()).await;
+    let subscribe_req = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = app.get_confirmation_link(subscribe_req).await;
+    sqlx::query!("UPDATE subscriptions SET status = 'unsubscribed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let response = reqwest::get(confirmation_link).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 409);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("You have unsubscribed since this link was sent."));
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "unsubscribed");
+}
+
+#[tokio::test]
+async fn expired_confirmation_links_are_rejected() {
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions(body.into()).await;
+    let subscribe_req = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = app.get_confirmation_link(subscribe_req).await;
+    sqlx::query!("UPDATE subscription_tokens SET created_at = now() - interval '25 hours'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let resp_confirm = reqwest::get(confirmation_link).await.unwrap();
+
+    assert_eq!(resp_confirm.status().as_u16(), 401);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "pending");
+}
+
+#[tokio::test]
+async fn confirmation_links_expire_right_after_the_ttl_and_skew_tolerance() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    let subscribe_req = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = app.get_confirmation_link(subscribe_req).await;
+
+    // 24 hours of TTL and 30 seconds of skew tolerance, plus one second
+    app.clock
+        .advance(Duration::hours(24) + Duration::seconds(31));
+    let expired = reqwest::get(confirmation_link.clone()).await.unwrap();
+    app.clock.advance(Duration::seconds(-1));
+    let on_the_boundary = reqwest::get(confirmation_link).await.unwrap();
+
+    assert_eq!(expired.status().as_u16(), 401);
+    assert_eq!(on_the_boundary.status().as_u16(), 200);
+}
+
+/// Make the first update of a subscriber fail with a serialization
+/// failure, which is transient.
+async fn fail_first_subscriber_update(app: &TestApp) {
+    sqlx::query!("CREATE SEQUENCE subscriber_update_attempts;")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    sqlx::query!(
+        r#"
+        CREATE FUNCTION fail_first_subscriber_update() RETURNS trigger AS $$
+        BEGIN
+            IF nextval('subscriber_update_attempts') = 1 THEN
+                RAISE EXCEPTION 'simulated blip' USING ERRCODE = 'serialization_failure';
+            END IF;
+            RETURN NEW;
+        END
+        $$ LANGUAGE plpgsql;
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        r#"
+        CREATE TRIGGER fail_first_subscriber_update BEFORE UPDATE ON subscriptions
+        FOR EACH ROW EXECUTE FUNCTION fail_first_subscriber_update();
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+async fn subscribe_and_get_confirmation_link(app: &TestApp) -> reqwest::Url {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    let subscribe_req = &app.email_server.received_requests().await.unwrap()[0];
+    app.get_confirmation_link(subscribe_req).await
+}
+
+#[tokio::test]
+async fn confirming_is_retried_after_a_transient_failure() {
+    let app = spawn_app().await;
+    let confirmation_link = subscribe_and_get_confirmation_link(&app).await;
+    fail_first_subscriber_update(&app).await;
+
+    let resp = reqwest::get(confirmation_link).await.unwrap();
+
+    assert_eq!(resp.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn confirming_asks_to_try_again_once_retries_are_exhausted() {
+    let app = spawn_app_with(|c| c.subscriptions.confirmation_max_retries = 0).await;
+    let confirmation_link = subscribe_and_get_confirmation_link(&app).await;
+    fail_first_subscriber_update(&app).await;
+
+    let resp = reqwest::get(confirmation_link.clone()).await.unwrap();
+
+    assert_eq!(resp.status().as_u16(), 503);
+    assert!(resp.text().await.unwrap().contains("try the link again"));
+    // Which does work once the blip is over.
+    let resp = reqwest::get(confirmation_link).await.unwrap();
+    assert_eq!(resp.status().as_u16(), 200);
+}
+
+async fn get_with_accept(link: &reqwest::Url, accept: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .get(link.clone())
+        .header("Accept", accept)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn confirm_answers_browsers_with_a_page_and_api_clients_with_json() {
+    let app = spawn_app().await;
+    let confirmation_link = subscribe_and_get_confirmation_link(&app).await;
+    let browser_accept = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+
+    let page = get_with_accept(&confirmation_link, browser_accept).await;
+    assert_eq!(page.status().as_u16(), 200);
+    assert!(page.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    assert!(page
+        .text()
+        .await
+        .unwrap()
+        .contains("Your subscription has been confirmed."));
+
+    // Following the same link again.
+    let json = get_with_accept(&confirmation_link, "application/json").await;
+    assert_eq!(json.status().as_u16(), 200);
+    let body: serde_json::Value = json.json().await.unwrap();
+    assert_eq!(body["status"], "already_confirmed");
+    let page = get_with_accept(&confirmation_link, browser_accept).await;
+    assert_eq!(page.status().as_u16(), 200);
+    assert!(page.text().await.unwrap().contains("already confirmed"));
+}
+
+#[tokio::test]
+async fn confirm_failures_have_the_same_status_in_both_formats() {
+    let app = spawn_app().await;
+    let confirmation_link = subscribe_and_get_confirmation_link(&app).await;
+    let mut unknown_link = confirmation_link.clone();
+    unknown_link.set_query(Some("subscription_token=unknown"));
+    app.clock.advance(Duration::hours(25));
+
+    for (link, status) in [
+        (&unknown_link, "invalid_token"),
+        (&confirmation_link, "expired_token"),
+    ] {
+        let json = get_with_accept(link, "application/json").await;
+        assert_eq!(json.status().as_u16(), 401);
+        let body: serde_json::Value = json.json().await.unwrap();
+        assert_eq!(body["status"], status);
+
+        let page = get_with_accept(link, "text/html").await;
+        assert_eq!(page.status().as_u16(), 401);
+        assert!(page
+            .text()
+            .await
+            .unwrap()
+            .contains("<p>This confirmation link"));
+    }
+}
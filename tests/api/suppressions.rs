@@ -0,0 +1,92 @@
+use uuid::Uuid;
+
+use crate::helper::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn insert_confirmed_subscriber(app: &TestApp, email: &str) {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, 'le guin', now(), 'confirmed')
+        "#,
+        Uuid::new_v4(),
+        email
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_import_suppressions() {
+    let app = spawn_app().await;
+
+    let response = app.post_import_suppressions("ursula@example.com").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn long_invalid_entries_are_cut_in_the_import_report() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let long_entries = vec!["<".repeat(2000); 20].join("\n");
+
+    let response = app.post_import_suppressions(&long_entries).await;
+    assert_is_redirect_to(&response, "/admin/suppressions");
+
+    let html = app.get_suppressions_html().await;
+    assert!(html.contains(&format!(
+        "Skipped 20 invalid entries: {}...",
+        "&lt;".repeat(64)
+    )));
+    assert!(!html.contains(&"&lt;".repeat(65)));
+}
+
+#[tokio::test]
+async fn valid_addresses_of_an_import_are_suppressed_and_invalid_ones_reported() {
+    let app = spawn_app().await;
+    for email in [
+        "ursula@example.com",
+        "le.guin@example.com",
+        "kept@example.com",
+    ] {
+        insert_confirmed_subscriber(&app, email).await;
+    }
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_import_suppressions(
+            "Ursula@example.com\nnot-an-email\nle.guin@example.com,<b>bold</b>\n",
+        )
+        .await;
+    assert_is_redirect_to(&response, "/admin/suppressions");
+
+    let html = app.get_suppressions_html().await;
+    assert!(html.contains(
+        "Suppressed 2 new addresses, 0 were already suppressed. \
+        Skipped 2 invalid entries: not-an-email, &lt;b&gt;bold&lt;/b&gt;"
+    ));
+    assert!(html.contains("2 addresses are never emailed."));
+
+    // Importing them again changes nothing
+    app.post_import_suppressions("le.guin@example.com").await;
+    assert!(app
+        .get_suppressions_html()
+        .await
+        .contains("Suppressed 0 new addresses, 1 were already suppressed."));
+
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    let response = app.post_publish_newsletters(&body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let queued = sqlx::query!("SELECT subscriber_email FROM issue_delivery_queue")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].subscriber_email, "kept@example.com");
+}
@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+use zero2prod::token_cleanup::purge_stale_tokens;
+
+use crate::helper::spawn_app;
+
+async fn seed_token(pool: &PgPool, token: &str, status: &str, age: chrono::Duration) {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, 'le guin', $3, $4)
+        "#,
+        subscriber_id,
+        format!("{token}@example.com"),
+        Utc::now() - age,
+        status
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscription_tokens (subscription_token, subscriber_id, created_at)
+        VALUES ($1, $2, $3)
+        "#,
+        token,
+        subscriber_id,
+        Utc::now() - age
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn expired_tokens_are_purged_whatever_the_subscriber_status() {
+    let app = spawn_app().await;
+    seed_token(
+        &app.db_pool,
+        "expired",
+        "pending",
+        chrono::Duration::hours(25),
+    )
+    .await;
+    seed_token(
+        &app.db_pool,
+        "expired_and_used",
+        "confirmed",
+        chrono::Duration::hours(25),
+    )
+    .await;
+    seed_token(
+        &app.db_pool,
+        "used",
+        "confirmed",
+        chrono::Duration::minutes(5),
+    )
+    .await;
+    seed_token(
+        &app.db_pool,
+        "fresh",
+        "pending",
+        chrono::Duration::minutes(5),
+    )
+    .await;
+
+    let n_deleted = purge_stale_tokens(
+        &app.db_pool,
+        Duration::from_secs(24 * 60 * 60),
+        Duration::from_secs(30),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(n_deleted, 2);
+    let remaining: Vec<String> = sqlx::query!(
+        "SELECT subscription_token FROM subscription_tokens ORDER BY subscription_token"
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|r| r.subscription_token)
+    .collect();
+    // A used token is kept for the "already confirmed" page until it expires.
+    assert_eq!(remaining, vec!["fresh".to_string(), "used".to_string()]);
+}
@@ -0,0 +1,168 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use actix_web::{middleware::from_fn, test, web, App, Handler, HttpResponse, Responder};
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+use zero2prod::{
+    transaction::{commit_request_transaction, RequestTransaction},
+    utils::e500,
+};
+
+use crate::helper::{spawn_app, TestApp};
+
+async fn insert_subscriber(transaction: &RequestTransaction) -> Result<(), actix_web::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, $4, 'pending')
+        "#,
+        Uuid::new_v4(),
+        "ursula_le_guin@gmail.com",
+        "le guin",
+        Utc::now()
+    )
+    .execute(&mut **transaction.lock().await)
+    .await
+    .map_err(e500)?;
+    Ok(())
+}
+
+async fn write_then_succeed(
+    transaction: RequestTransaction,
+) -> Result<HttpResponse, actix_web::Error> {
+    insert_subscriber(&transaction).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+async fn write_then_fail(
+    transaction: RequestTransaction,
+) -> Result<HttpResponse, actix_web::Error> {
+    insert_subscriber(&transaction).await?;
+    Err(e500("Something went wrong after the first write"))
+}
+
+async fn count_subscriptions(pool: &PgPool) -> i64 {
+    sqlx::query!(r#"SELECT count(*) as "count!" FROM subscriptions"#)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+        .count
+}
+
+#[tokio::test]
+async fn request_transaction_is_committed_when_the_handler_succeeds() {
+    let app = spawn_app().await;
+    let service = test::init_service(
+        App::new()
+            .wrap(from_fn(commit_request_transaction))
+            .route("/", web::post().to(write_then_succeed))
+            .app_data(web::Data::new(app.db_pool.clone())),
+    )
+    .await;
+
+    let resp = test::call_service(&service, test::TestRequest::post().uri("/").to_request()).await;
+
+    assert_eq!(resp.status().as_u16(), 200);
+    assert_eq!(count_subscriptions(&app.db_pool).await, 1);
+}
+
+#[tokio::test]
+async fn request_transaction_is_rolled_back_when_the_handler_fails() {
+    let app = spawn_app().await;
+    let service = test::init_service(
+        App::new()
+            .wrap(from_fn(commit_request_transaction))
+            .route("/", web::post().to(write_then_fail))
+            .app_data(web::Data::new(app.db_pool.clone())),
+    )
+    .await;
+
+    let resp = test::call_service(&service, test::TestRequest::post().uri("/").to_request()).await;
+
+    assert_eq!(resp.status().as_u16(), 500);
+    assert_eq!(count_subscriptions(&app.db_pool).await, 0);
+}
+
+type Notified = web::Data<Arc<AtomicBool>>;
+
+async fn write_then_notify(
+    transaction: RequestTransaction,
+    notified: Notified,
+) -> Result<HttpResponse, actix_web::Error> {
+    insert_subscriber(&transaction).await?;
+    transaction
+        .after_commit(async move { notified.store(true, Ordering::SeqCst) })
+        .await;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// The handler succeeds, but the commit fails on a deferred constraint.
+async fn notify_then_fail_to_commit(
+    transaction: RequestTransaction,
+    notified: Notified,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut t = transaction.lock().await;
+    sqlx::query("CREATE TEMP TABLE deferred (id int UNIQUE DEFERRABLE INITIALLY DEFERRED)")
+        .execute(&mut **t)
+        .await
+        .map_err(e500)?;
+    sqlx::query("INSERT INTO deferred VALUES (1), (1)")
+        .execute(&mut **t)
+        .await
+        .map_err(e500)?;
+    drop(t);
+    transaction
+        .after_commit(async move { notified.store(true, Ordering::SeqCst) })
+        .await;
+    Ok(HttpResponse::Ok().finish())
+}
+
+async fn call_notifying<F>(app: &TestApp, handler: F) -> (u16, bool)
+where
+    F: Handler<(RequestTransaction, Notified)>,
+    F::Output: Responder + 'static,
+{
+    let notified = Arc::new(AtomicBool::new(false));
+    let service = test::init_service(
+        App::new()
+            .wrap(from_fn(commit_request_transaction))
+            .route("/", web::post().to(handler))
+            .app_data(web::Data::new(app.db_pool.clone()))
+            .app_data(web::Data::new(notified.clone())),
+    )
+    .await;
+    // A failed commit is returned as an error by the middleware.
+    let status =
+        match test::try_call_service(&service, test::TestRequest::post().uri("/").to_request())
+            .await
+        {
+            Ok(resp) => resp.status(),
+            Err(e) => e.as_response_error().status_code(),
+        };
+    (status.as_u16(), notified.load(Ordering::SeqCst))
+}
+
+#[tokio::test]
+async fn after_commit_tasks_run_once_the_transaction_is_committed() {
+    let app = spawn_app().await;
+
+    let (status, notified) = call_notifying(&app, write_then_notify).await;
+
+    assert_eq!(status, 200);
+    assert!(notified);
+    assert_eq!(count_subscriptions(&app.db_pool).await, 1);
+}
+
+#[tokio::test]
+async fn after_commit_tasks_are_dropped_when_the_commit_fails() {
+    let app = spawn_app().await;
+
+    let (status, notified) = call_notifying(&app, notify_then_fail_to_commit).await;
+
+    assert_eq!(status, 500);
+    assert!(!notified);
+}
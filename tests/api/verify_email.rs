@@ -0,0 +1,171 @@
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helper::{assert_is_redirect_to, spawn_app_with, TestApp};
+
+async fn spawn_app_requiring_verified_admins() -> TestApp {
+    spawn_app_with(|c| c.features.require_verified_admin_email = true).await
+}
+
+#[tokio::test]
+async fn unverified_admins_are_sent_to_the_verification_page() {
+    let app = spawn_app_requiring_verified_admins().await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_admin_dashboard().await;
+
+    assert_is_redirect_to(&response, "/admin/verify_email");
+    let html_page = app.get_verify_email_html().await;
+    assert!(html_page.contains("Verify your email address"));
+    assert!(html_page.contains(&app.test_user.email));
+}
+
+#[tokio::test]
+async fn verified_admins_reach_the_dashboard() {
+    let app = spawn_app_requiring_verified_admins().await;
+    sqlx::query!(
+        "UPDATE users SET email_verified_at = now() WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.test_user.login(&app).await;
+
+    let response = app.get_admin_dashboard().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn unverified_admins_reach_the_dashboard_when_verification_is_not_required() {
+    let app = spawn_app_with(|_| {}).await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_admin_dashboard().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn following_the_emailed_link_verifies_the_admin() {
+    let app = spawn_app_requiring_verified_admins().await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_verify_email().await;
+    assert_is_redirect_to(&response, "/admin/verify_email");
+    assert!(app
+        .get_verify_email_html()
+        .await
+        .contains("A verification link has been sent"));
+
+    let email = app.last_email().await;
+    assert_eq!(email.to, app.test_user.email);
+    assert_eq!(email.html_links.len(), 1);
+    let link = app.local_link(&email.html_links[0]);
+    let response = app.api_client.get(link).send().await.unwrap();
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn a_forged_verification_link_is_rejected() {
+    let app = spawn_app_requiring_verified_admins().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/admin/verify_email/confirm?signature=forged",
+            app.address
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/admin/verify_email");
+    assert!(app
+        .get_verify_email_html()
+        .await
+        .contains("This verification link is invalid or outdated."));
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/admin/verify_email");
+}
+
+#[tokio::test]
+async fn admins_without_an_email_can_add_one_and_verify_it() {
+    let app = spawn_app_requiring_verified_admins().await;
+    sqlx::query!(
+        "UPDATE users SET email = NULL WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/admin/verify_email");
+    assert!(app
+        .get_verify_email_html()
+        .await
+        .contains("There is no email address on your account yet."));
+
+    let response = app.post_verify_email_address("not an email").await;
+    assert_is_redirect_to(&response, "/admin/verify_email");
+    assert!(app
+        .get_verify_email_html()
+        .await
+        .contains("Please enter a valid email address."));
+
+    let response = app.post_verify_email_address("admin@example.com").await;
+    assert_is_redirect_to(&response, "/admin/verify_email");
+    assert!(app
+        .get_verify_email_html()
+        .await
+        .contains("Verify your email address, admin@example.com"));
+
+    app.post_verify_email().await;
+    let email = app.last_email().await;
+    assert_eq!(email.to, "admin@example.com");
+    assert_eq!(email.html_links.len(), 1);
+    let link = app.local_link(&email.html_links[0]);
+    let response = app.api_client.get(link).send().await.unwrap();
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn a_verified_email_cannot_be_replaced_from_the_verification_page() {
+    let app = spawn_app_requiring_verified_admins().await;
+    sqlx::query!(
+        "UPDATE users SET email_verified_at = now() WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.test_user.login(&app).await;
+
+    let response = app.post_verify_email_address("someone@example.com").await;
+
+    assert_is_redirect_to(&response, "/admin/verify_email");
+    let saved = sqlx::query!(
+        "SELECT email FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.email.as_deref(), Some(app.test_user.email.as_str()));
+}
@@ -0,0 +1,202 @@
+use secrecy::Secret;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::webhook_delivery::{try_deliver_webhook, WebhookOutcome};
+
+use crate::helper::{spawn_app_with, TestApp};
+
+const SECRET: &str = "webhook-secret";
+const EMAIL: &str = "ursula@example.com";
+
+async fn spawn_app() -> TestApp {
+    spawn_app_with(|c| c.webhooks.postmark_secret = Secret::new(SECRET.into())).await
+}
+
+fn open_event(issue_id: uuid::Uuid, recipient: &str) -> serde_json::Value {
+    serde_json::json!({
+        "RecordType": "Open",
+        "MessageStream": "outbound",
+        "FirstOpen": true,
+        "Recipient": recipient,
+        "MessageID": "883953f4-6105-42a2-a16a-77a8eac79483",
+        "ReceivedAt": "2026-10-15T10:00:00Z",
+        "Metadata": { "newsletter_issue_id": issue_id.to_string() },
+        "Platform": "WebMail",
+        "Tag": ""
+    })
+}
+
+#[tokio::test]
+async fn deliveries_tell_the_provider_which_issue_they_belong_to() {
+    let app = spawn_app().await;
+
+    let issue_id = app
+        .deliver_an_issue(EMAIL, ResponseTemplate::new(200))
+        .await;
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let delivery: serde_json::Value = serde_json::from_slice(&requests[1].body).unwrap();
+    assert_eq!(
+        delivery["Metadata"],
+        serde_json::json!({ "newsletter_issue_id": issue_id.to_string() })
+    );
+}
+
+#[tokio::test]
+async fn open_events_are_counted_on_the_issue_status_page() {
+    let app = spawn_app().await;
+    let issue_id = app
+        .deliver_an_issue(EMAIL, ResponseTemplate::new(200))
+        .await;
+    let html_page = app.get_issue_status_html(issue_id).await;
+    assert!(html_page.contains("<tr><th>Sent</th><td>1</td></tr>"));
+    assert!(html_page.contains("<tr><th>Opens</th><td>0</td></tr>"));
+
+    let resp = app
+        .post_engagement_event(&open_event(issue_id, EMAIL), SECRET)
+        .await;
+    assert_eq!(resp.status().as_u16(), 200);
+
+    let html_page = app.get_issue_status_html(issue_id).await;
+    assert!(html_page.contains("<tr><th>Opens</th><td>1</td></tr>"));
+    assert!(html_page.contains("<tr><th>Clicks</th><td>0</td></tr>"));
+}
+
+#[tokio::test]
+async fn engagement_events_without_the_shared_secret_are_rejected() {
+    let app = spawn_app().await;
+    let issue_id = app
+        .deliver_an_issue(EMAIL, ResponseTemplate::new(200))
+        .await;
+
+    let resp = app
+        .post_engagement_event(&open_event(issue_id, EMAIL), "not-the-secret")
+        .await;
+    assert_eq!(resp.status().as_u16(), 401);
+
+    let html_page = app.get_issue_status_html(issue_id).await;
+    assert!(html_page.contains("<tr><th>Opens</th><td>0</td></tr>"));
+}
+
+#[tokio::test]
+async fn unknown_event_types_are_accepted_and_ignored() {
+    let app = spawn_app().await;
+    let issue_id = app
+        .deliver_an_issue(EMAIL, ResponseTemplate::new(200))
+        .await;
+    let mut event = open_event(issue_id, EMAIL);
+    event["RecordType"] = "SubscriptionChange".into();
+
+    let resp = app.post_engagement_event(&event, SECRET).await;
+    assert_eq!(resp.status().as_u16(), 200);
+
+    let n_events = sqlx::query!(r#"SELECT count(*) as "count!" FROM engagement_events"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_events, 0);
+}
+
+#[tokio::test]
+async fn subscription_events_are_retried_until_the_receiver_accepts_them() {
+    let app = spawn_app_with(|c| {
+        c.webhooks.events_url = Some(format!("{}/events", c.email_client.api_url));
+        c.webhooks.events_retry_base_delay_milliseconds = 0;
+    })
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+    Mock::given(path("/events"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    Mock::given(path("/events"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let http_client = reqwest::Client::new();
+    let url = app.webhook_settings.events_url.as_deref().unwrap();
+    let mut outcomes = Vec::new();
+    loop {
+        let outcome = try_deliver_webhook(&app.db_pool, &http_client, url, &app.webhook_settings)
+            .await
+            .unwrap();
+        if outcome == WebhookOutcome::EmptyQueue {
+            break;
+        }
+        outcomes.push(outcome);
+    }
+
+    assert_eq!(
+        outcomes,
+        [
+            WebhookOutcome::Retrying,
+            WebhookOutcome::Retrying,
+            WebhookOutcome::Delivered
+        ]
+    );
+    let requests = app.email_server.received_requests().await.unwrap();
+    let event: serde_json::Value = requests
+        .iter()
+        .rev()
+        .find(|r| r.url.path() == "/events")
+        .unwrap()
+        .body_json()
+        .unwrap();
+    assert_eq!(event["event"], "subscriber.created");
+    assert_eq!(event["email"], "ursula_le_guin@gmail.com");
+}
+
+#[tokio::test]
+async fn subscription_events_are_dead_lettered_after_too_many_attempts() {
+    let app = spawn_app_with(|c| {
+        c.webhooks.events_url = Some(format!("{}/events", c.email_client.api_url));
+        c.webhooks.events_retry_base_delay_milliseconds = 0;
+        c.webhooks.events_max_attempts = 2;
+    })
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+    Mock::given(path("/events"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let http_client = reqwest::Client::new();
+    let url = app.webhook_settings.events_url.as_deref().unwrap();
+    for expected in [WebhookOutcome::Retrying, WebhookOutcome::DeadLettered] {
+        let outcome = try_deliver_webhook(&app.db_pool, &http_client, url, &app.webhook_settings)
+            .await
+            .unwrap();
+        assert_eq!(outcome, expected);
+    }
+
+    let dead_letter = sqlx::query!("SELECT n_attempts FROM webhook_dead_letters")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(dead_letter.n_attempts, 2);
+}